@@ -0,0 +1,355 @@
+//! Tamper-evident audit log.
+//!
+//! When `audit.enabled` is set, the agent appends one JSON line per mutating
+//! API request and per authentication failure to `audit.path`. The audit log
+//! is separate from the tracing logs and is never rewritten.
+//!
+//! Every line carries the hash of the line before it (`prev_hash`) and ends
+//! with its own `hash`, the SHA-256 of the line without that field. Editing,
+//! inserting or deleting a line breaks the chain, which
+//! `shiki audit verify` detects. The first line of a log chains to
+//! [`GENESIS_HASH`].
+//!
+//! The chain is not keyed: someone who can write the log can also recompute
+//! every hash after an edit, and records removed from the end leave a
+//! shorter but intact chain. Verification only proves that the log is
+//! consistent; ship it to a separate host to protect it from the agent
+//! host itself.
+
+use crate::config::AuditConfig;
+use crate::error::{Result, ShikiError};
+use crate::util::to_hex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Previous hash of the first record of a log.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Marker of the hash field at the end of a line.
+const HASH_FIELD: &str = ",\"hash\":\"";
+
+/// An audited request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position in the log, starting at 1.
+    pub seq: u64,
+    /// When the request was received.
+    pub timestamp: DateTime<Utc>,
+    /// Address of the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    /// Authenticated identity: `anonymous` without authentication, `token`,
+    /// or `api-key:<fingerprint>`. Absent when authentication failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// Request ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// HTTP method.
+    pub method: String,
+    /// Request path.
+    pub path: String,
+    /// Service the request acts on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Action requested on the service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// ACL decision for the service, `allowed` or `denied`, on the systemd
+    /// backend and for forwarded requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl: Option<String>,
+    /// HTTP status of the response.
+    pub status: u16,
+    /// `success`, `failure` or `unauthenticated`.
+    pub outcome: String,
+    /// Error message of a failed request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Hash of the previous record.
+    pub prev_hash: String,
+}
+
+/// Append-only audit log writer.
+#[derive(Debug)]
+pub struct AuditLog {
+    state: Mutex<Tail>,
+}
+
+/// End of the log the next record is chained to.
+#[derive(Debug)]
+struct Tail {
+    file: File,
+    seq: u64,
+    hash: String,
+}
+
+impl AuditLog {
+    /// Opens the log at `config.path` for appending, continuing the chain
+    /// of its last record.
+    pub fn open(config: &AuditConfig) -> Result<Self> {
+        let path = &config.path;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                ShikiError::config_with_source(
+                    format!("Failed to create audit log directory: {}", parent.display()),
+                    e,
+                )
+            })?;
+        }
+
+        let (seq, hash) = match last_line(path)? {
+            Some(line) => {
+                let (record, hash) = parse_line(&line).map_err(|reason| {
+                    ShikiError::config(format!(
+                        "Audit log {} ends with an invalid record ({}); check it with `shiki audit verify`",
+                        path.display(),
+                        reason
+                    ))
+                })?;
+                (record.seq, hash)
+            }
+            None => (0, GENESIS_HASH.to_string()),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                ShikiError::config_with_source(
+                    format!("Failed to open audit log: {}", path.display()),
+                    e,
+                )
+            })?;
+        Ok(Self {
+            state: Mutex::new(Tail { file, seq, hash }),
+        })
+    }
+
+    /// Appends a record, filling in its `seq` and `prev_hash`.
+    pub fn append(&self, mut record: AuditRecord) -> Result<()> {
+        let mut tail = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        record.seq = tail.seq + 1;
+        record.prev_hash = tail.hash.clone();
+        let (line, hash) = format_line(&record)?;
+        tail.file
+            .write_all(line.as_bytes())
+            .and_then(|_| tail.file.write_all(b"\n"))
+            .map_err(|e| ShikiError::backend_with_source("Failed to write audit log", e))?;
+        tail.seq = record.seq;
+        tail.hash = hash;
+        Ok(())
+    }
+}
+
+/// Result of verifying an audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Verification {
+    /// Every record is intact and chained to the one before.
+    Intact {
+        /// Number of records.
+        records: u64,
+    },
+    /// The chain is broken.
+    Broken {
+        /// Line number (1-based) of the first invalid record.
+        line: usize,
+        /// What is wrong with it.
+        reason: String,
+    },
+}
+
+/// Verifies the hash chain of the audit log at `path`.
+pub fn verify(path: &Path) -> Result<Verification> {
+    let file = File::open(path).map_err(|e| {
+        ShikiError::config_with_source(format!("Failed to open audit log: {}", path.display()), e)
+    })?;
+
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut seq = 0;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let broken = |reason: String| Verification::Broken {
+            line: i + 1,
+            reason,
+        };
+        let (record, hash) = match parse_line(&line) {
+            Ok(parsed) => parsed,
+            Err(reason) => return Ok(broken(reason)),
+        };
+        if record.prev_hash != prev_hash {
+            return Ok(broken(
+                "previous hash does not match the record before".to_string(),
+            ));
+        }
+        if record.seq != seq + 1 {
+            return Ok(broken(format!(
+                "sequence number {} follows {}",
+                record.seq, seq
+            )));
+        }
+        prev_hash = hash;
+        seq = record.seq;
+    }
+    Ok(Verification::Intact { records: seq })
+}
+
+/// Serializes a record to a line ending with its hash.
+fn format_line(record: &AuditRecord) -> Result<(String, String)> {
+    let body = serde_json::to_string(record)?;
+    let hash = sha256_hex(&body);
+    let line = format!("{}{}{}\"}}", &body[..body.len() - 1], HASH_FIELD, hash);
+    Ok((line, hash))
+}
+
+/// Checks the hash of a line and returns its record and hash.
+fn parse_line(line: &str) -> std::result::Result<(AuditRecord, String), String> {
+    let start = line
+        .rfind(HASH_FIELD)
+        .filter(|_| line.ends_with("\"}"))
+        .ok_or_else(|| "record has no hash".to_string())?;
+    let hash = &line[start + HASH_FIELD.len()..line.len() - 2];
+    let body = format!("{}}}", &line[..start]);
+    if sha256_hex(&body) != hash {
+        return Err("hash does not match the record".to_string());
+    }
+    let record = serde_json::from_str(&body).map_err(|e| format!("invalid record: {}", e))?;
+    Ok((record, hash.to_string()))
+}
+
+fn sha256_hex(data: &str) -> String {
+    to_hex(&Sha256::digest(data.as_bytes()))
+}
+
+/// Returns the last non-empty line of a file, if the file exists.
+fn last_line(path: &Path) -> Result<Option<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(ShikiError::config_with_source(
+                format!("Failed to read audit log: {}", path.display()),
+                e,
+            ))
+        }
+    };
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(service: &str) -> AuditRecord {
+        AuditRecord {
+            timestamp: Utc::now(),
+            peer: Some("10.0.0.5".to_string()),
+            identity: Some("token".to_string()),
+            request_id: Some("550e8400-e29b-41d4-a716-446655440000".to_string()),
+            method: "POST".to_string(),
+            path: format!("/api/v1/services/{}/start", service),
+            service: Some(service.to_string()),
+            action: Some("start".to_string()),
+            acl: Some("allowed".to_string()),
+            status: 200,
+            outcome: "success".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn write_log(path: &Path, services: &[&str]) {
+        let log = AuditLog::open(&AuditConfig {
+            enabled: true,
+            path: path.to_path_buf(),
+        })
+        .unwrap();
+        for service in services {
+            log.append(record(service)).unwrap();
+        }
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_continues_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("audit.log");
+        write_log(&path, &["nginx", "redis"]);
+        write_log(&path, &["postgres"]);
+
+        assert_eq!(verify(&path).unwrap(), Verification::Intact { records: 3 });
+        let lines = lines(&path);
+        let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        let third: AuditRecord = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(first["prev_hash"], GENESIS_HASH);
+        assert_eq!(third.seq, 3);
+        assert_eq!(third.service.as_deref(), Some("postgres"));
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        write_log(&path, &["nginx", "redis", "postgres"]);
+        let original = lines(&path);
+        let broken_at = |lines: Vec<String>| {
+            std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+            match verify(&path).unwrap() {
+                Verification::Broken { line, .. } => line,
+                Verification::Intact { .. } => panic!("tampering not detected"),
+            }
+        };
+
+        // Edited record
+        let mut edited = original.clone();
+        edited[1] = edited[1].replace("redis", "mysql");
+        assert_eq!(broken_at(edited), 2);
+
+        // Deleted record
+        let mut deleted = original.clone();
+        deleted.remove(1);
+        assert_eq!(broken_at(deleted), 2);
+
+        // Reordered records
+        let mut swapped = original.clone();
+        swapped.swap(0, 1);
+        assert_eq!(broken_at(swapped), 1);
+
+        // Record with its hash recomputed no longer chains to the next one
+        let mut forged: AuditRecord = serde_json::from_str(&original[1]).unwrap();
+        forged.outcome = "failure".to_string();
+        let mut rehashed = original.clone();
+        rehashed[1] = format_line(&forged).unwrap().0;
+        assert_eq!(broken_at(rehashed), 3);
+
+        // Appending to a log with a broken last record is refused
+        let mut garbage = original;
+        garbage.push("not json".to_string());
+        assert_eq!(broken_at(garbage), 4);
+        assert!(AuditLog::open(&AuditConfig {
+            enabled: true,
+            path: path.clone(),
+        })
+        .is_err());
+    }
+}
@@ -3,6 +3,12 @@
 //! This module defines the CLI structure using clap derive macros,
 //! including all subcommands and their arguments.
 
+use crate::cluster::Selector;
+use crate::config::Backend;
+use crate::output::OutputFormat;
+use crate::plan::FailurePolicy;
+use crate::rollout::RolloutFailurePolicy;
+use chrono::{DateTime, Utc};
 use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -27,6 +33,17 @@ pub struct Cli {
     #[arg(short, long, global = true, conflicts_with = "verbose")]
     pub quiet: bool,
 
+    /// Output format (text, json, yaml, table)
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "SHIKI_OUTPUT",
+        default_value = "text",
+        value_parser = parse_output_format
+    )]
+    pub output: OutputFormat,
+
     /// Subcommand to execute
     #[command(subcommand)]
     pub command: Commands,
@@ -68,6 +85,50 @@ pub enum Commands {
     /// Configuration file operations
     #[command(subcommand)]
     Config(ConfigCommands),
+
+    /// Cross-host startup plans
+    #[command(subcommand)]
+    Plan(PlanCommands),
+
+    /// Rolling service restarts across agents
+    #[command(subcommand)]
+    Rollout(RolloutCommands),
+
+    /// Cluster operations
+    #[command(subcommand)]
+    Cluster(ClusterCommands),
+
+    /// Set, clear and list named signals
+    #[command(subcommand)]
+    Signal(SignalCommands),
+
+    /// Wait for named signals
+    #[command(subcommand)]
+    Barrier(BarrierCommands),
+
+    /// Run a command only if the local agent is the cluster leader
+    RunIfLeader(RunIfLeaderArgs),
+
+    /// Acquire, release and hold named locks
+    #[command(subcommand)]
+    Lock(LockCommands),
+
+    /// Show the operation history of an agent
+    History(HistoryArgs),
+
+    /// Audit log operations
+    #[command(subcommand)]
+    Audit(AuditCommands),
+
+    /// Show the service watchers of an agent and their decisions
+    Watch(WatchArgs),
+
+    /// Converge the services of an agent to their declared state
+    Reconcile(ReconcileArgs),
+
+    /// List, inspect, start, stop and restart the services of an agent
+    #[command(subcommand)]
+    Services(ServicesCommands),
 }
 
 /// Arguments for the `serve` subcommand.
@@ -85,9 +146,31 @@ pub struct ServeArgs {
 /// Arguments for the `notify` subcommand.
 #[derive(Debug, Args)]
 pub struct NotifyArgs {
-    /// Target agent address (host:port)
-    #[arg(short, long)]
-    pub target: String,
+    /// Target agent address (host:port). With --selector, the agent that
+    /// performs the fan-out. With --via, the peer to reach (peer names
+    /// separated by '/' for multi-hop routes, e.g. dc2-gw/db-node)
+    #[arg(short, long, required_unless_present = "selector")]
+    pub target: Option<String>,
+
+    /// Forward the request through this agent (host:port) to the peer named by --target
+    #[arg(long, conflicts_with = "selector")]
+    pub via: Option<String>,
+
+    /// Bearer token for the agent the request is sent to
+    #[arg(long, env = "SHIKI_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Notify every agent matching a tag selector (e.g. role=db,env=prod)
+    #[arg(long, value_parser = parse_selector)]
+    pub selector: Option<Selector>,
+
+    /// Maximum number of agents notified at the same time (with --selector)
+    #[arg(long, requires = "selector")]
+    pub parallelism: Option<usize>,
+
+    /// Number of failed agents tolerated before the rest are skipped (with --selector)
+    #[arg(long, requires = "selector")]
+    pub max_failures: Option<usize>,
 
     /// Action to perform
     #[arg(short, long, value_parser = parse_action)]
@@ -108,6 +191,10 @@ pub struct NotifyArgs {
     /// Do not wait for completion
     #[arg(long, conflicts_with = "wait")]
     pub no_wait: bool,
+
+    /// After start/restart, also wait for the service's readiness probe
+    #[arg(long, conflicts_with = "no_wait")]
+    pub wait_ready: bool,
 }
 
 impl NotifyArgs {
@@ -115,6 +202,18 @@ impl NotifyArgs {
     pub fn should_wait(&self) -> bool {
         !self.no_wait && self.wait
     }
+
+    /// Returns the peer route of a `--via` request.
+    pub fn route(&self) -> Vec<String> {
+        match (&self.via, &self.target) {
+            (Some(_), Some(target)) => target
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 /// Arguments for the `wait` subcommand.
@@ -135,6 +234,63 @@ pub struct WaitArgs {
     /// Polling interval in seconds
     #[arg(long, default_value = "5")]
     pub interval: u64,
+
+    /// Condition to wait for (running, stopped, ready)
+    #[arg(long, default_value = "running", value_parser = parse_wait_condition)]
+    pub until: WaitCondition,
+}
+
+/// Condition for the `wait` subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// Service status is running
+    Running,
+    /// Service status is stopped
+    Stopped,
+    /// Service readiness probe passes
+    Ready,
+}
+
+impl std::fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaitCondition::Running => write!(f, "running"),
+            WaitCondition::Stopped => write!(f, "stopped"),
+            WaitCondition::Ready => write!(f, "ready"),
+        }
+    }
+}
+
+impl std::str::FromStr for WaitCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "running" => Ok(WaitCondition::Running),
+            "stopped" => Ok(WaitCondition::Stopped),
+            "ready" => Ok(WaitCondition::Ready),
+            _ => Err(format!(
+                "Invalid condition '{}'. Valid conditions: running, stopped, ready",
+                s
+            )),
+        }
+    }
+}
+
+/// Parse wait condition from string.
+fn parse_wait_condition(s: &str) -> Result<WaitCondition, String> {
+    s.parse()
+}
+
+/// Parse service backend from string.
+fn parse_backend(s: &str) -> Result<Backend, String> {
+    s.parse()
+        .map_err(|_| format!("Invalid backend '{}'. Valid backends: systemd, exec", s))
+}
+
+/// Parse output format from string.
+fn parse_output_format(s: &str) -> Result<OutputFormat, String> {
+    s.parse()
 }
 
 /// Arguments for the `status` subcommand.
@@ -153,10 +309,521 @@ pub struct StatusArgs {
 #[derive(Debug, Subcommand)]
 pub enum ConfigCommands {
     /// Validate the configuration file
-    Validate,
+    Validate(ConfigValidateArgs),
+
+    /// Show the current configuration
+    Show,
+
+    /// Print the JSON Schema of the configuration file
+    Schema,
+
+    /// Write a commented starter configuration file
+    Init(ConfigInitArgs),
+}
+
+/// Arguments for the `config validate` subcommand.
+#[derive(Debug, Args)]
+pub struct ConfigValidateArgs {
+    /// Report unknown settings as warnings instead of errors
+    #[arg(long)]
+    pub no_strict: bool,
+}
+
+/// Arguments for the `config init` subcommand.
+#[derive(Debug, Args)]
+pub struct ConfigInitArgs {
+    /// Service backend (detected from `systemctl` if omitted)
+    #[arg(long, value_parser = parse_backend)]
+    pub backend: Option<Backend>,
+
+    /// File to write (defaults to the --config path, then config.yaml)
+    pub path: Option<PathBuf>,
+
+    /// Overwrite an existing file
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Audit log subcommands.
+#[derive(Debug, Subcommand)]
+pub enum AuditCommands {
+    /// Check that the hash chain of an audit log is intact
+    ///
+    /// The chain is unkeyed: it does not detect records removed from the
+    /// end of the log, or a log rewritten with recomputed hashes.
+    Verify(AuditVerifyArgs),
+}
+
+/// Arguments for the `audit verify` subcommand.
+#[derive(Debug, Args)]
+pub struct AuditVerifyArgs {
+    /// Path to the audit log
+    pub file: PathBuf,
+}
+
+/// Cluster subcommands.
+#[derive(Debug, Subcommand)]
+pub enum ClusterCommands {
+    /// Show the state of every agent in the cluster
+    Status(ClusterStatusArgs),
+}
+
+/// Arguments for the `cluster status` subcommand.
+#[derive(Debug, Args)]
+pub struct ClusterStatusArgs {
+    /// Agent to query (host:port) [default: the local agent]
+    #[arg(short, long)]
+    pub target: Option<String>,
+}
+
+/// Signal subcommands.
+#[derive(Debug, Subcommand)]
+pub enum SignalCommands {
+    /// Set a signal
+    Set(SignalSetArgs),
+
+    /// Clear a signal
+    Clear(SignalClearArgs),
+
+    /// List the signals that are set
+    List(AgentTargetArgs),
+}
+
+/// Agent options of the signal, barrier and lock subcommands.
+#[derive(Debug, Args)]
+pub struct AgentTargetArgs {
+    /// Agent to send the request to (host:port) [default: the local agent]
+    #[arg(short, long)]
+    pub target: Option<String>,
+
+    /// Bearer token for the agent
+    #[arg(long, env = "SHIKI_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+/// Arguments for the `signal set` subcommand.
+#[derive(Debug, Args)]
+pub struct SignalSetArgs {
+    /// Signal name
+    pub name: String,
+
+    /// Identity to signal as [default: the agent name in the configuration file]
+    #[arg(long)]
+    pub id: Option<String>,
+
+    /// Time to live in seconds [default: signals.default_ttl_seconds of the agent]
+    #[arg(long)]
+    pub ttl: Option<u64>,
+
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `signal clear` subcommand.
+#[derive(Debug, Args)]
+pub struct SignalClearArgs {
+    /// Signal name
+    pub name: String,
+
+    /// Clear only the signal set by this identity
+    #[arg(long)]
+    pub id: Option<String>,
+
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Barrier subcommands.
+#[derive(Debug, Subcommand)]
+pub enum BarrierCommands {
+    /// Wait until enough agents have set a signal
+    Wait(BarrierWaitArgs),
+}
+
+/// Arguments for the `barrier wait` subcommand.
+#[derive(Debug, Args)]
+pub struct BarrierWaitArgs {
+    /// Signal name
+    pub name: String,
+
+    /// Number of agents that must have set the signal
+    #[arg(long, default_value = "1")]
+    pub count: usize,
+
+    /// Timeout in seconds
+    #[arg(long, default_value = "60")]
+    pub timeout: u64,
+
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `run-if-leader` subcommand.
+#[derive(Debug, Args)]
+pub struct RunIfLeaderArgs {
+    /// Agent taking part in the election (host:port) [default: the local agent]
+    #[arg(short, long)]
+    pub target: Option<String>,
+
+    /// Bearer token for the agent
+    #[arg(long, env = "SHIKI_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Seconds to wait for a leader to be elected
+    #[arg(long, default_value = "30")]
+    pub wait: u64,
+
+    /// Command to run, after `--`
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+/// Lock subcommands.
+#[derive(Debug, Subcommand)]
+pub enum LockCommands {
+    /// Acquire a lock and print its fencing token
+    Acquire(LockAcquireArgs),
+
+    /// Release a lock
+    Release(LockTokenArgs),
+
+    /// Extend a held lock
+    Renew(LockTokenArgs),
+
+    /// List the held locks
+    List(AgentTargetArgs),
+
+    /// Run a command while holding a lock
+    Run(LockRunArgs),
+}
+
+/// Options of a lock acquisition.
+#[derive(Debug, Args)]
+pub struct LockAcquireArgs {
+    /// Lock name
+    pub name: String,
+
+    /// Owner of the lock [default: the agent name]
+    #[arg(long)]
+    pub owner: Option<String>,
+
+    /// Time to live in seconds [default: locks.default_ttl_seconds of the agent]
+    #[arg(long)]
+    pub ttl: Option<u64>,
+
+    /// Seconds to wait while another owner holds the lock
+    #[arg(long, default_value = "0")]
+    pub wait: u64,
+
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `lock release` and `lock renew` subcommands.
+#[derive(Debug, Args)]
+pub struct LockTokenArgs {
+    /// Lock name
+    pub name: String,
+
+    /// Fencing token printed by `lock acquire`
+    #[arg(long)]
+    pub fencing_token: u64,
+
+    /// New time to live in seconds, for `lock renew`
+    #[arg(long)]
+    pub ttl: Option<u64>,
+
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `lock run` subcommand.
+#[derive(Debug, Args)]
+pub struct LockRunArgs {
+    /// Options of the lock acquisition; the owner defaults to the agent
+    /// name and process id
+    #[command(flatten)]
+    pub lock: LockAcquireArgs,
+
+    /// Command to run, after `--`
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+/// Arguments for the `history` subcommand.
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+    /// Only operations on this service
+    #[arg(short, long)]
+    pub service: Option<String>,
+
+    /// Only this action (start, stop, restart)
+    #[arg(short, long, value_parser = parse_action)]
+    pub action: Option<ServiceAction>,
+
+    /// Only operations with this result
+    #[arg(short, long, value_parser = ["completed", "failed", "error"])]
+    pub result: Option<String>,
+
+    /// Only operations since this time (RFC 3339, or relative such as 30m, 12h, 7d)
+    #[arg(long, value_parser = parse_time)]
+    pub since: Option<DateTime<Utc>>,
+
+    /// Only operations before this time (RFC 3339, or relative such as 30m, 12h, 7d)
+    #[arg(long, value_parser = parse_time)]
+    pub until: Option<DateTime<Utc>>,
+
+    /// Maximum number of operations to show
+    #[arg(long, default_value = "50")]
+    pub limit: usize,
+
+    /// Number of operations to skip
+    #[arg(long, default_value = "0")]
+    pub offset: usize,
+
+    /// Agent options
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `watch` subcommand.
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Also list the recent decisions of each watcher
+    #[arg(short, long)]
+    pub decisions: bool,
+
+    /// Agent options
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `reconcile` subcommand.
+#[derive(Debug, Args)]
+pub struct ReconcileArgs {
+    /// Only print the planned actions
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Agent options
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Services subcommands.
+#[derive(Debug, Subcommand)]
+pub enum ServicesCommands {
+    /// List the services of an agent
+    List(ServicesListArgs),
+
+    /// Show the details of a service
+    Show(ServiceShowArgs),
+
+    /// Start a service
+    Start(ServiceActionArgs),
+
+    /// Stop a service
+    Stop(ServiceActionArgs),
+
+    /// Restart a service
+    Restart(ServiceActionArgs),
+}
+
+/// Arguments for the `services list` subcommand.
+#[derive(Debug, Args)]
+pub struct ServicesListArgs {
+    /// Only list services in this state
+    #[arg(long, value_parser = ["running", "stopped", "failed", "unknown"])]
+    pub status: Option<String>,
+
+    /// Maximum number of services [default: all]
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Number of services to skip
+    #[arg(long, default_value = "0")]
+    pub offset: usize,
+
+    /// Agent options
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `services show` subcommand.
+#[derive(Debug, Args)]
+pub struct ServiceShowArgs {
+    /// Service name
+    pub name: String,
+
+    /// Agent options
+    #[command(flatten)]
+    pub agent: AgentTargetArgs,
+}
+
+/// Arguments for the `services start`, `stop` and `restart` subcommands.
+#[derive(Debug, Args)]
+pub struct ServiceActionArgs {
+    /// Service name
+    pub name: String,
+
+    /// Agent running the service (host:port)
+    #[arg(short, long)]
+    pub target: String,
+
+    /// Bearer token for the agent
+    #[arg(long, env = "SHIKI_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+/// Parse a point in time: RFC 3339, or a duration before now with a unit
+/// of s, m, h or d.
+fn parse_time(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let invalid = || {
+        format!(
+            "Invalid time '{}'. Use RFC 3339 (2024-01-15T10:00:00Z) or a duration such as 30m, 12h, 7d",
+            s
+        )
+    };
+    let unit = s.chars().last().ok_or_else(invalid)?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => return Err(invalid()),
+    };
+    let amount: i64 = s[..s.len() - 1].parse().map_err(|_| invalid())?;
+    Ok(Utc::now() - chrono::Duration::seconds(amount * seconds))
+}
+
+/// Plan subcommands.
+#[derive(Debug, Subcommand)]
+pub enum PlanCommands {
+    /// Execute a plan
+    Apply(PlanApplyArgs),
+
+    /// Validate a plan file
+    Validate(PlanFileArgs),
+
+    /// Print the plan as a Graphviz DOT graph
+    Graph(PlanFileArgs),
+}
+
+/// Arguments for plan subcommands that only read the plan file.
+#[derive(Debug, Args)]
+pub struct PlanFileArgs {
+    /// Path to the plan file
+    pub file: PathBuf,
+}
+
+/// Arguments for the `plan apply` subcommand.
+#[derive(Debug, Args)]
+pub struct PlanApplyArgs {
+    /// Path to the plan file
+    pub file: PathBuf,
+
+    /// Override the plan's failure policy (stop, continue, rollback)
+    #[arg(long, value_parser = parse_failure_policy)]
+    pub on_failure: Option<FailurePolicy>,
+}
+
+/// Parse failure policy from string.
+fn parse_failure_policy(s: &str) -> Result<FailurePolicy, String> {
+    s.parse().map_err(|e: crate::ShikiError| e.to_string())
+}
+
+/// Rollout subcommands.
+#[derive(Debug, Subcommand)]
+pub enum RolloutCommands {
+    /// Restart a service on every matching agent, a batch at a time
+    Restart(RolloutRestartArgs),
+
+    /// Show the progress record of a rollout
+    Status(RolloutStatusArgs),
+}
+
+/// Arguments for the `rollout restart` subcommand.
+#[derive(Debug, Args)]
+pub struct RolloutRestartArgs {
+    /// Service to restart
+    #[arg(short, long)]
+    pub service: String,
+
+    /// Agents to restart the service on (e.g. role=api,env=prod)
+    #[arg(long, value_parser = parse_selector, required_unless_present = "resume")]
+    pub selector: Option<Selector>,
+
+    /// Maximum number of agents restarted at the same time
+    #[arg(long, default_value = "1")]
+    pub batch: usize,
+
+    /// Maximum number of agents down at the same time, failed agents included
+    #[arg(long, default_value = "1")]
+    pub max_unavailable: usize,
+
+    /// Number of failed agents tolerated before the rollout stops
+    #[arg(long, default_value = "0")]
+    pub max_failures: usize,
+
+    /// What to do after too many failures (pause, abort)
+    #[arg(long, default_value = "pause", value_parser = parse_rollout_failure_policy)]
+    pub on_failure: RolloutFailurePolicy,
+
+    /// Seconds to wait for each agent's service to come back
+    #[arg(long, default_value = "300")]
+    pub timeout: u64,
+
+    /// Progress record [default: shiki-rollout-<SERVICE>.json]
+    #[arg(long)]
+    pub state_file: Option<PathBuf>,
+
+    /// Continue the rollout recorded in the progress record
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Bearer token for agents without a token of their own
+    #[arg(long, env = "SHIKI_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+impl RolloutRestartArgs {
+    /// Returns the path of the progress record.
+    pub fn state_file(&self) -> PathBuf {
+        rollout_state_file(&self.service, self.state_file.as_ref())
+    }
+}
+
+/// Arguments for the `rollout status` subcommand.
+#[derive(Debug, Args)]
+pub struct RolloutStatusArgs {
+    /// Service of the rollout
+    #[arg(short, long)]
+    pub service: String,
+
+    /// Progress record [default: shiki-rollout-<SERVICE>.json]
+    #[arg(long)]
+    pub state_file: Option<PathBuf>,
+}
+
+impl RolloutStatusArgs {
+    /// Returns the path of the progress record.
+    pub fn state_file(&self) -> PathBuf {
+        rollout_state_file(&self.service, self.state_file.as_ref())
+    }
+}
 
-    /// Show the current configuration
-    Show,
+fn rollout_state_file(service: &str, state_file: Option<&PathBuf>) -> PathBuf {
+    state_file
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(format!("shiki-rollout-{}.json", service)))
+}
+
+/// Parse rollout failure policy from string.
+fn parse_rollout_failure_policy(s: &str) -> Result<RolloutFailurePolicy, String> {
+    s.parse().map_err(|e: crate::ShikiError| e.to_string())
 }
 
 /// Service action types.
@@ -196,6 +863,11 @@ impl std::str::FromStr for ServiceAction {
     }
 }
 
+/// Parse agent selector from string.
+fn parse_selector(s: &str) -> Result<Selector, String> {
+    s.parse().map_err(|e: crate::ShikiError| e.to_string())
+}
+
 /// Parse service action from string.
 fn parse_action(s: &str) -> Result<ServiceAction, String> {
     s.parse()
@@ -253,7 +925,8 @@ mod tests {
 
         match cli.command {
             Commands::Notify(args) => {
-                assert_eq!(args.target, "localhost:8080");
+                assert_eq!(args.target.as_deref(), Some("localhost:8080"));
+                assert!(args.selector.is_none());
                 assert_eq!(args.action, ServiceAction::Start);
                 assert_eq!(args.service, "nginx");
                 assert!(args.should_wait());
@@ -285,6 +958,391 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_notify_via() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "notify",
+            "--via",
+            "bastion:8080",
+            "--target",
+            "dc2-gw/db-node",
+            "-a",
+            "start",
+            "-s",
+            "postgres",
+        ]);
+
+        match cli.command {
+            Commands::Notify(args) => {
+                assert_eq!(args.via.as_deref(), Some("bastion:8080"));
+                assert_eq!(args.route(), vec!["dc2-gw", "db-node"]);
+            }
+            _ => panic!("Expected Notify command"),
+        }
+
+        assert!(Cli::try_parse_from([
+            "shiki",
+            "notify",
+            "--via",
+            "bastion:8080",
+            "--selector",
+            "role=db",
+            "-a",
+            "start",
+            "-s",
+            "postgres"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_signal_and_barrier_commands() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "signal",
+            "set",
+            "db-ready",
+            "--id",
+            "db-1",
+            "--ttl",
+            "600",
+            "-t",
+            "coord:8080",
+        ]);
+        match cli.command {
+            Commands::Signal(SignalCommands::Set(args)) => {
+                assert_eq!(args.name, "db-ready");
+                assert_eq!(args.id.as_deref(), Some("db-1"));
+                assert_eq!(args.ttl, Some(600));
+                assert_eq!(args.agent.target.as_deref(), Some("coord:8080"));
+            }
+            _ => panic!("Expected Signal Set command"),
+        }
+
+        let cli = Cli::parse_from([
+            "shiki",
+            "barrier",
+            "wait",
+            "db-ready",
+            "--count",
+            "3",
+            "--timeout",
+            "120",
+        ]);
+        match cli.command {
+            Commands::Barrier(BarrierCommands::Wait(args)) => {
+                assert_eq!(args.name, "db-ready");
+                assert_eq!(args.count, 3);
+                assert_eq!(args.timeout, 120);
+                assert!(args.agent.target.is_none());
+            }
+            _ => panic!("Expected Barrier Wait command"),
+        }
+    }
+
+    #[test]
+    fn test_run_if_leader_command() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "run-if-leader",
+            "--wait",
+            "5",
+            "--",
+            "shiki",
+            "plan",
+            "apply",
+            "boot.yaml",
+        ]);
+        match cli.command {
+            Commands::RunIfLeader(args) => {
+                assert_eq!(args.wait, 5);
+                assert_eq!(args.command, vec!["shiki", "plan", "apply", "boot.yaml"]);
+                assert!(args.target.is_none());
+            }
+            _ => panic!("Expected RunIfLeader command"),
+        }
+
+        // The command is required
+        assert!(Cli::try_parse_from(["shiki", "run-if-leader"]).is_err());
+    }
+
+    #[test]
+    fn test_lock_commands() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "lock",
+            "run",
+            "db-migrate",
+            "--ttl",
+            "30",
+            "--wait",
+            "600",
+            "-t",
+            "coord:8080",
+            "--",
+            "./migrate.sh",
+            "--all",
+        ]);
+        match cli.command {
+            Commands::Lock(LockCommands::Run(args)) => {
+                assert_eq!(args.lock.name, "db-migrate");
+                assert_eq!(args.lock.ttl, Some(30));
+                assert_eq!(args.lock.wait, 600);
+                assert!(args.lock.owner.is_none());
+                assert_eq!(args.lock.agent.target.as_deref(), Some("coord:8080"));
+                assert_eq!(args.command, vec!["./migrate.sh", "--all"]);
+            }
+            _ => panic!("Expected Lock Run command"),
+        }
+
+        let cli = Cli::parse_from([
+            "shiki",
+            "lock",
+            "release",
+            "db-migrate",
+            "--fencing-token",
+            "42",
+        ]);
+        match cli.command {
+            Commands::Lock(LockCommands::Release(args)) => {
+                assert_eq!(args.name, "db-migrate");
+                assert_eq!(args.fencing_token, 42);
+            }
+            _ => panic!("Expected Lock Release command"),
+        }
+
+        // Releasing needs the fencing token
+        assert!(Cli::try_parse_from(["shiki", "lock", "release", "db-migrate"]).is_err());
+    }
+
+    #[test]
+    fn test_history_command() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "history",
+            "--service",
+            "nginx",
+            "--action",
+            "restart",
+            "--result",
+            "failed",
+            "--since",
+            "2h",
+            "--until",
+            "2024-01-15T10:00:00+09:00",
+            "--limit",
+            "10",
+        ]);
+        match cli.command {
+            Commands::History(args) => {
+                assert_eq!(args.service.as_deref(), Some("nginx"));
+                assert_eq!(args.action, Some(ServiceAction::Restart));
+                assert_eq!(args.result.as_deref(), Some("failed"));
+                let age = Utc::now() - args.since.unwrap();
+                assert!((7190..=7210).contains(&age.num_seconds()));
+                assert_eq!(
+                    args.until.unwrap().to_rfc3339(),
+                    "2024-01-15T01:00:00+00:00"
+                );
+                assert_eq!(args.limit, 10);
+                assert_eq!(args.offset, 0);
+                assert!(args.agent.target.is_none());
+            }
+            _ => panic!("Expected History command"),
+        }
+
+        for since in ["yesterday", "10w", "h", ""] {
+            assert!(Cli::try_parse_from(["shiki", "history", "--since", since]).is_err());
+        }
+        assert!(Cli::try_parse_from(["shiki", "history", "--result", "ok"]).is_err());
+    }
+
+    #[test]
+    fn test_audit_command() {
+        let cli = Cli::parse_from(["shiki", "audit", "verify", "/var/log/shiki/audit.log"]);
+        match cli.command {
+            Commands::Audit(AuditCommands::Verify(args)) => {
+                assert_eq!(args.file, PathBuf::from("/var/log/shiki/audit.log"));
+            }
+            _ => panic!("Expected Audit Verify command"),
+        }
+        assert!(Cli::try_parse_from(["shiki", "audit", "verify"]).is_err());
+    }
+
+    #[test]
+    fn test_watch_command() {
+        let cli = Cli::parse_from(["shiki", "watch", "--decisions", "--target", "web:8080"]);
+        match cli.command {
+            Commands::Watch(args) => {
+                assert!(args.decisions);
+                assert_eq!(args.agent.target.as_deref(), Some("web:8080"));
+            }
+            _ => panic!("Expected Watch command"),
+        }
+    }
+
+    #[test]
+    fn test_reconcile_command() {
+        let cli = Cli::parse_from(["shiki", "reconcile", "--dry-run"]);
+        match cli.command {
+            Commands::Reconcile(args) => {
+                assert!(args.dry_run);
+                assert!(args.agent.target.is_none());
+            }
+            _ => panic!("Expected Reconcile command"),
+        }
+    }
+
+    #[test]
+    fn test_services_commands() {
+        let cli = Cli::parse_from([
+            "shiki", "services", "list", "--status", "running", "--limit", "20",
+        ]);
+        match cli.command {
+            Commands::Services(ServicesCommands::List(args)) => {
+                assert_eq!(args.status.as_deref(), Some("running"));
+                assert_eq!(args.limit, Some(20));
+                assert_eq!(args.offset, 0);
+                assert!(args.agent.target.is_none());
+            }
+            _ => panic!("Expected Services List command"),
+        }
+
+        let cli = Cli::parse_from(["shiki", "services", "show", "nginx", "-t", "web:8080"]);
+        match cli.command {
+            Commands::Services(ServicesCommands::Show(args)) => {
+                assert_eq!(args.name, "nginx");
+                assert_eq!(args.agent.target.as_deref(), Some("web:8080"));
+            }
+            _ => panic!("Expected Services Show command"),
+        }
+
+        let cli = Cli::parse_from(["shiki", "services", "restart", "nginx", "-t", "web:8080"]);
+        match cli.command {
+            Commands::Services(ServicesCommands::Restart(args)) => {
+                assert_eq!(args.name, "nginx");
+                assert_eq!(args.target, "web:8080");
+            }
+            _ => panic!("Expected Services Restart command"),
+        }
+
+        // Actions need an explicit target, and the state filter is checked
+        assert!(Cli::try_parse_from(["shiki", "services", "stop", "nginx"]).is_err());
+        assert!(
+            Cli::try_parse_from(["shiki", "services", "list", "--status", "sleeping"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_rollout_commands() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "rollout",
+            "restart",
+            "--service",
+            "api",
+            "--selector",
+            "role=api",
+            "--batch",
+            "2",
+            "--max-unavailable",
+            "2",
+            "--on-failure",
+            "abort",
+        ]);
+        match cli.command {
+            Commands::Rollout(RolloutCommands::Restart(args)) => {
+                assert_eq!(args.service, "api");
+                assert_eq!(args.selector.unwrap().as_str(), "role=api");
+                assert_eq!(args.batch, 2);
+                assert_eq!(args.max_unavailable, 2);
+                assert_eq!(args.max_failures, 0);
+                assert_eq!(args.on_failure, RolloutFailurePolicy::Abort);
+                assert!(!args.resume);
+                assert_eq!(args.state_file, None);
+            }
+            _ => panic!("Expected Rollout Restart command"),
+        }
+
+        // A resumed rollout takes its agents from the progress record
+        let cli = Cli::parse_from([
+            "shiki",
+            "rollout",
+            "restart",
+            "-s",
+            "api",
+            "--resume",
+            "--state-file",
+            "/tmp/api.json",
+        ]);
+        match cli.command {
+            Commands::Rollout(RolloutCommands::Restart(args)) => {
+                assert!(args.resume);
+                assert_eq!(args.state_file(), PathBuf::from("/tmp/api.json"));
+            }
+            _ => panic!("Expected Rollout Restart command"),
+        }
+
+        assert!(Cli::try_parse_from(["shiki", "rollout", "restart", "-s", "api"]).is_err());
+    }
+
+    #[test]
+    fn test_notify_selector() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "notify",
+            "--selector",
+            "role=db",
+            "-a",
+            "restart",
+            "-s",
+            "postgres",
+            "--parallelism",
+            "2",
+            "--max-failures",
+            "1",
+        ]);
+
+        match cli.command {
+            Commands::Notify(args) => {
+                assert!(args.target.is_none());
+                assert_eq!(args.selector.unwrap().as_str(), "role=db");
+                assert_eq!(args.parallelism, Some(2));
+                assert_eq!(args.max_failures, Some(1));
+            }
+            _ => panic!("Expected Notify command"),
+        }
+
+        // Either a target or a selector is required
+        assert!(Cli::try_parse_from(["shiki", "notify", "-a", "start", "-s", "nginx"]).is_err());
+        // Fan-out limits only apply to selectors
+        assert!(Cli::try_parse_from([
+            "shiki",
+            "notify",
+            "-t",
+            "localhost:8080",
+            "-a",
+            "start",
+            "-s",
+            "nginx",
+            "--parallelism",
+            "2",
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from([
+            "shiki",
+            "notify",
+            "--selector",
+            "",
+            "-a",
+            "start",
+            "-s",
+            "nginx"
+        ])
+        .is_err());
+    }
+
     #[test]
     fn test_wait_command() {
         let cli = Cli::parse_from([
@@ -306,9 +1364,103 @@ mod tests {
                 assert_eq!(args.service, "postgres");
                 assert_eq!(args.timeout, 120);
                 assert_eq!(args.interval, 10);
+                assert_eq!(args.until, WaitCondition::Running);
+            }
+            _ => panic!("Expected Wait command"),
+        }
+    }
+
+    #[test]
+    fn test_wait_until_ready() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "wait",
+            "-t",
+            "db.local:8080",
+            "-s",
+            "postgres",
+            "--until",
+            "ready",
+        ]);
+
+        match cli.command {
+            Commands::Wait(args) => {
+                assert_eq!(args.until, WaitCondition::Ready);
             }
             _ => panic!("Expected Wait command"),
         }
+
+        assert!("invalid".parse::<WaitCondition>().is_err());
+    }
+
+    #[test]
+    fn test_notify_wait_ready() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "notify",
+            "-t",
+            "localhost:8080",
+            "-a",
+            "start",
+            "-s",
+            "postgres",
+            "--wait-ready",
+        ]);
+
+        match cli.command {
+            Commands::Notify(args) => {
+                assert!(args.wait_ready);
+                assert!(args.should_wait());
+            }
+            _ => panic!("Expected Notify command"),
+        }
+    }
+
+    #[test]
+    fn test_plan_commands() {
+        let cli = Cli::parse_from([
+            "shiki",
+            "plan",
+            "apply",
+            "stack.yaml",
+            "--on-failure",
+            "rollback",
+        ]);
+
+        match cli.command {
+            Commands::Plan(PlanCommands::Apply(args)) => {
+                assert_eq!(args.file, PathBuf::from("stack.yaml"));
+                assert_eq!(args.on_failure, Some(FailurePolicy::Rollback));
+            }
+            _ => panic!("Expected Plan Apply command"),
+        }
+
+        let cli = Cli::parse_from(["shiki", "plan", "graph", "stack.yaml"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Plan(PlanCommands::Graph(_))
+        ));
+
+        assert!(
+            Cli::try_parse_from(["shiki", "plan", "apply", "x", "--on-failure", "abort"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cluster_status_command() {
+        let cli = Cli::parse_from(["shiki", "cluster", "status"]);
+        match cli.command {
+            Commands::Cluster(ClusterCommands::Status(args)) => assert!(args.target.is_none()),
+            _ => panic!("Expected Cluster Status command"),
+        }
+
+        let cli = Cli::parse_from(["shiki", "cluster", "status", "-t", "node1:8080"]);
+        match cli.command {
+            Commands::Cluster(ClusterCommands::Status(args)) => {
+                assert_eq!(args.target, Some("node1:8080".to_string()))
+            }
+            _ => panic!("Expected Cluster Status command"),
+        }
     }
 
     #[test]
@@ -342,7 +1494,13 @@ mod tests {
         let cli = Cli::parse_from(["shiki", "config", "validate"]);
 
         match cli.command {
-            Commands::Config(ConfigCommands::Validate) => {}
+            Commands::Config(ConfigCommands::Validate(args)) => assert!(!args.no_strict),
+            _ => panic!("Expected Config Validate command"),
+        }
+
+        let cli = Cli::parse_from(["shiki", "config", "validate", "--no-strict"]);
+        match cli.command {
+            Commands::Config(ConfigCommands::Validate(args)) => assert!(args.no_strict),
             _ => panic!("Expected Config Validate command"),
         }
     }
@@ -357,6 +1515,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_config_init() {
+        let cli = Cli::parse_from(["shiki", "config", "init", "--backend", "exec", "--force"]);
+
+        match cli.command {
+            Commands::Config(ConfigCommands::Init(args)) => {
+                assert_eq!(args.backend, Some(Backend::Exec));
+                assert!(args.path.is_none());
+                assert!(args.force);
+            }
+            _ => panic!("Expected Config Init command"),
+        }
+
+        let cli = Cli::parse_from(["shiki", "config", "init", "/etc/shiki/config.yaml"]);
+        match cli.command {
+            Commands::Config(ConfigCommands::Init(args)) => {
+                assert!(args.backend.is_none());
+                assert_eq!(args.path, Some(PathBuf::from("/etc/shiki/config.yaml")));
+            }
+            _ => panic!("Expected Config Init command"),
+        }
+
+        assert!(Cli::try_parse_from(["shiki", "config", "init", "--backend", "docker"]).is_err());
+    }
+
     #[test]
     fn test_global_config_option() {
         let cli = Cli::parse_from(["shiki", "-c", "/custom/config.yaml", "serve"]);
@@ -2,19 +2,28 @@
 //!
 //! This module provides the client for communicating with shiki agents.
 
-use crate::error::{Result, ShikiError};
+use crate::cluster::forward::ROUTE_HEADER;
+use crate::cluster::Selector;
+use crate::error::{ErrorCode, Result, ShikiError};
+use crate::history::HistoryQuery;
 use crate::server::response::{
-    ApiResponse, HealthData, NotifyOptions, NotifyRequest, NotifyResponseData, ServiceDetailData,
-    ServicesListData, StatusData,
+    AcquireLockRequest, ApiResponse, BarrierData, ClusterData, ClusterNotifyRequest, DriftData,
+    FanoutOptions, FanoutResponseData, HealthData, HeartbeatData, HeartbeatRequest, HistoryData,
+    LeaderData, LockData, NotifyOptions, NotifyRequest, NotifyResponseData, ReleaseLockData,
+    ReloadData, RenewLockRequest, ServiceDetailData, ServiceOperationData, ServicesListData,
+    SetSignalRequest, SignalData, StatusData, VoteData, VoteRequest, WatchData,
 };
 use crate::service::ServiceAction;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use std::time::Duration;
 use tracing::{debug, error, info};
 
 /// Default timeout for HTTP requests.
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
 
+/// Services requested per page by [`ShikiClient::list_all_services`].
+const SERVICES_PAGE_SIZE: usize = 100;
+
 /// Shiki HTTP client for communicating with agents.
 #[derive(Debug, Clone)]
 pub struct ShikiClient {
@@ -22,13 +31,19 @@ pub struct ShikiClient {
     client: Client,
     /// Base URL of the target agent.
     base_url: String,
+    /// Bearer token sent with every request.
+    token: Option<String>,
+    /// Peers the target agent forwards requests through, ending with the
+    /// agent that handles them. Empty when the target handles them itself.
+    route: Vec<String>,
 }
 
 impl ShikiClient {
     /// Creates a new client for the specified agent URL.
     ///
     /// # Arguments
-    /// * `base_url` - Base URL of the agent (e.g., "http://localhost:8080")
+    /// * `base_url` - Base URL of the agent (e.g., "http://localhost:8080").
+    ///   A bare "host:port" is treated as "http://host:port".
     pub fn new(base_url: impl Into<String>) -> Result<Self> {
         let client = Client::builder()
             .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
@@ -39,7 +54,9 @@ impl ShikiClient {
 
         Ok(Self {
             client,
-            base_url: base_url.into(),
+            base_url: normalize_base_url(base_url.into()),
+            token: None,
+            route: Vec::new(),
         })
     }
 
@@ -51,20 +68,76 @@ impl ShikiClient {
 
         Ok(Self {
             client,
-            base_url: base_url.into(),
+            base_url: normalize_base_url(base_url.into()),
+            token: None,
+            route: Vec::new(),
         })
     }
 
+    /// Sends a bearer token with every request.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Routes requests through the target agent to a peer.
+    ///
+    /// `route` lists the peer names to traverse; the last one handles the
+    /// request. Each agent on the way must have forwarding enabled and know
+    /// the next peer. Only the notify and service endpoints can be forwarded.
+    pub fn via(mut self, route: Vec<String>) -> Self {
+        self.route = route;
+        self
+    }
+
+    /// Returns the URL of an API endpoint, through the peer route if any.
+    fn endpoint(&self, path: &str) -> String {
+        match self.route.first() {
+            Some(peer) => format!("{}/api/v1/peers/{}/{}", self.base_url, peer, path),
+            None => format!("{}/api/v1/{}", self.base_url, path),
+        }
+    }
+
+    /// Builds a GET request.
+    fn get(&self, url: &str) -> RequestBuilder {
+        self.prepare(self.client.get(url))
+    }
+
+    /// Builds a POST request.
+    fn post(&self, url: &str) -> RequestBuilder {
+        self.prepare(self.client.post(url))
+    }
+
+    /// Builds a PUT request.
+    fn put(&self, url: &str) -> RequestBuilder {
+        self.prepare(self.client.put(url))
+    }
+
+    /// Builds a DELETE request.
+    fn delete(&self, url: &str) -> RequestBuilder {
+        self.prepare(self.client.delete(url))
+    }
+
+    /// Adds the authentication and routing headers.
+    fn prepare(&self, mut builder: RequestBuilder) -> RequestBuilder {
+        if let Some(token) = &self.token {
+            builder = builder.bearer_auth(token);
+        }
+        if self.route.len() > 1 {
+            builder = builder.header(ROUTE_HEADER, self.route[1..].join(","));
+        }
+        builder
+    }
+
     /// Checks the health of the target agent.
     ///
     /// # Returns
     /// Health status data from the agent.
     pub async fn health(&self) -> Result<HealthData> {
-        let url = format!("{}/api/v1/health", self.base_url);
+        let url = self.endpoint("health");
         debug!(url = %url, "Checking agent health");
 
         let response = self
-            .client
             .get(&url)
             .send()
             .await
@@ -88,11 +161,10 @@ impl ShikiClient {
     /// # Returns
     /// Detailed status data from the agent.
     pub async fn status(&self) -> Result<StatusData> {
-        let url = format!("{}/api/v1/status", self.base_url);
+        let url = self.endpoint("status");
         debug!(url = %url, "Getting agent status");
 
         let response = self
-            .client
             .get(&url)
             .send()
             .await
@@ -111,6 +183,30 @@ impl ShikiClient {
         }
     }
 
+    /// Gets the cluster view of the target agent (itself and its peers).
+    pub async fn cluster(&self) -> Result<ClusterData> {
+        let url = self.endpoint("cluster");
+        debug!(url = %url, "Getting cluster status");
+
+        let response = self
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| ShikiError::connection_with_source(&self.base_url, e))?;
+
+        let api_response: ApiResponse<ClusterData> = response.json().await.map_err(|e| {
+            ShikiError::backend_with_source("Failed to parse cluster response".to_string(), e)
+        })?;
+
+        if api_response.success {
+            api_response
+                .data
+                .ok_or_else(|| ShikiError::backend("Cluster response missing data".to_string()))
+        } else {
+            Err(Self::extract_error(&api_response))
+        }
+    }
+
     /// Sends a notification to the target agent to perform a service operation.
     ///
     /// # Arguments
@@ -128,15 +224,35 @@ impl ShikiClient {
         wait: bool,
         timeout_seconds: u64,
     ) -> Result<NotifyResponseData> {
-        let url = format!("{}/api/v1/notify", self.base_url);
+        let options = NotifyOptions {
+            wait,
+            timeout_seconds,
+            ..Default::default()
+        };
+        self.notify_with_options(service, action, options).await
+    }
+
+    /// Sends a notification with explicit request options.
+    ///
+    /// # Arguments
+    /// * `service` - Name of the service to operate on
+    /// * `action` - Action to perform (start, stop, restart)
+    /// * `options` - Request options (wait, timeout, readiness wait)
+    ///
+    /// # Returns
+    /// Response data from the notify operation.
+    pub async fn notify_with_options(
+        &self,
+        service: &str,
+        action: ServiceAction,
+        options: NotifyOptions,
+    ) -> Result<NotifyResponseData> {
+        let url = self.endpoint("notify");
 
         let request = NotifyRequest {
             action: action.to_string(),
             service: service.to_string(),
-            options: NotifyOptions {
-                wait,
-                timeout_seconds,
-            },
+            options,
         };
 
         info!(
@@ -147,7 +263,6 @@ impl ShikiClient {
         );
 
         let response = self
-            .client
             .post(&url)
             .json(&request)
             .send()
@@ -167,6 +282,57 @@ impl ShikiClient {
         }
     }
 
+    /// Asks the target agent to notify every agent matching `selector`
+    /// (itself included) and returns the per-agent results.
+    pub async fn cluster_notify(
+        &self,
+        selector: &Selector,
+        service: &str,
+        action: ServiceAction,
+        options: NotifyOptions,
+        fanout: FanoutOptions,
+    ) -> Result<FanoutResponseData> {
+        let url = self.endpoint("cluster/notify");
+
+        let request = ClusterNotifyRequest {
+            selector: selector.clone(),
+            action: action.to_string(),
+            service: service.to_string(),
+            options,
+            fanout,
+        };
+
+        info!(
+            url = %url,
+            selector = %selector,
+            service = %service,
+            action = %action,
+            "Sending cluster notify request"
+        );
+
+        let response = self
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| ShikiError::connection_with_source(&self.base_url, e))?;
+
+        let api_response: ApiResponse<FanoutResponseData> = response.json().await.map_err(|e| {
+            ShikiError::backend_with_source(
+                "Failed to parse cluster notify response".to_string(),
+                e,
+            )
+        })?;
+
+        if api_response.success {
+            api_response.data.ok_or_else(|| {
+                ShikiError::backend("Cluster notify response missing data".to_string())
+            })
+        } else {
+            Err(Self::extract_error(&api_response))
+        }
+    }
+
     /// Lists all services on the target agent.
     ///
     /// # Arguments
@@ -182,7 +348,7 @@ impl ShikiClient {
         limit: Option<usize>,
         offset: Option<usize>,
     ) -> Result<ServicesListData> {
-        let mut url = format!("{}/api/v1/services", self.base_url);
+        let mut url = self.endpoint("services");
         let mut params = Vec::new();
 
         if let Some(status) = status_filter {
@@ -202,7 +368,6 @@ impl ShikiClient {
         debug!(url = %url, "Listing services");
 
         let response = self
-            .client
             .get(&url)
             .send()
             .await
@@ -229,11 +394,10 @@ impl ShikiClient {
     /// # Returns
     /// Service details from the agent.
     pub async fn get_service(&self, name: &str) -> Result<ServiceDetailData> {
-        let url = format!("{}/api/v1/services/{}", self.base_url, name);
+        let url = self.endpoint(&format!("services/{}", name));
         debug!(url = %url, service = %name, "Getting service details");
 
         let response = self
-            .client
             .get(&url)
             .send()
             .await
@@ -282,6 +446,59 @@ impl ShikiClient {
             .await
     }
 
+    /// Performs an action through the service endpoints
+    /// (`POST /api/v1/services/{name}/{action}`).
+    ///
+    /// # Arguments
+    /// * `name` - Name of the service
+    /// * `action` - Action to perform
+    pub async fn service_action(
+        &self,
+        name: &str,
+        action: ServiceAction,
+    ) -> Result<ServiceOperationData> {
+        let url = self.endpoint(&format!("services/{}/{}", name, action));
+        debug!(url = %url, service = %name, action = %action, "Performing service action");
+        self.data_response("service action", self.post(&url)).await
+    }
+
+    /// Lists the services of the agent, fetching as many pages as needed.
+    ///
+    /// # Arguments
+    /// * `status_filter` - Optional status filter (running, stopped, failed)
+    /// * `offset` - Number of services to skip
+    /// * `limit` - Maximum number of services, all when `None`
+    pub async fn list_all_services(
+        &self,
+        status_filter: Option<&str>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<ServicesListData> {
+        let mut services = Vec::new();
+        let mut total;
+        loop {
+            let wanted = limit.map_or(SERVICES_PAGE_SIZE, |limit| {
+                (limit - services.len()).min(SERVICES_PAGE_SIZE)
+            });
+            let page = self
+                .list_services(status_filter, Some(wanted), Some(offset + services.len()))
+                .await?;
+            total = page.total;
+            let received = page.services.len();
+            services.extend(page.services);
+            let done = limit.is_some_and(|limit| services.len() >= limit);
+            if done || received == 0 || offset + services.len() >= total {
+                break;
+            }
+        }
+        Ok(ServicesListData {
+            limit: limit.unwrap_or(services.len()),
+            services,
+            total,
+            offset,
+        })
+    }
+
     /// Waits for a service to reach a specific state.
     ///
     /// # Arguments
@@ -291,14 +508,14 @@ impl ShikiClient {
     /// * `poll_interval` - Time between status checks
     ///
     /// # Returns
-    /// Ok(()) if the service reaches the target state, Err if timeout.
+    /// The service details once it reaches the target state, Err if timeout.
     pub async fn wait_for_service(
         &self,
         name: &str,
         target_status: &str,
         timeout: Duration,
         poll_interval: Duration,
-    ) -> Result<()> {
+    ) -> Result<ServiceDetailData> {
         let start = std::time::Instant::now();
 
         info!(
@@ -317,7 +534,7 @@ impl ShikiClient {
                             status = %service.status,
                             "Service reached target state"
                         );
-                        return Ok(());
+                        return Ok(service);
                     }
 
                     debug!(
@@ -347,9 +564,316 @@ impl ShikiClient {
         }
     }
 
+    /// Waits for a service's readiness probe to pass.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the service
+    /// * `timeout` - Maximum time to wait
+    /// * `poll_interval` - Time between status checks
+    ///
+    /// # Returns
+    /// The service details once it reports ready, Err on timeout or if the
+    /// service has no readiness probe.
+    pub async fn wait_for_ready(
+        &self,
+        name: &str,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<ServiceDetailData> {
+        let start = std::time::Instant::now();
+
+        info!(
+            service = %name,
+            timeout_secs = %timeout.as_secs(),
+            "Waiting for service to become ready"
+        );
+
+        loop {
+            match self.get_service(name).await {
+                Ok(service) => match service.ready {
+                    Some(true) => {
+                        info!(service = %name, "Service is ready");
+                        return Ok(service);
+                    }
+                    Some(false) => {
+                        debug!(
+                            service = %name,
+                            current_status = %service.status,
+                            "Service not yet ready"
+                        );
+                    }
+                    None => {
+                        return Err(ShikiError::invalid_request(format!(
+                            "No readiness probe configured for service: {}",
+                            name
+                        )));
+                    }
+                },
+                Err(e) => {
+                    error!(
+                        service = %name,
+                        error = %e,
+                        "Failed to get service status while waiting"
+                    );
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(ShikiError::Timeout {
+                    operation: format!("wait for {} to be ready", name),
+                    seconds: timeout.as_secs(),
+                });
+            }
+
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Lists the signals held by the target agent (or its coordinator).
+    pub async fn signals(&self) -> Result<Vec<SignalData>> {
+        let url = self.endpoint("signals");
+        debug!(url = %url, "Listing signals");
+        self.data_response("signal", self.get(&url)).await
+    }
+
+    /// Returns the state of a signal.
+    pub async fn signal(&self, name: &str) -> Result<SignalData> {
+        let url = self.endpoint(&format!("signals/{}", name));
+        debug!(url = %url, "Getting signal");
+        self.data_response("signal", self.get(&url)).await
+    }
+
+    /// Sets a signal. Without `agent`, the target agent's name is used.
+    pub async fn set_signal(
+        &self,
+        name: &str,
+        agent: Option<&str>,
+        ttl_seconds: Option<u64>,
+    ) -> Result<SignalData> {
+        let url = self.endpoint(&format!("signals/{}", name));
+        let request = SetSignalRequest {
+            agent: agent.map(str::to_string),
+            ttl_seconds,
+        };
+        info!(url = %url, signal = %name, "Setting signal");
+        self.data_response("signal", self.put(&url).json(&request))
+            .await
+    }
+
+    /// Clears a signal for one signaler, or for everyone when `agent` is `None`.
+    pub async fn clear_signal(&self, name: &str, agent: Option<&str>) -> Result<SignalData> {
+        let url = self.endpoint(&format!("signals/{}", name));
+        let mut request = self.delete(&url);
+        if let Some(agent) = agent {
+            request = request.query(&[("agent", agent)]);
+        }
+        info!(url = %url, signal = %name, "Clearing signal");
+        self.data_response("signal", request).await
+    }
+
+    /// Waits until `count` signalers have set a signal.
+    pub async fn wait_barrier(
+        &self,
+        name: &str,
+        count: usize,
+        timeout: Duration,
+    ) -> Result<BarrierData> {
+        let url = self.endpoint(&format!("barriers/{}/wait", name));
+        info!(url = %url, signal = %name, count = count, "Waiting at barrier");
+
+        let request = self
+            .get(&url)
+            .query(&[("count", count as u64), ("timeout", timeout.as_secs())])
+            // The agent answers a timed out wait itself
+            .timeout(timeout + Duration::from_secs(10));
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ShikiError::connection_with_source(&self.base_url, e))?;
+
+        let api_response: ApiResponse<BarrierData> = response.json().await.map_err(|e| {
+            ShikiError::backend_with_source("Failed to parse barrier response".to_string(), e)
+        })?;
+
+        match (api_response.success, &api_response.error) {
+            (true, _) => api_response
+                .data
+                .ok_or_else(|| ShikiError::backend("Barrier response missing data".to_string())),
+            (false, Some(err)) if err.code == ErrorCode::Timeout => Err(ShikiError::Timeout {
+                operation: err
+                    .details
+                    .as_ref()
+                    .and_then(|d| d.fields.get("operation"))
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("barrier {}", name)),
+                seconds: timeout.as_secs(),
+            }),
+            (false, _) => Err(Self::extract_error(&api_response)),
+        }
+    }
+
+    /// Lists the locks held by the target agent (or its coordinator).
+    pub async fn locks(&self) -> Result<Vec<LockData>> {
+        let url = self.endpoint("locks");
+        debug!(url = %url, "Listing locks");
+        self.data_response("lock", self.get(&url)).await
+    }
+
+    /// Acquires a lock, waiting up to `wait_seconds` while another owner
+    /// holds it. Without `owner`, the target agent's name is used.
+    pub async fn acquire_lock(
+        &self,
+        name: &str,
+        owner: Option<&str>,
+        ttl_seconds: Option<u64>,
+        wait_seconds: Option<u64>,
+    ) -> Result<LockData> {
+        let url = self.endpoint(&format!("locks/{}", name));
+        let request = AcquireLockRequest {
+            owner: owner.map(str::to_string),
+            ttl_seconds,
+            wait_seconds,
+        };
+        info!(url = %url, lock = %name, "Acquiring lock");
+        let request = self
+            .post(&url)
+            .json(&request)
+            // The agent answers a timed out wait itself
+            .timeout(Duration::from_secs(
+                wait_seconds.unwrap_or(0) + DEFAULT_TIMEOUT_SECS,
+            ));
+        self.data_response("lock", request).await
+    }
+
+    /// Extends a held lock.
+    pub async fn renew_lock(
+        &self,
+        name: &str,
+        fencing_token: u64,
+        ttl_seconds: Option<u64>,
+    ) -> Result<LockData> {
+        let url = self.endpoint(&format!("locks/{}/renew", name));
+        let request = RenewLockRequest {
+            fencing_token,
+            ttl_seconds,
+        };
+        debug!(url = %url, lock = %name, "Renewing lock");
+        self.data_response("lock", self.post(&url).json(&request))
+            .await
+    }
+
+    /// Releases a held lock.
+    pub async fn release_lock(&self, name: &str, fencing_token: u64) -> Result<ReleaseLockData> {
+        let url = self.endpoint(&format!("locks/{}", name));
+        info!(url = %url, lock = %name, "Releasing lock");
+        let request = self.delete(&url).query(&[("fencing_token", fencing_token)]);
+        self.data_response("lock", request).await
+    }
+
+    /// Queries the operation history of the target agent.
+    pub async fn history(&self, query: &HistoryQuery) -> Result<HistoryData> {
+        let url = self.endpoint("history");
+        debug!(url = %url, "Querying operation history");
+        self.data_response("history", self.get(&url).query(query))
+            .await
+    }
+
+    /// Returns the drift between the declared and actual state of the
+    /// services of the target agent.
+    pub async fn drift(&self) -> Result<DriftData> {
+        let url = self.endpoint("drift");
+        debug!(url = %url, "Getting desired-state drift");
+        self.data_response("drift", self.get(&url)).await
+    }
+
+    /// Runs a reconciliation pass on the target agent.
+    pub async fn reconcile(&self) -> Result<DriftData> {
+        let url = self.endpoint("reconcile");
+        debug!(url = %url, "Reconciling desired state");
+        self.data_response("reconcile", self.post(&url)).await
+    }
+
+    /// Makes the target agent reload its configuration file.
+    pub async fn reload_config(&self) -> Result<ReloadData> {
+        let url = self.endpoint("admin/reload");
+        debug!(url = %url, "Reloading configuration");
+        self.data_response("reload configuration", self.post(&url))
+            .await
+    }
+
+    /// Returns the service watchers of the target agent.
+    pub async fn watch(&self) -> Result<WatchData> {
+        let url = self.endpoint("watch");
+        debug!(url = %url, "Getting service watchers");
+        self.data_response("watch", self.get(&url)).await
+    }
+
+    /// Returns the leader election state of the target agent.
+    pub async fn leader(&self) -> Result<LeaderData> {
+        let url = self.endpoint("cluster/leader");
+        debug!(url = %url, "Getting cluster leader");
+        self.data_response("leader", self.get(&url)).await
+    }
+
+    /// Asks the target agent for its vote in a leader election.
+    pub async fn request_vote(&self, request: &VoteRequest) -> Result<VoteData> {
+        let url = self.endpoint("cluster/election/vote");
+        debug!(url = %url, term = request.term, "Requesting vote");
+        self.data_response("vote", self.post(&url).json(request))
+            .await
+    }
+
+    /// Renews the leader's lease on the target agent.
+    pub async fn heartbeat(&self, request: &HeartbeatRequest) -> Result<HeartbeatData> {
+        let url = self.endpoint("cluster/election/heartbeat");
+        debug!(url = %url, term = request.term, "Sending leader heartbeat");
+        self.data_response("heartbeat", self.post(&url).json(request))
+            .await
+    }
+
+    /// Sends a request and extracts the data of its response.
+    async fn data_response<T: serde::de::DeserializeOwned>(
+        &self,
+        what: &str,
+        request: RequestBuilder,
+    ) -> Result<T> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ShikiError::connection_with_source(&self.base_url, e))?;
+
+        let api_response: ApiResponse<T> = response.json().await.map_err(|e| {
+            ShikiError::backend_with_source(format!("Failed to parse {} response", what), e)
+        })?;
+
+        if api_response.success {
+            api_response
+                .data
+                .ok_or_else(|| ShikiError::backend(format!("Missing data in {} response", what)))
+        } else {
+            Err(Self::extract_error(&api_response))
+        }
+    }
+
     /// Extracts an error from an API response.
     fn extract_error<T>(response: &ApiResponse<T>) -> ShikiError {
         if let Some(err) = &response.error {
+            if err.code == ErrorCode::LockConflict {
+                // Keep lock conflicts distinguishable for callers that retry
+                let field = |name: &str| {
+                    err.details
+                        .as_ref()
+                        .and_then(|d| d.fields.get(name))
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string)
+                };
+                return ShikiError::LockConflict {
+                    lock: field("lock").unwrap_or_default(),
+                    holder: field("holder"),
+                    reason: field("reason").unwrap_or_else(|| err.message.clone()),
+                };
+            }
             ShikiError::backend(format!("[{}] {}", err.code, err.message))
         } else {
             ShikiError::backend("Unknown error".to_string())
@@ -357,6 +881,16 @@ impl ShikiClient {
     }
 }
 
+/// Adds the default scheme to a bare "host:port" and strips trailing slashes.
+fn normalize_base_url(base_url: String) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    if base_url.contains("://") {
+        base_url.to_string()
+    } else {
+        format!("http://{}", base_url)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -374,6 +908,15 @@ mod tests {
         assert_eq!(client.base_url, "http://localhost:8080");
     }
 
+    #[test]
+    fn test_client_normalizes_base_url() {
+        let client = ShikiClient::new("db.local:8080").unwrap();
+        assert_eq!(client.base_url, "http://db.local:8080");
+
+        let client = ShikiClient::new("https://db.local:8443/").unwrap();
+        assert_eq!(client.base_url, "https://db.local:8443");
+    }
+
     // Integration tests would require a running server
     // These are marked as ignored by default
     #[tokio::test]
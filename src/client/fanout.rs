@@ -0,0 +1,311 @@
+//! Multi-target fan-out.
+//!
+//! Runs an operation against several agents concurrently, with a limit on
+//! the number of agents in flight and a failure tolerance: once more than
+//! `max_failures` agents have failed, agents that have not been contacted
+//! yet are skipped.
+
+use crate::client::ShikiClient;
+use crate::cluster::Selector;
+use crate::error::Result;
+use crate::server::response::{AgentResult, FanoutStatus, NotifyOptions, NotifyResponseData};
+use crate::service::ServiceAction;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{debug, info};
+
+/// An agent to fan out to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FanoutTarget {
+    /// Agent name.
+    pub name: String,
+    /// Agent address.
+    pub address: String,
+    /// Whether the target is the agent performing the fan-out.
+    pub local: bool,
+    /// Bearer token for the agent.
+    pub token: Option<String>,
+}
+
+impl FanoutTarget {
+    /// Creates a remote target.
+    pub fn new(name: impl Into<String>, address: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            address: address.into(),
+            local: false,
+            token: None,
+        }
+    }
+
+    /// Returns a client for the agent.
+    pub fn client(&self) -> Result<ShikiClient> {
+        let client = ShikiClient::new(&self.address)?;
+        Ok(match &self.token {
+            Some(token) => client.with_token(token),
+            None => client,
+        })
+    }
+}
+
+/// Limits of a fan-out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FanoutLimits {
+    /// Maximum number of targets in flight.
+    pub parallelism: usize,
+    /// Number of failed targets tolerated.
+    pub max_failures: usize,
+}
+
+/// Resolves the agents matching a selector. Candidates are given as
+/// `(target, tags)` pairs; order is preserved.
+pub fn select_targets<'a>(
+    selector: &Selector,
+    candidates: impl IntoIterator<Item = (FanoutTarget, &'a [String])>,
+) -> Vec<FanoutTarget> {
+    candidates
+        .into_iter()
+        .filter(|(target, tags)| selector.matches(&target.name, tags))
+        .map(|(target, _)| target)
+        .collect()
+}
+
+/// Runs `op` for every target and returns one result per target, in order.
+///
+/// `op` returns the agent's response; a response whose `result` is
+/// `"failed"` counts as a failure.
+pub async fn fan_out<F, Fut>(
+    targets: &[FanoutTarget],
+    limits: FanoutLimits,
+    op: F,
+) -> Vec<AgentResult>
+where
+    F: Fn(FanoutTarget) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<NotifyResponseData>> + Send + 'static,
+{
+    let op = Arc::new(op);
+    let semaphore = Arc::new(Semaphore::new(limits.parallelism.max(1)));
+    let failures = Arc::new(AtomicUsize::new(0));
+    let mut tasks = JoinSet::new();
+
+    for (index, target) in targets.iter().cloned().enumerate() {
+        // Permits are taken here rather than in the tasks so that targets
+        // start in order and the failure check sees every earlier result.
+        let permit = Arc::clone(&semaphore).acquire_owned().await;
+        if failures.load(Ordering::SeqCst) > limits.max_failures {
+            debug!(agent = %target.name, "Skipping agent, failure tolerance exceeded");
+            tasks.spawn(async move { (index, skipped(target)) });
+            continue;
+        }
+
+        let op = Arc::clone(&op);
+        let failures = Arc::clone(&failures);
+        tasks.spawn(async move {
+            let _permit = permit;
+
+            let started = Instant::now();
+            let outcome = op(target.clone()).await;
+            let duration_ms = Some(started.elapsed().as_millis() as u64);
+
+            let result = match outcome {
+                Ok(data) if data.result != "failed" => AgentResult {
+                    agent: target.name,
+                    address: target.address,
+                    status: FanoutStatus::Succeeded,
+                    duration_ms,
+                    result: Some(data),
+                    error: None,
+                },
+                Ok(data) => AgentResult {
+                    agent: target.name,
+                    address: target.address,
+                    status: FanoutStatus::Failed,
+                    duration_ms,
+                    error: data.message.clone(),
+                    result: Some(data),
+                },
+                Err(e) => AgentResult {
+                    agent: target.name,
+                    address: target.address,
+                    status: FanoutStatus::Failed,
+                    duration_ms,
+                    result: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            if result.status == FanoutStatus::Failed {
+                failures.fetch_add(1, Ordering::SeqCst);
+            }
+            (index, result)
+        });
+    }
+
+    let mut results: Vec<Option<AgentResult>> = vec![None; targets.len()];
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((index, result)) = joined {
+            results[index] = Some(result);
+        }
+    }
+
+    results
+        .into_iter()
+        .zip(targets.iter())
+        .map(|(result, target)| {
+            result.unwrap_or_else(|| AgentResult {
+                error: Some("Fan-out task failed".to_string()),
+                status: FanoutStatus::Failed,
+                ..skipped(target.clone())
+            })
+        })
+        .collect()
+}
+
+fn skipped(target: FanoutTarget) -> AgentResult {
+    AgentResult {
+        agent: target.name,
+        address: target.address,
+        status: FanoutStatus::Skipped,
+        duration_ms: None,
+        result: None,
+        error: None,
+    }
+}
+
+/// Sends the same notification to several agents concurrently.
+pub async fn notify_many(
+    targets: &[FanoutTarget],
+    service: &str,
+    action: ServiceAction,
+    options: NotifyOptions,
+    limits: FanoutLimits,
+) -> Vec<AgentResult> {
+    info!(
+        targets = targets.len(),
+        service = %service,
+        action = %action,
+        parallelism = limits.parallelism,
+        max_failures = limits.max_failures,
+        "Fanning out notification"
+    );
+
+    let service = service.to_string();
+    fan_out(targets, limits, move |target| {
+        let service = service.clone();
+        let options = options.clone();
+        async move {
+            target
+                .client()?
+                .notify_with_options(&service, action, options)
+                .await
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ShikiError;
+    use uuid::Uuid;
+
+    fn response(result: &str) -> NotifyResponseData {
+        NotifyResponseData {
+            request_id: Uuid::new_v4(),
+            service: "app".to_string(),
+            action: "start".to_string(),
+            result: result.to_string(),
+            previous_status: None,
+            current_status: None,
+            duration_ms: None,
+            message: None,
+            related: Vec::new(),
+        }
+    }
+
+    fn targets(n: usize) -> Vec<FanoutTarget> {
+        (0..n)
+            .map(|i| FanoutTarget::new(format!("node-{}", i), format!("10.0.0.{}:8080", i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_select_targets() {
+        let selector: Selector = "role=db".parse().unwrap();
+        let db = vec!["role=db".to_string()];
+        let web = vec!["role=web".to_string()];
+        let all = targets(3);
+
+        let selected = select_targets(
+            &selector,
+            vec![
+                (all[0].clone(), db.as_slice()),
+                (all[1].clone(), web.as_slice()),
+                (all[2].clone(), db.as_slice()),
+            ],
+        );
+        assert_eq!(selected, vec![all[0].clone(), all[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_respects_parallelism() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let limits = FanoutLimits {
+            parallelism: 2,
+            max_failures: 0,
+        };
+
+        let (in_flight_op, peak_op) = (Arc::clone(&in_flight), Arc::clone(&peak));
+        let results = fan_out(&targets(6), limits, move |_| {
+            let in_flight = Arc::clone(&in_flight_op);
+            let peak = Arc::clone(&peak_op);
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(response("completed"))
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 6);
+        assert!(results.iter().all(|r| r.status == FanoutStatus::Succeeded));
+        assert_eq!(results[3].agent, "node-3");
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_failure_tolerance() {
+        let limits = FanoutLimits {
+            parallelism: 1,
+            max_failures: 1,
+        };
+
+        let results = fan_out(&targets(5), limits, |target| async move {
+            match target.name.as_str() {
+                "node-1" => Ok(response("failed")),
+                "node-2" => Err(ShikiError::connection(target.address)),
+                _ => Ok(response("completed")),
+            }
+        })
+        .await;
+
+        let statuses: Vec<FanoutStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                FanoutStatus::Succeeded,
+                FanoutStatus::Failed,
+                FanoutStatus::Failed,
+                FanoutStatus::Skipped,
+                FanoutStatus::Skipped,
+            ]
+        );
+        assert!(results[2].error.as_deref().unwrap().contains("10.0.0.2"));
+    }
+}
@@ -4,5 +4,7 @@
 //! with other shiki agents.
 
 pub mod api;
+pub mod fanout;
 
 pub use api::ShikiClient;
+pub use fanout::{notify_many, FanoutLimits, FanoutTarget};
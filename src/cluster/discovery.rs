@@ -0,0 +1,466 @@
+//! Automatic peer discovery.
+//!
+//! Each agent periodically sends an announcement (name, address, port,
+//! tags, version) to a UDP multicast group or broadcast address and listens
+//! for the announcements of others. Announced agents are merged into the
+//! peer registry next to the static `cluster.peers` entries, and removed
+//! again once they stop announcing for `expire_seconds`.
+//!
+//! With a shared `key`, announcements carry an HMAC-SHA256 signature of the
+//! payload and unsigned or forged announcements are ignored. The payload
+//! includes a timestamp so that old announcements cannot be replayed after
+//! the expiry window.
+
+use crate::cluster::peers::{PeerSource, PeerStatus};
+use crate::config::DiscoveryConfig;
+use crate::error::{Result, ShikiError};
+use crate::util::to_hex;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+/// Maximum size of an announcement datagram.
+const MAX_DATAGRAM: usize = 8192;
+
+/// Information an agent announces about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Announcement {
+    /// Agent name.
+    pub name: String,
+    /// Advertised host or host:port; the source address is used when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// HTTP port of the agent.
+    pub port: u16,
+    /// Whether the agent serves HTTPS.
+    #[serde(default)]
+    pub tls: bool,
+    /// Agent tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Agent version.
+    pub version: String,
+    /// Send time in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub timestamp_ms: i64,
+}
+
+impl Announcement {
+    /// Returns the address peers should use to reach the announcing agent.
+    pub fn peer_address(&self, source: SocketAddr) -> String {
+        let host_port = match &self.address {
+            Some(address) if address.contains(':') => address.clone(),
+            Some(host) => format!("{}:{}", host, self.port),
+            None => format!("{}:{}", source.ip(), self.port),
+        };
+        if self.tls {
+            format!("https://{}", host_port)
+        } else {
+            host_port
+        }
+    }
+}
+
+/// Wire format: the serialized announcement and its signature.
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    payload: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+/// Encodes an announcement, signing it when a key is given.
+pub fn encode(announcement: &Announcement, key: Option<&str>) -> Result<Vec<u8>> {
+    let payload = serde_json::to_string(announcement)?;
+    let signature = key.map(|key| to_hex(&sign(key, payload.as_bytes())));
+    Ok(serde_json::to_vec(&Envelope { payload, signature })?)
+}
+
+/// Decodes and authenticates an announcement.
+///
+/// With a key, the signature must be present and valid. Announcements
+/// whose timestamp is further than `max_age` from now are rejected.
+pub fn decode(data: &[u8], key: Option<&str>, max_age: Duration) -> Result<Announcement> {
+    let envelope: Envelope = serde_json::from_slice(data)?;
+
+    if let Some(key) = key {
+        let signature = envelope
+            .signature
+            .as_deref()
+            .and_then(from_hex)
+            .ok_or_else(|| ShikiError::AuthFailed {
+                reason: "Unsigned announcement".to_string(),
+            })?;
+        let mut mac = new_mac(key);
+        mac.update(envelope.payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| ShikiError::AuthFailed {
+                reason: "Invalid announcement signature".to_string(),
+            })?;
+    }
+
+    let announcement: Announcement = serde_json::from_str(&envelope.payload)?;
+    let age_ms = (Utc::now().timestamp_millis() - announcement.timestamp_ms).unsigned_abs();
+    if age_ms > max_age.as_millis() as u64 {
+        return Err(ShikiError::invalid_request(format!(
+            "Stale announcement from {}",
+            announcement.name
+        )));
+    }
+    Ok(announcement)
+}
+
+fn new_mac(key: &str) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length
+    Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length")
+}
+
+fn sign(key: &str, payload: &[u8]) -> Vec<u8> {
+    let mut mac = new_mac(key);
+    mac.update(payload);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Announces this agent and listens for other agents.
+pub struct Discovery {
+    /// Socket bound to the discovery port.
+    socket: UdpSocket,
+    /// Multicast group or broadcast address to announce to.
+    target: SocketAddr,
+    /// This agent's announcement.
+    local: Announcement,
+    /// Shared key.
+    key: Option<String>,
+    /// Interval between announcements.
+    interval: Duration,
+    /// Age after which a silent peer is removed.
+    expire: Duration,
+}
+
+impl Discovery {
+    /// Binds the discovery socket. Several agents on one host may bind the
+    /// same port.
+    pub fn bind(config: &DiscoveryConfig, local: Announcement) -> Result<Self> {
+        let group: Ipv4Addr = config.group.parse().map_err(|_| {
+            ShikiError::config(format!("Invalid discovery group: {}", config.group))
+        })?;
+        let interface: Ipv4Addr = config.interface.parse().map_err(|_| {
+            ShikiError::config(format!("Invalid discovery interface: {}", config.interface))
+        })?;
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.set_reuse_port(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, config.port).into())?;
+        if group.is_multicast() {
+            socket.join_multicast_v4(&group, &interface)?;
+            socket.set_multicast_if_v4(&interface)?;
+            socket.set_multicast_loop_v4(true)?;
+        } else {
+            socket.set_broadcast(true)?;
+        }
+        let socket = UdpSocket::from_std(socket.into())?;
+
+        info!(
+            group = %group,
+            port = config.port,
+            interface = %interface,
+            authenticated = config.key.is_some(),
+            "Peer discovery enabled"
+        );
+
+        Ok(Self {
+            socket,
+            target: SocketAddr::V4(SocketAddrV4::new(group, config.port)),
+            local,
+            key: config.key.clone(),
+            interval: Duration::from_secs(config.announce_interval_seconds),
+            expire: Duration::from_secs(config.expire_seconds),
+        })
+    }
+
+    /// Overrides the announcement interval and expiry age.
+    pub fn with_timing(mut self, interval: Duration, expire: Duration) -> Self {
+        self.interval = interval;
+        self.expire = expire;
+        self
+    }
+
+    /// Announces, listens and expires peers until the task is aborted.
+    pub async fn run(mut self, peers: Arc<RwLock<Vec<PeerStatus>>>) {
+        let mut ticker = tokio::time::interval(self.interval);
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    self.announce().await;
+                    expire_peers(&peers, self.expire);
+                }
+                received = self.socket.recv_from(&mut buf) => match received {
+                    Ok((len, source)) => self.receive(&buf[..len], source, &peers),
+                    Err(e) => warn!(error = %e, "Failed to receive discovery announcement"),
+                },
+            }
+        }
+    }
+
+    /// Sends this agent's announcement.
+    async fn announce(&mut self) {
+        self.local.timestamp_ms = Utc::now().timestamp_millis();
+        let sent = match encode(&self.local, self.key.as_deref()) {
+            Ok(data) => self.socket.send_to(&data, self.target).await.map(|_| ()),
+            Err(e) => {
+                warn!(error = %e, "Failed to encode discovery announcement");
+                return;
+            }
+        };
+        if let Err(e) = sent {
+            warn!(target = %self.target, error = %e, "Failed to send discovery announcement");
+        }
+    }
+
+    /// Merges a received announcement into the registry.
+    fn receive(&self, data: &[u8], source: SocketAddr, peers: &RwLock<Vec<PeerStatus>>) {
+        let announcement = match decode(data, self.key.as_deref(), self.expire) {
+            Ok(announcement) => announcement,
+            Err(e) => {
+                debug!(source = %source, error = %e, "Ignoring discovery announcement");
+                return;
+            }
+        };
+        if announcement.name == self.local.name {
+            return;
+        }
+
+        let address = announcement.peer_address(source);
+        let Ok(mut peers) = peers.write() else {
+            return;
+        };
+        let now = Utc::now();
+
+        match peers.iter_mut().find(|p| p.name == announcement.name) {
+            // Static configuration takes precedence over announcements
+            Some(peer) if peer.source == PeerSource::Static => {
+                peer.last_announced = Some(now);
+            }
+            Some(peer) => {
+                if peer.address != address {
+                    info!(peer = %peer.name, address = %address, "Discovered peer moved");
+                    peer.address = address;
+                    peer.reachable = false;
+                }
+                peer.tags = announcement.tags;
+                peer.version = Some(announcement.version);
+                peer.last_announced = Some(now);
+            }
+            None => {
+                info!(peer = %announcement.name, address = %address, "Discovered peer");
+                let mut peer = PeerStatus::new(
+                    &announcement.name,
+                    &address,
+                    &announcement.tags,
+                    PeerSource::Discovered,
+                );
+                peer.version = Some(announcement.version);
+                peer.last_announced = Some(now);
+                peers.push(peer);
+            }
+        }
+    }
+}
+
+/// Removes discovered peers that have not announced themselves within `expire`.
+fn expire_peers(peers: &RwLock<Vec<PeerStatus>>, expire: Duration) {
+    let Ok(mut peers) = peers.write() else {
+        return;
+    };
+    let cutoff = Utc::now() - chrono::Duration::from_std(expire).unwrap_or(chrono::Duration::MAX);
+    peers.retain(|peer| {
+        let alive =
+            peer.source == PeerSource::Static || peer.last_announced.is_some_and(|t| t >= cutoff);
+        if !alive {
+            info!(peer = %peer.name, address = %peer.address, "Discovered peer expired");
+        }
+        alive
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::PeerMonitor;
+    use crate::config::PeerConfig;
+
+    fn announcement(name: &str) -> Announcement {
+        Announcement {
+            name: name.to_string(),
+            address: None,
+            port: 8080,
+            tls: false,
+            tags: vec!["role=db".to_string()],
+            version: "0.1.0".to_string(),
+            timestamp_ms: Utc::now().timestamp_millis(),
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let max_age = Duration::from_secs(30);
+        let original = announcement("node-1");
+
+        let data = encode(&original, None).unwrap();
+        assert_eq!(decode(&data, None, max_age).unwrap(), original);
+
+        let signed = encode(&original, Some("secret")).unwrap();
+        assert_eq!(decode(&signed, Some("secret"), max_age).unwrap(), original);
+        // Signatures are ignored when no key is configured
+        assert!(decode(&signed, None, max_age).is_ok());
+
+        assert!(decode(&data, Some("secret"), max_age).is_err());
+        assert!(decode(&signed, Some("other"), max_age).is_err());
+
+        let tampered = String::from_utf8(signed)
+            .unwrap()
+            .replace("node-1", "node-2");
+        assert!(decode(tampered.as_bytes(), Some("secret"), max_age).is_err());
+
+        let mut stale = original;
+        stale.timestamp_ms -= 60_000;
+        let data = encode(&stale, Some("secret")).unwrap();
+        assert!(decode(&data, Some("secret"), max_age).is_err());
+    }
+
+    #[test]
+    fn test_peer_address() {
+        let source: SocketAddr = "10.0.0.5:7946".parse().unwrap();
+        let mut a = announcement("node-1");
+        assert_eq!(a.peer_address(source), "10.0.0.5:8080");
+
+        a.address = Some("db.internal".to_string());
+        assert_eq!(a.peer_address(source), "db.internal:8080");
+
+        a.address = Some("db.internal:9090".to_string());
+        a.tls = true;
+        assert_eq!(a.peer_address(source), "https://db.internal:9090");
+    }
+
+    /// Returns a free UDP port.
+    fn free_port() -> u16 {
+        std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn loopback_config(port: u16, key: Option<&str>) -> DiscoveryConfig {
+        DiscoveryConfig {
+            enabled: true,
+            port,
+            interface: "127.0.0.1".to_string(),
+            key: key.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    /// Starts an agent's registry with discovery on loopback.
+    fn start(name: &str, port: u16, key: Option<&str>, peers: &[PeerConfig]) -> PeerMonitor {
+        let monitor = PeerMonitor::new(peers, Duration::from_secs(10), Duration::from_secs(1));
+        let discovery = Discovery::bind(&loopback_config(port, key), announcement(name))
+            .unwrap()
+            .with_timing(Duration::from_millis(50), Duration::from_millis(400));
+        monitor.spawn_discovery(discovery);
+        monitor
+    }
+
+    async fn wait_for(monitor: &PeerMonitor, condition: impl Fn(&[PeerStatus]) -> bool) -> bool {
+        for _ in 0..60 {
+            if condition(&monitor.peers()) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_discovery_on_loopback() {
+        let port = free_port();
+        let static_peer = PeerConfig {
+            name: "node-3".to_string(),
+            address: "10.0.0.3:8080".to_string(),
+            tags: vec![],
+            token: None,
+        };
+        let a = start(
+            "node-1",
+            port,
+            Some("k"),
+            std::slice::from_ref(&static_peer),
+        );
+        let b = start("node-2", port, Some("k"), &[]);
+        let c = start("node-3", port, Some("k"), &[]);
+        let intruder = start("intruder", port, Some("wrong"), &[]);
+
+        assert!(
+            wait_for(&a, |peers| peers.iter().any(|p| p.name == "node-2")).await,
+            "node-2 was not discovered"
+        );
+        assert!(wait_for(&b, |peers| peers.len() == 2).await);
+
+        let peers = a.peers();
+        let discovered = peers.iter().find(|p| p.name == "node-2").unwrap();
+        assert_eq!(discovered.source, PeerSource::Discovered);
+        assert_eq!(discovered.address, "127.0.0.1:8080");
+        assert_eq!(discovered.tags, vec!["role=db"]);
+        assert_eq!(discovered.version.as_deref(), Some("0.1.0"));
+
+        // Static entries are kept as configured
+        assert!(
+            wait_for(&a, |peers| peers
+                .iter()
+                .any(|p| p.name == "node-3" && p.last_announced.is_some()))
+            .await
+        );
+        let node3: Vec<_> = a
+            .peers()
+            .into_iter()
+            .filter(|p| p.name == "node-3")
+            .collect();
+        assert_eq!(node3.len(), 1);
+        assert_eq!(node3[0].source, PeerSource::Static);
+        assert_eq!(node3[0].address, "10.0.0.3:8080");
+
+        // Announcements signed with another key are ignored
+        assert!(a.peers().iter().all(|p| p.name != "intruder"));
+        drop(intruder);
+
+        // Peers that stop announcing expire; static peers stay
+        drop(b);
+        drop(c);
+        assert!(
+            wait_for(&a, |peers| peers.iter().all(|p| p.name != "node-2")).await,
+            "node-2 did not expire"
+        );
+        assert!(a.peer("node-3").is_some());
+    }
+}
@@ -0,0 +1,522 @@
+//! Lease-based leader election.
+//!
+//! This agent and the agents of `cluster.peers` elect one leader over the
+//! HTTP API, so that a step launched on every node runs only once. Elections
+//! are numbered by terms: a candidate starts a new term, asks every voter for
+//! its vote and becomes leader with the votes of a majority.
+//!
+//! A vote is also a lease: the voter promises not to vote for anyone else
+//! until the lease expires. The leader renews the leases of a majority with
+//! heartbeats and steps down once its own lease runs out without renewal.
+//! The leader measures its lease from before sending its requests, and the
+//! voters from when they receive them, so the leader always gives up a lease
+//! before the voters release it and two leaders never hold valid leases at
+//! the same time.
+//!
+//! An agent does not vote during the first lease after it starts, since it
+//! may have granted a lease before restarting.
+
+use crate::client::ShikiClient;
+use crate::config::{ElectionConfig, PeerConfig};
+use crate::error::Result;
+use crate::server::response::{HeartbeatData, HeartbeatRequest, LeaderData, VoteData, VoteRequest};
+use chrono::Utc;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// A voting peer.
+struct Voter {
+    name: String,
+    client: ShikiClient,
+}
+
+/// Election state of this agent.
+#[derive(Debug, Default)]
+struct State {
+    /// Highest term voted in or led.
+    term: u64,
+    /// Agent holding this agent's vote or lease.
+    holder: Option<String>,
+    /// When the vote or lease of `holder` expires.
+    expires: Option<Instant>,
+    /// Whether `holder` is known to be the leader, because it won the
+    /// election or renewed its lease.
+    confirmed: bool,
+}
+
+impl State {
+    /// Returns the holder of a valid vote or lease.
+    fn holder(&self, now: Instant) -> Option<&str> {
+        match self.expires {
+            Some(expires) if expires > now => self.holder.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the leader holding a valid lease.
+    fn leader(&self, now: Instant) -> Option<&str> {
+        self.holder(now).filter(|_| self.confirmed)
+    }
+
+    /// Forgets the holder, e.g. when a campaign fails or the leader steps down.
+    fn release(&mut self) {
+        self.holder = None;
+        self.expires = None;
+        self.confirmed = false;
+    }
+}
+
+/// Leader election of this agent.
+pub struct Election {
+    /// Name of this agent.
+    name: String,
+    /// Other voting agents.
+    voters: Vec<Voter>,
+    /// Lease duration.
+    lease: Duration,
+    /// Interval between lease renewals.
+    renew_interval: Duration,
+    /// When this agent started; it does not vote during the first lease.
+    started: Instant,
+    state: Mutex<State>,
+}
+
+impl Election {
+    /// Creates the election of this agent among `peers`. Requests to peers
+    /// without a token of their own carry `token`.
+    pub fn new(
+        config: &ElectionConfig,
+        name: &str,
+        peers: &[PeerConfig],
+        token: Option<&str>,
+    ) -> Result<Self> {
+        let renew_interval = Duration::from_millis(config.renew_interval_ms);
+        let voters = peers
+            .iter()
+            .map(|peer| {
+                // A round of requests must finish before the next renewal
+                let client = ShikiClient::with_timeout(&peer.address, renew_interval)?;
+                let client = match peer.token.as_deref().or(token) {
+                    Some(token) => client.with_token(token),
+                    None => client,
+                };
+                Ok(Voter {
+                    name: peer.name.clone(),
+                    client,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            voters,
+            lease: Duration::from_millis(config.lease_ms),
+            renew_interval,
+            started: Instant::now(),
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    /// Returns the number of votes needed to become leader.
+    pub fn quorum(&self) -> usize {
+        (self.voters.len() + 1) / 2 + 1
+    }
+
+    /// Returns whether this agent is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.lock().leader(Instant::now()) == Some(self.name.as_str())
+    }
+
+    /// Returns the election state as seen by this agent.
+    pub fn leader(&self) -> LeaderData {
+        let now = Instant::now();
+        let state = self.lock();
+        let leader = state.leader(now).map(str::to_string);
+        let lease_expires_at = leader.as_ref().and(state.expires).map(|expires| {
+            Utc::now() + chrono::Duration::from_std(expires - now).unwrap_or_default()
+        });
+
+        LeaderData {
+            enabled: true,
+            agent: self.name.clone(),
+            term: state.term,
+            is_leader: leader.as_deref() == Some(self.name.as_str()),
+            leader,
+            lease_expires_at,
+            voters: self.voters.len() + 1,
+            quorum: self.quorum(),
+        }
+    }
+
+    /// Handles a vote request from a candidate.
+    pub fn vote(&self, request: &VoteRequest) -> VoteData {
+        let mut state = self.lock();
+        let granted = self.grant(&mut state, Instant::now(), request.term, &request.candidate);
+        debug!(
+            candidate = %request.candidate,
+            term = request.term,
+            granted = granted,
+            "Vote requested"
+        );
+        VoteData {
+            term: state.term,
+            granted,
+        }
+    }
+
+    /// Handles a lease renewal from the leader.
+    pub fn heartbeat(&self, request: &HeartbeatRequest) -> HeartbeatData {
+        let now = Instant::now();
+        let mut state = self.lock();
+        let accepted = request.term >= state.term;
+        if accepted {
+            if state.leader(now) != Some(request.leader.as_str()) {
+                info!(leader = %request.leader, term = request.term, "Following new leader");
+            }
+            state.term = request.term;
+            state.holder = Some(request.leader.clone());
+            state.expires = Some(now + Duration::from_millis(request.lease_ms));
+            state.confirmed = true;
+        } else {
+            debug!(
+                leader = %request.leader,
+                term = request.term,
+                current_term = state.term,
+                "Rejected heartbeat from an earlier term"
+            );
+        }
+        HeartbeatData {
+            term: state.term,
+            accepted,
+        }
+    }
+
+    /// Starts the election in the background.
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(Arc::clone(self).run())
+    }
+
+    /// Runs the election forever: renews the lease while leader, and
+    /// campaigns once no valid lease is held.
+    pub async fn run(self: Arc<Self>) {
+        info!(
+            voters = self.voters.len() + 1,
+            quorum = self.quorum(),
+            lease_ms = self.lease.as_millis() as u64,
+            "Starting leader election"
+        );
+        loop {
+            if self.is_leader() {
+                self.renew().await;
+                tokio::time::sleep(self.renew_interval).await;
+            } else if let Some(wait) = self.time_to_campaign() {
+                // Candidates start at random times to avoid split votes
+                tokio::time::sleep(wait + jitter(self.renew_interval)).await;
+            } else if !self.campaign().await {
+                tokio::time::sleep(self.renew_interval / 2 + jitter(self.renew_interval)).await;
+            }
+        }
+    }
+
+    /// Returns how long to wait before this agent may campaign.
+    fn time_to_campaign(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let startup = (self.started + self.lease).saturating_duration_since(now);
+        let state = self.lock();
+        let lease = match (state.holder(now), state.expires) {
+            (Some(_), Some(expires)) => expires - now,
+            _ => Duration::ZERO,
+        };
+        let wait = startup.max(lease);
+        (!wait.is_zero()).then_some(wait)
+    }
+
+    /// Grants a vote for `term` to `candidate` if this agent may: it has
+    /// been running for a lease, holds no valid lease for someone else, and
+    /// has not voted for someone else in that term.
+    fn grant(&self, state: &mut State, now: Instant, term: u64, candidate: &str) -> bool {
+        let same_holder = state.holder.as_deref() == Some(candidate);
+        let granted = now.duration_since(self.started) >= self.lease
+            && state.holder(now).is_none_or(|holder| holder == candidate)
+            && (term > state.term || (term == state.term && same_holder));
+        if granted {
+            state.term = term;
+            state.holder = Some(candidate.to_string());
+            state.expires = Some(now + self.lease);
+            state.confirmed = false;
+        }
+        granted
+    }
+
+    /// Runs for leader in a new term. Returns whether this agent was elected.
+    async fn campaign(&self) -> bool {
+        let started = Instant::now();
+        let term = {
+            let mut state = self.lock();
+            let term = state.term + 1;
+            if !self.grant(&mut state, started, term, &self.name) {
+                return false;
+            }
+            term
+        };
+        debug!(term = term, "Running for leader");
+
+        let request = VoteRequest {
+            term,
+            candidate: self.name.clone(),
+        };
+        let mut votes = 1;
+        let mut highest_term = term;
+        for (voter, answer) in self
+            .ask_all(|client| {
+                let request = request.clone();
+                async move { client.request_vote(&request).await }
+            })
+            .await
+        {
+            match answer {
+                Ok(vote) => {
+                    votes += usize::from(vote.granted);
+                    highest_term = highest_term.max(vote.term);
+                }
+                Err(e) => debug!(voter = %voter, error = %e, "Vote request failed"),
+            }
+        }
+
+        let mut state = self.lock();
+        // A leader may have been accepted while the votes were collected
+        if state.term != term || state.holder.as_deref() != Some(self.name.as_str()) {
+            return false;
+        }
+        if votes >= self.quorum() {
+            state.expires = Some(started + self.lease);
+            state.confirmed = true;
+            info!(term = term, votes = votes, "Elected leader");
+            true
+        } else {
+            debug!(
+                term = term,
+                votes = votes,
+                quorum = self.quorum(),
+                "Not elected"
+            );
+            state.release();
+            state.term = highest_term;
+            false
+        }
+    }
+
+    /// Renews the lease of this agent with every voter.
+    async fn renew(&self) {
+        let started = Instant::now();
+        let term = self.lock().term;
+        let request = HeartbeatRequest {
+            term,
+            leader: self.name.clone(),
+            lease_ms: self.lease.as_millis() as u64,
+        };
+        let mut acks = 1;
+        let mut highest_term = term;
+        for (voter, answer) in self
+            .ask_all(|client| {
+                let request = request.clone();
+                async move { client.heartbeat(&request).await }
+            })
+            .await
+        {
+            match answer {
+                Ok(ack) => {
+                    acks += usize::from(ack.accepted);
+                    highest_term = highest_term.max(ack.term);
+                }
+                Err(e) => debug!(voter = %voter, error = %e, "Heartbeat failed"),
+            }
+        }
+
+        let now = Instant::now();
+        let mut state = self.lock();
+        if state.term != term || state.leader(now) != Some(self.name.as_str()) {
+            if state.holder.as_deref() == Some(self.name.as_str()) {
+                warn!(term = term, "Leader lease expired");
+                state.release();
+            }
+            return;
+        }
+        if highest_term > term {
+            warn!(
+                term = term,
+                newer_term = highest_term,
+                "Stepping down for a newer term"
+            );
+            state.release();
+            state.term = highest_term;
+        } else if acks >= self.quorum() {
+            state.expires = Some(started + self.lease);
+            debug!(term = term, acks = acks, "Leader lease renewed");
+        } else {
+            warn!(
+                term = term,
+                acks = acks,
+                quorum = self.quorum(),
+                "Failed to renew leader lease"
+            );
+        }
+    }
+
+    /// Sends a request to every voter concurrently and collects the answers.
+    async fn ask_all<T, F, Fut>(&self, call: F) -> Vec<(String, Result<T>)>
+    where
+        T: Send + 'static,
+        F: Fn(ShikiClient) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let mut requests = JoinSet::new();
+        for voter in &self.voters {
+            let name = voter.name.clone();
+            let call = call(voter.client.clone());
+            requests.spawn(async move { (name, call.await) });
+        }
+        let mut answers = Vec::with_capacity(self.voters.len());
+        while let Some(joined) = requests.join_next().await {
+            if let Ok(answer) = joined {
+                answers.push(answer);
+            }
+        }
+        answers
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Returns a random duration below `max`.
+fn jitter(max: Duration) -> Duration {
+    let max_ms = max.as_millis().max(1);
+    Duration::from_millis((Uuid::new_v4().as_u128() % max_ms) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn election(peers: usize, lease_ms: u64) -> Election {
+        let peers: Vec<PeerConfig> = (0..peers)
+            .map(|i| PeerConfig {
+                name: format!("node-{}", i + 2),
+                address: format!("127.0.0.1:{}", 1 + i),
+                tags: vec![],
+                token: None,
+            })
+            .collect();
+        let config = ElectionConfig {
+            enabled: true,
+            lease_ms,
+            renew_interval_ms: lease_ms / 4,
+        };
+        Election::new(&config, "node-1", &peers, None).unwrap()
+    }
+
+    /// Pretends the agent has been running for longer than a lease.
+    fn running(mut election: Election) -> Election {
+        election.started = Instant::now() - election.lease;
+        election
+    }
+
+    fn vote(election: &Election, term: u64, candidate: &str) -> bool {
+        election
+            .vote(&VoteRequest {
+                term,
+                candidate: candidate.to_string(),
+            })
+            .granted
+    }
+
+    fn heartbeat(election: &Election, term: u64, leader: &str) -> bool {
+        election
+            .heartbeat(&HeartbeatRequest {
+                term,
+                leader: leader.to_string(),
+                lease_ms: 60_000,
+            })
+            .accepted
+    }
+
+    #[test]
+    fn test_quorum() {
+        assert_eq!(election(0, 1000).quorum(), 1);
+        assert_eq!(election(1, 1000).quorum(), 2);
+        assert_eq!(election(2, 1000).quorum(), 2);
+        assert_eq!(election(4, 1000).quorum(), 3);
+    }
+
+    #[test]
+    fn test_no_votes_during_first_lease() {
+        let election = election(2, 60_000);
+        assert!(!vote(&election, 1, "node-2"));
+        assert_eq!(election.leader().term, 0);
+    }
+
+    #[test]
+    fn test_vote_is_a_lease() {
+        let election = running(election(2, 60_000));
+
+        assert!(vote(&election, 1, "node-2"));
+        // Voting again for the same candidate is allowed, for another is not
+        assert!(vote(&election, 1, "node-2"));
+        assert!(!vote(&election, 1, "node-3"));
+        assert!(!vote(&election, 2, "node-3"));
+        // A denied vote does not move the term
+        assert_eq!(election.leader().term, 1);
+        // The candidate is not the leader until it renews its lease
+        assert!(election.leader().leader.is_none());
+
+        assert!(heartbeat(&election, 1, "node-2"));
+        let leader = election.leader();
+        assert_eq!(leader.leader.as_deref(), Some("node-2"));
+        assert!(!leader.is_leader);
+        assert!(leader.lease_expires_at.is_some());
+    }
+
+    #[test]
+    fn test_expired_lease_allows_new_term() {
+        let election = running(election(2, 60_000));
+        assert!(vote(&election, 1, "node-2"));
+        election.lock().expires = Some(Instant::now());
+
+        assert!(!vote(&election, 1, "node-3"));
+        assert!(vote(&election, 2, "node-3"));
+        assert_eq!(election.leader().term, 2);
+    }
+
+    #[test]
+    fn test_heartbeat_from_earlier_term_is_rejected() {
+        let election = election(2, 60_000);
+        assert!(heartbeat(&election, 3, "node-2"));
+        assert!(!heartbeat(&election, 2, "node-3"));
+        assert!(heartbeat(&election, 4, "node-3"));
+        assert_eq!(election.leader().leader.as_deref(), Some("node-3"));
+    }
+
+    #[tokio::test]
+    async fn test_single_agent_elects_itself() {
+        let election = Arc::new(election(0, 200));
+        let task = election.spawn();
+        for _ in 0..40 {
+            if election.is_leader() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        task.abort();
+        let leader = election.leader();
+        assert!(leader.is_leader, "{:?}", leader);
+        assert_eq!(leader.term, 1);
+    }
+}
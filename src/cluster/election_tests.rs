@@ -0,0 +1,183 @@
+//! Multi-agent tests of leader election.
+//!
+//! Every agent serves the full API in-process on its own loopback port, with
+//! short leases. A crash is simulated by stopping an agent's server and
+//! election task.
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config, PeerConfig, ServiceDefinition};
+    use crate::server::{create_router, state::AppState};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    const LEASE_MS: u64 = 600;
+
+    /// A running agent.
+    struct Agent {
+        name: String,
+        state: Arc<AppState>,
+        tasks: Vec<JoinHandle<()>>,
+    }
+
+    impl Agent {
+        fn is_leader(&self) -> bool {
+            self.state.election.as_ref().unwrap().is_leader()
+        }
+
+        fn leader(&self) -> Option<String> {
+            self.state.election.as_ref().unwrap().leader().leader
+        }
+
+        fn term(&self) -> u64 {
+            self.state.election.as_ref().unwrap().leader().term
+        }
+
+        fn stop(&self) {
+            for task in &self.tasks {
+                task.abort();
+            }
+        }
+    }
+
+    impl Drop for Agent {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    fn config(name: &str, peers: Vec<PeerConfig>) -> Config {
+        let mut config = Config::default();
+        config.agent.backend = Backend::Exec;
+        config.agent.name = Some(name.to_string());
+        config.services.insert(
+            "app".to_string(),
+            ServiceDefinition {
+                start: "true".to_string(),
+                stop: "true".to_string(),
+                status: "true".to_string(),
+                ..Default::default()
+            },
+        );
+        config.cluster.enabled = true;
+        config.cluster.peers = peers;
+        config.cluster.election.enabled = true;
+        config.cluster.election.lease_ms = LEASE_MS;
+        config.cluster.election.renew_interval_ms = LEASE_MS / 4;
+        config
+    }
+
+    /// Starts `n` agents that know each other, of which only the first
+    /// `running` are actually started.
+    async fn start_cluster(n: usize, running: usize) -> Vec<Agent> {
+        let mut listeners = Vec::new();
+        for _ in 0..n {
+            listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        }
+        let addresses: Vec<String> = listeners
+            .iter()
+            .map(|l| l.local_addr().unwrap().to_string())
+            .collect();
+
+        let mut agents = Vec::new();
+        for (i, listener) in listeners.into_iter().enumerate().take(running) {
+            let name = format!("node-{}", i);
+            let peers = addresses
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(j, address)| PeerConfig {
+                    name: format!("node-{}", j),
+                    address: address.clone(),
+                    tags: vec![],
+                    token: None,
+                })
+                .collect();
+            let state = Arc::new(AppState::new(&config(&name, peers)).unwrap());
+            let router = create_router(Arc::clone(&state));
+            let server = tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            let election = state.election.as_ref().unwrap().spawn();
+            agents.push(Agent {
+                name,
+                state,
+                tasks: vec![server, election],
+            });
+        }
+        agents
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        false
+    }
+
+    /// Returns the leader every agent agrees on.
+    fn agreed_leader(agents: &[Agent]) -> Option<String> {
+        let leader = agents[0].leader()?;
+        agents
+            .iter()
+            .all(|a| a.leader().as_ref() == Some(&leader))
+            .then_some(leader)
+    }
+
+    #[tokio::test]
+    async fn test_single_leader_is_elected() {
+        let agents = start_cluster(3, 3).await;
+        assert!(
+            wait_until(|| agreed_leader(&agents).is_some()).await,
+            "no leader was agreed on"
+        );
+
+        // The leader keeps its lease over several renewals
+        let leader = agreed_leader(&agents).unwrap();
+        let term = agents[0].term();
+        for _ in 0..10 {
+            let leaders: Vec<&str> = agents
+                .iter()
+                .filter(|a| a.is_leader())
+                .map(|a| a.name.as_str())
+                .collect();
+            assert_eq!(leaders, vec![leader.as_str()]);
+            tokio::time::sleep(Duration::from_millis(LEASE_MS / 4)).await;
+        }
+        assert!(agents.iter().all(|a| a.term() == term));
+    }
+
+    #[tokio::test]
+    async fn test_new_leader_after_failure() {
+        let mut agents = start_cluster(3, 3).await;
+        assert!(wait_until(|| agreed_leader(&agents).is_some()).await);
+        let old_leader = agreed_leader(&agents).unwrap();
+        let old_term = agents[0].term();
+
+        let index = agents.iter().position(|a| a.name == old_leader).unwrap();
+        let failed = agents.remove(index);
+        failed.stop();
+
+        assert!(
+            wait_until(|| agreed_leader(&agents).is_some_and(|l| l != old_leader)).await,
+            "no new leader was elected"
+        );
+        assert!(agents[0].term() > old_term);
+        // The failed leader gave up its lease
+        assert!(!failed.is_leader());
+    }
+
+    #[tokio::test]
+    async fn test_no_leader_without_quorum() {
+        // One agent out of three cannot win a majority
+        let agents = start_cluster(3, 1).await;
+        tokio::time::sleep(Duration::from_millis(LEASE_MS * 4)).await;
+        assert!(agents[0].leader().is_none());
+        assert!(!agents[0].is_leader());
+    }
+}
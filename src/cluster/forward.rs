@@ -0,0 +1,235 @@
+//! Request forwarding through peers.
+//!
+//! An agent with `cluster.forwarding.enabled` accepts requests on
+//! `/api/v1/peers/{peer}/...` and relays them to the named peer, so hosts
+//! that can only reach a bastion agent can still operate internal nodes.
+//!
+//! Multi-hop routes are carried in the `X-Shiki-Route` header (the peers
+//! after `{peer}`, comma-separated). Every forwarding agent appends its name
+//! to `X-Shiki-Hops`; a request that comes back to an agent already in the
+//! list, or that exceeds `max_hops`, is rejected.
+
+use crate::config::PeerConfig;
+use crate::error::{Result, ShikiError};
+use crate::server::response::NotifyRequest;
+use crate::service::ServiceAction;
+use reqwest::{Client, Method};
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+/// Header carrying the remaining peers of a multi-hop route.
+pub const ROUTE_HEADER: &str = "x-shiki-route";
+
+/// Header carrying the agents a request has been forwarded by.
+pub const HOPS_HEADER: &str = "x-shiki-hops";
+
+/// Header carrying the caller's request ID.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Splits a comma-separated header value into names.
+pub fn parse_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Checks that `path` (relative to `/api/v1/`) may be forwarded with
+/// `method`, and returns the service it operates on, if any.
+///
+/// Only the notify and service endpoints are forwarded. For notify, the
+/// service is read from the request body.
+pub fn forwarded_service(method: &Method, path: &str, body: &[u8]) -> Result<Option<String>> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let service = match (method, segments.as_slice()) {
+        (&Method::POST, ["notify"]) => {
+            let request: NotifyRequest = serde_json::from_slice(body).map_err(|e| {
+                ShikiError::invalid_request(format!("Invalid notify request: {}", e))
+            })?;
+            Some(request.service)
+        }
+        (&Method::GET, ["services"]) => None,
+        (&Method::GET, ["services", name]) => Some(name.to_string()),
+        (&Method::POST, ["services", name, action]) if action.parse::<ServiceAction>().is_ok() => {
+            Some(name.to_string())
+        }
+        _ => {
+            return Err(ShikiError::invalid_request(format!(
+                "Endpoint cannot be forwarded: {} /api/v1/{}",
+                method, path
+            )))
+        }
+    };
+    Ok(service)
+}
+
+/// Checks the hop list of an incoming request before `agent` forwards it.
+pub fn check_hops(agent: &str, peer: &str, hops: &[String], max_hops: usize) -> Result<()> {
+    if peer == agent {
+        return Err(ShikiError::invalid_request(format!(
+            "Forwarding loop detected: {} cannot forward to itself",
+            agent
+        )));
+    }
+    if hops.iter().any(|h| h == agent) {
+        return Err(ShikiError::invalid_request(format!(
+            "Forwarding loop detected at {} (hops: {})",
+            agent,
+            hops.join(" -> ")
+        )));
+    }
+    if hops.len() >= max_hops {
+        return Err(ShikiError::invalid_request(format!(
+            "Too many forwarding hops ({} >= {})",
+            hops.len(),
+            max_hops
+        )));
+    }
+    Ok(())
+}
+
+/// A request to relay to a peer.
+#[derive(Debug)]
+pub struct ForwardRequest<'a> {
+    /// Peer to send the request to.
+    pub peer: &'a PeerConfig,
+    /// Peers after `peer`, ending with the one that handles the request.
+    pub route: &'a [String],
+    /// HTTP method.
+    pub method: Method,
+    /// Path relative to `/api/v1/`.
+    pub path: &'a str,
+    /// Raw query string.
+    pub query: Option<&'a str>,
+    /// Request body.
+    pub body: Vec<u8>,
+    /// Request ID to propagate.
+    pub request_id: Uuid,
+    /// Hop list including the forwarding agent.
+    pub hops: &'a [String],
+    /// `Authorization` header of the caller, used when the peer has no token.
+    /// Never set for discovered peers.
+    pub authorization: Option<&'a str>,
+    /// Request timeout.
+    pub timeout: Duration,
+}
+
+/// Relays a request to a peer and returns its status code and body.
+pub async fn forward(request: ForwardRequest<'_>) -> Result<(u16, Vec<u8>)> {
+    let base_url = if request.peer.address.contains("://") {
+        request.peer.address.trim_end_matches('/').to_string()
+    } else {
+        format!("http://{}", request.peer.address.trim_end_matches('/'))
+    };
+    let mut url = match request.route.first() {
+        Some(next) => format!("{}/api/v1/peers/{}/{}", base_url, next, request.path),
+        None => format!("{}/api/v1/{}", base_url, request.path),
+    };
+    if let Some(query) = request.query.filter(|q| !q.is_empty()) {
+        url = format!("{}?{}", url, query);
+    }
+
+    info!(
+        request_id = %request.request_id,
+        peer = %request.peer.name,
+        url = %url,
+        hops = %request.hops.join(","),
+        "Forwarding request to peer"
+    );
+
+    let client = Client::builder()
+        .timeout(request.timeout)
+        .build()
+        .map_err(|e| {
+            ShikiError::backend_with_source("Failed to create HTTP client".to_string(), e)
+        })?;
+
+    let mut builder = client
+        .request(request.method, &url)
+        .header(REQUEST_ID_HEADER, request.request_id.to_string())
+        .header(HOPS_HEADER, request.hops.join(","));
+    if request.route.len() > 1 {
+        builder = builder.header(ROUTE_HEADER, request.route[1..].join(","));
+    }
+    match (&request.peer.token, request.authorization) {
+        (Some(token), _) => builder = builder.bearer_auth(token),
+        (None, Some(authorization)) => builder = builder.header("authorization", authorization),
+        (None, None) => {}
+    }
+    if !request.body.is_empty() {
+        builder = builder
+            .header("content-type", "application/json")
+            .body(request.body);
+    }
+
+    let response = builder.send().await.map_err(|e| {
+        if e.is_timeout() {
+            ShikiError::Timeout {
+                operation: format!("forward to {}", request.peer.name),
+                seconds: request.timeout.as_secs(),
+            }
+        } else {
+            ShikiError::connection_with_source(&base_url, e)
+        }
+    })?;
+
+    let status = response.status().as_u16();
+    let body = response.bytes().await.map_err(|e| {
+        ShikiError::backend_with_source(
+            format!("Failed to read response from {}", request.peer.name),
+            e,
+        )
+    })?;
+    Ok((status, body.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        assert!(parse_list(None).is_empty());
+        assert!(parse_list(Some(" ")).is_empty());
+        assert_eq!(parse_list(Some("a, b,,c")), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_forwarded_service() {
+        let body = br#"{"action":"start","service":"nginx"}"#;
+        assert_eq!(
+            forwarded_service(&Method::POST, "notify", body).unwrap(),
+            Some("nginx".to_string())
+        );
+        assert_eq!(
+            forwarded_service(&Method::GET, "services", b"").unwrap(),
+            None
+        );
+        assert_eq!(
+            forwarded_service(&Method::GET, "services/redis", b"").unwrap(),
+            Some("redis".to_string())
+        );
+        assert_eq!(
+            forwarded_service(&Method::POST, "services/redis/restart", b"").unwrap(),
+            Some("redis".to_string())
+        );
+
+        assert!(forwarded_service(&Method::POST, "notify", b"{}").is_err());
+        assert!(forwarded_service(&Method::GET, "notify", b"").is_err());
+        assert!(forwarded_service(&Method::POST, "services/redis/reload", b"").is_err());
+        assert!(forwarded_service(&Method::POST, "cluster/notify", b"").is_err());
+    }
+
+    #[test]
+    fn test_check_hops() {
+        let hops = vec!["dmz".to_string()];
+        assert!(check_hops("bastion", "db", &hops, 4).is_ok());
+        assert!(check_hops("bastion", "bastion", &[], 4).is_err());
+        assert!(check_hops("dmz", "db", &hops, 4).is_err());
+        assert!(check_hops("bastion", "db", &hops, 1).is_err());
+    }
+}
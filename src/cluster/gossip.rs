@@ -0,0 +1,892 @@
+//! Gossip-based cluster membership.
+//!
+//! A SWIM-style protocol over UDP. Every probe interval an agent pings one
+//! member (round-robin over a shuffled list). A member that does not answer
+//! within the probe timeout is probed indirectly through `indirect_probes`
+//! other members; if no acknowledgement arrives by the end of the interval
+//! it becomes suspect, and a suspect that does not refute the suspicion
+//! within `suspicion_timeout_ms` is declared dead.
+//!
+//! Membership updates are piggybacked on probe traffic instead of being
+//! broadcast. Each update carries the member's incarnation number: a member
+//! refutes a suspicion about itself by raising its incarnation, and for the
+//! same incarnation dead overrides suspect, which overrides alive.
+//!
+//! Gossip members are kept in the same peer registry as static and
+//! discovered peers, with [`PeerSource::Gossip`] and their membership state.
+
+use crate::cluster::peers::{PeerSource, PeerStatus};
+use crate::config::GossipConfig;
+use crate::error::{Result, ShikiError};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, MissedTickBehavior};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Maximum size of a gossip datagram.
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Maximum number of membership updates piggybacked on a message.
+const MAX_PIGGYBACK: usize = 16;
+
+/// An update is piggybacked this many times the log2 of the cluster size.
+const RETRANSMIT_MULT: u32 = 3;
+
+/// Membership state of a member.
+///
+/// The order is the precedence of updates with the same incarnation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberState {
+    /// Answering probes.
+    Alive,
+    /// Failed a probe; dead unless it refutes the suspicion in time.
+    Suspect,
+    /// Failed to refute a suspicion.
+    Dead,
+}
+
+impl std::fmt::Display for MemberState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemberState::Alive => write!(f, "alive"),
+            MemberState::Suspect => write!(f, "suspect"),
+            MemberState::Dead => write!(f, "dead"),
+        }
+    }
+}
+
+/// A cluster member as carried in membership updates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    /// Agent name.
+    pub name: String,
+    /// Address of the member's gossip socket.
+    pub gossip_address: SocketAddr,
+    /// HTTP address of the agent.
+    pub address: String,
+    /// Agent tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Agent version.
+    pub version: String,
+    /// Incarnation number, raised by the member to refute suspicions.
+    pub incarnation: u64,
+    /// Membership state.
+    pub state: MemberState,
+}
+
+impl Member {
+    /// Returns whether this update overrides `other`, an update about the
+    /// same member.
+    fn supersedes(&self, other: &Member) -> bool {
+        self.incarnation > other.incarnation
+            || (self.incarnation == other.incarnation && self.state > other.state)
+    }
+
+    /// Replaces a wildcard host in the member's addresses with `ip`.
+    fn resolve_host(&mut self, ip: IpAddr) {
+        if self.gossip_address.ip().is_unspecified() {
+            self.gossip_address.set_ip(ip);
+        }
+        self.address = replace_unspecified_host(&self.address, ip);
+    }
+}
+
+/// Replaces the host of `address` ([scheme://]host:port) with `ip` when it
+/// is a wildcard address.
+fn replace_unspecified_host(address: &str, ip: IpAddr) -> String {
+    let (scheme, host_port) = match address.split_once("://") {
+        Some((scheme, rest)) => (format!("{}://", scheme), rest),
+        None => (String::new(), address),
+    };
+    let Some((host, port)) = host_port.rsplit_once(':') else {
+        return address.to_string();
+    };
+    let unspecified = host
+        .trim_matches(['[', ']'])
+        .parse::<IpAddr>()
+        .is_ok_and(|h| h.is_unspecified());
+    match port.parse::<u16>() {
+        Ok(port) if unspecified => format!("{}{}", scheme, SocketAddr::new(ip, port)),
+        _ => address.to_string(),
+    }
+}
+
+/// Gossip protocol messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// Direct probe.
+    Ping {
+        seq: u64,
+        from: String,
+        #[serde(default)]
+        updates: Vec<Member>,
+    },
+    /// Request to probe `target` on behalf of the sender.
+    PingReq {
+        seq: u64,
+        from: String,
+        target: SocketAddr,
+        #[serde(default)]
+        updates: Vec<Member>,
+    },
+    /// Acknowledgement of a probe.
+    Ack {
+        seq: u64,
+        from: String,
+        #[serde(default)]
+        updates: Vec<Member>,
+    },
+    /// Join request carrying the joining member.
+    Join { from: String, member: Member },
+    /// Full membership, sent in reply to a join.
+    Sync { from: String, members: Vec<Member> },
+}
+
+/// A member in the local view.
+#[derive(Debug)]
+struct Entry {
+    member: Member,
+    /// When the member's state last changed.
+    changed: Instant,
+}
+
+/// An agent's view of the cluster membership.
+#[derive(Debug)]
+pub struct Membership {
+    /// This agent.
+    local: Member,
+    /// Other members by name.
+    members: BTreeMap<String, Entry>,
+    /// Updates to piggyback, with the number of times each has been sent.
+    queue: Vec<(Member, u32)>,
+}
+
+impl Membership {
+    /// Creates a view containing only the local member.
+    pub fn new(local: Member) -> Self {
+        Self {
+            local,
+            members: BTreeMap::new(),
+            queue: Vec::new(),
+        }
+    }
+
+    /// Returns the local member.
+    pub fn local(&self) -> &Member {
+        &self.local
+    }
+
+    /// Returns the other members, by name.
+    pub fn members(&self) -> impl Iterator<Item = &Member> {
+        self.members.values().map(|e| &e.member)
+    }
+
+    /// Returns a member by name.
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.members.get(name).map(|e| &e.member)
+    }
+
+    /// Applies a membership update and returns whether the view changed.
+    ///
+    /// Updates about the local member are never applied; a suspect or dead
+    /// update is refuted by raising the local incarnation instead.
+    pub fn apply(&mut self, update: Member) -> bool {
+        if update.name == self.local.name {
+            if update.state != MemberState::Alive && update.incarnation >= self.local.incarnation {
+                self.local.incarnation = update.incarnation + 1;
+                info!(
+                    state = %update.state,
+                    incarnation = self.local.incarnation,
+                    "Refuting membership update about this agent"
+                );
+                self.enqueue(self.local.clone());
+            }
+            return false;
+        }
+
+        match self.members.get(&update.name) {
+            Some(entry) if !update.supersedes(&entry.member) => return false,
+            Some(entry) if entry.member.state != update.state => match update.state {
+                MemberState::Alive => {
+                    info!(member = %update.name, incarnation = update.incarnation, "Member is alive")
+                }
+                MemberState::Suspect => {
+                    warn!(member = %update.name, incarnation = update.incarnation, "Member is suspect")
+                }
+                MemberState::Dead => {
+                    warn!(member = %update.name, incarnation = update.incarnation, "Member is dead")
+                }
+            },
+            Some(_) => {}
+            // Members that are already dead when first heard of are not added
+            None if update.state == MemberState::Dead => return false,
+            None => {
+                info!(
+                    member = %update.name,
+                    address = %update.address,
+                    state = %update.state,
+                    "Member joined"
+                );
+            }
+        }
+
+        self.enqueue(update.clone());
+        self.members.insert(
+            update.name.clone(),
+            Entry {
+                member: update,
+                changed: Instant::now(),
+            },
+        );
+        true
+    }
+
+    /// Marks an alive member as suspect. Returns whether the view changed.
+    pub fn suspect(&mut self, name: &str) -> bool {
+        match self.member(name) {
+            Some(member) if member.state == MemberState::Alive => {
+                let suspect = Member {
+                    state: MemberState::Suspect,
+                    ..member.clone()
+                };
+                self.apply(suspect)
+            }
+            _ => false,
+        }
+    }
+
+    /// Declares members dead that have been suspect for `timeout`.
+    pub fn expire_suspects(&mut self, timeout: Duration) -> bool {
+        let expired: Vec<Member> = self
+            .members
+            .values()
+            .filter(|e| e.member.state == MemberState::Suspect && e.changed.elapsed() >= timeout)
+            .map(|e| Member {
+                state: MemberState::Dead,
+                ..e.member.clone()
+            })
+            .collect();
+        expired
+            .into_iter()
+            .fold(false, |changed, m| self.apply(m) || changed)
+    }
+
+    /// Forgets members that have been dead for `retention`.
+    pub fn reap(&mut self, retention: Duration) -> bool {
+        let before = self.members.len();
+        self.members.retain(|name, e| {
+            let keep = e.member.state != MemberState::Dead || e.changed.elapsed() < retention;
+            if !keep {
+                debug!(member = %name, "Forgetting dead member");
+            }
+            keep
+        });
+        self.members.len() != before
+    }
+
+    /// Returns the updates to piggyback on the next message, preferring
+    /// the least transmitted ones.
+    pub fn piggyback(&mut self) -> Vec<Member> {
+        let limit = self.retransmit_limit();
+        self.queue.sort_by_key(|(_, sent)| *sent);
+        let updates = self
+            .queue
+            .iter_mut()
+            .take(MAX_PIGGYBACK)
+            .map(|(member, sent)| {
+                *sent += 1;
+                member.clone()
+            })
+            .collect();
+        self.queue.retain(|(_, sent)| *sent < limit);
+        updates
+    }
+
+    /// Returns every member including the local one.
+    pub fn snapshot(&self) -> Vec<Member> {
+        std::iter::once(&self.local)
+            .chain(self.members())
+            .cloned()
+            .collect()
+    }
+
+    /// Queues an update, replacing any queued update about the same member.
+    fn enqueue(&mut self, member: Member) {
+        self.queue.retain(|(m, _)| m.name != member.name);
+        self.queue.push((member, 0));
+    }
+
+    /// Number of times an update is piggybacked: a multiple of
+    /// ceil(log2(cluster size + 1)).
+    fn retransmit_limit(&self) -> u32 {
+        let size = self.members.len() as u32 + 1;
+        RETRANSMIT_MULT * (u32::BITS - size.leading_zeros())
+    }
+}
+
+/// An outstanding probe.
+#[derive(Debug)]
+enum Pending {
+    /// A probe of our own, completed by an acknowledgement.
+    Probe(oneshot::Sender<()>),
+    /// A probe made on behalf of `requester`, whose acknowledgement is
+    /// relayed under the requester's sequence number.
+    Relay {
+        requester: SocketAddr,
+        seq: u64,
+        created: Instant,
+    },
+}
+
+/// Runs the gossip protocol for this agent.
+pub struct Gossip {
+    /// Gossip socket.
+    socket: UdpSocket,
+    /// Membership view.
+    membership: Mutex<Membership>,
+    /// Members to join through.
+    seeds: Vec<String>,
+    /// Interval between probes.
+    probe_interval: Duration,
+    /// Time to wait for a direct probe.
+    probe_timeout: Duration,
+    /// Number of members asked to probe indirectly.
+    indirect_probes: usize,
+    /// Time before a suspect is declared dead.
+    suspicion_timeout: Duration,
+    /// Time a dead member is remembered.
+    dead_retention: Duration,
+    /// Next probe sequence number.
+    seq: AtomicU64,
+    /// Outstanding probes by sequence number.
+    pending: Mutex<HashMap<u64, Pending>>,
+    /// Members left to probe in the current round.
+    probe_order: Mutex<Vec<String>>,
+    /// Addresses whose traffic is dropped, to simulate partitions.
+    #[cfg(test)]
+    blocked: std::sync::Arc<Mutex<std::collections::HashSet<SocketAddr>>>,
+}
+
+impl Gossip {
+    /// Binds the gossip socket for the local agent `name`, reachable over
+    /// HTTP at `address`.
+    pub async fn bind(
+        config: &GossipConfig,
+        name: &str,
+        address: String,
+        tags: Vec<String>,
+    ) -> Result<Self> {
+        let bind: IpAddr = config.bind.parse().map_err(|_| {
+            ShikiError::config(format!("Invalid gossip bind address: {}", config.bind))
+        })?;
+        let socket = UdpSocket::bind(SocketAddr::new(bind, config.port))
+            .await
+            .map_err(|e| {
+                ShikiError::backend_with_source(
+                    format!("Failed to bind gossip socket to {}:{}", bind, config.port),
+                    e,
+                )
+            })?;
+
+        let mut gossip_address = socket.local_addr()?;
+        if let Some(advertise) = &config.advertise_address {
+            let ip = advertise.parse().map_err(|_| {
+                ShikiError::config(format!("Invalid gossip advertise address: {}", advertise))
+            })?;
+            gossip_address.set_ip(ip);
+        }
+
+        let local = Member {
+            name: name.to_string(),
+            gossip_address,
+            address,
+            tags,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            // Start from the clock so that a restarted agent supersedes
+            // what the cluster remembers about its previous run.
+            incarnation: Utc::now().timestamp().max(0) as u64,
+            state: MemberState::Alive,
+        };
+
+        info!(
+            address = %gossip_address,
+            seeds = config.seeds.len(),
+            "Gossip membership enabled"
+        );
+
+        Ok(Self {
+            socket,
+            membership: Mutex::new(Membership::new(local)),
+            seeds: config.seeds.clone(),
+            probe_interval: Duration::from_millis(config.probe_interval_ms),
+            probe_timeout: Duration::from_millis(config.probe_timeout_ms),
+            indirect_probes: config.indirect_probes,
+            suspicion_timeout: Duration::from_millis(config.suspicion_timeout_ms),
+            dead_retention: Duration::from_secs(config.dead_retention_seconds),
+            seq: AtomicU64::new(0),
+            pending: Mutex::new(HashMap::new()),
+            probe_order: Mutex::new(Vec::new()),
+            #[cfg(test)]
+            blocked: Default::default(),
+        })
+    }
+
+    /// Returns the gossip address announced to other members.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.lock().local().gossip_address
+    }
+
+    /// Returns the set of addresses whose traffic is dropped.
+    #[cfg(test)]
+    pub(crate) fn blocked(&self) -> std::sync::Arc<Mutex<std::collections::HashSet<SocketAddr>>> {
+        std::sync::Arc::clone(&self.blocked)
+    }
+
+    /// Probes members and handles messages until the task is aborted,
+    /// keeping the gossip entries of `peers` up to date.
+    pub async fn run(self, peers: std::sync::Arc<RwLock<Vec<PeerStatus>>>) {
+        self.join_seeds().await;
+        tokio::join!(self.receive_loop(&peers), self.probe_loop(&peers));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Membership> {
+        self.membership
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Sends a join request to every seed.
+    async fn join_seeds(&self) {
+        let (name, local) = {
+            let membership = self.lock();
+            (membership.local().name.clone(), membership.local().clone())
+        };
+        for seed in &self.seeds {
+            let addresses = match tokio::net::lookup_host(seed.as_str()).await {
+                Ok(addresses) => addresses,
+                Err(e) => {
+                    warn!(seed = %seed, error = %e, "Failed to resolve gossip seed");
+                    continue;
+                }
+            };
+            for address in addresses.filter(|a| *a != local.gossip_address) {
+                debug!(seed = %address, "Joining through seed");
+                let join = Message::Join {
+                    from: name.clone(),
+                    member: local.clone(),
+                };
+                self.send(address, &join).await;
+            }
+        }
+    }
+
+    /// Probes one member per interval and ages suspects and dead members.
+    async fn probe_loop(&self, peers: &RwLock<Vec<PeerStatus>>) {
+        let mut ticker = tokio::time::interval(self.probe_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            let alone = self.lock().members().all(|m| m.state == MemberState::Dead);
+            if alone {
+                self.join_seeds().await;
+            }
+            self.probe_next().await;
+
+            {
+                let mut membership = self.lock();
+                membership.expire_suspects(self.suspicion_timeout);
+                membership.reap(self.dead_retention);
+            }
+            self.purge_relays();
+            self.sync(peers);
+        }
+    }
+
+    /// Probes the next member directly, then indirectly, and marks it
+    /// suspect if neither is acknowledged within the probe interval.
+    async fn probe_next(&self) {
+        let Some(target) = self.next_target() else {
+            return;
+        };
+
+        let seq = self.next_seq();
+        let (tx, mut rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(seq, Pending::Probe(tx));
+        }
+
+        let ping = self.message(|from, updates| Message::Ping { seq, from, updates });
+        self.send(target.gossip_address, &ping).await;
+        let mut acked = matches!(timeout(self.probe_timeout, &mut rx).await, Ok(Ok(())));
+
+        if !acked {
+            let helpers = self.random_members(self.indirect_probes, &target.name);
+            debug!(
+                member = %target.name,
+                helpers = helpers.len(),
+                "Direct probe timed out, probing indirectly"
+            );
+            for helper in helpers {
+                let request = self.message(|from, updates| Message::PingReq {
+                    seq,
+                    from,
+                    target: target.gossip_address,
+                    updates,
+                });
+                self.send(helper.gossip_address, &request).await;
+            }
+            let remaining = self.probe_interval.saturating_sub(self.probe_timeout);
+            acked = matches!(timeout(remaining, &mut rx).await, Ok(Ok(())));
+        }
+
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&seq);
+        }
+        if !acked {
+            self.lock().suspect(&target.name);
+        }
+    }
+
+    /// Returns the next member to probe, reshuffling after every round.
+    fn next_target(&self) -> Option<Member> {
+        let mut order = self.probe_order.lock().ok()?;
+        let membership = self.lock();
+        if order.is_empty() {
+            *order = membership
+                .members()
+                .filter(|m| m.state != MemberState::Dead)
+                .map(|m| m.name.clone())
+                .collect();
+            order.sort_by_cached_key(|_| Uuid::new_v4());
+        }
+        while let Some(name) = order.pop() {
+            match membership.member(&name) {
+                Some(member) if member.state != MemberState::Dead => return Some(member.clone()),
+                _ => continue,
+            }
+        }
+        None
+    }
+
+    /// Picks up to `count` random alive members other than `exclude`.
+    fn random_members(&self, count: usize, exclude: &str) -> Vec<Member> {
+        let mut members: Vec<Member> = self
+            .lock()
+            .members()
+            .filter(|m| m.state == MemberState::Alive && m.name != exclude)
+            .cloned()
+            .collect();
+        members.sort_by_cached_key(|_| Uuid::new_v4());
+        members.truncate(count);
+        members
+    }
+
+    /// Builds a message from the local name and the pending updates.
+    fn message(&self, build: impl FnOnce(String, Vec<Member>) -> Message) -> Message {
+        let mut membership = self.lock();
+        let from = membership.local().name.clone();
+        let updates = membership.piggyback();
+        build(from, updates)
+    }
+
+    /// Drops relayed probes that were never acknowledged.
+    fn purge_relays(&self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            let interval = self.probe_interval;
+            pending.retain(|_, p| match p {
+                Pending::Probe(_) => true,
+                Pending::Relay { created, .. } => created.elapsed() < interval,
+            });
+        }
+    }
+
+    /// Receives and handles messages.
+    async fn receive_loop(&self, peers: &RwLock<Vec<PeerStatus>>) {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        loop {
+            match self.socket.recv_from(&mut buf).await {
+                Ok((len, source)) => {
+                    #[cfg(test)]
+                    if self.is_blocked(source) {
+                        continue;
+                    }
+                    if self.handle(&buf[..len], source).await {
+                        self.sync(peers);
+                    }
+                }
+                Err(e) => warn!(error = %e, "Failed to receive gossip message"),
+            }
+        }
+    }
+
+    /// Handles a message and returns whether the membership view changed.
+    async fn handle(&self, data: &[u8], source: SocketAddr) -> bool {
+        let message: Message = match serde_json::from_slice(data) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!(source = %source, error = %e, "Ignoring invalid gossip message");
+                return false;
+            }
+        };
+
+        match message {
+            Message::Ping { seq, from, updates } => {
+                let changed = self.apply_all(&from, source, updates);
+                let ack = self.message(|from, updates| Message::Ack { seq, from, updates });
+                self.send(source, &ack).await;
+                changed
+            }
+            Message::PingReq {
+                seq,
+                from,
+                target,
+                updates,
+            } => {
+                let changed = self.apply_all(&from, source, updates);
+                let relay_seq = self.next_seq();
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.insert(
+                        relay_seq,
+                        Pending::Relay {
+                            requester: source,
+                            seq,
+                            created: Instant::now(),
+                        },
+                    );
+                }
+                let ping = self.message(|from, updates| Message::Ping {
+                    seq: relay_seq,
+                    from,
+                    updates,
+                });
+                self.send(target, &ping).await;
+                changed
+            }
+            Message::Ack { seq, from, updates } => {
+                let changed = self.apply_all(&from, source, updates);
+                let pending = self.pending.lock().ok().and_then(|mut p| p.remove(&seq));
+                match pending {
+                    Some(Pending::Probe(tx)) => {
+                        let _ = tx.send(());
+                    }
+                    Some(Pending::Relay { requester, seq, .. }) => {
+                        let ack = self.message(|from, updates| Message::Ack { seq, from, updates });
+                        self.send(requester, &ack).await;
+                    }
+                    // Late acknowledgement of a finished probe
+                    None => {}
+                }
+                changed
+            }
+            Message::Join { from, member } => {
+                let changed = self.apply_all(&from, source, vec![member]);
+                let members = self.lock().snapshot();
+                let from = self.lock().local().name.clone();
+                self.send(source, &Message::Sync { from, members }).await;
+                changed
+            }
+            Message::Sync { from, members } => self.apply_all(&from, source, members),
+        }
+    }
+
+    /// Applies updates received from `from` at `source`. The sender's own
+    /// record is completed with the source address when it was bound to a
+    /// wildcard address.
+    fn apply_all(&self, from: &str, source: SocketAddr, updates: Vec<Member>) -> bool {
+        let mut membership = self.lock();
+        updates.into_iter().fold(false, |changed, mut update| {
+            if update.name == from {
+                update.resolve_host(source.ip());
+            }
+            membership.apply(update) || changed
+        })
+    }
+
+    async fn send(&self, to: SocketAddr, message: &Message) {
+        #[cfg(test)]
+        if self.is_blocked(to) {
+            return;
+        }
+        let data = match serde_json::to_vec(message) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!(error = %e, "Failed to encode gossip message");
+                return;
+            }
+        };
+        if let Err(e) = self.socket.send_to(&data, to).await {
+            debug!(to = %to, error = %e, "Failed to send gossip message");
+        }
+    }
+
+    #[cfg(test)]
+    fn is_blocked(&self, address: SocketAddr) -> bool {
+        self.blocked
+            .lock()
+            .map(|b| b.contains(&address))
+            .unwrap_or(false)
+    }
+
+    /// Mirrors the membership view into the peer registry.
+    fn sync(&self, peers: &RwLock<Vec<PeerStatus>>) {
+        let members: Vec<Member> = self.lock().members().cloned().collect();
+        let Ok(mut peers) = peers.write() else {
+            return;
+        };
+        let now = Utc::now();
+
+        peers
+            .retain(|p| p.source != PeerSource::Gossip || members.iter().any(|m| m.name == p.name));
+        for member in members {
+            let peer = match peers.iter_mut().find(|p| p.name == member.name) {
+                // Static and discovered entries keep their own health checks
+                Some(peer) if peer.source != PeerSource::Gossip => {
+                    peer.member_state = Some(member.state);
+                    continue;
+                }
+                Some(peer) => peer,
+                None => {
+                    peers.push(PeerStatus::new(
+                        &member.name,
+                        &member.address,
+                        &member.tags,
+                        PeerSource::Gossip,
+                    ));
+                    peers.last_mut().expect("peer was just added")
+                }
+            };
+            peer.address = member.address;
+            peer.tags = member.tags;
+            peer.version = Some(member.version);
+            peer.member_state = Some(member.state);
+            peer.reachable = member.state == MemberState::Alive;
+            peer.last_checked = Some(now);
+            if peer.reachable {
+                peer.last_seen = Some(now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str, incarnation: u64, state: MemberState) -> Member {
+        Member {
+            name: name.to_string(),
+            gossip_address: "127.0.0.1:7947".parse().unwrap(),
+            address: "127.0.0.1:8080".to_string(),
+            tags: vec![],
+            version: "0.1.0".to_string(),
+            incarnation,
+            state,
+        }
+    }
+
+    #[test]
+    fn test_apply_precedence() {
+        let mut view = Membership::new(member("local", 1, MemberState::Alive));
+
+        // Members first heard of as dead are ignored
+        assert!(!view.apply(member("a", 1, MemberState::Dead)));
+        assert!(view.member("a").is_none());
+
+        assert!(view.apply(member("a", 1, MemberState::Alive)));
+        assert!(view.apply(member("a", 1, MemberState::Suspect)));
+        // Alive does not override suspect with the same incarnation
+        assert!(!view.apply(member("a", 1, MemberState::Alive)));
+        assert!(!view.apply(member("a", 0, MemberState::Dead)));
+        assert!(view.apply(member("a", 2, MemberState::Alive)));
+        assert!(view.apply(member("a", 2, MemberState::Dead)));
+        // A higher incarnation revives a dead member
+        assert!(view.apply(member("a", 3, MemberState::Alive)));
+        assert_eq!(view.member("a").unwrap().state, MemberState::Alive);
+    }
+
+    #[test]
+    fn test_refute_suspicion() {
+        let mut view = Membership::new(member("local", 5, MemberState::Alive));
+
+        assert!(!view.apply(member("local", 5, MemberState::Suspect)));
+        assert_eq!(view.local().incarnation, 6);
+        let updates = view.piggyback();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].state, MemberState::Alive);
+        assert_eq!(updates[0].incarnation, 6);
+
+        // Older suspicions are ignored
+        assert!(!view.apply(member("local", 2, MemberState::Dead)));
+        assert_eq!(view.local().incarnation, 6);
+    }
+
+    #[test]
+    fn test_suspect_and_expire() {
+        let mut view = Membership::new(member("local", 1, MemberState::Alive));
+        view.apply(member("a", 4, MemberState::Alive));
+
+        assert!(view.suspect("a"));
+        assert!(!view.suspect("a"));
+        assert_eq!(view.member("a").unwrap().state, MemberState::Suspect);
+        assert_eq!(view.member("a").unwrap().incarnation, 4);
+
+        assert!(!view.expire_suspects(Duration::from_secs(60)));
+        assert!(view.expire_suspects(Duration::ZERO));
+        assert_eq!(view.member("a").unwrap().state, MemberState::Dead);
+
+        assert!(!view.reap(Duration::from_secs(60)));
+        assert!(view.reap(Duration::ZERO));
+        assert!(view.member("a").is_none());
+    }
+
+    #[test]
+    fn test_piggyback_retransmits() {
+        let mut view = Membership::new(member("local", 1, MemberState::Alive));
+        view.apply(member("a", 1, MemberState::Alive));
+        // One member plus the local one: ceil(log2(2 + 1)) = 2
+        let limit = RETRANSMIT_MULT * 2;
+
+        for _ in 0..limit {
+            assert_eq!(view.piggyback().len(), 1);
+        }
+        assert!(view.piggyback().is_empty());
+
+        // A newer update about the same member replaces the queued one
+        view.apply(member("a", 1, MemberState::Suspect));
+        view.apply(member("a", 1, MemberState::Dead));
+        let updates = view.piggyback();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].state, MemberState::Dead);
+    }
+
+    #[test]
+    fn test_resolve_host() {
+        let ip: IpAddr = "10.0.0.5".parse().unwrap();
+        let mut m = member("a", 1, MemberState::Alive);
+        m.gossip_address = "0.0.0.0:7947".parse().unwrap();
+        m.address = "https://0.0.0.0:8443".to_string();
+        m.resolve_host(ip);
+        assert_eq!(m.gossip_address, "10.0.0.5:7947".parse().unwrap());
+        assert_eq!(m.address, "https://10.0.0.5:8443");
+
+        assert_eq!(replace_unspecified_host("db:8080", ip), "db:8080");
+        assert_eq!(replace_unspecified_host("[::]:8080", ip), "10.0.0.5:8080");
+    }
+}
@@ -0,0 +1,188 @@
+//! Multi-node tests of the gossip protocol.
+//!
+//! Every node runs in-process on its own loopback UDP socket, with short
+//! protocol timings. Partitions are simulated by dropping the traffic of a
+//! node to and from selected addresses.
+
+#[cfg(test)]
+mod tests {
+    use crate::cluster::gossip::{Gossip, MemberState};
+    use crate::cluster::{PeerMonitor, PeerSource, PeerStatus};
+    use crate::config::GossipConfig;
+    use std::collections::HashSet;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A running agent.
+    struct Node {
+        name: String,
+        address: SocketAddr,
+        monitor: PeerMonitor,
+        blocked: Arc<Mutex<HashSet<SocketAddr>>>,
+    }
+
+    impl Node {
+        fn peers(&self) -> Vec<PeerStatus> {
+            self.monitor.peers()
+        }
+
+        fn state_of(&self, name: &str) -> Option<MemberState> {
+            self.monitor.peer(name).and_then(|p| p.member_state)
+        }
+
+        /// Drops all traffic between this node and `other`.
+        fn block(&self, other: &Node) {
+            self.blocked.lock().unwrap().insert(other.address);
+        }
+    }
+
+    fn config(seeds: &[SocketAddr]) -> GossipConfig {
+        GossipConfig {
+            enabled: true,
+            bind: "127.0.0.1".to_string(),
+            port: 0,
+            seeds: seeds.iter().map(|s| s.to_string()).collect(),
+            probe_interval_ms: 100,
+            probe_timeout_ms: 30,
+            indirect_probes: 2,
+            suspicion_timeout_ms: 300,
+            ..Default::default()
+        }
+    }
+
+    async fn start(name: &str, tags: &[&str], seeds: &[SocketAddr]) -> Node {
+        let tags = tags.iter().map(|t| t.to_string()).collect();
+        let gossip = Gossip::bind(&config(seeds), name, format!("127.0.0.1:{}", 18000), tags)
+            .await
+            .unwrap();
+        let address = gossip.local_addr();
+        let blocked = gossip.blocked();
+
+        let monitor = PeerMonitor::new(&[], Duration::from_secs(10), Duration::from_secs(1));
+        monitor.spawn_gossip(gossip);
+        Node {
+            name: name.to_string(),
+            address,
+            monitor,
+            blocked,
+        }
+    }
+
+    /// Starts `n` nodes joining through the first one. Odd nodes are tagged
+    /// `role=db`, even nodes `role=web`.
+    async fn start_cluster(n: usize) -> Vec<Node> {
+        let mut nodes = vec![start("node-0", &["role=web"], &[]).await];
+        let seed = nodes[0].address;
+        for i in 1..n {
+            let role = if i % 2 == 1 { "role=db" } else { "role=web" };
+            nodes.push(start(&format!("node-{}", i), &[role], &[seed]).await);
+        }
+        nodes
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        false
+    }
+
+    fn all_alive(nodes: &[Node]) -> bool {
+        nodes.iter().all(|node| {
+            nodes
+                .iter()
+                .filter(|other| other.name != node.name)
+                .all(|other| node.state_of(&other.name) == Some(MemberState::Alive))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_membership_converges() {
+        let nodes = start_cluster(5).await;
+        assert!(
+            wait_until(|| all_alive(&nodes)).await,
+            "membership did not converge"
+        );
+
+        for node in &nodes {
+            let peers = node.peers();
+            assert_eq!(peers.len(), 4, "{}: {:?}", node.name, peers);
+            for peer in peers {
+                assert_eq!(peer.source, PeerSource::Gossip);
+                assert!(peer.reachable);
+                assert_eq!(peer.address, "127.0.0.1:18000");
+                let index: usize = peer.name["node-".len()..].parse().unwrap();
+                let role = if index % 2 == 1 {
+                    "role=db"
+                } else {
+                    "role=web"
+                };
+                assert_eq!(peer.tags, vec![role]);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_member_is_declared_dead() {
+        let mut nodes = start_cluster(4).await;
+        assert!(wait_until(|| all_alive(&nodes)).await);
+
+        // Stopping the monitor stops the node's gossip task
+        let failed = nodes.pop().unwrap();
+        let failed_name = failed.name.clone();
+        drop(failed);
+
+        assert!(
+            wait_until(|| nodes
+                .iter()
+                .all(|n| n.state_of(&failed_name) == Some(MemberState::Dead)))
+            .await,
+            "failure was not detected by every member"
+        );
+        for node in &nodes {
+            let peer = node.monitor.peer(&failed_name).unwrap();
+            assert!(!peer.reachable);
+        }
+        assert!(all_alive(&nodes));
+    }
+
+    #[tokio::test]
+    async fn test_indirect_probe_avoids_false_suspicion() {
+        let nodes = start_cluster(3).await;
+        assert!(wait_until(|| all_alive(&nodes)).await);
+
+        // node-0 and node-2 can only reach each other through node-1
+        nodes[0].block(&nodes[2]);
+
+        for _ in 0..30 {
+            assert_eq!(nodes[0].state_of("node-2"), Some(MemberState::Alive));
+            assert_eq!(nodes[2].state_of("node-0"), Some(MemberState::Alive));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejoin_after_partition() {
+        let nodes = start_cluster(3).await;
+        assert!(wait_until(|| all_alive(&nodes)).await);
+
+        // Isolate node-2 until the others declare it dead
+        nodes[2].block(&nodes[0]);
+        nodes[2].block(&nodes[1]);
+        assert!(
+            wait_until(|| nodes[0].state_of("node-2") == Some(MemberState::Dead)).await,
+            "partitioned member was not declared dead"
+        );
+
+        // Once the partition heals, node-2 refutes its death and rejoins
+        nodes[2].blocked.lock().unwrap().clear();
+        assert!(
+            wait_until(|| all_alive(&nodes)).await,
+            "member did not rejoin after the partition"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Named locks.
+//!
+//! A lock gives one owner at a time exclusive access to a named operation,
+//! e.g. "only one node runs the DB migrations". Locks have a time to live
+//! and are kept by renewing them; a lock that is not renewed expires, so a
+//! crashed owner does not hold it forever.
+//!
+//! Every acquisition is given a fencing token that is larger than any token
+//! handed out before. An owner whose lock expired can tell from the token
+//! that someone else may have acquired it since, and resources guarded by
+//! the lock can reject stale tokens. The high-water mark is not persisted:
+//! after a restart tokens continue from the current time in milliseconds,
+//! so the guarantee only holds across restarts while the system clock does
+//! not go back and fewer locks are acquired than one per millisecond.
+//!
+//! Locks live in memory on the agent that receives them. In a cluster,
+//! `locks.coordinator` designates one agent that holds them for everyone.
+
+use crate::error::{Result, ShikiError};
+use crate::server::response::LockData;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{debug, info};
+
+/// In-memory lock store.
+#[derive(Debug)]
+pub struct LockStore {
+    /// Held locks and the next fencing token.
+    state: Mutex<State>,
+    /// Bumped on every release to wake up waiting acquisitions.
+    released: watch::Sender<u64>,
+}
+
+#[derive(Debug)]
+struct State {
+    locks: BTreeMap<String, LockData>,
+    next_token: u64,
+}
+
+impl Default for LockStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks that a lock name is non-empty and URL-safe.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(ShikiError::invalid_request(format!(
+            "Invalid lock name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        )))
+    }
+}
+
+impl LockStore {
+    /// Creates an empty store. Fencing tokens start from the current time
+    /// in milliseconds, so that they keep increasing across restarts as
+    /// long as the clock is monotonic.
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                locks: BTreeMap::new(),
+                next_token: Utc::now().timestamp_millis().max(1) as u64,
+            }),
+            released: watch::channel(0).0,
+        }
+    }
+
+    /// Acquires a lock for `owner`, waiting up to `wait` while another owner
+    /// holds it.
+    pub async fn acquire(
+        &self,
+        name: &str,
+        owner: &str,
+        ttl: Duration,
+        wait: Duration,
+    ) -> Result<LockData> {
+        validate_name(name)?;
+        let ttl = chrono::Duration::from_std(ttl)
+            .map_err(|_| ShikiError::invalid_request("Lock TTL is too large"))?;
+        // Subscribe before trying so that no release is missed
+        let mut released = self.released.subscribe();
+        let deadline = tokio::time::Instant::now() + wait;
+
+        loop {
+            let holder = {
+                let mut state = self.lock();
+                let now = Utc::now();
+                purge(&mut state.locks, now);
+                match state.locks.get(name) {
+                    Some(lock) => lock.owner.clone(),
+                    None => {
+                        let lock = LockData {
+                            name: name.to_string(),
+                            owner: owner.to_string(),
+                            fencing_token: state.next_token,
+                            acquired_at: now,
+                            expires_at: now + ttl,
+                        };
+                        state.next_token += 1;
+                        state.locks.insert(name.to_string(), lock.clone());
+                        info!(
+                            lock = %name,
+                            owner = %owner,
+                            fencing_token = lock.fencing_token,
+                            "Lock acquired"
+                        );
+                        return Ok(lock);
+                    }
+                }
+            };
+            debug!(lock = %name, owner = %owner, holder = %holder, "Waiting for lock");
+
+            tokio::select! {
+                _ = released.changed() => {}
+                // Expiry is not announced; look again once in a while
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                _ = tokio::time::sleep_until(deadline) => {
+                    return Err(ShikiError::LockConflict {
+                        lock: name.to_string(),
+                        reason: format!("held by {}", holder),
+                        holder: Some(holder),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Extends a lock held with `fencing_token` by `ttl` from now.
+    pub fn renew(&self, name: &str, fencing_token: u64, ttl: Duration) -> Result<LockData> {
+        let ttl = chrono::Duration::from_std(ttl)
+            .map_err(|_| ShikiError::invalid_request("Lock TTL is too large"))?;
+        let mut state = self.lock();
+        let now = Utc::now();
+        purge(&mut state.locks, now);
+        match state.locks.get_mut(name) {
+            Some(lock) if lock.fencing_token == fencing_token => {
+                lock.expires_at = now + ttl;
+                debug!(lock = %name, fencing_token = fencing_token, "Lock renewed");
+                Ok(lock.clone())
+            }
+            held => Err(lost(name, fencing_token, held.map(|l| l.owner.clone()))),
+        }
+    }
+
+    /// Releases a lock held with `fencing_token`. Returns whether it was
+    /// held; releasing an expired lock is not an error.
+    pub fn release(&self, name: &str, fencing_token: u64) -> Result<bool> {
+        let released = {
+            let mut state = self.lock();
+            purge(&mut state.locks, Utc::now());
+            match state.locks.get(name) {
+                Some(lock) if lock.fencing_token == fencing_token => {
+                    state.locks.remove(name);
+                    true
+                }
+                Some(lock) => {
+                    return Err(lost(name, fencing_token, Some(lock.owner.clone())));
+                }
+                None => false,
+            }
+        };
+        if released {
+            info!(lock = %name, fencing_token = fencing_token, "Lock released");
+            self.released.send_modify(|v| *v += 1);
+        }
+        Ok(released)
+    }
+
+    /// Returns every held lock.
+    pub fn list(&self) -> Vec<LockData> {
+        let mut state = self.lock();
+        purge(&mut state.locks, Utc::now());
+        state.locks.values().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Removes expired locks.
+fn purge(locks: &mut BTreeMap<String, LockData>, now: DateTime<Utc>) {
+    locks.retain(|name, lock| {
+        let alive = lock.expires_at > now;
+        if !alive {
+            info!(lock = %name, owner = %lock.owner, "Lock expired");
+        }
+        alive
+    });
+}
+
+/// Error for a fencing token that no longer holds the lock.
+fn lost(name: &str, fencing_token: u64, holder: Option<String>) -> ShikiError {
+    let reason = match &holder {
+        Some(holder) => format!(
+            "fencing token {} is stale, the lock is held by {}",
+            fencing_token, holder
+        ),
+        None => format!(
+            "fencing token {} is stale, the lock has expired",
+            fencing_token
+        ),
+    };
+    ShikiError::LockConflict {
+        lock: name.to_string(),
+        holder,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn test_acquire_and_release() {
+        let store = LockStore::new();
+        let lock = store
+            .acquire("db-migrate", "node-1", MINUTE, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(lock.owner, "node-1");
+
+        let err = store
+            .acquire("db-migrate", "node-2", MINUTE, Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ShikiError::LockConflict { holder: Some(h), .. } if h == "node-1"),
+            "{}",
+            err
+        );
+        // Locks are not reentrant
+        assert!(store
+            .acquire("db-migrate", "node-1", MINUTE, Duration::ZERO)
+            .await
+            .is_err());
+
+        // Only the fencing token of the holder releases the lock
+        assert!(store.release("db-migrate", lock.fencing_token + 1).is_err());
+        assert!(store.release("db-migrate", lock.fencing_token).unwrap());
+        assert!(!store.release("db-migrate", lock.fencing_token).unwrap());
+
+        let next = store
+            .acquire("db-migrate", "node-2", MINUTE, Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(next.fencing_token > lock.fencing_token);
+        assert_eq!(store.list(), vec![next]);
+
+        assert!(store
+            .acquire("bad name", "node-1", MINUTE, Duration::ZERO)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_renew_and_expiry() {
+        let store = LockStore::new();
+        let lock = store
+            .acquire(
+                "db-migrate",
+                "node-1",
+                Duration::from_millis(100),
+                Duration::ZERO,
+            )
+            .await
+            .unwrap();
+        let renewed = store
+            .renew("db-migrate", lock.fencing_token, MINUTE)
+            .unwrap();
+        assert!(renewed.expires_at > lock.expires_at);
+        assert_eq!(renewed.fencing_token, lock.fencing_token);
+
+        // Once the lock expired, its holder can no longer renew it
+        store
+            .renew("db-migrate", lock.fencing_token, Duration::ZERO)
+            .unwrap();
+        assert!(store.list().is_empty());
+        let err = store
+            .renew("db-migrate", lock.fencing_token, MINUTE)
+            .unwrap_err();
+        assert!(err.to_string().contains("expired"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_release() {
+        let store = Arc::new(LockStore::new());
+        let lock = store
+            .acquire("db-migrate", "node-1", MINUTE, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let waiter = {
+            let store = Arc::clone(&store);
+            tokio::spawn(async move {
+                store
+                    .acquire("db-migrate", "node-2", MINUTE, Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        store.release("db-migrate", lock.fencing_token).unwrap();
+        let next = waiter.await.unwrap().unwrap();
+        assert_eq!(next.owner, "node-2");
+
+        // A wait that is not long enough fails with the holder
+        let err = store
+            .acquire("db-migrate", "node-3", MINUTE, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("node-2"), "{}", err);
+    }
+}
@@ -0,0 +1,31 @@
+//! Cluster mode.
+//!
+//! In cluster mode an agent knows its peers (from `cluster.peers`) and
+//! periodically checks their health, so that any node can report the state
+//! of the whole fleet. Peers can also be discovered automatically from
+//! multicast announcements, or tracked with a gossip membership protocol.
+//! Agents can be targeted by tag selectors, coordinate through named
+//! signals, barriers and locks, and elect a leader for steps that must run
+//! once.
+
+pub mod discovery;
+pub mod election;
+pub mod forward;
+pub mod gossip;
+pub mod locks;
+pub mod peers;
+pub mod selector;
+pub mod signals;
+
+pub use discovery::{Announcement, Discovery};
+pub use election::Election;
+pub use gossip::{Gossip, MemberState};
+pub use locks::LockStore;
+pub use peers::{PeerMonitor, PeerSource, PeerStatus};
+pub use selector::Selector;
+pub use signals::SignalStore;
+
+#[cfg(test)]
+mod election_tests;
+#[cfg(test)]
+mod gossip_tests;
@@ -0,0 +1,370 @@
+//! Peer registry and health monitoring.
+
+use crate::client::ShikiClient;
+use crate::cluster::discovery::Discovery;
+use crate::cluster::gossip::{Gossip, MemberState};
+use crate::config::PeerConfig;
+use crate::server::response::HealthStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info, warn};
+
+/// How a peer became known.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerSource {
+    /// Listed in `cluster.peers`.
+    #[default]
+    Static,
+    /// Learned from discovery announcements.
+    Discovered,
+    /// Tracked by the gossip membership protocol.
+    Gossip,
+}
+
+impl std::fmt::Display for PeerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeerSource::Static => write!(f, "static"),
+            PeerSource::Discovered => write!(f, "discovered"),
+            PeerSource::Gossip => write!(f, "gossip"),
+        }
+    }
+}
+
+/// Health of a single peer as seen by this agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerStatus {
+    /// Peer name.
+    pub name: String,
+    /// Peer address.
+    pub address: String,
+    /// Peer tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How the peer became known.
+    #[serde(default)]
+    pub source: PeerSource,
+    /// Time of the last discovery announcement from the peer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_announced: Option<DateTime<Utc>>,
+    /// Membership state according to gossip.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_state: Option<MemberState>,
+    /// Whether the last health check succeeded.
+    pub reachable: bool,
+    /// Version reported by the peer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Health status reported by the peer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthStatus>,
+    /// Latency of the last successful health check in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Time of the last successful health check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Time of the last health check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Error of the last failed health check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Number of consecutive failed health checks.
+    #[serde(default)]
+    pub consecutive_failures: u32,
+}
+
+impl PeerStatus {
+    /// Creates the initial status of a peer.
+    pub fn new(name: &str, address: &str, tags: &[String], source: PeerSource) -> Self {
+        Self {
+            name: name.to_string(),
+            address: address.to_string(),
+            tags: tags.to_vec(),
+            source,
+            last_announced: None,
+            member_state: None,
+            reachable: false,
+            version: None,
+            health: None,
+            latency_ms: None,
+            last_seen: None,
+            last_checked: None,
+            last_error: None,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// Tracks the configured peers and checks their health in the background.
+pub struct PeerMonitor {
+    /// Peer states, in configuration order.
+    peers: Arc<RwLock<Vec<PeerStatus>>>,
+    /// Interval between health checks.
+    interval: Duration,
+    /// Timeout of a single health check.
+    timeout: Duration,
+    /// Background tasks.
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+    /// Whether the health checks have been started.
+    health_started: AtomicBool,
+}
+
+impl PeerMonitor {
+    /// Creates a monitor for the given peers. Checks do not run until
+    /// [`PeerMonitor::spawn`] is called.
+    pub fn new(peers: &[PeerConfig], interval: Duration, timeout: Duration) -> Self {
+        Self {
+            peers: Arc::new(RwLock::new(
+                peers
+                    .iter()
+                    .map(|p| PeerStatus::new(&p.name, &p.address, &p.tags, PeerSource::Static))
+                    .collect(),
+            )),
+            interval,
+            timeout,
+            tasks: Mutex::new(Vec::new()),
+            health_started: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns a snapshot of all peers.
+    pub fn peers(&self) -> Vec<PeerStatus> {
+        self.peers.read().map(|p| p.clone()).unwrap_or_default()
+    }
+
+    /// Returns a snapshot of a single peer.
+    pub fn peer(&self, name: &str) -> Option<PeerStatus> {
+        self.peers
+            .read()
+            .ok()
+            .and_then(|p| p.iter().find(|s| s.name == name).cloned())
+    }
+
+    /// Starts the background health checks. Calling this more than once has no effect.
+    pub fn spawn(&self) {
+        let Ok(mut tasks) = self.tasks.lock() else {
+            return;
+        };
+        if self.health_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        info!(
+            peers = self.peers.read().map(|p| p.len()).unwrap_or(0),
+            interval_secs = self.interval.as_secs(),
+            "Starting peer health checks"
+        );
+        tasks.push(tokio::spawn(check_loop(
+            Arc::clone(&self.peers),
+            self.interval,
+            self.timeout,
+        )));
+    }
+
+    /// Starts announcing this agent and merging announced peers into the registry.
+    pub fn spawn_discovery(&self, discovery: Discovery) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.push(tokio::spawn(discovery.run(Arc::clone(&self.peers))));
+        }
+    }
+
+    /// Starts the gossip membership protocol, which keeps the gossip
+    /// entries of the registry up to date.
+    pub fn spawn_gossip(&self, gossip: Gossip) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.push(tokio::spawn(gossip.run(Arc::clone(&self.peers))));
+        }
+    }
+
+    /// Checks every peer once, concurrently.
+    pub async fn check_all(&self) {
+        check_all(&self.peers, self.timeout).await;
+    }
+}
+
+impl Drop for PeerMonitor {
+    fn drop(&mut self) {
+        if let Ok(tasks) = self.tasks.lock() {
+            for task in tasks.iter() {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Runs the health checks forever.
+async fn check_loop(peers: Arc<RwLock<Vec<PeerStatus>>>, interval: Duration, timeout: Duration) {
+    loop {
+        check_all(&peers, timeout).await;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Checks every peer currently in the registry once, concurrently. Gossip
+/// members are skipped; their state comes from the membership protocol.
+async fn check_all(peers: &Arc<RwLock<Vec<PeerStatus>>>, timeout: Duration) {
+    let targets: Vec<(String, String)> = peers
+        .read()
+        .map(|p| {
+            p.iter()
+                .filter(|s| s.source != PeerSource::Gossip)
+                .map(|s| (s.name.clone(), s.address.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut checks = JoinSet::new();
+    for (name, address) in targets {
+        let peers = Arc::clone(peers);
+        checks.spawn(async move { check_peer(&peers, name, address, timeout).await });
+    }
+    while checks.join_next().await.is_some() {}
+}
+
+/// Checks the health of a peer and records the result.
+async fn check_peer(
+    peers: &RwLock<Vec<PeerStatus>>,
+    name: String,
+    address: String,
+    timeout: Duration,
+) {
+    let started = Instant::now();
+    let outcome = match ShikiClient::with_timeout(&address, timeout) {
+        Ok(client) => client.health().await,
+        Err(e) => Err(e),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let Ok(mut peers) = peers.write() else {
+        return;
+    };
+    // The peer may have expired or moved while the check was running
+    let Some(status) = peers
+        .iter_mut()
+        .find(|s| s.name == name && s.address == address)
+    else {
+        return;
+    };
+    let now = Utc::now();
+    status.last_checked = Some(now);
+
+    match outcome {
+        Ok(health) => {
+            if !status.reachable {
+                info!(peer = %name, address = %address, "Peer is reachable");
+            }
+            status.reachable = true;
+            status.version = Some(health.version);
+            status.health = Some(health.status);
+            status.latency_ms = Some(latency_ms);
+            status.last_seen = Some(now);
+            status.last_error = None;
+            status.consecutive_failures = 0;
+            debug!(peer = %name, latency_ms = latency_ms, "Peer health check succeeded");
+        }
+        Err(e) => {
+            if status.reachable || status.consecutive_failures == 0 {
+                warn!(peer = %name, address = %address, error = %e, "Peer is unreachable");
+            }
+            status.reachable = false;
+            status.last_error = Some(e.to_string());
+            status.consecutive_failures = status.consecutive_failures.saturating_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Backend, Config, ServiceDefinition};
+    use crate::server::{create_router, state::AppState};
+
+    /// Starts an agent on an ephemeral port and returns its address.
+    async fn start_agent() -> String {
+        let mut config = Config::default();
+        config.agent.backend = Backend::Exec;
+        config.services.insert(
+            "app".to_string(),
+            ServiceDefinition {
+                start: "true".to_string(),
+                stop: "true".to_string(),
+                status: "true".to_string(),
+                ..Default::default()
+            },
+        );
+        let state = Arc::new(AppState::new(&config).unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            axum::serve(listener, create_router(state)).await.unwrap();
+        });
+        address
+    }
+
+    fn peer(name: &str, address: &str) -> PeerConfig {
+        PeerConfig {
+            name: name.to_string(),
+            address: address.to_string(),
+            tags: vec!["test".to_string()],
+            token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_all() {
+        let address = start_agent().await;
+        let monitor = PeerMonitor::new(
+            &[peer("up", &address), peer("down", "127.0.0.1:1")],
+            Duration::from_secs(10),
+            Duration::from_secs(2),
+        );
+
+        let initial = monitor.peers();
+        assert_eq!(initial.len(), 2);
+        assert!(initial
+            .iter()
+            .all(|p| !p.reachable && p.last_checked.is_none()));
+
+        monitor.check_all().await;
+
+        let up = monitor.peer("up").unwrap();
+        assert!(up.reachable);
+        assert_eq!(up.version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(up.health, Some(HealthStatus::Healthy));
+        assert!(up.latency_ms.is_some());
+        assert!(up.last_seen.is_some());
+
+        let down = monitor.peer("down").unwrap();
+        assert!(!down.reachable);
+        assert!(down.last_seen.is_none());
+        assert!(down.last_checked.is_some());
+        assert!(down.last_error.is_some());
+        assert_eq!(down.consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_checks_in_background() {
+        let address = start_agent().await;
+        let monitor = PeerMonitor::new(
+            &[peer("up", &address)],
+            Duration::from_millis(100),
+            Duration::from_secs(2),
+        );
+
+        monitor.spawn();
+        for _ in 0..50 {
+            if monitor.peer("up").unwrap().reachable {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("peer was never marked reachable");
+    }
+}
@@ -0,0 +1,181 @@
+//! Tag selectors for targeting agents.
+//!
+//! A selector is a comma-separated list of terms; an agent matches when it
+//! matches every term:
+//!
+//! - `tag:db` or `db` - the agent has the tag `db`
+//! - `role=db` - the agent has the tag `role=db` (or `role:db`)
+//! - `name=node-*` - the agent name matches
+//!
+//! Values may contain `*` and `?` wildcards.
+
+use crate::error::{Result, ShikiError};
+use serde::{Deserialize, Serialize};
+
+/// A parsed selector term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    /// Bare tag.
+    Tag(String),
+    /// `key=value` label, stored as a tag.
+    Label { key: String, value: String },
+    /// Agent name.
+    Name(String),
+}
+
+/// Tag selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    /// Original selector text.
+    raw: String,
+    /// Terms, all of which must match.
+    terms: Vec<Term>,
+}
+
+impl Selector {
+    /// Returns whether an agent with the given name and tags matches.
+    pub fn matches(&self, name: &str, tags: &[String]) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Tag(pattern) => tags.iter().any(|t| glob_match::glob_match(pattern, t)),
+            Term::Label { key, value } => tags.iter().any(|t| {
+                t.split_once(['=', ':'])
+                    .is_some_and(|(k, v)| k == key && glob_match::glob_match(value, v))
+            }),
+            Term::Name(pattern) => glob_match::glob_match(pattern, name),
+        })
+    }
+
+    /// Returns the selector text.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl std::fmt::Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = ShikiError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut terms = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            if part.is_empty() {
+                continue;
+            }
+            let term = if let Some(tag) = part.strip_prefix("tag:") {
+                Term::Tag(tag.to_string())
+            } else if let Some((key, value)) = part.split_once('=') {
+                if key == "name" {
+                    Term::Name(value.to_string())
+                } else {
+                    Term::Label {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    }
+                }
+            } else {
+                Term::Tag(part.to_string())
+            };
+
+            let empty = match &term {
+                Term::Tag(t) | Term::Name(t) => t.is_empty(),
+                Term::Label { key, value } => key.is_empty() || value.is_empty(),
+            };
+            if empty {
+                return Err(ShikiError::invalid_request(format!(
+                    "Invalid selector term '{}'",
+                    part
+                )));
+            }
+            terms.push(term);
+        }
+
+        if terms.is_empty() {
+            return Err(ShikiError::invalid_request("Selector must not be empty"));
+        }
+
+        Ok(Self {
+            raw: s.trim().to_string(),
+            terms,
+        })
+    }
+}
+
+impl Serialize for Selector {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Selector {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tag_terms() {
+        let selector: Selector = "tag:db".parse().unwrap();
+        assert!(selector.matches("node-1", &tags(&["db", "prod"])));
+        assert!(!selector.matches("node-1", &tags(&["web"])));
+
+        let bare: Selector = "db".parse().unwrap();
+        assert_eq!(
+            bare.matches("n", &tags(&["db"])),
+            selector.matches("n", &tags(&["db"]))
+        );
+    }
+
+    #[test]
+    fn test_label_terms() {
+        let selector: Selector = "role=db,env=prod".parse().unwrap();
+        assert!(selector.matches("n", &tags(&["role=db", "env=prod"])));
+        assert!(selector.matches("n", &tags(&["role:db", "env:prod"])));
+        assert!(!selector.matches("n", &tags(&["role=db", "env=staging"])));
+        assert!(!selector.matches("n", &tags(&["role=db"])));
+    }
+
+    #[test]
+    fn test_name_and_wildcards() {
+        let selector: Selector = "name=db-*".parse().unwrap();
+        assert!(selector.matches("db-01", &[]));
+        assert!(!selector.matches("web-01", &[]));
+
+        let selector: Selector = "env=prod-*".parse().unwrap();
+        assert!(selector.matches("n", &tags(&["env=prod-eu"])));
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        assert!("".parse::<Selector>().is_err());
+        assert!(" , ".parse::<Selector>().is_err());
+        assert!("role=".parse::<Selector>().is_err());
+        assert!("tag:".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_selector_serde() {
+        let selector: Selector = serde_json::from_str("\"role=db\"").unwrap();
+        assert_eq!(selector.as_str(), "role=db");
+        assert_eq!(serde_json::to_string(&selector).unwrap(), "\"role=db\"");
+        assert!(serde_json::from_str::<Selector>("\"\"").is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Named signals and barriers.
+//!
+//! A signal is a named checkpoint that any number of agents or scripts set
+//! under their own identity, each with a time to live. A barrier waits until
+//! a signal has been set by a given number of signalers, e.g. "all three
+//! DB replicas are up".
+//!
+//! Signals live in memory on the agent that receives them. In a cluster,
+//! `signals.coordinator` designates one agent that holds them for everyone.
+
+use crate::error::{Result, ShikiError};
+use crate::server::response::{SignalData, SignerData};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{debug, info};
+
+/// A signaler of a signal.
+#[derive(Debug, Clone)]
+struct Signer {
+    set_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// In-memory signal store.
+#[derive(Debug)]
+pub struct SignalStore {
+    /// Signalers by signal name and identity.
+    signals: Mutex<BTreeMap<String, BTreeMap<String, Signer>>>,
+    /// Bumped on every change to wake up barrier waits.
+    changed: watch::Sender<u64>,
+}
+
+impl Default for SignalStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks that a signal name is non-empty and URL-safe.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(ShikiError::invalid_request(format!(
+            "Invalid signal name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        )))
+    }
+}
+
+impl SignalStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self {
+            signals: Mutex::new(BTreeMap::new()),
+            changed: watch::channel(0).0,
+        }
+    }
+
+    /// Sets a signal for `agent` for `ttl`, replacing an earlier set by the
+    /// same agent.
+    pub fn set(&self, name: &str, agent: &str, ttl: Duration) -> Result<SignalData> {
+        validate_name(name)?;
+        let now = Utc::now();
+        let ttl = chrono::Duration::from_std(ttl)
+            .map_err(|_| ShikiError::invalid_request("Signal TTL is too large"))?;
+
+        let signal = {
+            let mut signals = self.lock();
+            let signers = signals.entry(name.to_string()).or_default();
+            signers.insert(
+                agent.to_string(),
+                Signer {
+                    set_at: now,
+                    expires_at: now + ttl,
+                },
+            );
+            purge(&mut signals, now);
+            data(name, signals.get(name))
+        };
+
+        info!(signal = %name, agent = %agent, count = signal.count, "Signal set");
+        self.changed.send_modify(|v| *v += 1);
+        Ok(signal)
+    }
+
+    /// Clears a signal for one agent, or for everyone when `agent` is
+    /// `None`. Returns whether anything was removed.
+    pub fn clear(&self, name: &str, agent: Option<&str>) -> bool {
+        let removed = {
+            let mut signals = self.lock();
+            purge(&mut signals, Utc::now());
+            match agent {
+                Some(agent) => {
+                    let removed = signals
+                        .get_mut(name)
+                        .is_some_and(|s| s.remove(agent).is_some());
+                    if signals.get(name).is_some_and(|s| s.is_empty()) {
+                        signals.remove(name);
+                    }
+                    removed
+                }
+                None => signals.remove(name).is_some(),
+            }
+        };
+        if removed {
+            info!(signal = %name, agent = agent.unwrap_or("*"), "Signal cleared");
+            self.changed.send_modify(|v| *v += 1);
+        }
+        removed
+    }
+
+    /// Returns the state of a signal; unknown signals have no signalers.
+    pub fn get(&self, name: &str) -> SignalData {
+        let mut signals = self.lock();
+        purge(&mut signals, Utc::now());
+        data(name, signals.get(name))
+    }
+
+    /// Returns every signal that has signalers.
+    pub fn list(&self) -> Vec<SignalData> {
+        let mut signals = self.lock();
+        purge(&mut signals, Utc::now());
+        signals
+            .iter()
+            .map(|(name, signers)| data(name, Some(signers)))
+            .collect()
+    }
+
+    /// Waits until `count` signalers have set the signal.
+    pub async fn wait(&self, name: &str, count: usize, timeout: Duration) -> Result<SignalData> {
+        validate_name(name)?;
+        // Subscribe before checking so that no change is missed
+        let mut changed = self.changed.subscribe();
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let signal = self.get(name);
+            if signal.count >= count {
+                return Ok(signal);
+            }
+            debug!(signal = %name, count = signal.count, required = count, "Waiting at barrier");
+
+            tokio::select! {
+                _ = changed.changed() => {}
+                _ = tokio::time::sleep_until(deadline) => {
+                    return Err(ShikiError::Timeout {
+                        operation: format!(
+                            "barrier {} ({} of {} signals)",
+                            name, signal.count, count
+                        ),
+                        seconds: timeout.as_secs(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, BTreeMap<String, Signer>>> {
+        self.signals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Removes expired signalers and signals left without signalers.
+fn purge(signals: &mut BTreeMap<String, BTreeMap<String, Signer>>, now: DateTime<Utc>) {
+    signals.retain(|name, signers| {
+        signers.retain(|agent, signer| {
+            let alive = signer.expires_at > now;
+            if !alive {
+                debug!(signal = %name, agent = %agent, "Signal expired");
+            }
+            alive
+        });
+        !signers.is_empty()
+    });
+}
+
+fn data(name: &str, signers: Option<&BTreeMap<String, Signer>>) -> SignalData {
+    let signers: Vec<SignerData> = signers
+        .into_iter()
+        .flatten()
+        .map(|(agent, signer)| SignerData {
+            agent: agent.clone(),
+            set_at: signer.set_at,
+            expires_at: signer.expires_at,
+        })
+        .collect();
+    SignalData {
+        name: name.to_string(),
+        count: signers.len(),
+        signers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_set_and_clear() {
+        let store = SignalStore::new();
+        assert_eq!(store.get("db-ready").count, 0);
+
+        store.set("db-ready", "db-1", HOUR).unwrap();
+        store.set("db-ready", "db-2", HOUR).unwrap();
+        // Setting again does not count twice
+        let signal = store.set("db-ready", "db-1", HOUR).unwrap();
+        assert_eq!(signal.count, 2);
+        assert_eq!(signal.signers[1].agent, "db-2");
+        assert_eq!(store.list().len(), 1);
+
+        assert!(store.clear("db-ready", Some("db-1")));
+        assert!(!store.clear("db-ready", Some("db-1")));
+        assert_eq!(store.get("db-ready").count, 1);
+        assert!(store.clear("db-ready", None));
+        assert!(store.list().is_empty());
+
+        assert!(store.set("bad name", "db-1", HOUR).is_err());
+    }
+
+    #[test]
+    fn test_signal_expires() {
+        let store = SignalStore::new();
+        store.set("db-ready", "db-1", Duration::ZERO).unwrap();
+        store.set("db-ready", "db-2", HOUR).unwrap();
+
+        let signal = store.get("db-ready");
+        assert_eq!(signal.count, 1);
+        assert_eq!(signal.signers[0].agent, "db-2");
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_count() {
+        let store = Arc::new(SignalStore::new());
+        store.set("db-ready", "db-1", HOUR).unwrap();
+
+        let waiter = {
+            let store = Arc::clone(&store);
+            tokio::spawn(async move { store.wait("db-ready", 3, Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        store.set("db-ready", "db-2", HOUR).unwrap();
+        store.set("db-ready", "db-3", HOUR).unwrap();
+        let signal = waiter.await.unwrap().unwrap();
+        assert_eq!(signal.count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_timeout() {
+        let store = SignalStore::new();
+        store.set("db-ready", "db-1", HOUR).unwrap();
+
+        let err = store
+            .wait("db-ready", 2, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ShikiError::Timeout { .. }));
+        assert!(err.to_string().contains("1 of 2"));
+    }
+}
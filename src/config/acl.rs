@@ -1,9 +1,10 @@
 //! Access control list configuration.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Access control list configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct AclConfig {
     /// Allowed services (empty = allow all).
@@ -38,6 +39,20 @@ impl AclConfig {
 
         false
     }
+
+    /// Returns whether every service is denied: a denied pattern matches
+    /// any name, or every allowed pattern is also denied.
+    pub fn denies_all(&self) -> bool {
+        let denied = |name: &str| {
+            self.denied
+                .iter()
+                .any(|pattern| glob_match::glob_match(pattern, name))
+        };
+        self.denied
+            .iter()
+            .any(|pattern| !pattern.is_empty() && pattern.chars().all(|c| c == '*'))
+            || (!self.allowed.is_empty() && self.allowed.iter().all(|pattern| denied(pattern)))
+    }
 }
 
 #[cfg(test)]
@@ -70,6 +85,28 @@ mod tests {
         assert!(!acl.is_allowed("secret-service"));
     }
 
+    #[test]
+    fn test_acl_denies_all() {
+        let mut acl = AclConfig {
+            allowed: vec![],
+            denied: vec!["secret-*".to_string()],
+        };
+        assert!(!acl.denies_all());
+        acl.denied.push("*".to_string());
+        assert!(acl.denies_all());
+
+        let acl = AclConfig {
+            allowed: vec!["redis-*".to_string(), "nginx".to_string()],
+            denied: vec!["redis-*".to_string(), "nginx".to_string()],
+        };
+        assert!(acl.denies_all());
+        let acl = AclConfig {
+            allowed: vec!["redis-*".to_string()],
+            denied: vec!["redis-test".to_string()],
+        };
+        assert!(!acl.denies_all());
+    }
+
     #[test]
     fn test_acl_default() {
         let acl = AclConfig::default();
@@ -1,5 +1,6 @@
 //! Agent and service configuration types.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -7,7 +8,7 @@ use std::str::FromStr;
 use crate::error::ShikiError;
 
 /// Agent configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct AgentConfig {
     /// Agent name (defaults to hostname).
@@ -39,7 +40,7 @@ impl Default for AgentConfig {
 }
 
 /// Agent operating mode.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AgentMode {
     /// Standalone mode.
@@ -63,7 +64,7 @@ impl FromStr for AgentMode {
 }
 
 /// Service control backend type.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Backend {
     /// systemd backend.
@@ -74,6 +75,15 @@ pub enum Backend {
     Exec,
 }
 
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Systemd => write!(f, "systemd"),
+            Backend::Exec => write!(f, "exec"),
+        }
+    }
+}
+
 impl FromStr for Backend {
     type Err = ShikiError;
 
@@ -87,9 +97,13 @@ impl FromStr for Backend {
 }
 
 /// Service definition for exec backend.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ServiceDefinition {
+    /// Service type.
+    #[serde(rename = "type")]
+    pub kind: ServiceKind,
+
     /// Start command.
     pub start: String,
 
@@ -113,6 +127,147 @@ pub struct ServiceDefinition {
 
     /// Command timeout in seconds.
     pub timeout: Option<u64>,
+
+    /// Process supervision settings (for `type: supervised`).
+    pub supervisor: SupervisorConfig,
+
+    /// Services that must be running before this one (started first, failure aborts).
+    pub requires: Vec<String>,
+
+    /// Services started before this one whose failure is tolerated.
+    pub wants: Vec<String>,
+
+    /// Services this one is ordered after when started together.
+    pub after: Vec<String>,
+
+    /// Services this one is ordered before when started together.
+    pub before: Vec<String>,
+}
+
+/// How the exec backend manages a service.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceKind {
+    /// Start/stop/status are user-defined commands.
+    #[default]
+    Command,
+
+    /// shiki spawns the start command and supervises the process itself.
+    Supervised,
+}
+
+/// Process supervision configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    /// Signal sent to the process on stop.
+    pub stop_signal: StopSignal,
+
+    /// Seconds to wait after `stop_signal` before sending SIGKILL.
+    pub stop_timeout: u64,
+
+    /// Restart policy applied when the process exits on its own.
+    pub restart_policy: RestartPolicy,
+
+    /// Initial restart delay in milliseconds.
+    pub restart_delay_ms: u64,
+
+    /// Maximum restart delay in milliseconds.
+    pub restart_max_delay_ms: u64,
+
+    /// Restart delay backoff multiplier.
+    pub restart_multiplier: f64,
+
+    /// Number of recent stdout/stderr lines kept in memory.
+    pub output_lines: usize,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            stop_signal: StopSignal::Term,
+            stop_timeout: 10,
+            restart_policy: RestartPolicy::Never,
+            restart_delay_ms: 1000,
+            restart_max_delay_ms: 30000,
+            restart_multiplier: 2.0,
+            output_lines: 100,
+        }
+    }
+}
+
+/// Restart policy for supervised processes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Never restart.
+    #[default]
+    Never,
+
+    /// Restart only when the process exits unsuccessfully.
+    OnFailure,
+
+    /// Always restart.
+    Always,
+}
+
+impl RestartPolicy {
+    /// Returns whether a process that exited with `success` should be restarted.
+    pub fn should_restart(&self, success: bool) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !success,
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// Signal used to stop a supervised process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum StopSignal {
+    /// SIGTERM.
+    #[default]
+    #[serde(rename = "SIGTERM", alias = "TERM")]
+    Term,
+
+    /// SIGINT.
+    #[serde(rename = "SIGINT", alias = "INT")]
+    Int,
+
+    /// SIGQUIT.
+    #[serde(rename = "SIGQUIT", alias = "QUIT")]
+    Quit,
+
+    /// SIGHUP.
+    #[serde(rename = "SIGHUP", alias = "HUP")]
+    Hup,
+
+    /// SIGUSR1.
+    #[serde(rename = "SIGUSR1", alias = "USR1")]
+    Usr1,
+
+    /// SIGUSR2.
+    #[serde(rename = "SIGUSR2", alias = "USR2")]
+    Usr2,
+
+    /// SIGKILL.
+    #[serde(rename = "SIGKILL", alias = "KILL")]
+    Kill,
+}
+
+impl StopSignal {
+    /// Returns the raw signal number.
+    pub fn as_raw(&self) -> i32 {
+        match self {
+            StopSignal::Term => libc::SIGTERM,
+            StopSignal::Int => libc::SIGINT,
+            StopSignal::Quit => libc::SIGQUIT,
+            StopSignal::Hup => libc::SIGHUP,
+            StopSignal::Usr1 => libc::SIGUSR1,
+            StopSignal::Usr2 => libc::SIGUSR2,
+            StopSignal::Kill => libc::SIGKILL,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -154,5 +309,30 @@ mod tests {
         assert!(def.reload.is_none());
         assert!(def.working_dir.is_none());
         assert!(def.env.is_empty());
+        assert_eq!(def.kind, ServiceKind::Command);
+        assert_eq!(def.supervisor.restart_policy, RestartPolicy::Never);
+    }
+
+    #[test]
+    fn test_restart_policy() {
+        let policy: RestartPolicy = serde_yaml::from_str("on-failure").unwrap();
+        assert_eq!(policy, RestartPolicy::OnFailure);
+        assert!(serde_yaml::from_str::<RestartPolicy>("invalid").is_err());
+
+        assert!(!RestartPolicy::Never.should_restart(false));
+        assert!(RestartPolicy::OnFailure.should_restart(false));
+        assert!(!RestartPolicy::OnFailure.should_restart(true));
+        assert!(RestartPolicy::Always.should_restart(true));
+    }
+
+    #[test]
+    fn test_stop_signal_parse() {
+        assert_eq!(StopSignal::Kill.as_raw(), libc::SIGKILL);
+        assert!(serde_yaml::from_str::<StopSignal>("SIGFOO").is_err());
+
+        let signal: StopSignal = serde_yaml::from_str("SIGQUIT").unwrap();
+        assert_eq!(signal, StopSignal::Quit);
+        let signal: StopSignal = serde_yaml::from_str("INT").unwrap();
+        assert_eq!(signal, StopSignal::Int);
     }
 }
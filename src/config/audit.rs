@@ -0,0 +1,37 @@
+//! Audit log configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Audit log configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Record mutating requests and authentication failures.
+    pub enabled: bool,
+
+    /// Path of the audit log (JSON lines, appended to).
+    pub path: PathBuf,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("/var/log/shiki/audit.log"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_config_default() {
+        let config = AuditConfig::default();
+        assert!(!config.enabled);
+        assert_eq!(config.path, PathBuf::from("/var/log/shiki/audit.log"));
+    }
+}
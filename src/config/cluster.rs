@@ -1,9 +1,13 @@
 //! Cluster configuration types.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-/// Cluster configuration (future implementation).
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+use super::secret::REDACTED;
+
+/// Cluster configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ClusterConfig {
     /// Enable cluster mode.
@@ -11,10 +15,226 @@ pub struct ClusterConfig {
 
     /// Peer agents.
     pub peers: Vec<PeerConfig>,
+
+    /// Interval between peer health checks in seconds.
+    pub health_interval_seconds: u64,
+
+    /// Defaults for selector-based fan-out.
+    pub fanout: FanoutConfig,
+
+    /// Request forwarding to peers.
+    pub forwarding: ForwardingConfig,
+
+    /// Automatic peer discovery.
+    pub discovery: DiscoveryConfig,
+
+    /// Gossip-based membership.
+    pub gossip: GossipConfig,
+
+    /// Leader election among the configured peers.
+    pub election: ElectionConfig,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: Vec::new(),
+            health_interval_seconds: 10,
+            fanout: FanoutConfig::default(),
+            forwarding: ForwardingConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            gossip: GossipConfig::default(),
+            election: ElectionConfig::default(),
+        }
+    }
+}
+
+/// Fan-out configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FanoutConfig {
+    /// Maximum number of agents notified at the same time.
+    pub parallelism: usize,
+
+    /// Number of failed agents tolerated; once exceeded, agents that have
+    /// not been notified yet are skipped.
+    pub max_failures: usize,
+}
+
+impl Default for FanoutConfig {
+    fn default() -> Self {
+        Self {
+            parallelism: 4,
+            max_failures: 0,
+        }
+    }
+}
+
+/// Forwarding configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ForwardingConfig {
+    /// Accept `/api/v1/peers/{peer}/...` requests and forward them to peers.
+    pub enabled: bool,
+
+    /// Maximum number of agents a forwarded request may pass through.
+    pub max_hops: usize,
+}
+
+impl Default for ForwardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_hops: 4,
+        }
+    }
+}
+
+/// Discovery configuration.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    /// Announce this agent and learn peers from announcements.
+    pub enabled: bool,
+
+    /// Multicast group, or a broadcast address, to announce to.
+    pub group: String,
+
+    /// UDP port of the announcements.
+    pub port: u16,
+
+    /// Local interface address used for the multicast group.
+    pub interface: String,
+
+    /// Address announced to peers (host or host:port). Defaults to the
+    /// announcement's source address and `server.port`.
+    pub advertise_address: Option<String>,
+
+    /// Interval between announcements in seconds.
+    pub announce_interval_seconds: u64,
+
+    /// Seconds without announcements after which a discovered peer is removed.
+    pub expire_seconds: u64,
+
+    /// Shared key authenticating announcements (HMAC-SHA256). When set,
+    /// unsigned announcements and announcements with a wrong signature are ignored.
+    pub key: Option<String>,
+}
+
+impl fmt::Debug for DiscoveryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiscoveryConfig")
+            .field("enabled", &self.enabled)
+            .field("group", &self.group)
+            .field("port", &self.port)
+            .field("interface", &self.interface)
+            .field("advertise_address", &self.advertise_address)
+            .field("announce_interval_seconds", &self.announce_interval_seconds)
+            .field("expire_seconds", &self.expire_seconds)
+            .field("key", &self.key.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            group: "239.255.42.99".to_string(),
+            port: 7946,
+            interface: "0.0.0.0".to_string(),
+            advertise_address: None,
+            announce_interval_seconds: 5,
+            expire_seconds: 30,
+            key: None,
+        }
+    }
+}
+
+/// Gossip membership configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct GossipConfig {
+    /// Track cluster members with the SWIM gossip protocol instead of
+    /// polling every peer.
+    pub enabled: bool,
+
+    /// Address the gossip socket binds to.
+    pub bind: String,
+
+    /// UDP port of the gossip protocol.
+    pub port: u16,
+
+    /// IP address announced to other members for gossip and HTTP. Defaults
+    /// to the bind address, or the source address seen by other members
+    /// when the bind address is a wildcard.
+    pub advertise_address: Option<String>,
+
+    /// Gossip addresses (host:port) of members to join through.
+    pub seeds: Vec<String>,
+
+    /// Interval between probes in milliseconds.
+    pub probe_interval_ms: u64,
+
+    /// Time to wait for a direct probe before probing indirectly, in milliseconds.
+    pub probe_timeout_ms: u64,
+
+    /// Number of members asked to probe an unresponsive member.
+    pub indirect_probes: usize,
+
+    /// Time a member stays suspect before it is declared dead, in milliseconds.
+    pub suspicion_timeout_ms: u64,
+
+    /// Seconds a dead member is remembered before it is removed.
+    pub dead_retention_seconds: u64,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "0.0.0.0".to_string(),
+            port: 7947,
+            advertise_address: None,
+            seeds: Vec::new(),
+            probe_interval_ms: 1000,
+            probe_timeout_ms: 500,
+            indirect_probes: 3,
+            suspicion_timeout_ms: 5000,
+            dead_retention_seconds: 300,
+        }
+    }
+}
+
+/// Leader election configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ElectionConfig {
+    /// Elect a leader among this agent and `cluster.peers`.
+    pub enabled: bool,
+
+    /// Time a leader holds its lease without renewing it, in milliseconds.
+    /// Agents do not vote for another candidate while a lease they granted
+    /// is valid.
+    pub lease_ms: u64,
+
+    /// Interval between lease renewals by the leader, in milliseconds.
+    pub renew_interval_ms: u64,
+}
+
+impl Default for ElectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lease_ms: 10000,
+            renew_interval_ms: 3000,
+        }
+    }
 }
 
 /// Peer agent configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PeerConfig {
     /// Peer name.
     pub name: String,
@@ -25,6 +245,23 @@ pub struct PeerConfig {
     /// Peer tags.
     #[serde(default)]
     pub tags: Vec<String>,
+
+    /// Bearer token presented to the peer when forwarding or fanning out.
+    /// When unset, the caller's `Authorization` header is passed through.
+    /// Discovered peers have no token and never receive the caller's header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for PeerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerConfig")
+            .field("name", &self.name)
+            .field("address", &self.address)
+            .field("tags", &self.tags)
+            .field("token", &self.token.as_ref().map(|_| REDACTED))
+            .finish()
+    }
 }
 
 #[cfg(test)]
@@ -36,6 +273,22 @@ mod tests {
         let config = ClusterConfig::default();
         assert!(!config.enabled);
         assert!(config.peers.is_empty());
+        assert_eq!(config.health_interval_seconds, 10);
+        assert_eq!(config.fanout.parallelism, 4);
+        assert_eq!(config.fanout.max_failures, 0);
+        assert!(!config.forwarding.enabled);
+        assert_eq!(config.forwarding.max_hops, 4);
+        assert!(!config.discovery.enabled);
+        assert_eq!(config.discovery.group, "239.255.42.99");
+        assert_eq!(config.discovery.port, 7946);
+        assert_eq!(config.discovery.expire_seconds, 30);
+        assert!(!config.gossip.enabled);
+        assert_eq!(config.gossip.port, 7947);
+        assert_eq!(config.gossip.probe_interval_ms, 1000);
+        assert_eq!(config.gossip.suspicion_timeout_ms, 5000);
+        assert!(!config.election.enabled);
+        assert_eq!(config.election.lease_ms, 10000);
+        assert_eq!(config.election.renew_interval_ms, 3000);
     }
 
     #[test]
@@ -44,6 +297,7 @@ mod tests {
             name: "peer1".to_string(),
             address: "192.168.1.100:8080".to_string(),
             tags: vec!["production".to_string()],
+            token: None,
         };
 
         assert_eq!(peer.name, "peer1");
@@ -0,0 +1,312 @@
+//! Desired-state configuration types.
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::error::ShikiError;
+
+/// Declared state of the local services, kept by the reconciliation loop.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DesiredStateConfig {
+    /// Interval between reconciliation passes in seconds.
+    pub interval_seconds: u64,
+
+    /// Declarations keyed by service name. A service is either given a
+    /// state (`nginx: running`) or a full declaration.
+    pub services: HashMap<String, DesiredService>,
+}
+
+impl Default for DesiredStateConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: 30,
+            services: HashMap::new(),
+        }
+    }
+}
+
+/// Declaration of a service.
+///
+/// `state` is desired while the schedule is active and every condition
+/// holds; otherwise the opposite state is desired.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "DesiredServiceSpec")]
+pub struct DesiredService {
+    /// Desired state.
+    pub state: DesiredState,
+
+    /// When `state` applies (local time).
+    pub schedule: Option<Schedule>,
+
+    /// Conditions under which `state` applies.
+    pub conditions: DesiredConditions,
+}
+
+/// Short or full form of a declaration.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum DesiredServiceSpec {
+    State(DesiredState),
+    Full {
+        /// Desired state.
+        state: DesiredState,
+        /// When `state` applies (local time).
+        #[serde(default)]
+        schedule: Option<Schedule>,
+        /// Conditions under which `state` applies.
+        #[serde(default)]
+        conditions: DesiredConditions,
+    },
+}
+
+// Described as it is written, in the short or the full form
+impl JsonSchema for DesiredService {
+    fn schema_name() -> String {
+        "DesiredService".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        DesiredServiceSpec::json_schema(gen)
+    }
+}
+
+impl From<DesiredServiceSpec> for DesiredService {
+    fn from(spec: DesiredServiceSpec) -> Self {
+        match spec {
+            DesiredServiceSpec::State(state) => Self {
+                state,
+                schedule: None,
+                conditions: DesiredConditions::default(),
+            },
+            DesiredServiceSpec::Full {
+                state,
+                schedule,
+                conditions,
+            } => Self {
+                state,
+                schedule,
+                conditions,
+            },
+        }
+    }
+}
+
+/// Desired state of a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DesiredState {
+    /// The service should be running.
+    Running,
+    /// The service should be stopped.
+    Stopped,
+}
+
+impl DesiredState {
+    /// Returns the other state.
+    pub fn opposite(self) -> Self {
+        match self {
+            DesiredState::Running => DesiredState::Stopped,
+            DesiredState::Stopped => DesiredState::Running,
+        }
+    }
+}
+
+impl std::fmt::Display for DesiredState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DesiredState::Running => write!(f, "running"),
+            DesiredState::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// Conditions of a declaration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DesiredConditions {
+    /// Only while this agent is the cluster leader.
+    pub leader: bool,
+
+    /// Only while this signal is set.
+    pub signal: Option<String>,
+}
+
+/// A daily time window, optionally limited to some days of the week, such
+/// as `mon-fri 08:00-20:00`. A window ending at or before its start time
+/// runs past midnight; `00:00-00:00` is the whole day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    /// Days the window starts on (empty = every day).
+    pub days: Vec<Weekday>,
+    /// Start of the window.
+    pub from: NaiveTime,
+    /// End of the window.
+    pub to: NaiveTime,
+}
+
+impl Schedule {
+    /// Returns whether the window is active at `now`.
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let today = now.weekday();
+        if self.from < self.to {
+            starts_on(today) && self.from <= time && time < self.to
+        } else {
+            // Past midnight: the part after `from` today, or the part
+            // before `to` of the window that started yesterday
+            (starts_on(today) && time >= self.from) || (starts_on(today.pred()) && time < self.to)
+        }
+    }
+}
+
+// Written as a string such as `mon-fri 08:00-20:00`
+impl JsonSchema for Schedule {
+    fn schema_name() -> String {
+        "Schedule".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ShikiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            ShikiError::config(format!(
+                "Invalid schedule '{}': {} (expected e.g. 'mon-fri 08:00-20:00')",
+                s, reason
+            ))
+        };
+        let (days, window) = match s.trim().rsplit_once(' ') {
+            Some((days, window)) => (parse_days(days.trim()).map_err(|e| invalid(&e))?, window),
+            None => (Vec::new(), s.trim()),
+        };
+        let (from, to) = window
+            .split_once('-')
+            .ok_or_else(|| invalid("missing time range"))?;
+        let time = |t: &str| {
+            NaiveTime::parse_from_str(t, "%H:%M").map_err(|_| invalid(&format!("bad time '{}'", t)))
+        };
+        Ok(Self {
+            days,
+            from: time(from)?,
+            to: time(to)?,
+        })
+    }
+}
+
+/// Parses `mon,wed,fri` or `mon-fri` style day lists.
+fn parse_days(s: &str) -> Result<Vec<Weekday>, String> {
+    let day =
+        |d: &str| Weekday::from_str(d.trim()).map_err(|_| format!("unknown day '{}'", d.trim()));
+    let mut days = Vec::new();
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (mut current, last) = (day(first)?, day(last)?);
+                loop {
+                    days.push(current);
+                    if current == last {
+                        break;
+                    }
+                    current = current.succ();
+                }
+            }
+            None => days.push(day(part)?),
+        }
+    }
+    Ok(days)
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = ShikiError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.to_string()
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.days.is_empty() {
+            let days: Vec<String> = self
+                .days
+                .iter()
+                .map(|d| d.to_string().to_lowercase())
+                .collect();
+            write!(f, "{} ", days.join(","))?;
+        }
+        write!(
+            f,
+            "{}-{}",
+            self.from.format("%H:%M"),
+            self.to.format("%H:%M")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_desired_state_config_default() {
+        let config = DesiredStateConfig::default();
+        assert_eq!(config.interval_seconds, 30);
+        assert!(config.services.is_empty());
+    }
+
+    #[test]
+    fn test_schedule() {
+        // 2024-01-15 is a Monday
+        let office: Schedule = "mon-fri 08:00-20:00".parse().unwrap();
+        assert_eq!(office.days.len(), 5);
+        assert!(office.is_active(at("2024-01-15", "08:00")));
+        assert!(!office.is_active(at("2024-01-15", "20:00")));
+        assert!(!office.is_active(at("2024-01-20", "12:00")));
+        assert_eq!(office.to_string(), "mon,tue,wed,thu,fri 08:00-20:00");
+
+        // Overnight window started on Friday ends on Saturday morning
+        let night: Schedule = "fri 22:00-06:00".parse().unwrap();
+        assert!(night.is_active(at("2024-01-19", "23:00")));
+        assert!(night.is_active(at("2024-01-20", "05:59")));
+        assert!(!night.is_active(at("2024-01-19", "05:00")));
+
+        let always: Schedule = "00:00-00:00".parse().unwrap();
+        assert!(always.is_active(at("2024-01-17", "13:37")));
+
+        for invalid in [
+            "",
+            "08:00",
+            "mon-xyz 08:00-20:00",
+            "8am-5pm",
+            "mon 08:00-25:00",
+        ] {
+            assert!(invalid.parse::<Schedule>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Operation history configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Operation history configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Record operations and request counters in a database.
+    pub enabled: bool,
+
+    /// Path of the SQLite database.
+    pub path: PathBuf,
+
+    /// Maximum number of operations kept. Older ones are deleted.
+    pub max_entries: u64,
+
+    /// Maximum age of kept operations in days.
+    pub max_age_days: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("/var/lib/shiki/history.db"),
+            max_entries: 100_000,
+            max_age_days: 90,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_config_default() {
+        let config = HistoryConfig::default();
+        assert!(!config.enabled);
+        assert_eq!(config.path, PathBuf::from("/var/lib/shiki/history.db"));
+        assert_eq!(config.max_entries, 100_000);
+        assert_eq!(config.max_age_days, 90);
+    }
+}
@@ -0,0 +1,578 @@
+//! Configuration includes and drop-in files.
+//!
+//! The main configuration file may list more files under `include:` (paths
+//! relative to the main file, with globs in the file name), and every
+//! `*.yaml` / `*.yml` file of its drop-in directory is read automatically.
+//! The drop-in directory is named after the main file: `config.d` for
+//! `config.yaml`, so a file passed with `-c` never picks up the drop-ins
+//! meant for another configuration in the same directory. Files are merged in order: the main file, the includes in
+//! the order listed (the matches of a glob in lexical order), then the
+//! drop-in files in lexical order.
+//!
+//! Mappings merge key by key and sequences append. A value tagged
+//! `!replace` replaces the value of the files before it instead. Two files
+//! setting the same scalar to different values is an error.
+
+use crate::error::ShikiError;
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Key listing the files to include.
+pub(super) const INCLUDE_KEY: &str = "include";
+
+/// Extension of the drop-in directory, which replaces the extension of
+/// the main file.
+const DROP_IN_EXTENSION: &str = "d";
+
+/// Tag of a value replacing the value of the files before it.
+const REPLACE_TAG: &str = "replace";
+
+/// Configuration files merged into one document.
+#[derive(Debug, Default)]
+pub struct Sources {
+    /// Files in merge order, the main file first.
+    pub files: Vec<PathBuf>,
+    /// Content of each file.
+    contents: Vec<String>,
+    /// Index of the file that last set each setting, by dotted path.
+    origins: HashMap<String, usize>,
+}
+
+/// Reads the configuration file at `path` with its includes and drop-in
+/// files, and returns the merged document.
+pub fn load(path: &Path) -> Result<(Value, Sources), ShikiError> {
+    let mut sources = Sources::default();
+    let mut merged = match strip_replace(sources.read(path)?) {
+        Value::Null => Value::Mapping(Mapping::new()),
+        Value::Mapping(mapping) => Value::Mapping(mapping),
+        _ => {
+            return Err(ShikiError::config(format!(
+                "{}: Failed to parse config: expected a mapping",
+                path.display()
+            )))
+        }
+    };
+    let includes = merged
+        .as_mapping_mut()
+        .and_then(|mapping| mapping.remove(INCLUDE_KEY));
+    sources.record("", &merged, 0);
+
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut files = Vec::new();
+    for pattern in include_patterns(path, includes)? {
+        files.extend(expand(path, dir, &pattern)?);
+    }
+    files.extend(drop_ins(&path.with_extension(DROP_IN_EXTENSION))?);
+
+    for file in files {
+        if sources.files.contains(&file) {
+            continue;
+        }
+        let index = sources.files.len();
+        let value = sources.read(&file)?;
+        if value.get(INCLUDE_KEY).is_some() {
+            return Err(ShikiError::config(format!(
+                "{}: include is only supported in the main configuration file",
+                sources.position(index, INCLUDE_KEY)
+            )));
+        }
+        sources.merge(&mut merged, value, "", index)?;
+    }
+    Ok((merged, sources))
+}
+
+impl Sources {
+    /// Deserializes the merged document, locating type errors in the file
+    /// that set the setting.
+    pub fn deserialize<T: DeserializeOwned>(&self, value: &Value) -> Result<T, ShikiError> {
+        // Deserializing from text gives errors the path of the setting
+        let text = serde_yaml::to_string(value)
+            .map_err(|e| ShikiError::config(format!("Failed to parse config: {}", e)))?;
+        serde_yaml::from_str(&text).map_err(|e| {
+            let message = without_location(&e);
+            match self.position_of(&message) {
+                Some(position) => {
+                    ShikiError::config(format!("{}: Failed to parse config: {}", position, message))
+                }
+                None => ShikiError::config(format!("Failed to parse config: {}", message)),
+            }
+        })
+    }
+
+    /// Prefixes a configuration error with the file and line of the setting
+    /// it names, when known.
+    pub fn locate(&self, err: ShikiError) -> ShikiError {
+        let ShikiError::Config { message, .. } = &err else {
+            return err;
+        };
+        match self.position_of(message) {
+            Some(position) => ShikiError::config(format!("{}: {}", position, message)),
+            None => err,
+        }
+    }
+
+    /// Prefixes a message with the file and line of the setting it names,
+    /// when known.
+    pub fn located(&self, message: &str) -> String {
+        match self.position_of(message) {
+            Some(position) => format!("{}: {}", position, message),
+            None => message.to_string(),
+        }
+    }
+
+    /// Returns the position of the setting an error message starts with, or
+    /// of its closest parent that is set.
+    pub(super) fn position_of(&self, message: &str) -> Option<String> {
+        let mut path = message
+            .split(|c: char| c.is_whitespace() || c == ':')
+            .next()
+            .unwrap_or_default();
+        loop {
+            if let Some(&file) = self.origins.get(path) {
+                return Some(self.position(file, path));
+            }
+            path = &path[..path.rfind('.')?];
+        }
+    }
+
+    /// Returns `file:line` of a setting, or the file alone when the line is
+    /// not found.
+    fn position(&self, file: usize, path: &str) -> String {
+        let name = self.files[file].display();
+        match key_line(&self.contents[file], path) {
+            Some(line) => format!("{}:{}", name, line),
+            None => name.to_string(),
+        }
+    }
+
+    /// Reads and parses a file.
+    fn read(&mut self, path: &Path) -> Result<Value, ShikiError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ShikiError::config(format!(
+                "Failed to read config file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let value = serde_yaml::from_str(&content).map_err(|e| {
+            let position = match e.location() {
+                Some(location) => format!("{}:{}", path.display(), location.line()),
+                None => path.display().to_string(),
+            };
+            ShikiError::config(format!(
+                "{}: Failed to parse config: {}",
+                position,
+                without_location(&e)
+            ))
+        })?;
+        self.files.push(path.to_path_buf());
+        self.contents.push(content);
+        Ok(value)
+    }
+
+    /// Records `file` as the origin of a value and of everything in it.
+    fn record(&mut self, path: &str, value: &Value, file: usize) {
+        if !path.is_empty() {
+            self.origins.insert(path.to_string(), file);
+        }
+        if let Value::Mapping(mapping) = value {
+            for (key, value) in mapping {
+                self.record(&child_path(path, key), value, file);
+            }
+        }
+    }
+
+    /// Merges the value of a file into the values of the files before it.
+    fn merge(
+        &mut self,
+        base: &mut Value,
+        overlay: Value,
+        path: &str,
+        file: usize,
+    ) -> Result<(), ShikiError> {
+        match overlay {
+            Value::Tagged(tagged) if tagged.tag == REPLACE_TAG => {
+                *base = strip_replace(tagged.value);
+                self.record(path, base, file);
+            }
+            Value::Null => {}
+            overlay if base.is_null() => {
+                *base = strip_replace(overlay);
+                self.record(path, base, file);
+            }
+            Value::Mapping(overlay) => {
+                let Value::Mapping(base) = base else {
+                    return Err(self.conflict(path, file));
+                };
+                for (key, value) in overlay {
+                    let child = child_path(path, &key);
+                    match base.get_mut(&key) {
+                        Some(existing) => self.merge(existing, value, &child, file)?,
+                        None => {
+                            let value = strip_replace(value);
+                            self.record(&child, &value, file);
+                            base.insert(key, value);
+                        }
+                    }
+                }
+            }
+            Value::Sequence(items) => {
+                let Value::Sequence(base) = base else {
+                    return Err(self.conflict(path, file));
+                };
+                base.extend(items.into_iter().map(strip_replace));
+                self.origins.insert(path.to_string(), file);
+            }
+            scalar => {
+                if *base != scalar {
+                    return Err(self.conflict(path, file));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn conflict(&self, path: &str, file: usize) -> ShikiError {
+        let earlier = self
+            .origins
+            .get(path)
+            .map(|&earlier| self.position(earlier, path))
+            .unwrap_or_else(|| "an earlier file".to_string());
+        ShikiError::config(format!(
+            "{}: {} conflicts with the value set in {}; tag the value !replace to override it",
+            self.position(file, path),
+            path,
+            earlier
+        ))
+    }
+}
+
+/// Returns the patterns listed under `include`.
+fn include_patterns(main: &Path, includes: Option<Value>) -> Result<Vec<String>, ShikiError> {
+    let invalid = || {
+        ShikiError::config(format!(
+            "{}: include must be a path or a list of paths",
+            main.display()
+        ))
+    };
+    match includes {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(pattern)) => Ok(vec![pattern]),
+        Some(Value::Sequence(patterns)) => patterns
+            .into_iter()
+            .map(|p| match p {
+                Value::String(pattern) => Ok(pattern),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Returns the files an include pattern names. A glob may match no file; a
+/// plain path must exist.
+fn expand(main: &Path, dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, ShikiError> {
+    let path = dir.join(pattern);
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+    if !name.contains(['*', '?', '[', '{']) {
+        if !path.is_file() {
+            return Err(ShikiError::config(format!(
+                "{}: included file not found: {}",
+                main.display(),
+                path.display()
+            )));
+        }
+        return Ok(vec![path]);
+    }
+    let parent = path.parent().unwrap_or(dir);
+    list_files(parent, |file| glob_match::glob_match(&name, file))
+}
+
+/// Returns the YAML files of the drop-in directory, if it exists.
+fn drop_ins(dir: &Path) -> Result<Vec<PathBuf>, ShikiError> {
+    list_files(dir, |file| {
+        file.ends_with(".yaml") || file.ends_with(".yml")
+    })
+}
+
+/// Lists the files of a directory whose name matches, in lexical order. A
+/// missing directory has no files.
+fn list_files(dir: &Path, matches: impl Fn(&str) -> bool) -> Result<Vec<PathBuf>, ShikiError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(ShikiError::config(format!(
+                "Failed to read config directory '{}': {}",
+                dir.display(),
+                e
+            )))
+        }
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(&matches)
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Removes the `!replace` tags of a value.
+fn strip_replace(value: Value) -> Value {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == REPLACE_TAG => strip_replace(tagged.value),
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(key, value)| (key, strip_replace(value)))
+                .collect(),
+        ),
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(strip_replace).collect()),
+        value => value,
+    }
+}
+
+fn child_path(path: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        key => serde_yaml::to_string(key)
+            .map(|k| k.trim().to_string())
+            .unwrap_or_default(),
+    };
+    if path.is_empty() {
+        key
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Returns the message of a YAML error without its position, which is
+/// reported separately.
+fn without_location(err: &serde_yaml::Error) -> String {
+    let message = err.to_string();
+    match err.location() {
+        Some(location) => message
+            .trim_end_matches(&format!(
+                " at line {} column {}",
+                location.line(),
+                location.column()
+            ))
+            .to_string(),
+        None => message,
+    }
+}
+
+/// Returns the line (1-based) of the key at a dotted path in a block-style
+/// YAML document. Keys inside sequences are not searched.
+fn key_line(content: &str, path: &str) -> Option<usize> {
+    let target: Vec<&str> = path.split('.').collect();
+    let mut stack: Vec<(usize, &str)> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("---") {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        while stack.last().is_some_and(|(depth, _)| *depth >= indent) {
+            stack.pop();
+        }
+        if trimmed == "-" || trimmed.starts_with("- ") {
+            stack.push((indent, "-"));
+            continue;
+        }
+        let Some((key, _)) = trimmed.split_once(':') else {
+            continue;
+        };
+        stack.push((indent, key.trim().trim_matches(['"', '\''])));
+        if stack.iter().map(|(_, key)| *key).eq(target.iter().copied()) {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    const MAIN: &str = r#"
+include:
+  - services/*.yaml
+agent:
+  backend: exec
+  tags: [web]
+acl:
+  denied: ["secret-*"]
+services:
+  web:
+    start: "web start"
+    stop: "web stop"
+    status: "web status"
+"#;
+
+    #[test]
+    fn test_includes_and_drop_ins_merge_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yaml", MAIN);
+        write(
+            dir.path(),
+            "services/db.yaml",
+            "services:\n  db:\n    start: db start\n    stop: db stop\n    status: db status\n",
+        );
+        write(
+            dir.path(),
+            "services/cache.yaml",
+            "services:\n  cache:\n    start: c start\n    stop: c stop\n    status: c status\n",
+        );
+        write(
+            dir.path(),
+            "config.d/20-acl.yaml",
+            "acl:\n  denied: !replace [\"internal-*\"]\n",
+        );
+        write(
+            dir.path(),
+            "config.d/10-tags.yaml",
+            "agent:\n  tags: [eu]\n",
+        );
+        write(dir.path(), "config.d/README", "not yaml");
+        // Drop-ins of another configuration in the same directory
+        write(
+            dir.path(),
+            "conf.d/00-other.yaml",
+            "agent:\n  tags: [other]\n",
+        );
+        write(
+            dir.path(),
+            "other.d/00-other.yaml",
+            "agent:\n  tags: [other]\n",
+        );
+
+        let config = Config::load_from_path(&main).unwrap();
+        let mut services: Vec<&String> = config.services.keys().collect();
+        services.sort();
+        assert_eq!(services, vec!["cache", "db", "web"]);
+        assert_eq!(config.agent.tags, vec!["web", "eu"]);
+        assert_eq!(config.acl.denied, vec!["internal-*"]);
+        assert_eq!(
+            config.includes,
+            vec![
+                dir.path().join("services/cache.yaml"),
+                dir.path().join("services/db.yaml"),
+                dir.path().join("config.d/10-tags.yaml"),
+                dir.path().join("config.d/20-acl.yaml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scalar_conflicts_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yaml", MAIN);
+        write(
+            dir.path(),
+            "config.d/web.yaml",
+            "# Override\nservices:\n  web:\n    start: \"web serve\"\n",
+        );
+        let err = Config::load_from_path(&main).unwrap_err().to_string();
+        assert!(
+            err.contains(&format!(
+                "{}:4: services.web.start conflicts with the value set in {}:11",
+                dir.path().join("config.d/web.yaml").display(),
+                main.display()
+            )),
+            "{}",
+            err
+        );
+
+        // The same value, or an explicit replacement, is accepted
+        write(
+            dir.path(),
+            "config.d/web.yaml",
+            "services:\n  web:\n    start: web start\n    stop: !replace web halt\n",
+        );
+        let config = Config::load_from_path(&main).unwrap();
+        assert_eq!(config.services["web"].stop, "web halt");
+    }
+
+    #[test]
+    fn test_errors_point_to_file_and_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yaml", MAIN);
+        let drop_in = dir.path().join("config.d/db.yaml");
+        let load_error = |content: &str| {
+            write(dir.path(), "config.d/db.yaml", content);
+            Config::load_from_path(&main).unwrap_err().to_string()
+        };
+
+        // Validation error
+        let err = load_error("services:\n  db:\n    start: db start\n    stop: db stop\n");
+        assert!(
+            err.contains(&format!(
+                "{}:2: services.db.status is required",
+                drop_in.display()
+            )),
+            "{}",
+            err
+        );
+
+        // Type error
+        let err = load_error("server:\n  port: http\n");
+        assert!(
+            err.contains(&format!(
+                "{}:2: Failed to parse config: server.port",
+                drop_in.display()
+            )),
+            "{}",
+            err
+        );
+
+        // Syntax error
+        let err = load_error("server:\n  port: [\n");
+        assert!(
+            err.contains(&format!("{}:3: Failed to parse config", drop_in.display())),
+            "{}",
+            err
+        );
+
+        // Missing include
+        write(
+            dir.path(),
+            "config.yaml",
+            &MAIN.replace("services/*.yaml", "missing.yaml"),
+        );
+        std::fs::remove_file(&drop_in).unwrap();
+        let err = Config::load_from_path(&main).unwrap_err().to_string();
+        assert!(err.contains("included file not found"), "{}", err);
+    }
+
+    #[test]
+    fn test_key_line() {
+        let content = "server:\n  port: 8080\n\ncluster:\n  peers:\n    - name: a\n      port: 1\n  # note\n  enabled: true\n";
+        assert_eq!(key_line(content, "server.port"), Some(2));
+        assert_eq!(key_line(content, "cluster.peers"), Some(5));
+        assert_eq!(key_line(content, "cluster.enabled"), Some(9));
+        assert_eq!(key_line(content, "cluster.port"), None);
+    }
+}
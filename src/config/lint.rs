@@ -0,0 +1,425 @@
+//! Configuration lint.
+//!
+//! Settings that parse but are likely mistakes: unknown keys, which serde
+//! would otherwise ignore, and combinations of valid settings that rarely do
+//! what was meant. They are reported as warnings by `shiki config validate`
+//! and logged when the agent loads its configuration; strict loading rejects
+//! unknown keys instead.
+
+use super::include::Sources;
+use super::secret::key_name;
+use super::{schema, Backend, Config, ServiceDefinition, ServiceKind};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::net::IpAddr;
+use std::path::Path;
+use tracing::warn;
+
+/// A suspicious setting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintWarning {
+    /// Dotted path of the setting.
+    pub path: String,
+    /// File and line of the setting, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// What is suspicious about it.
+    pub message: String,
+}
+
+impl LintWarning {
+    /// Creates a warning about the setting at `path`.
+    pub fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            location: None,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the warning with the position of its setting.
+    pub(super) fn located(mut self, sources: &Sources) -> Self {
+        self.location = sources.position_of(&self.path);
+        self
+    }
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Logs the warnings of a loaded configuration.
+pub fn log_warnings(warnings: &[LintWarning]) {
+    for warning in warnings {
+        warn!(
+            path = %warning.path,
+            location = warning.location.as_deref().unwrap_or("-"),
+            "Configuration warning: {}",
+            warning.message
+        );
+    }
+}
+
+/// A key the configuration types do not have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct UnknownSetting {
+    /// Dotted path of the key.
+    pub path: String,
+    /// Known key of the same object the key is likely a typo of.
+    pub suggestion: Option<String>,
+}
+
+impl UnknownSetting {
+    pub fn message(&self) -> String {
+        match &self.suggestion {
+            Some(suggestion) => format!("unknown setting, did you mean '{}'?", suggestion),
+            None => "unknown setting".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for UnknownSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message())
+    }
+}
+
+/// Returns the keys of a configuration document that are not settings,
+/// found by walking it along the JSON Schema derived from the
+/// configuration types, the one `shiki config schema` prints.
+pub(super) fn unknown_settings(document: &Value) -> Vec<UnknownSetting> {
+    let mut unknown = Vec::new();
+    walk(schema::cached(), document, &mut Vec::new(), &mut unknown);
+    unknown
+}
+
+fn walk(
+    schema: &serde_json::Value,
+    value: &Value,
+    path: &mut Vec<String>,
+    unknown: &mut Vec<UnknownSetting>,
+) {
+    // Pick the variant of the shape of the value (`nginx: running` or a
+    // full declaration), and of its tag (`type: http`) if the variants are
+    // tagged; of those left, the one the value fits best
+    let variants = schema["anyOf"].as_array().or(schema["oneOf"].as_array());
+    if let Some(variants) = variants {
+        let shape = match value {
+            Value::Mapping(_) => "object",
+            Value::Sequence(_) => "array",
+            _ => return,
+        };
+        let best = variants
+            .iter()
+            .filter(|variant| variant["type"] == shape && is_tagged_as(variant, value))
+            .map(|variant| {
+                let mut found = Vec::new();
+                walk(variant, value, path, &mut found);
+                found
+            })
+            .min_by_key(Vec::len);
+        unknown.extend(best.unwrap_or_default());
+        return;
+    }
+    match value {
+        Value::Mapping(mapping) => {
+            let properties = schema["properties"].as_object();
+            for (key, value) in mapping {
+                let key = key_name(key);
+                let field = properties.and_then(|properties| properties.get(&key));
+                let child = match (field, &schema["additionalProperties"]) {
+                    (Some(field), _) => field,
+                    (None, serde_json::Value::Object(_)) => &schema["additionalProperties"],
+                    (None, serde_json::Value::Bool(false)) => {
+                        let mut path = path.clone();
+                        path.push(key.clone());
+                        unknown.push(UnknownSetting {
+                            path: path.join("."),
+                            suggestion: properties.and_then(|properties| {
+                                suggest(&key, properties.keys().map(String::as_str))
+                            }),
+                        });
+                        continue;
+                    }
+                    _ => continue,
+                };
+                path.push(key);
+                walk(child, value, path, unknown);
+                path.pop();
+            }
+        }
+        Value::Sequence(items) if schema["items"].is_object() => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(index.to_string());
+                walk(&schema["items"], item, path, unknown);
+                path.pop();
+            }
+        }
+        Value::Tagged(tagged) => walk(schema, &tagged.value, path, unknown),
+        _ => {}
+    }
+}
+
+/// Returns whether `value` has the tag of `variant`: the value of every
+/// property the variant limits to a single value, where `value` sets it.
+fn is_tagged_as(variant: &serde_json::Value, value: &Value) -> bool {
+    let (Some(properties), Value::Mapping(mapping)) = (variant["properties"].as_object(), value)
+    else {
+        return true;
+    };
+    properties.iter().all(|(key, property)| {
+        let tag = match property["enum"].as_array() {
+            Some(values) if values.len() == 1 => &values[0],
+            _ => return true,
+        };
+        match mapping.get(key.as_str()) {
+            Some(Value::String(given)) => tag == given.as_str(),
+            _ => true,
+        }
+    })
+}
+
+/// Returns the candidate `key` is most likely a typo of, if any is close
+/// enough.
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    let limit = (key.chars().count() / 4).max(1);
+    candidates
+        .map(|candidate| (strsim::osa_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// Returns warnings about suspicious combinations of settings.
+pub(super) fn lint(config: &Config) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    if !config.auth.enabled && !is_loopback(&config.server.bind) {
+        warnings.push(LintWarning::new(
+            "auth.enabled",
+            format!(
+                "authentication is disabled while the server listens on {}; anyone who can reach port {} can control services",
+                config.server.bind, config.server.port
+            ),
+        ));
+    }
+
+    if config.acl.denies_all() {
+        warnings.push(LintWarning::new(
+            "acl.denied",
+            "denies every service; all service operations will be rejected",
+        ));
+    }
+
+    if config.agent.backend == Backend::Exec {
+        let mut services: Vec<_> = config.services.iter().collect();
+        services.sort_by_key(|(name, _)| *name);
+        for (name, definition) in services {
+            for (field, command) in commands(definition) {
+                if let Some(problem) = missing_program(command, definition) {
+                    warnings.push(LintWarning::new(
+                        &format!("services.{}.{}", name, field),
+                        problem,
+                    ));
+                }
+            }
+        }
+    }
+
+    let agent_name = config.agent_name();
+    for (index, peer) in config.cluster.peers.iter().enumerate() {
+        if peer.name == agent_name {
+            warnings.push(LintWarning::new(
+                &format!("cluster.peers.{}.name", index),
+                format!("'{}' is the name of this agent", peer.name),
+            ));
+        } else if points_to_self(config, &agent_name, &peer.address) {
+            warnings.push(LintWarning::new(
+                &format!("cluster.peers.{}.address", index),
+                format!("{} points to this agent", peer.address),
+            ));
+        }
+    }
+
+    warnings
+}
+
+/// Returns whether `bind` only accepts local connections.
+fn is_loopback(bind: &str) -> bool {
+    match bind.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => bind == "localhost",
+    }
+}
+
+/// Returns the commands the exec backend runs for a service, by field.
+fn commands(definition: &ServiceDefinition) -> Vec<(&'static str, &str)> {
+    let mut commands = vec![("start", definition.start.as_str())];
+    // Supervised services are stopped and checked by shiki itself
+    if definition.kind == ServiceKind::Command {
+        commands.push(("stop", &definition.stop));
+        commands.push(("status", &definition.status));
+    }
+    commands.extend(definition.reload.as_deref().map(|c| ("reload", c)));
+    commands.extend(definition.restart.as_deref().map(|c| ("restart", c)));
+    commands.retain(|(_, command)| !command.is_empty());
+    commands
+}
+
+/// Returns why the program of `command` cannot be run, if it cannot be
+/// found.
+fn missing_program(command: &str, definition: &ServiceDefinition) -> Option<String> {
+    let parts = shell_words::split(command).ok()?;
+    let program = parts.first()?;
+    if program.contains('/') {
+        let path = match &definition.working_dir {
+            Some(dir) => Path::new(dir).join(program),
+            None => Path::new(program).to_path_buf(),
+        };
+        return (!path.is_file()).then(|| format!("program '{}' does not exist", program));
+    }
+    let paths = std::env::var_os("PATH").unwrap_or_default();
+    let found = std::env::split_paths(&paths).any(|dir| dir.join(program).is_file());
+    (!found).then(|| format!("program '{}' is not found on PATH", program))
+}
+
+/// Returns whether a peer address (host:port) is the address of this
+/// agent.
+fn points_to_self(config: &Config, agent_name: &str, address: &str) -> bool {
+    let Some((host, port)) = address.rsplit_once(':') else {
+        return false;
+    };
+    if port.parse::<u16>().ok() != Some(config.server.port) {
+        return false;
+    }
+    let host = host.trim_matches(['[', ']']);
+    let local = match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback() || ip.is_unspecified(),
+        Err(_) => host == "localhost",
+    };
+    let own = [
+        Some(config.server.bind.as_str()),
+        Some(agent_name),
+        config.cluster.discovery.advertise_address.as_deref(),
+        config.cluster.gossip.advertise_address.as_deref(),
+    ];
+    local || own.contains(&Some(host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PeerConfig;
+
+    fn document(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_unknown_settings() {
+        let unknown = unknown_settings(&document(
+            r#"
+servces: {}
+server:
+  prot: 8080
+  tls: {enabled: false, cert: x}
+include: conf/*.yaml
+services:
+  web:
+    start: web
+    tiemout: 30
+readiness:
+  web: {check: {type: tcp, address: "127.0.0.1:80"}, intervall_seconds: 5}
+  api: {check: {type: http, urll: "http://127.0.0.1/"}}
+cluster:
+  peers:
+    - {name: a, address: "a:8080", tgs: [web]}
+desired_state:
+  services:
+    web: running
+    db: {state: running, schedul: "08:00-20:00"}
+agent: {metadata: {anything: goes}, xyzzy: 1}
+"#,
+        ));
+        let found: Vec<(&str, Option<&str>)> = unknown
+            .iter()
+            .map(|u| (u.path.as_str(), u.suggestion.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("servces", Some("services")),
+                ("server.prot", Some("port")),
+                ("server.tls.cert", None),
+                ("services.web.tiemout", Some("timeout")),
+                ("readiness.web.intervall_seconds", Some("interval_seconds")),
+                ("readiness.api.check.urll", Some("url")),
+                ("cluster.peers.0.tgs", Some("tags")),
+                ("desired_state.services.db.schedul", Some("schedule")),
+                ("agent.xyzzy", None),
+            ]
+        );
+        assert_eq!(
+            unknown[0].to_string(),
+            "servces: unknown setting, did you mean 'services'?"
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut config = Config::default();
+        config.agent.name = Some("web-01".to_string());
+        config.agent.backend = Backend::Exec;
+        config.services.insert(
+            "web".to_string(),
+            ServiceDefinition {
+                start: "shiki-test-no-such-program --serve".to_string(),
+                stop: "/nonexistent/stop".to_string(),
+                status: "sh -c true".to_string(),
+                ..Default::default()
+            },
+        );
+        config.acl.denied = vec!["*".to_string()];
+        for (name, address) in [
+            ("web-01", "10.0.0.1:8080"),
+            ("self", "127.0.0.1:8080"),
+            ("other", "10.0.0.2:8080"),
+        ] {
+            config.cluster.peers.push(PeerConfig {
+                name: name.to_string(),
+                address: address.to_string(),
+                tags: Vec::new(),
+                token: None,
+            });
+        }
+
+        let warnings = lint(&config);
+        let paths: Vec<&str> = warnings.iter().map(|w| w.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "auth.enabled",
+                "acl.denied",
+                "services.web.start",
+                "services.web.stop",
+                "cluster.peers.0.name",
+                "cluster.peers.1.address",
+            ]
+        );
+        assert!(warnings[2].message.contains("not found on PATH"));
+        assert!(warnings[3].message.contains("does not exist"));
+
+        // A local server with authentication has nothing to report
+        let mut config = Config::default();
+        config.server.bind = "127.0.0.1".to_string();
+        assert!(lint(&config).is_empty());
+        config.server.bind = "0.0.0.0".to_string();
+        config.auth.enabled = true;
+        assert!(lint(&config).is_empty());
+    }
+}
@@ -0,0 +1,47 @@
+//! Lock configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Lock configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LocksConfig {
+    /// Peer that holds the locks of the cluster. When set, this agent
+    /// forwards lock requests to it.
+    pub coordinator: Option<String>,
+
+    /// Time to live of a lock in seconds when the request gives none.
+    pub default_ttl_seconds: u64,
+
+    /// Maximum time to live of a lock in seconds.
+    pub max_ttl_seconds: u64,
+
+    /// Maximum time an acquisition may wait for a held lock in seconds.
+    pub max_wait_seconds: u64,
+}
+
+impl Default for LocksConfig {
+    fn default() -> Self {
+        Self {
+            coordinator: None,
+            default_ttl_seconds: 30,
+            max_ttl_seconds: 3600,
+            max_wait_seconds: 3600,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locks_config_default() {
+        let config = LocksConfig::default();
+        assert!(config.coordinator.is_none());
+        assert_eq!(config.default_ttl_seconds, 30);
+        assert_eq!(config.max_ttl_seconds, 3600);
+        assert_eq!(config.max_wait_seconds, 3600);
+    }
+}
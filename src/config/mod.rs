@@ -5,26 +5,60 @@
 
 mod acl;
 mod agent;
+mod audit;
 mod cluster;
+mod desired_state;
+mod history;
+mod include;
+mod lint;
+mod locks;
 mod logging;
+mod readiness;
 mod retry;
+mod schema;
+mod secret;
 mod server;
+mod signals;
+mod starter;
+mod watch;
+mod webhooks;
 
 pub use acl::AclConfig;
-pub use agent::{AgentConfig, AgentMode, Backend, ServiceDefinition};
-pub use cluster::{ClusterConfig, PeerConfig};
+pub use agent::{
+    AgentConfig, AgentMode, Backend, RestartPolicy, ServiceDefinition, ServiceKind, StopSignal,
+    SupervisorConfig,
+};
+pub use audit::AuditConfig;
+pub use cluster::{
+    ClusterConfig, DiscoveryConfig, ElectionConfig, FanoutConfig, ForwardingConfig, GossipConfig,
+    PeerConfig,
+};
+pub use desired_state::{
+    DesiredConditions, DesiredService, DesiredState, DesiredStateConfig, Schedule,
+};
+pub use history::HistoryConfig;
+pub use lint::{log_warnings, LintWarning};
+pub use locks::LocksConfig;
 pub use logging::{LogFormat, LogLevel, LogOutput, LoggingConfig};
+pub use readiness::{ProbeCheck, ReadinessProbe};
 pub use retry::{RetryConfig, TimeoutConfig};
+pub use schema::json_schema;
+pub(crate) use secret::key_name;
 pub use server::{AuthConfig, AuthMethod, ServerConfig, TlsConfig};
+pub use signals::SignalsConfig;
+pub use starter::{detect_backend, starter_config};
+pub use watch::{WatchConfig, WatchPolicy, WatchRemote};
+pub use webhooks::{WebhookEndpoint, WebhookEvent, WebhooksConfig};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::error::ShikiError;
 
 /// Application configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct Config {
     /// Server configuration.
@@ -51,17 +85,81 @@ pub struct Config {
     /// Cluster configuration.
     pub cluster: ClusterConfig,
 
+    /// Signals and barriers.
+    pub signals: SignalsConfig,
+
+    /// Named locks.
+    pub locks: LocksConfig,
+
+    /// Operation history.
+    pub history: HistoryConfig,
+
+    /// Audit log.
+    pub audit: AuditConfig,
+
+    /// Outbound webhooks.
+    pub webhooks: WebhooksConfig,
+
     /// Service definitions (for exec backend).
     #[serde(default)]
     pub services: HashMap<String, ServiceDefinition>,
+
+    /// Readiness probes keyed by service name (any backend).
+    #[serde(default)]
+    pub readiness: HashMap<String, ReadinessProbe>,
+
+    /// Service watchers keyed by service name (any backend).
+    #[serde(default)]
+    pub watch: HashMap<String, WatchConfig>,
+
+    /// Declared state of the local services.
+    pub desired_state: DesiredStateConfig,
+
+    /// File the configuration was loaded from, re-read on reload.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+
+    /// Included and drop-in files merged into the configuration, in order.
+    #[serde(skip)]
+    pub includes: Vec<PathBuf>,
+
+    /// Values resolved from secret references, redacted when displayed.
+    #[serde(skip)]
+    pub secrets: Vec<String>,
+
+    /// Suspicious settings found when the configuration was loaded.
+    #[serde(skip)]
+    pub warnings: Vec<LintWarning>,
+}
+
+/// Maximum length of a service name.
+pub const MAX_SERVICE_NAME_LEN: usize = 256;
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_yaml::to_string(&self.redacted()) {
+            Ok(yaml) => f.write_str(&yaml),
+            Err(_) => f.write_str("Config { .. }"),
+        }
+    }
 }
 
 impl Config {
     /// Loads configuration from an optional path.
     /// If path is None, uses default search paths.
     pub fn load<P: AsRef<Path>>(path: Option<P>) -> Result<Self, ShikiError> {
+        Self::load_with(path, false)
+    }
+
+    /// Loads configuration like [`Config::load`], rejecting unknown
+    /// settings instead of ignoring them.
+    pub fn load_strict<P: AsRef<Path>>(path: Option<P>) -> Result<Self, ShikiError> {
+        Self::load_with(path, true)
+    }
+
+    fn load_with<P: AsRef<Path>>(path: Option<P>, strict: bool) -> Result<Self, ShikiError> {
         match path {
-            Some(p) => Self::load_from_path(p),
+            Some(p) => Self::read(p.as_ref(), strict),
             None => {
                 // Try default paths
                 let default_paths = [
@@ -73,27 +171,62 @@ impl Config {
 
                 for path in &default_paths {
                     if std::path::Path::new(path).exists() {
-                        return Self::load_from_path(path);
+                        return Self::read(Path::new(path), strict);
                     }
                 }
 
                 // No config file found, use defaults
-                Ok(Self::default())
+                let mut config = Self::default();
+                config.warnings = lint::lint(&config);
+                Ok(config)
             }
         }
     }
 
-    /// Loads configuration from a YAML file.
+    /// Loads configuration from a YAML file, merged with the files it
+    /// includes and the drop-in files of its `<name>.d` directory.
+    /// Errors name the file and line of the setting at fault. Unknown
+    /// settings are ignored, and listed in the warnings.
     pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self, ShikiError> {
-        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| {
-            ShikiError::config(format!(
-                "Failed to read config file '{}': {}",
-                path.as_ref().display(),
-                e
-            ))
-        })?;
+        Self::read(path.as_ref(), false)
+    }
+
+    fn read(path: &Path, strict: bool) -> Result<Self, ShikiError> {
+        let (mut document, sources) = include::load(path)?;
+        let unknown = lint::unknown_settings(&document);
+        if strict && !unknown.is_empty() {
+            let settings: Vec<String> = unknown
+                .iter()
+                .map(|setting| sources.located(&setting.to_string()))
+                .collect();
+            return Err(ShikiError::config(settings.join("\n  ")));
+        }
+        let base = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let secrets = secret::resolve(&mut document, base).map_err(|e| sources.locate(e))?;
+        let mut config: Config = sources.deserialize(&document)?;
+        config.validate().map_err(|e| sources.locate(e))?;
+
+        config.source = Some(path.to_path_buf());
+        config.includes = sources.files[1..].to_vec();
+        config.secrets = secrets;
+        config.warnings = unknown
+            .iter()
+            .map(|setting| LintWarning::new(&setting.path, setting.message()))
+            .chain(lint::lint(&config))
+            .map(|warning| warning.located(&sources))
+            .collect();
+        Ok(config)
+    }
 
-        Self::load_from_str(&content)
+    /// Returns the configuration with secret settings and the values of
+    /// secret references redacted, for display.
+    pub fn redacted(&self) -> serde_yaml::Value {
+        let mut document = serde_yaml::to_value(self).unwrap_or_default();
+        secret::redact(&mut document, &self.secrets);
+        document
     }
 
     /// Loads configuration from a YAML string.
@@ -143,6 +276,28 @@ impl Config {
             }
         }
 
+        // Validate service names
+        let names = self
+            .services
+            .keys()
+            .map(|name| ("services", name))
+            .chain(self.readiness.keys().map(|name| ("readiness", name)))
+            .chain(self.watch.keys().map(|name| ("watch", name)))
+            .chain(
+                self.desired_state
+                    .services
+                    .keys()
+                    .map(|name| ("desired_state.services", name)),
+            );
+        for (section, name) in names {
+            if let Err(reason) = validate_service_name(name) {
+                return Err(ShikiError::config(format!(
+                    "{}.{}: {}",
+                    section, name, reason
+                )));
+            }
+        }
+
         // Validate exec backend requires service definitions
         if self.agent.backend == Backend::Exec && self.services.is_empty() {
             return Err(ShikiError::config(
@@ -158,6 +313,22 @@ impl Config {
                     name
                 )));
             }
+            // Supervised services are stopped and checked by shiki itself
+            if def.kind == ServiceKind::Supervised {
+                if def.supervisor.restart_multiplier < 1.0 {
+                    return Err(ShikiError::config(format!(
+                        "services.{}.supervisor.restart_multiplier must be >= 1.0",
+                        name
+                    )));
+                }
+                if def.supervisor.stop_timeout == 0 {
+                    return Err(ShikiError::config(format!(
+                        "services.{}.supervisor.stop_timeout must be greater than 0",
+                        name
+                    )));
+                }
+                continue;
+            }
             if def.stop.is_empty() {
                 return Err(ShikiError::config(format!(
                     "services.{}.stop is required",
@@ -172,6 +343,101 @@ impl Config {
             }
         }
 
+        // Validate dependencies between services (unknown references, cycles)
+        crate::service::deps::DependencyGraph::from_services(&self.services)?;
+
+        // Validate readiness probes
+        for (name, probe) in &self.readiness {
+            if probe.interval_seconds == 0 || probe.timeout_seconds == 0 {
+                return Err(ShikiError::config(format!(
+                    "readiness.{}: interval_seconds and timeout_seconds must be > 0",
+                    name
+                )));
+            }
+            if probe.success_threshold == 0 || probe.failure_threshold == 0 {
+                return Err(ShikiError::config(format!(
+                    "readiness.{}: success_threshold and failure_threshold must be > 0",
+                    name
+                )));
+            }
+            let target_missing = match &probe.check {
+                ProbeCheck::Tcp { address } => address.is_empty(),
+                ProbeCheck::Http { url, .. } => url.is_empty(),
+                ProbeCheck::Command { command } => command.is_empty(),
+                ProbeCheck::File { path } => path.is_empty(),
+            };
+            if target_missing {
+                return Err(ShikiError::config(format!(
+                    "readiness.{}.check: {} probe target is required",
+                    name,
+                    probe.check.kind()
+                )));
+            }
+        }
+
+        // Validate watchers
+        for (name, watch) in &self.watch {
+            if watch.interval_seconds == 0 || watch.window_seconds == 0 {
+                return Err(ShikiError::config(format!(
+                    "watch.{}: interval_seconds and window_seconds must be > 0",
+                    name
+                )));
+            }
+            if watch.max_restarts == 0 {
+                return Err(ShikiError::config(format!(
+                    "watch.{}: max_restarts must be > 0",
+                    name
+                )));
+            }
+            if watch.restart_multiplier < 1.0 {
+                return Err(ShikiError::config(format!(
+                    "watch.{}: restart_multiplier must be >= 1.0",
+                    name
+                )));
+            }
+            if watch.policy == WatchPolicy::Notify && self.webhooks.endpoints.is_empty() {
+                return Err(ShikiError::config(format!(
+                    "watch.{}: policy notify requires webhooks.endpoints",
+                    name
+                )));
+            }
+            match &watch.remote {
+                None if watch.policy == WatchPolicy::Remote => {
+                    return Err(ShikiError::config(format!(
+                        "watch.{}.remote is required when policy is remote",
+                        name
+                    )));
+                }
+                Some(remote) if remote.agent.is_empty() || remote.service.is_empty() => {
+                    return Err(ShikiError::config(format!(
+                        "watch.{}.remote: agent and service are required",
+                        name
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        // Validate desired state
+        if self.desired_state.interval_seconds == 0 {
+            return Err(ShikiError::config(
+                "desired_state.interval_seconds must be > 0",
+            ));
+        }
+        for (name, service) in &self.desired_state.services {
+            if service.conditions.leader && !self.cluster.election.enabled {
+                return Err(ShikiError::config(format!(
+                    "desired_state.services.{}: the leader condition requires cluster.election.enabled",
+                    name
+                )));
+            }
+            if let Some(signal) = &service.conditions.signal {
+                crate::cluster::signals::validate_name(signal).map_err(|e| {
+                    ShikiError::config(format!("desired_state.services.{}: {}", name, e))
+                })?;
+            }
+        }
+
         // Validate logging
         if self.logging.output == LogOutput::File && self.logging.file_path.is_none() {
             return Err(ShikiError::config(
@@ -185,15 +451,210 @@ impl Config {
         }
 
         // Validate cluster
-        if self.cluster.enabled && self.cluster.peers.is_empty() {
+        if self.cluster_enabled() {
+            if self.cluster.peers.is_empty()
+                && !self.cluster.discovery.enabled
+                && !self.cluster.gossip.enabled
+            {
+                return Err(ShikiError::config(
+                    "cluster.peers is required when cluster is enabled and discovery and gossip are disabled",
+                ));
+            }
+            if self.cluster.fanout.parallelism == 0 {
+                return Err(ShikiError::config("cluster.fanout.parallelism must be > 0"));
+            }
+            if self.cluster.health_interval_seconds == 0 {
+                return Err(ShikiError::config(
+                    "cluster.health_interval_seconds must be > 0",
+                ));
+            }
+        }
+        if self.cluster.discovery.enabled {
+            let discovery = &self.cluster.discovery;
+            if !self.cluster_enabled() {
+                return Err(ShikiError::config(
+                    "cluster.discovery requires cluster mode",
+                ));
+            }
+            if discovery.group.parse::<std::net::Ipv4Addr>().is_err() {
+                return Err(ShikiError::config(format!(
+                    "cluster.discovery.group: invalid IPv4 address '{}'",
+                    discovery.group
+                )));
+            }
+            if discovery.interface.parse::<std::net::Ipv4Addr>().is_err() {
+                return Err(ShikiError::config(format!(
+                    "cluster.discovery.interface: invalid IPv4 address '{}'",
+                    discovery.interface
+                )));
+            }
+            if discovery.port == 0 || discovery.announce_interval_seconds == 0 {
+                return Err(ShikiError::config(
+                    "cluster.discovery.port and announce_interval_seconds must be > 0",
+                ));
+            }
+            if discovery.expire_seconds <= discovery.announce_interval_seconds {
+                return Err(ShikiError::config(
+                    "cluster.discovery.expire_seconds must be greater than announce_interval_seconds",
+                ));
+            }
+        }
+        if self.cluster.gossip.enabled {
+            let gossip = &self.cluster.gossip;
+            if !self.cluster_enabled() {
+                return Err(ShikiError::config("cluster.gossip requires cluster mode"));
+            }
+            if gossip.bind.parse::<std::net::IpAddr>().is_err() {
+                return Err(ShikiError::config(format!(
+                    "cluster.gossip.bind: invalid IP address '{}'",
+                    gossip.bind
+                )));
+            }
+            if gossip.port == 0 || gossip.probe_interval_ms == 0 || gossip.probe_timeout_ms == 0 {
+                return Err(ShikiError::config(
+                    "cluster.gossip.port, probe_interval_ms and probe_timeout_ms must be > 0",
+                ));
+            }
+            if gossip.probe_timeout_ms >= gossip.probe_interval_ms {
+                return Err(ShikiError::config(
+                    "cluster.gossip.probe_timeout_ms must be less than probe_interval_ms",
+                ));
+            }
+            if gossip.suspicion_timeout_ms < gossip.probe_interval_ms {
+                return Err(ShikiError::config(
+                    "cluster.gossip.suspicion_timeout_ms must be at least probe_interval_ms",
+                ));
+            }
+            if let Some(advertise) = &gossip.advertise_address {
+                if advertise.parse::<std::net::IpAddr>().is_err() {
+                    return Err(ShikiError::config(format!(
+                        "cluster.gossip.advertise_address: invalid IP address '{}'",
+                        advertise
+                    )));
+                }
+            }
+            if let Some(seed) = gossip.seeds.iter().find(|s| s.rsplit_once(':').is_none()) {
+                return Err(ShikiError::config(format!(
+                    "cluster.gossip.seeds: '{}' must be host:port",
+                    seed
+                )));
+            }
+        }
+        if self.cluster.election.enabled {
+            let election = &self.cluster.election;
+            if !self.cluster_enabled() {
+                return Err(ShikiError::config("cluster.election requires cluster mode"));
+            }
+            if election.lease_ms == 0 || election.renew_interval_ms == 0 {
+                return Err(ShikiError::config(
+                    "cluster.election.lease_ms and renew_interval_ms must be > 0",
+                ));
+            }
+            if election.renew_interval_ms * 2 > election.lease_ms {
+                return Err(ShikiError::config(
+                    "cluster.election.renew_interval_ms must be at most half of lease_ms",
+                ));
+            }
+        }
+        if self.cluster.forwarding.enabled && self.cluster.forwarding.max_hops == 0 {
+            return Err(ShikiError::config(
+                "cluster.forwarding.max_hops must be > 0",
+            ));
+        }
+        let mut peer_names = std::collections::HashSet::new();
+        for peer in &self.cluster.peers {
+            if peer.name.is_empty() || peer.address.is_empty() {
+                return Err(ShikiError::config(
+                    "cluster.peers: name and address are required",
+                ));
+            }
+            if !peer_names.insert(peer.name.as_str()) {
+                return Err(ShikiError::config(format!(
+                    "cluster.peers: duplicate peer name '{}'",
+                    peer.name
+                )));
+            }
+        }
+
+        // Validate signals
+        let signals = &self.signals;
+        if signals.default_ttl_seconds == 0 || signals.max_wait_seconds == 0 {
+            return Err(ShikiError::config(
+                "signals.default_ttl_seconds and max_wait_seconds must be > 0",
+            ));
+        }
+        if signals.default_ttl_seconds > signals.max_ttl_seconds {
+            return Err(ShikiError::config(
+                "signals.default_ttl_seconds must not exceed max_ttl_seconds",
+            ));
+        }
+        if signals.coordinator.as_deref() == Some("") {
+            return Err(ShikiError::config("signals.coordinator must not be empty"));
+        }
+
+        // Validate locks
+        let locks = &self.locks;
+        if locks.default_ttl_seconds == 0 || locks.max_wait_seconds == 0 {
+            return Err(ShikiError::config(
+                "locks.default_ttl_seconds and max_wait_seconds must be > 0",
+            ));
+        }
+        if locks.default_ttl_seconds > locks.max_ttl_seconds {
             return Err(ShikiError::config(
-                "cluster.peers is required when cluster is enabled",
+                "locks.default_ttl_seconds must not exceed max_ttl_seconds",
             ));
         }
+        if locks.coordinator.as_deref() == Some("") {
+            return Err(ShikiError::config("locks.coordinator must not be empty"));
+        }
+
+        // Validate history
+        let history = &self.history;
+        if history.enabled && history.path.as_os_str().is_empty() {
+            return Err(ShikiError::config("history.path must not be empty"));
+        }
+        if history.max_entries == 0 || history.max_age_days == 0 {
+            return Err(ShikiError::config(
+                "history.max_entries and max_age_days must be > 0",
+            ));
+        }
+
+        // Validate audit
+        if self.audit.enabled && self.audit.path.as_os_str().is_empty() {
+            return Err(ShikiError::config("audit.path must not be empty"));
+        }
+
+        // Validate webhooks
+        let webhooks = &self.webhooks;
+        if webhooks.queue_size == 0 || webhooks.timeout_seconds == 0 {
+            return Err(ShikiError::config(
+                "webhooks.queue_size and timeout_seconds must be > 0",
+            ));
+        }
+        for endpoint in &webhooks.endpoints {
+            if !endpoint.url.starts_with("http://") && !endpoint.url.starts_with("https://") {
+                return Err(ShikiError::config(format!(
+                    "webhooks.endpoints: url must be an http(s) URL: '{}'",
+                    endpoint.url
+                )));
+            }
+            if endpoint.secret.as_deref() == Some("") {
+                return Err(ShikiError::config(format!(
+                    "webhooks.endpoints: secret of {} must not be empty",
+                    endpoint.url
+                )));
+            }
+        }
 
         Ok(())
     }
 
+    /// Returns whether cluster mode is enabled (`agent.mode: cluster` or
+    /// `cluster.enabled: true`).
+    pub fn cluster_enabled(&self) -> bool {
+        self.agent.mode == AgentMode::Cluster || self.cluster.enabled
+    }
+
     /// Returns the agent name (configured name or hostname).
     pub fn agent_name(&self) -> String {
         self.agent.name.clone().unwrap_or_else(|| {
@@ -205,6 +666,31 @@ impl Config {
     }
 }
 
+/// Checks that a service name is at most [`MAX_SERVICE_NAME_LEN`]
+/// characters of letters, digits, `-`, `_`, `.`, `@` and `:` (the
+/// characters of systemd unit names), and returns why it is not.
+pub fn validate_service_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("service name must not be empty".to_string());
+    }
+    if name.chars().count() > MAX_SERVICE_NAME_LEN {
+        return Err(format!(
+            "service name is longer than {} characters",
+            MAX_SERVICE_NAME_LEN
+        ));
+    }
+    match name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@' | ':')))
+    {
+        Some(c) => Err(format!(
+            "invalid character {:?} in service name; use letters, digits, '-', '_', '.', '@' and ':'",
+            c
+        )),
+        None => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,6 +849,510 @@ services:
         assert!(result.unwrap_err().to_string().contains("stop"));
     }
 
+    #[test]
+    fn test_load_supervised_service() {
+        let yaml = r#"
+agent:
+  backend: exec
+
+services:
+  app:
+    type: supervised
+    start: "/app/server --port 8000"
+    supervisor:
+      stop_signal: SIGINT
+      stop_timeout: 5
+      restart_policy: on-failure
+"#;
+
+        let config = Config::load_from_str(yaml).unwrap();
+        let app = config.services.get("app").unwrap();
+
+        assert_eq!(app.kind, ServiceKind::Supervised);
+        assert!(app.stop.is_empty());
+        assert_eq!(app.supervisor.stop_signal, StopSignal::Int);
+        assert_eq!(app.supervisor.stop_timeout, 5);
+        assert_eq!(app.supervisor.restart_policy, RestartPolicy::OnFailure);
+
+        let err = Config::load_from_str(&yaml.replace("stop_timeout: 5", "stop_timeout: 0"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("services.app.supervisor.stop_timeout"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_load_readiness_probes() {
+        let yaml = r#"
+readiness:
+  postgresql:
+    check:
+      type: tcp
+      address: "127.0.0.1:5432"
+    interval_seconds: 2
+    failure_threshold: 5
+  web:
+    check:
+      type: http
+      url: "http://127.0.0.1:8000/healthz"
+      expected_status: 204
+"#;
+
+        let config = Config::load_from_str(yaml).unwrap();
+        assert_eq!(config.readiness.len(), 2);
+
+        let pg = config.readiness.get("postgresql").unwrap();
+        assert_eq!(pg.interval_seconds, 2);
+        assert_eq!(pg.failure_threshold, 5);
+    }
+
+    #[test]
+    fn test_validation_readiness_empty_target() {
+        let yaml = r#"
+readiness:
+  app:
+    check:
+      type: command
+      command: ""
+"#;
+
+        let result = Config::load_from_str(yaml);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("readiness.app"));
+    }
+
+    #[test]
+    fn test_validation_dependency_cycle() {
+        let yaml = r#"
+agent:
+  backend: exec
+
+services:
+  app:
+    start: "true"
+    stop: "true"
+    status: "true"
+    requires: [redis]
+  redis:
+    start: "true"
+    stop: "true"
+    status: "true"
+    after: [app]
+"#;
+
+        let result = Config::load_from_str(yaml);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn test_cluster_mode() {
+        let yaml = r#"
+agent:
+  mode: cluster
+
+cluster:
+  peers:
+    - name: agent-02
+      address: "192.168.1.102:8080"
+"#;
+
+        let config = Config::load_from_str(yaml).unwrap();
+        assert!(config.cluster_enabled());
+        assert_eq!(config.cluster.peers.len(), 1);
+
+        let yaml = r#"
+agent:
+  mode: cluster
+"#;
+        let result = Config::load_from_str(yaml);
+        assert!(result.unwrap_err().to_string().contains("cluster.peers"));
+
+        let yaml = r#"
+cluster:
+  enabled: true
+  peers:
+    - { name: a, address: "10.0.0.1:8080" }
+    - { name: a, address: "10.0.0.2:8080" }
+"#;
+        let result = Config::load_from_str(yaml);
+        assert!(result.unwrap_err().to_string().contains("duplicate peer"));
+    }
+
+    #[test]
+    fn test_discovery_validation() {
+        // Discovery replaces static peers
+        let yaml = r#"
+agent:
+  mode: cluster
+cluster:
+  discovery:
+    enabled: true
+    key: "shared"
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert!(config.cluster.discovery.enabled);
+        assert_eq!(config.cluster.discovery.key.as_deref(), Some("shared"));
+
+        let invalid = [
+            "cluster:\n  discovery:\n    enabled: true\n",
+            "agent:\n  mode: cluster\ncluster:\n  discovery:\n    enabled: true\n    group: nope\n",
+            "agent:\n  mode: cluster\ncluster:\n  discovery:\n    enabled: true\n    expire_seconds: 5\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("cluster.discovery"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_gossip_validation() {
+        let yaml = r#"
+agent:
+  mode: cluster
+cluster:
+  gossip:
+    enabled: true
+    seeds: ["10.0.0.1:7947"]
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert_eq!(config.cluster.gossip.seeds, vec!["10.0.0.1:7947"]);
+
+        let invalid = [
+            "cluster:\n  gossip:\n    enabled: true\n",
+            "agent:\n  mode: cluster\ncluster:\n  gossip:\n    enabled: true\n    seeds: [node1]\n",
+            "agent:\n  mode: cluster\ncluster:\n  gossip:\n    enabled: true\n    probe_timeout_ms: 2000\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("cluster.gossip"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_election_validation() {
+        let yaml = r#"
+agent:
+  mode: cluster
+cluster:
+  peers:
+    - name: node2
+      address: "10.0.0.2:8080"
+  election:
+    enabled: true
+    lease_ms: 6000
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert_eq!(config.cluster.election.lease_ms, 6000);
+        assert_eq!(config.cluster.election.renew_interval_ms, 3000);
+
+        let invalid = [
+            "cluster:\n  election:\n    enabled: true\n",
+            "agent:\n  mode: cluster\ncluster:\n  peers: [{name: a, address: \"a:1\"}]\n  election:\n    enabled: true\n    lease_ms: 5000\n",
+            "agent:\n  mode: cluster\ncluster:\n  peers: [{name: a, address: \"a:1\"}]\n  election:\n    enabled: true\n    renew_interval_ms: 0\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("cluster.election"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_signals_validation() {
+        let yaml = r#"
+signals:
+  coordinator: "db-1"
+  default_ttl_seconds: 600
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert_eq!(config.signals.coordinator.as_deref(), Some("db-1"));
+        assert_eq!(config.signals.default_ttl_seconds, 600);
+
+        let invalid = [
+            "signals:\n  default_ttl_seconds: 0\n",
+            "signals:\n  default_ttl_seconds: 100\n  max_ttl_seconds: 10\n",
+            "signals:\n  coordinator: \"\"\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("signals."), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_locks_validation() {
+        let yaml = r#"
+locks:
+  coordinator: "db-1"
+  default_ttl_seconds: 60
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert_eq!(config.locks.coordinator.as_deref(), Some("db-1"));
+        assert_eq!(config.locks.default_ttl_seconds, 60);
+
+        let invalid = [
+            "locks:\n  max_wait_seconds: 0\n",
+            "locks:\n  default_ttl_seconds: 100\n  max_ttl_seconds: 10\n",
+            "locks:\n  coordinator: \"\"\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("locks."), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_history_validation() {
+        let yaml = r#"
+history:
+  enabled: true
+  path: "/tmp/shiki-history.db"
+  max_entries: 500
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert!(config.history.enabled);
+        assert_eq!(config.history.max_entries, 500);
+        assert_eq!(config.history.max_age_days, 90);
+
+        let invalid = [
+            "history:\n  enabled: true\n  path: \"\"\n",
+            "history:\n  max_entries: 0\n",
+            "history:\n  max_age_days: 0\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("history."), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_audit_validation() {
+        let yaml = r#"
+audit:
+  enabled: true
+  path: "/tmp/shiki-audit.log"
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert!(config.audit.enabled);
+
+        let err = Config::load_from_str("audit:\n  enabled: true\n  path: \"\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("audit.path"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_watchers() {
+        let yaml = r#"
+watch:
+  nginx:
+    interval_seconds: 5
+    max_restarts: 3
+  postgres:
+    policy: remote
+    remote:
+      agent: "db-standby"
+      service: "postgres-promote"
+      action: start
+  redis:
+    policy: notify
+webhooks:
+  endpoints:
+    - url: "http://127.0.0.1:9000/hooks"
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        assert_eq!(config.watch.len(), 3);
+        let nginx = &config.watch["nginx"];
+        assert_eq!(nginx.policy, WatchPolicy::Restart);
+        assert_eq!((nginx.interval_seconds, nginx.max_restarts), (5, 3));
+        assert_eq!(nginx.window_seconds, 600);
+        let remote = config.watch["postgres"].remote.as_ref().unwrap();
+        assert_eq!(remote.agent, "db-standby");
+        assert_eq!(remote.action, crate::service::ServiceAction::Start);
+        assert_eq!(config.watch["redis"].policy, WatchPolicy::Notify);
+
+        let invalid = [
+            "watch:\n  nginx:\n    interval_seconds: 0\n",
+            "watch:\n  nginx:\n    max_restarts: 0\n",
+            "watch:\n  nginx:\n    restart_multiplier: 0.5\n",
+            "watch:\n  nginx:\n    policy: remote\n",
+            "watch:\n  nginx:\n    policy: notify\n",
+            "watch:\n  nginx:\n    policy: remote\n    remote:\n      agent: \"\"\n      service: \"nginx\"\n      action: start\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("watch.nginx"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_load_desired_state() {
+        let yaml = r#"
+desired_state:
+  interval_seconds: 10
+  services:
+    nginx: running
+    legacy-app: stopped
+    batch:
+      state: running
+      schedule: "mon-fri 08:00-20:00"
+    cron-runner:
+      state: running
+      conditions:
+        leader: true
+        signal: db-ready
+cluster:
+  enabled: true
+  peers:
+    - name: "node2"
+      address: "10.0.0.2:8080"
+  election:
+    enabled: true
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        let desired = &config.desired_state;
+        assert_eq!(desired.interval_seconds, 10);
+        assert_eq!(desired.services.len(), 4);
+        assert_eq!(desired.services["nginx"].state, DesiredState::Running);
+        assert!(desired.services["nginx"].schedule.is_none());
+        assert_eq!(desired.services["legacy-app"].state, DesiredState::Stopped);
+        assert_eq!(
+            desired.services["batch"].schedule.as_ref().unwrap().from,
+            chrono::NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+        );
+        let conditions = &desired.services["cron-runner"].conditions;
+        assert!(conditions.leader);
+        assert_eq!(conditions.signal.as_deref(), Some("db-ready"));
+
+        let invalid = [
+            "desired_state:\n  interval_seconds: 0\n",
+            "desired_state:\n  services:\n    nginx:\n      state: running\n      conditions:\n        leader: true\n",
+            "desired_state:\n  services:\n    nginx:\n      state: running\n      conditions:\n        signal: \"db ready\"\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("desired_state."), "{}", err);
+        }
+        for yaml in [
+            "desired_state:\n  services:\n    nginx: paused\n",
+            "desired_state:\n  services:\n    nginx:\n      state: running\n      schedule: \"8am-5pm\"\n",
+        ] {
+            assert!(Config::load_from_str(yaml).is_err());
+        }
+    }
+
+    #[test]
+    fn test_webhooks_validation() {
+        let yaml = r#"
+webhooks:
+  endpoints:
+    - url: "https://chat.example.com/hooks/shiki"
+      events: [operation.failed, agent.shutting_down]
+      secret: "s3cret"
+    - url: "http://127.0.0.1:9000/all"
+  retry:
+    max_attempts: 5
+"#;
+        let config = Config::load_from_str(yaml).unwrap();
+        let webhooks = &config.webhooks;
+        assert_eq!(webhooks.endpoints.len(), 2);
+        assert_eq!(
+            webhooks.endpoints[0].events,
+            vec![
+                WebhookEvent::OperationFailed,
+                WebhookEvent::AgentShuttingDown
+            ]
+        );
+        assert!(webhooks.endpoints[1].events.is_empty());
+        assert_eq!(webhooks.retry.max_attempts, 5);
+        assert_eq!(webhooks.queue_size, 1000);
+
+        let invalid = [
+            "webhooks:\n  queue_size: 0\n",
+            "webhooks:\n  endpoints:\n    - url: \"chat.example.com\"\n",
+            "webhooks:\n  endpoints:\n    - url: \"http://bot\"\n      secret: \"\"\n",
+        ];
+        for yaml in invalid {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains("webhooks."), "{}", err);
+        }
+        let err = Config::load_from_str(
+            "webhooks:\n  endpoints:\n    - url: \"http://bot\"\n      events: [service.deleted]\n",
+        );
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_strict_loading() {
+        let yaml = r#"
+server:
+  bind: "127.0.0.1"
+  prot: 9090
+servces: {}
+"#;
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(yaml.as_bytes()).unwrap();
+        let name = file.path().display().to_string();
+
+        let err = Config::load_strict(Some(file.path()))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(&format!(
+                "{}:4: server.prot: unknown setting, did you mean 'port'?\n  {}:5: servces:",
+                name, name
+            )),
+            "{}",
+            err
+        );
+
+        // Ignored, with a warning, when not strict
+        let config = Config::load_from_path(file.path()).unwrap();
+        assert_eq!(config.server.port, 8080);
+        let warnings: Vec<String> = config.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                format!(
+                    "{}:4: server.prot: unknown setting, did you mean 'port'?",
+                    name
+                ),
+                format!(
+                    "{}:5: servces: unknown setting, did you mean 'services'?",
+                    name
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validation_service_names() {
+        assert!(validate_service_name("nginx.service").is_ok());
+        assert!(validate_service_name("app@1:worker_2-a").is_ok());
+        assert!(validate_service_name(&"a".repeat(MAX_SERVICE_NAME_LEN)).is_ok());
+        assert!(validate_service_name(&"a".repeat(MAX_SERVICE_NAME_LEN + 1)).is_err());
+        assert!(validate_service_name("").is_err());
+
+        let long = format!("watch:\n  {}: {{}}\n", "a".repeat(MAX_SERVICE_NAME_LEN + 1));
+        for (yaml, expected) in [
+            (
+                "agent:\n  backend: exec\nservices:\n  \"my app\": {start: a, stop: b, status: c}\n",
+                "services.my app: invalid character ' '",
+            ),
+            (
+                "readiness:\n  web/1: {check: {type: file, path: /tmp/x}}\n",
+                "readiness.web/1: invalid character '/'",
+            ),
+            (long.as_str(), "longer than 256 characters"),
+        ] {
+            let err = Config::load_from_str(yaml).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = Config::default();
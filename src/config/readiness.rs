@@ -0,0 +1,148 @@
+//! Readiness probe configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Readiness probe for a service.
+///
+/// A service is considered ready once `success_threshold` consecutive checks
+/// pass, and not ready again after `failure_threshold` consecutive failures.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReadinessProbe {
+    /// The check to perform.
+    pub check: ProbeCheck,
+
+    /// Interval between checks in seconds.
+    #[serde(default = "default_interval")]
+    pub interval_seconds: u64,
+
+    /// Timeout for a single check in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Consecutive successes required to become ready.
+    #[serde(default = "default_threshold")]
+    pub success_threshold: u32,
+
+    /// Consecutive failures required to become not ready.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Delay before the first check in seconds.
+    #[serde(default)]
+    pub initial_delay_seconds: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+fn default_timeout() -> u64 {
+    1
+}
+
+fn default_threshold() -> u32 {
+    1
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+/// Readiness check type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProbeCheck {
+    /// TCP connect to an address (host:port).
+    Tcp {
+        /// Address to connect to.
+        address: String,
+    },
+
+    /// HTTP GET with an expected status code.
+    Http {
+        /// URL to request.
+        url: String,
+        /// Expected HTTP status code.
+        #[serde(default = "default_expected_status")]
+        expected_status: u16,
+    },
+
+    /// Command that must exit with code 0.
+    Command {
+        /// Command line to execute.
+        command: String,
+    },
+
+    /// File that must exist.
+    File {
+        /// Path of the file.
+        path: String,
+    },
+}
+
+fn default_expected_status() -> u16 {
+    200
+}
+
+impl ProbeCheck {
+    /// Returns the check type name.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProbeCheck::Tcp { .. } => "tcp",
+            ProbeCheck::Http { .. } => "http",
+            ProbeCheck::Command { .. } => "command",
+            ProbeCheck::File { .. } => "file",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_probe_defaults() {
+        let yaml = r#"
+check:
+  type: tcp
+  address: "127.0.0.1:5432"
+"#;
+        let probe: ReadinessProbe = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(
+            probe.check,
+            ProbeCheck::Tcp {
+                address: "127.0.0.1:5432".to_string()
+            }
+        );
+        assert_eq!(probe.interval_seconds, 5);
+        assert_eq!(probe.timeout_seconds, 1);
+        assert_eq!(probe.success_threshold, 1);
+        assert_eq!(probe.failure_threshold, 3);
+        assert_eq!(probe.initial_delay_seconds, 0);
+    }
+
+    #[test]
+    fn test_probe_check_variants() {
+        let http: ProbeCheck =
+            serde_yaml::from_str("type: http\nurl: \"http://localhost/healthz\"").unwrap();
+        assert_eq!(
+            http,
+            ProbeCheck::Http {
+                url: "http://localhost/healthz".to_string(),
+                expected_status: 200
+            }
+        );
+        assert_eq!(http.kind(), "http");
+
+        let command: ProbeCheck =
+            serde_yaml::from_str("type: command\ncommand: pg_isready").unwrap();
+        assert_eq!(command.kind(), "command");
+
+        let file: ProbeCheck = serde_yaml::from_str("type: file\npath: /run/app.ready").unwrap();
+        assert_eq!(file.kind(), "file");
+
+        assert!(serde_yaml::from_str::<ProbeCheck>("type: grpc").is_err());
+    }
+}
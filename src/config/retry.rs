@@ -1,9 +1,10 @@
 //! Retry and timeout configuration types.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Retry configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct RetryConfig {
     /// Maximum number of retry attempts.
@@ -31,7 +32,7 @@ impl Default for RetryConfig {
 }
 
 /// Timeout configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct TimeoutConfig {
     /// Service operation timeout in seconds.
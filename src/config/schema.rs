@@ -0,0 +1,187 @@
+//! JSON Schema of the configuration file.
+//!
+//! The schema is derived from the [`Config`] types with `schemars`, so it
+//! follows them as settings are added. Objects with fixed settings are
+//! closed (`additionalProperties: false`): serde ignores unknown keys, but
+//! in a configuration file they are mistakes. The configuration lint walks
+//! the same schema to find them.
+
+use super::include::INCLUDE_KEY;
+use super::Config;
+use schemars::gen::SchemaSettings;
+use schemars::schema::{Schema, SchemaObject};
+use schemars::visit::{visit_schema_object, Visitor};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// Returns the schema, generated once per process.
+pub(super) fn cached() -> &'static Value {
+    static SCHEMA: OnceLock<Value> = OnceLock::new();
+    SCHEMA.get_or_init(json_schema)
+}
+
+/// Returns the JSON Schema (draft-07) of the configuration file.
+pub fn json_schema() -> Value {
+    let settings = SchemaSettings::draft07()
+        .with(|settings| {
+            // Every setting is described where it appears, without `$ref`s
+            settings.inline_subschemas = true;
+        })
+        .with_visitor(DenyUnknownFields);
+    let root = settings.into_generator().into_root_schema_for::<Config>();
+    let mut schema = serde_json::to_value(root).unwrap_or_default();
+    schema["title"] = json!("shiki configuration");
+    // Handled before the settings are read
+    schema["properties"][INCLUDE_KEY] = json!({
+        "anyOf": [
+            {"type": "string"},
+            {"type": "array", "items": {"type": "string"}},
+        ],
+    });
+    schema
+}
+
+/// Closes every object that has fixed properties.
+#[derive(Debug, Clone)]
+struct DenyUnknownFields;
+
+impl Visitor for DenyUnknownFields {
+    fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+        if let Some(object) = schema.object.as_mut() {
+            if !object.properties.is_empty() && object.additional_properties.is_none() {
+                object.additional_properties = Some(Box::new(Schema::Bool(false)));
+            }
+        }
+        visit_schema_object(self, schema);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calls `f` with the dotted path of every schema below `schema`.
+    fn walk(path: &str, schema: &Value, f: &mut dyn FnMut(&str, &Value)) {
+        f(path, schema);
+        let child = |key: &str| {
+            format!("{}.{}", path, key)
+                .trim_start_matches('.')
+                .to_string()
+        };
+        if let Some(properties) = schema["properties"].as_object() {
+            for (key, schema) in properties {
+                walk(&child(key), schema, f);
+            }
+        }
+        for (key, name) in [("additionalProperties", "*"), ("items", "*")] {
+            if schema[key].is_object() {
+                walk(&child(name), &schema[key], f);
+            }
+        }
+        for key in ["anyOf", "oneOf"] {
+            for variant in schema[key].as_array().into_iter().flatten() {
+                walk(path, variant, f);
+            }
+        }
+    }
+
+    /// Returns the values of an enum, whether or not its variants are
+    /// documented (which gives each its own schema).
+    fn variants(schema: &Value) -> Vec<Value> {
+        match schema["enum"].as_array() {
+            Some(values) => values.clone(),
+            None => schema["oneOf"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(variants)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_schema_types_every_setting() {
+        let schema = json_schema();
+        let mut untyped = Vec::new();
+        walk("", &schema, &mut |path, schema| {
+            let typed = ["type", "anyOf", "oneOf"]
+                .iter()
+                .any(|key| schema.get(key).is_some());
+            let open = schema["type"] == "array" && schema.get("items").is_none();
+            if !typed || open {
+                untyped.push(path.to_string());
+            }
+        });
+        assert!(untyped.is_empty(), "untyped settings: {:?}", untyped);
+
+        // Every setting of the default configuration is in the schema
+        let mut settings = Vec::new();
+        walk("", &schema, &mut |path, _| settings.push(path.to_string()));
+        let defaults = serde_yaml::to_value(Config::default()).unwrap();
+        for (section, fields) in defaults.as_mapping().unwrap() {
+            let section = section.as_str().unwrap();
+            assert!(settings.iter().any(|s| s == section), "{}", section);
+            for field in fields.as_mapping().into_iter().flat_map(|m| m.keys()) {
+                let path = format!("{}.{}", section, field.as_str().unwrap());
+                assert!(settings.contains(&path), "{} is not in the schema", path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_schema_details() {
+        let schema = json_schema();
+        let properties = &schema["properties"];
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(
+            variants(&properties["agent"]["properties"]["backend"]),
+            vec![json!("systemd"), json!("exec")]
+        );
+        assert_eq!(properties["server"]["properties"]["port"]["default"], 8080);
+        assert_eq!(
+            properties["retry"]["properties"]["multiplier"]["type"],
+            "number"
+        );
+
+        // Maps of definitions keyed by name
+        let service = &properties["services"]["additionalProperties"];
+        assert_eq!(service["properties"]["start"]["type"], "string");
+        assert_eq!(
+            variants(&service["properties"]["supervisor"]["properties"]["restart_policy"]),
+            vec![json!("never"), json!("on-failure"), json!("always")]
+        );
+        assert_eq!(
+            properties["agent"]["properties"]["metadata"]["additionalProperties"]["type"],
+            "string"
+        );
+
+        // Required fields, and the fields of every probe check type
+        let probe = &properties["readiness"]["additionalProperties"];
+        assert_eq!(probe["required"], json!(["check"]));
+        let checks = probe["properties"]["check"]["oneOf"].as_array().unwrap();
+        for (check, field) in checks
+            .iter()
+            .zip(["address", "expected_status", "command", "path"])
+        {
+            assert!(check["required"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("type")));
+            assert!(check["properties"][field].is_object(), "{}", field);
+        }
+        assert!(checks
+            .iter()
+            .all(|check| check["additionalProperties"] == false));
+        let peer = &properties["cluster"]["properties"]["peers"]["items"];
+        assert_eq!(peer["required"], json!(["address", "name"]));
+
+        // Short form of desired state declarations
+        let declaration = &properties["desired_state"]["properties"]["services"];
+        assert_eq!(
+            variants(&declaration["additionalProperties"]["anyOf"][0]),
+            vec![json!("running"), json!("stopped")]
+        );
+        assert!(properties[INCLUDE_KEY]["anyOf"].is_array());
+    }
+}
@@ -0,0 +1,286 @@
+//! Secret references.
+//!
+//! Credentials need not be written in plaintext: the secret settings and
+//! the `env` entries of service definitions may contain references that are
+//! resolved when the configuration is loaded.
+//!
+//! - `${file:/run/secrets/shiki_token}`: content of a file, without the
+//!   trailing newline. Relative paths are relative to the main file.
+//! - `${env:SHIKI_TOKEN}`: value of an environment variable.
+//! - `${credential:shiki_token}`: systemd credential, read from
+//!   `$CREDENTIALS_DIRECTORY`.
+//!
+//! `$${` stands for a literal `${`. Secret settings and resolved values are
+//! redacted wherever the configuration is displayed.
+
+use crate::error::ShikiError;
+use serde_yaml::Value;
+use std::path::Path;
+
+/// Settings holding credentials, by dotted path; `*` matches any key or
+/// index.
+const SECRET_PATHS: &[&str] = &[
+    "auth.token",
+    "auth.api_keys.*",
+    "cluster.peers.*.token",
+    "cluster.discovery.key",
+    "webhooks.endpoints.*.secret",
+];
+
+/// Settings that may contain references besides the secret settings.
+const REFERENCE_PATHS: &[&str] = &["services.*.env.*"];
+
+/// Replacement of redacted values.
+pub const REDACTED: &str = "********";
+
+/// Environment variable set by systemd to the directory of the unit's
+/// credentials.
+const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+/// Resolves the references of `document` in place. `base` is the directory
+/// relative file references are resolved against. Returns the resolved
+/// values.
+pub fn resolve(document: &mut Value, base: &Path) -> Result<Vec<String>, ShikiError> {
+    let mut resolved = Vec::new();
+    let mut result = Ok(());
+    visit(document, &mut Vec::new(), &mut |path, value| {
+        if result.is_err()
+            || !(matches_any(SECRET_PATHS, path) || matches_any(REFERENCE_PATHS, path))
+        {
+            return;
+        }
+        let Value::String(text) = value else {
+            return;
+        };
+        match expand(text, base, &mut resolved) {
+            Ok(expanded) => *text = expanded,
+            Err(reason) => {
+                result = Err(ShikiError::config(format!(
+                    "{}: {}",
+                    path.join("."),
+                    reason
+                )))
+            }
+        }
+    });
+    result?;
+    resolved.retain(|value| !value.is_empty());
+    Ok(resolved)
+}
+
+/// Redacts `document` in place: secret settings are replaced as a whole,
+/// and occurrences of the `secrets` values in other strings.
+pub fn redact(document: &mut Value, secrets: &[String]) {
+    visit(document, &mut Vec::new(), &mut |path, value| {
+        let Value::String(text) = value else {
+            return;
+        };
+        if matches_any(SECRET_PATHS, path) {
+            *text = REDACTED.to_string();
+        } else {
+            for secret in secrets {
+                if text.contains(secret.as_str()) {
+                    *text = text.replace(secret.as_str(), REDACTED);
+                }
+            }
+        }
+    });
+}
+
+/// Calls `f` with the path and value of every scalar of `value`.
+fn visit(value: &mut Value, path: &mut Vec<String>, f: &mut dyn FnMut(&[String], &mut Value)) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                path.push(key_name(key));
+                visit(value, path, f);
+                path.pop();
+            }
+        }
+        Value::Sequence(sequence) => {
+            for (index, value) in sequence.iter_mut().enumerate() {
+                path.push(index.to_string());
+                visit(value, path, f);
+                path.pop();
+            }
+        }
+        Value::Tagged(tagged) => visit(&mut tagged.value, path, f),
+        _ => f(path, value),
+    }
+}
+
+/// Returns the name of a mapping key as it appears in a dotted path.
+/// Redaction, the lint and table output all name settings this way.
+pub(crate) fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|key| key.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn matches_any(patterns: &[&str], path: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        let segments: Vec<&str> = pattern.split('.').collect();
+        segments.len() == path.len()
+            && segments
+                .iter()
+                .zip(path)
+                .all(|(segment, key)| *segment == "*" || segment == key)
+    })
+}
+
+/// Expands the references of `text`, adding their values to `resolved`.
+fn expand(text: &str, base: &Path, resolved: &mut Vec<String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        expanded.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let Some(end) = reference.find('}') else {
+            return Err(format!("unterminated reference in '{}'", text));
+        };
+        let value = lookup(&reference[..end], base)?;
+        expanded.push_str(&value);
+        resolved.push(value);
+        rest = &reference[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Returns the value of a reference, given without `${` and `}`.
+fn lookup(reference: &str, base: &Path) -> Result<String, String> {
+    let Some((kind, name)) = reference.split_once(':') else {
+        return Err(format!(
+            "invalid reference '${{{}}}', expected file:, env: or credential:",
+            reference
+        ));
+    };
+    match kind {
+        "file" => read(&base.join(name)),
+        "env" => {
+            std::env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+        }
+        "credential" => {
+            if name.is_empty() || name.contains('/') {
+                return Err(format!("invalid credential name '{}'", name));
+            }
+            let directory = std::env::var_os(CREDENTIALS_DIRECTORY).ok_or_else(|| {
+                format!(
+                    "credential {} requested but {} is not set",
+                    name, CREDENTIALS_DIRECTORY
+                )
+            })?;
+            read(&Path::new(&directory).join(name))
+        }
+        _ => Err(format!(
+            "unknown reference kind '{}', expected file, env or credential",
+            kind
+        )),
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map(|content| content.trim_end_matches(['\n', '\r']).to_string())
+        .map_err(|e| format!("cannot read secret file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("token"), "file-token\n").unwrap();
+        std::env::set_var("SHIKI_TEST_SECRET_KEY", "env-key");
+
+        let mut config = document(
+            r#"
+auth:
+  token: ${file:token}
+  api_keys: ["${env:SHIKI_TEST_SECRET_KEY}", plain-key]
+services:
+  web:
+    start: echo ${HOME}
+    env: ["DB_PASS=${env:SHIKI_TEST_SECRET_KEY}", "PRICE=$${file:x}"]
+"#,
+        );
+        let resolved = resolve(&mut config, dir.path()).unwrap();
+        assert_eq!(resolved, vec!["file-token", "env-key", "env-key"]);
+        assert_eq!(config["auth"]["token"], "file-token");
+        assert_eq!(config["auth"]["api_keys"][0], "env-key");
+        assert_eq!(config["services"]["web"]["start"], "echo ${HOME}");
+        assert_eq!(config["services"]["web"]["env"][0], "DB_PASS=env-key");
+        assert_eq!(config["services"]["web"]["env"][1], "PRICE=${file:x}");
+
+        redact(&mut config, &resolved);
+        assert_eq!(config["auth"]["token"], REDACTED);
+        assert_eq!(config["auth"]["api_keys"][1], REDACTED);
+        assert_eq!(
+            config["services"]["web"]["env"][0],
+            format!("DB_PASS={}", REDACTED).as_str()
+        );
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let config: crate::config::Config = serde_yaml::from_str(
+            r#"
+auth: { token: auth-secret, api_keys: [key-secret] }
+cluster:
+  peers: [{ name: b, address: "b:8080", token: peer-secret }]
+  discovery: { key: discovery-secret }
+webhooks:
+  endpoints: [{ url: "http://hooks", secret: hook-secret }]
+"#,
+        )
+        .unwrap();
+        let debug = format!(
+            "{:?} {:?} {:?} {:?}",
+            config.auth, config.cluster.peers, config.cluster.discovery, config.webhooks
+        );
+        assert!(!debug.contains("-secret"), "{}", debug);
+        assert_eq!(debug.matches(REDACTED).count(), 5);
+        assert!(debug.contains("http://hooks"));
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let base = Path::new("/nonexistent");
+        for (yaml, expected) in [
+            (
+                "auth: {token: '${env:SHIKI_TEST_UNSET_VARIABLE}'}",
+                "auth.token: environment variable SHIKI_TEST_UNSET_VARIABLE is not set",
+            ),
+            (
+                "auth: {api_keys: ['${vault:x}']}",
+                "auth.api_keys.0: unknown reference kind 'vault'",
+            ),
+            (
+                "auth: {token: '${file:token'}",
+                "auth.token: unterminated reference",
+            ),
+            (
+                "auth: {token: '${credential:a/b}'}",
+                "auth.token: invalid credential name 'a/b'",
+            ),
+        ] {
+            let err = resolve(&mut document(yaml), base).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}", err);
+        }
+    }
+}
@@ -2,13 +2,16 @@
 //!
 //! Contains configurations for HTTP server, TLS, and authentication.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::str::FromStr;
 
+use super::secret::REDACTED;
 use crate::error::ShikiError;
 
 /// HTTP server configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ServerConfig {
     /// Listen address.
@@ -32,7 +35,7 @@ impl Default for ServerConfig {
 }
 
 /// TLS configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct TlsConfig {
     /// Enable TLS.
@@ -46,7 +49,7 @@ pub struct TlsConfig {
 }
 
 /// Authentication configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct AuthConfig {
     /// Enable authentication.
@@ -62,8 +65,20 @@ pub struct AuthConfig {
     pub api_keys: Vec<String>,
 }
 
+// Written by hand so that the token and the keys never end up in logs
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("enabled", &self.enabled)
+            .field("method", &self.method)
+            .field("token", &self.token.as_ref().map(|_| REDACTED))
+            .field("api_keys", &vec![REDACTED; self.api_keys.len()])
+            .finish()
+    }
+}
+
 /// Authentication method.
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AuthMethod {
     /// No authentication.
@@ -0,0 +1,47 @@
+//! Signal and barrier configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Signal configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SignalsConfig {
+    /// Peer that holds the signals of the cluster. When set, this agent
+    /// forwards signal and barrier requests to it.
+    pub coordinator: Option<String>,
+
+    /// Time to live of a signal in seconds when the request gives none.
+    pub default_ttl_seconds: u64,
+
+    /// Maximum time to live of a signal in seconds.
+    pub max_ttl_seconds: u64,
+
+    /// Maximum time a barrier wait may block in seconds.
+    pub max_wait_seconds: u64,
+}
+
+impl Default for SignalsConfig {
+    fn default() -> Self {
+        Self {
+            coordinator: None,
+            default_ttl_seconds: 3600,
+            max_ttl_seconds: 86400,
+            max_wait_seconds: 3600,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals_config_default() {
+        let config = SignalsConfig::default();
+        assert!(config.coordinator.is_none());
+        assert_eq!(config.default_ttl_seconds, 3600);
+        assert_eq!(config.max_ttl_seconds, 86400);
+        assert_eq!(config.max_wait_seconds, 3600);
+    }
+}
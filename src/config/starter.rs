@@ -0,0 +1,116 @@
+//! Starter configuration written by `shiki config init`.
+
+use super::Backend;
+use std::path::Path;
+
+/// Returns the backend suited to this machine: `systemd` when `systemctl`
+/// is on the `PATH`, `exec` otherwise (containers and the like).
+pub fn detect_backend() -> Backend {
+    let found = std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| dir.join("systemctl").is_file())
+    });
+    if found {
+        Backend::Systemd
+    } else {
+        Backend::Exec
+    }
+}
+
+/// Returns a commented starter configuration for the agent `name` using
+/// `backend`. `schema` is where the JSON Schema is meant to be written, for
+/// the editor hint at the top of the file.
+pub fn starter_config(name: &str, backend: Backend, schema: &Path) -> String {
+    // A JSON string is a valid YAML scalar, whatever the name contains
+    let quoted = serde_json::to_string(name).unwrap_or_default();
+    let services = match backend {
+        Backend::Systemd => SYSTEMD_SERVICES,
+        Backend::Exec => EXEC_SERVICES,
+    };
+    let schema = schema.display();
+    format!(
+        "\
+# yaml-language-server: $schema={schema}
+#
+# shiki 設定ファイル（shiki config init で生成）
+#
+# エディタで補完・検証を使うには、上記のパスにスキーマを出力してください:
+#   shiki config schema > {schema}
+# 設定の確認: shiki config validate -c <このファイル>
+# 詳細は docs/CONFIGURATION.md を参照してください。
+
+# HTTP サーバー設定
+server:
+  # バインドアドレス（外部から操作する場合は 0.0.0.0 にし、認証を有効化してください）
+  bind: \"127.0.0.1\"
+  port: 8080
+
+# 認証設定（本番環境では有効化を推奨）
+auth:
+  enabled: false
+  # method: token
+  # token: \"${{file:/etc/shiki/token}}\"
+
+# ログ設定
+logging:
+  # trace, debug, info, warn, error
+  level: info
+  # json, text
+  format: text
+
+# エージェント設定
+agent:
+  # エージェント識別名（省略時はホスト名）
+  name: {quoted}
+  # systemd: systemctl 経由でサービスを操作
+  # exec: サービスごとのコマンドで操作（コンテナ向け）
+  backend: {backend}
+  # tags: [web, production]
+{services}"
+    )
+}
+
+const SYSTEMD_SERVICES: &str = "
+# サービスアクセス制御（ユニット名の glob パターン、省略時はすべて許可）
+# acl:
+#   allowed: [\"nginx.service\", \"app-*\"]
+#   denied: [\"sshd.service\"]
+";
+
+const EXEC_SERVICES: &str = "
+# サービス定義（backend: exec では 1 つ以上必要）
+services:
+  # shiki がプロセスを直接起動・監視するサービス（start を実際のコマンドに置き換えてください）
+  app:
+    type: supervised
+    start: \"/bin/sleep 86400\"
+    supervisor:
+      restart_policy: on-failure
+  # 起動・停止・状態確認をコマンドで行う場合:
+  # worker:
+  #   start: \"/usr/local/bin/worker --daemon\"
+  #   stop: \"pkill -x worker\"
+  #   status: \"pgrep -x worker\"
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_starter_config_is_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        for backend in [Backend::Systemd, Backend::Exec] {
+            let content = starter_config("web: 01", backend, Path::new("config.schema.json"));
+            let path = dir.path().join(format!("{}.yaml", backend));
+            std::fs::write(&path, &content).unwrap();
+
+            let config = Config::load_from_path(&path).unwrap();
+            assert!(config.warnings.is_empty(), "{:?}", config.warnings);
+            assert_eq!(config.agent.name.as_deref(), Some("web: 01"));
+            assert_eq!(config.agent.backend, backend);
+            assert_eq!(config.services.is_empty(), backend == Backend::Systemd);
+            assert!(content.starts_with("# yaml-language-server: $schema=config.schema.json\n"));
+        }
+    }
+}
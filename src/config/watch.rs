@@ -0,0 +1,107 @@
+//! Service watcher configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::ServiceAction;
+
+/// Watchdog settings of a service.
+///
+/// The watcher polls the state of the service and applies `policy` when the
+/// service goes down without being stopped through shiki.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Interval between status checks in seconds.
+    pub interval_seconds: u64,
+
+    /// What to do when the service is down.
+    pub policy: WatchPolicy,
+
+    /// Maximum number of restarts within `window_seconds`. The watcher gives
+    /// up once the limit is reached.
+    pub max_restarts: u32,
+
+    /// Length of the restart window in seconds.
+    pub window_seconds: u64,
+
+    /// Initial delay between restarts in milliseconds.
+    pub restart_delay_ms: u64,
+
+    /// Maximum delay between restarts in milliseconds.
+    pub restart_max_delay_ms: u64,
+
+    /// Restart delay backoff multiplier.
+    pub restart_multiplier: f64,
+
+    /// Request sent to another agent (for `policy: remote`).
+    pub remote: Option<WatchRemote>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: 10,
+            policy: WatchPolicy::Restart,
+            max_restarts: 5,
+            window_seconds: 600,
+            restart_delay_ms: 1000,
+            restart_max_delay_ms: 60000,
+            restart_multiplier: 2.0,
+            remote: None,
+        }
+    }
+}
+
+/// Policy applied to a service that went down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchPolicy {
+    /// Restart the service with backoff.
+    #[default]
+    Restart,
+
+    /// Emit a `service.unhealthy` webhook event.
+    Notify,
+
+    /// Send a request to another agent.
+    Remote,
+}
+
+impl std::fmt::Display for WatchPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchPolicy::Restart => write!(f, "restart"),
+            WatchPolicy::Notify => write!(f, "notify"),
+            WatchPolicy::Remote => write!(f, "remote"),
+        }
+    }
+}
+
+/// Request sent to another agent when a watched service goes down.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchRemote {
+    /// Peer name or agent address (host:port).
+    pub agent: String,
+
+    /// Service on the remote agent.
+    pub service: String,
+
+    /// Action performed on the remote service.
+    pub action: ServiceAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_config_default() {
+        let config = WatchConfig::default();
+        assert_eq!(config.interval_seconds, 10);
+        assert_eq!(config.policy, WatchPolicy::Restart);
+        assert_eq!(config.max_restarts, 5);
+        assert_eq!(config.window_seconds, 600);
+        assert!(config.remote.is_none());
+    }
+}
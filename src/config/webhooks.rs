@@ -0,0 +1,127 @@
+//! Webhook configuration types.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::secret::REDACTED;
+use super::RetryConfig;
+
+/// Outbound webhook configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    /// Webhook receivers.
+    pub endpoints: Vec<WebhookEndpoint>,
+
+    /// Maximum number of events waiting for delivery. Events emitted while
+    /// the queue is full are dropped.
+    pub queue_size: usize,
+
+    /// Timeout of a delivery attempt in seconds.
+    pub timeout_seconds: u64,
+
+    /// Retry policy of failed deliveries.
+    pub retry: RetryConfig,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            queue_size: 1000,
+            timeout_seconds: 10,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// A webhook receiver.
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookEndpoint {
+    /// URL the events are posted to.
+    pub url: String,
+
+    /// Events sent to this receiver (empty = all events).
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+
+    /// Secret the payload is signed with (HMAC-SHA256).
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl fmt::Debug for WebhookEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookEndpoint")
+            .field("url", &self.url)
+            .field("events", &self.events)
+            .field("secret", &self.secret.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+impl WebhookEndpoint {
+    /// Returns whether the receiver subscribed to `event`.
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Webhook event types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum WebhookEvent {
+    /// A service operation failed or ended in an error.
+    #[serde(rename = "operation.failed")]
+    OperationFailed,
+    /// A service operation changed the state of the service.
+    #[serde(rename = "service.state_changed")]
+    ServiceStateChanged,
+    /// The agent is shutting down.
+    #[serde(rename = "agent.shutting_down")]
+    AgentShuttingDown,
+    /// A watched service went down, or its watcher gave up restarting it.
+    #[serde(rename = "service.unhealthy")]
+    ServiceUnhealthy,
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookEvent::OperationFailed => write!(f, "operation.failed"),
+            WebhookEvent::ServiceStateChanged => write!(f, "service.state_changed"),
+            WebhookEvent::AgentShuttingDown => write!(f, "agent.shutting_down"),
+            WebhookEvent::ServiceUnhealthy => write!(f, "service.unhealthy"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhooks_config_default() {
+        let config = WebhooksConfig::default();
+        assert!(config.endpoints.is_empty());
+        assert_eq!(config.queue_size, 1000);
+        assert_eq!(config.timeout_seconds, 10);
+        assert_eq!(config.retry.max_attempts, 3);
+    }
+
+    #[test]
+    fn test_endpoint_event_filter() {
+        let all = WebhookEndpoint {
+            url: "http://bot:9000/hook".to_string(),
+            ..Default::default()
+        };
+        assert!(all.wants(WebhookEvent::AgentShuttingDown));
+
+        let failures = WebhookEndpoint {
+            events: vec![WebhookEvent::OperationFailed],
+            ..all
+        };
+        assert!(failures.wants(WebhookEvent::OperationFailed));
+        assert!(!failures.wants(WebhookEvent::ServiceStateChanged));
+    }
+}
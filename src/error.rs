@@ -4,7 +4,7 @@
 //! including error codes, error responses for the API, and CLI exit codes.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt;
 use thiserror::Error;
 
@@ -47,6 +47,10 @@ pub enum ErrorCode {
     /// E009: Agent is busy
     #[serde(rename = "E009")]
     AgentBusy,
+
+    /// E010: Lock is held by another owner
+    #[serde(rename = "E010")]
+    LockConflict,
 }
 
 impl ErrorCode {
@@ -62,6 +66,7 @@ impl ErrorCode {
             ErrorCode::AuthFailed => "E007",
             ErrorCode::InvalidRequest => "E008",
             ErrorCode::AgentBusy => "E009",
+            ErrorCode::LockConflict => "E010",
         }
     }
 
@@ -77,6 +82,7 @@ impl ErrorCode {
             ErrorCode::AuthFailed => "Authentication failed",
             ErrorCode::InvalidRequest => "Request is invalid",
             ErrorCode::AgentBusy => "Agent is busy",
+            ErrorCode::LockConflict => "Lock is held by another owner",
         }
     }
 
@@ -92,6 +98,7 @@ impl ErrorCode {
             ErrorCode::AuthFailed => 401,
             ErrorCode::InvalidRequest => 400,
             ErrorCode::AgentBusy => 503,
+            ErrorCode::LockConflict => 409,
         }
     }
 }
@@ -171,6 +178,14 @@ pub enum ShikiError {
     #[error("Agent is busy: {reason}")]
     AgentBusy { reason: String },
 
+    /// Lock is held by another owner, or no longer held by the caller.
+    #[error("Lock conflict on {lock}: {reason}")]
+    LockConflict {
+        lock: String,
+        holder: Option<String>,
+        reason: String,
+    },
+
     /// I/O error.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -197,6 +212,7 @@ impl ShikiError {
             ShikiError::AuthFailed { .. } => ErrorCode::AuthFailed,
             ShikiError::InvalidRequest { .. } => ErrorCode::InvalidRequest,
             ShikiError::AgentBusy { .. } => ErrorCode::AgentBusy,
+            ShikiError::LockConflict { .. } => ErrorCode::LockConflict,
             ShikiError::Io(_) => ErrorCode::BackendError,
             ShikiError::Yaml(_) => ErrorCode::ConfigInvalid,
             ShikiError::Json(_) => ErrorCode::InvalidRequest,
@@ -284,14 +300,14 @@ impl ShikiError {
 pub struct ErrorDetails {
     /// Additional context fields.
     #[serde(flatten)]
-    pub fields: HashMap<String, serde_json::Value>,
+    pub fields: BTreeMap<String, serde_json::Value>,
 }
 
 impl ErrorDetails {
     /// Creates empty error details.
     pub fn new() -> Self {
         Self {
-            fields: HashMap::new(),
+            fields: BTreeMap::new(),
         }
     }
 
@@ -376,6 +392,19 @@ impl ErrorResponse {
             ShikiError::Connection { target, .. } => {
                 Some(ErrorDetails::new().with_field("target", target.clone()))
             }
+            ShikiError::LockConflict {
+                lock,
+                holder,
+                reason,
+            } => {
+                let details = ErrorDetails::new()
+                    .with_field("lock", lock.clone())
+                    .with_field("reason", reason.clone());
+                Some(match holder {
+                    Some(holder) => details.with_field("holder", holder.clone()),
+                    None => details,
+                })
+            }
             _ => None,
         };
 
@@ -405,6 +434,7 @@ mod tests {
         assert_eq!(ErrorCode::AuthFailed.as_str(), "E007");
         assert_eq!(ErrorCode::InvalidRequest.as_str(), "E008");
         assert_eq!(ErrorCode::AgentBusy.as_str(), "E009");
+        assert_eq!(ErrorCode::LockConflict.as_str(), "E010");
     }
 
     #[test]
@@ -418,6 +448,7 @@ mod tests {
         assert_eq!(ErrorCode::AuthFailed.http_status(), 401);
         assert_eq!(ErrorCode::InvalidRequest.http_status(), 400);
         assert_eq!(ErrorCode::AgentBusy.http_status(), 503);
+        assert_eq!(ErrorCode::LockConflict.http_status(), 409);
     }
 
     #[test]
@@ -0,0 +1,539 @@
+//! Persistent operation history.
+//!
+//! Every service operation performed by the agent is recorded in an embedded
+//! SQLite database, together with the request counters reported by
+//! `/api/v1/status`, so that both survive restarts of the agent. Entries
+//! older than `history.max_age_days` and beyond the newest
+//! `history.max_entries` are deleted as new ones are recorded.
+//!
+//! The database is only accessed from a dedicated thread. Recording an
+//! operation queues it and returns at once, so that request handlers, the
+//! watcher and the reconciler never wait on disk I/O; queries are queued
+//! behind the writes before them and see their results.
+
+use crate::config::HistoryConfig;
+use crate::error::{Result, ShikiError};
+use crate::server::response::{HistoryData, HistoryEntry};
+use crate::server::state::StatsSnapshot;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use tokio::sync::{mpsc, oneshot};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Default number of entries returned by a query.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Maximum number of entries returned by a query.
+pub const MAX_LIMIT: usize = 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id TEXT NOT NULL,
+    timestamp_ms INTEGER NOT NULL,
+    caller TEXT,
+    service TEXT NOT NULL,
+    action TEXT NOT NULL,
+    previous_state TEXT,
+    current_state TEXT,
+    duration_ms INTEGER NOT NULL,
+    result TEXT NOT NULL,
+    message TEXT
+);
+CREATE INDEX IF NOT EXISTS operations_timestamp ON operations (timestamp_ms);
+CREATE INDEX IF NOT EXISTS operations_service ON operations (service, timestamp_ms);
+CREATE TABLE IF NOT EXISTS counters (
+    name TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+";
+
+/// Filters and pagination of a history query.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    /// Only entries of this service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Only entries of this action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Only entries with this result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Only entries started at or after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries started before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of entries (default: 50, max: 1000).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Number of entries to skip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+/// SQLite-backed operation history.
+#[derive(Debug)]
+pub struct HistoryStore {
+    /// Requests to the database thread.
+    commands: mpsc::UnboundedSender<Command>,
+    /// Counters saved when the database was opened.
+    counters: StatsSnapshot,
+    /// Database thread, joined on drop.
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// A request to the database thread.
+#[derive(Debug)]
+enum Command {
+    Record(Box<HistoryEntry>),
+    SaveCounters(StatsSnapshot),
+    Query(HistoryQuery, oneshot::Sender<Result<HistoryData>>),
+    Flush(oneshot::Sender<()>),
+    Close,
+}
+
+impl HistoryStore {
+    /// Opens the database at `config.path`, creating it if needed, and
+    /// starts the thread that accesses it.
+    pub fn open(config: &HistoryConfig) -> Result<Self> {
+        let database = Database::open(config)?;
+        let counters = database.load_counters()?;
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let thread = std::thread::Builder::new()
+            .name("shiki-history".to_string())
+            .spawn(move || run(database, receiver))
+            .map_err(|e| {
+                ShikiError::backend_with_source("Failed to start the history thread", e)
+            })?;
+        Ok(Self {
+            commands,
+            counters,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Queues an operation to be recorded. Entries beyond the retention
+    /// limits are deleted at the same time; the `id` of `entry` is ignored.
+    pub fn record(&self, entry: HistoryEntry) {
+        self.send(Command::Record(Box::new(entry)));
+    }
+
+    /// Returns the entries matching `query`, newest first.
+    pub async fn query(&self, query: HistoryQuery) -> Result<HistoryData> {
+        let (reply, response) = oneshot::channel();
+        self.send(Command::Query(query, reply));
+        response.await.unwrap_or_else(|_| Err(closed()))
+    }
+
+    /// Returns the request counters saved by [`HistoryStore::save_counters`]
+    /// when the database was opened.
+    pub fn saved_counters(&self) -> StatsSnapshot {
+        self.counters.clone()
+    }
+
+    /// Queues the request counters to be saved.
+    pub fn save_counters(&self, stats: StatsSnapshot) {
+        self.send(Command::SaveCounters(stats));
+    }
+
+    /// Waits until everything queued so far has been written.
+    pub async fn flush(&self) {
+        let (reply, done) = oneshot::channel();
+        self.send(Command::Flush(reply));
+        let _ = done.await;
+    }
+
+    fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            warn!("History database thread has stopped");
+        }
+    }
+}
+
+impl Drop for HistoryStore {
+    fn drop(&mut self) {
+        // Queued writes are finished before the database is closed
+        self.send(Command::Close);
+        let thread = self.thread.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Runs the commands sent to the database thread until the store is closed.
+fn run(database: Database, mut commands: mpsc::UnboundedReceiver<Command>) {
+    while let Some(command) = commands.blocking_recv() {
+        match command {
+            Command::Record(entry) => {
+                if let Err(e) = database.record(&entry) {
+                    warn!(service = %entry.service, error = %e, "Failed to record operation history");
+                }
+            }
+            Command::SaveCounters(stats) => {
+                if let Err(e) = database.save_counters(&stats) {
+                    warn!(error = %e, "Failed to save request counters");
+                }
+            }
+            Command::Query(query, reply) => {
+                let _ = reply.send(database.query(&query));
+            }
+            Command::Flush(reply) => {
+                let _ = reply.send(());
+            }
+            Command::Close => return,
+        }
+    }
+}
+
+/// Connection to the history database, owned by the database thread.
+#[derive(Debug)]
+struct Database {
+    conn: Connection,
+    max_entries: u64,
+    max_age: chrono::Duration,
+}
+
+impl Database {
+    /// Opens the database at `config.path`, creating it if needed.
+    fn open(config: &HistoryConfig) -> Result<Self> {
+        if let Some(parent) = config.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                ShikiError::config_with_source(
+                    format!("Failed to create history directory: {}", parent.display()),
+                    e,
+                )
+            })?;
+        }
+        let conn = Connection::open(&config.path).map_err(|e| open_error(&config.path, e))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| open_error(&config.path, e))?;
+        Ok(Self {
+            conn,
+            max_entries: config.max_entries,
+            max_age: chrono::Duration::days(config.max_age_days as i64),
+        })
+    }
+
+    /// Records an operation and deletes entries beyond the retention
+    /// limits. Returns the sequence number of the new entry.
+    fn record(&self, entry: &HistoryEntry) -> Result<i64> {
+        let conn = &self.conn;
+        conn.execute(
+            "INSERT INTO operations (request_id, timestamp_ms, caller, service, action, \
+             previous_state, current_state, duration_ms, result, message) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                entry.request_id.to_string(),
+                entry.timestamp.timestamp_millis(),
+                entry.caller,
+                entry.service,
+                entry.action,
+                entry.previous_state,
+                entry.current_state,
+                entry.duration_ms as i64,
+                entry.result,
+                entry.message,
+            ],
+        )
+        .map_err(db_error)?;
+        let id = conn.last_insert_rowid();
+
+        let cutoff = (Utc::now() - self.max_age).timestamp_millis();
+        conn.execute(
+            "DELETE FROM operations WHERE timestamp_ms < ?1",
+            params![cutoff],
+        )
+        .map_err(db_error)?;
+        conn.execute(
+            "DELETE FROM operations WHERE id <= ?1 - ?2",
+            params![id, self.max_entries as i64],
+        )
+        .map_err(db_error)?;
+        Ok(id)
+    }
+
+    /// Returns the entries matching `query`, newest first.
+    fn query(&self, query: &HistoryQuery) -> Result<HistoryData> {
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        let offset = query.offset.unwrap_or(0);
+
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for (column, value) in [
+            ("service", &query.service),
+            ("action", &query.action),
+            ("result", &query.result),
+        ] {
+            if let Some(value) = value {
+                conditions.push(format!("{} = ?", column));
+                values.push(Value::Text(value.clone()));
+            }
+        }
+        if let Some(since) = query.since {
+            conditions.push("timestamp_ms >= ?".to_string());
+            values.push(Value::Integer(since.timestamp_millis()));
+        }
+        if let Some(until) = query.until {
+            conditions.push("timestamp_ms < ?".to_string());
+            values.push(Value::Integer(until.timestamp_millis()));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+
+        let conn = &self.conn;
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM operations{}", filter),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+
+        let mut statement = conn
+            .prepare(&format!(
+                "SELECT id, request_id, timestamp_ms, caller, service, action, previous_state, \
+                 current_state, duration_ms, result, message FROM operations{} \
+                 ORDER BY id DESC LIMIT {} OFFSET {}",
+                filter, limit, offset
+            ))
+            .map_err(db_error)?;
+        let entries = statement
+            .query_map(params_from_iter(values.iter()), |row| {
+                let request_id: String = row.get(1)?;
+                let timestamp_ms: i64 = row.get(2)?;
+                let duration_ms: i64 = row.get(8)?;
+                Ok(HistoryEntry {
+                    id: row.get(0)?,
+                    request_id: request_id.parse().unwrap_or_else(|_| Uuid::nil()),
+                    timestamp: Utc
+                        .timestamp_millis_opt(timestamp_ms)
+                        .single()
+                        .unwrap_or_default(),
+                    caller: row.get(3)?,
+                    service: row.get(4)?,
+                    action: row.get(5)?,
+                    previous_state: row.get(6)?,
+                    current_state: row.get(7)?,
+                    duration_ms: duration_ms as u64,
+                    result: row.get(9)?,
+                    message: row.get(10)?,
+                })
+            })
+            .map_err(db_error)?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+
+        Ok(HistoryData {
+            entries,
+            total: total as usize,
+            limit,
+            offset,
+        })
+    }
+
+    /// Returns the saved request counters.
+    fn load_counters(&self) -> Result<StatsSnapshot> {
+        let conn = &self.conn;
+        let get = |name: &str| -> Result<u64> {
+            let value: Option<i64> = conn
+                .query_row(
+                    "SELECT value FROM counters WHERE name = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?;
+            Ok(value.unwrap_or(0) as u64)
+        };
+        Ok(StatsSnapshot {
+            requests_total: get("requests_total")?,
+            requests_success: get("requests_success")?,
+            requests_failed: get("requests_failed")?,
+        })
+    }
+
+    /// Saves the request counters.
+    fn save_counters(&self, stats: &StatsSnapshot) -> Result<()> {
+        let conn = &self.conn;
+        for (name, value) in [
+            ("requests_total", stats.requests_total),
+            ("requests_success", stats.requests_success),
+            ("requests_failed", stats.requests_failed),
+        ] {
+            conn.execute(
+                "INSERT INTO counters (name, value) VALUES (?1, ?2) \
+                 ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+                params![name, value as i64],
+            )
+            .map_err(db_error)?;
+        }
+        Ok(())
+    }
+}
+
+fn open_error(path: &Path, e: rusqlite::Error) -> ShikiError {
+    ShikiError::config_with_source(
+        format!("Failed to open history database: {}", path.display()),
+        e,
+    )
+}
+
+fn closed() -> ShikiError {
+    ShikiError::backend("History database thread has stopped")
+}
+
+fn db_error(e: rusqlite::Error) -> ShikiError {
+    ShikiError::backend_with_source(format!("History database error: {}", e), e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &tempfile::TempDir, max_entries: u64) -> HistoryConfig {
+        HistoryConfig {
+            enabled: true,
+            path: dir.path().join("data").join("history.db"),
+            max_entries,
+            max_age_days: 1,
+        }
+    }
+
+    fn open(dir: &tempfile::TempDir, max_entries: u64) -> Database {
+        Database::open(&config(dir, max_entries)).unwrap()
+    }
+
+    fn entry(service: &str, action: &str, result: &str, age_minutes: i64) -> HistoryEntry {
+        HistoryEntry {
+            id: 0,
+            request_id: Uuid::new_v4(),
+            timestamp: Utc::now() - chrono::Duration::minutes(age_minutes),
+            caller: Some("127.0.0.1".to_string()),
+            service: service.to_string(),
+            action: action.to_string(),
+            previous_state: Some("stopped".to_string()),
+            current_state: Some("running".to_string()),
+            duration_ms: 12,
+            result: result.to_string(),
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(&dir, 100);
+        let first = entry("api", "start", "completed", 30);
+        store.record(&first).unwrap();
+        store.record(&entry("api", "stop", "failed", 20)).unwrap();
+        store
+            .record(&entry("db", "restart", "completed", 10))
+            .unwrap();
+
+        let all = store.query(&HistoryQuery::default()).unwrap();
+        assert_eq!(all.total, 3);
+        assert_eq!(all.limit, DEFAULT_LIMIT);
+        assert_eq!(all.entries[0].service, "db");
+        let oldest = &all.entries[2];
+        assert_eq!(oldest.request_id, first.request_id);
+        assert_eq!(
+            oldest.timestamp.timestamp_millis(),
+            first.timestamp.timestamp_millis()
+        );
+        assert_eq!(oldest.caller.as_deref(), Some("127.0.0.1"));
+
+        let api = store
+            .query(&HistoryQuery {
+                service: Some("api".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(api.total, 2);
+
+        let failed = store
+            .query(&HistoryQuery {
+                result: Some("failed".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(failed.entries.len(), 1);
+        assert_eq!(failed.entries[0].action, "stop");
+
+        let window = store
+            .query(&HistoryQuery {
+                since: Some(Utc::now() - chrono::Duration::minutes(25)),
+                until: Some(Utc::now() - chrono::Duration::minutes(15)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(window.total, 1);
+        assert_eq!(window.entries[0].action, "stop");
+
+        let page = store
+            .query(&HistoryQuery {
+                limit: Some(1),
+                offset: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].action, "stop");
+    }
+
+    #[test]
+    fn test_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(&dir, 2);
+        store
+            .record(&entry("api", "start", "completed", 3000))
+            .unwrap();
+        assert_eq!(store.query(&HistoryQuery::default()).unwrap().total, 0);
+
+        for action in ["start", "stop", "restart"] {
+            store.record(&entry("api", action, "completed", 0)).unwrap();
+        }
+        let data = store.query(&HistoryQuery::default()).unwrap();
+        let actions: Vec<&str> = data.entries.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, vec!["restart", "stop"]);
+    }
+
+    #[tokio::test]
+    async fn test_counters_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::open(&config(&dir, 100)).unwrap();
+        assert_eq!(store.saved_counters().requests_total, 0);
+        store.save_counters(StatsSnapshot {
+            requests_total: 10,
+            requests_success: 8,
+            requests_failed: 2,
+        });
+        store.record(entry("api", "start", "completed", 0));
+        // Queries see the writes queued before them
+        let data = store.query(HistoryQuery::default()).await.unwrap();
+        assert_eq!(data.total, 1);
+        store.record(entry("api", "stop", "completed", 0));
+        drop(store);
+
+        let store = HistoryStore::open(&config(&dir, 100)).unwrap();
+        let counters = store.saved_counters();
+        assert_eq!(counters.requests_total, 10);
+        assert_eq!(counters.requests_success, 8);
+        assert_eq!(counters.requests_failed, 2);
+        let data = store.query(HistoryQuery::default()).await.unwrap();
+        assert_eq!(data.total, 2);
+    }
+}
@@ -11,19 +11,35 @@
 //!
 //! # Modules
 //!
+//! - [`audit`] - Tamper-evident audit log
 //! - [`cli`] - Command-line interface definitions
 //! - [`client`] - HTTP client for communicating with agents
+//! - [`cluster`] - Cluster mode (peer registry and health)
 //! - [`config`] - Configuration file parsing and validation
 //! - [`error`] - Error types and error handling
+//! - [`history`] - Persistent operation history
+//! - [`logging`] - Process-wide logging setup
+//! - [`plan`] - Cross-host startup plans
+//! - [`rollout`] - Rolling restarts across agents
 //! - [`server`] - HTTP server and API handlers
 //! - [`service`] - Service management and backends
+//! - [`webhooks`] - Outbound webhooks
 
+pub mod audit;
 pub mod cli;
 pub mod client;
+pub mod cluster;
 pub mod config;
 pub mod error;
+pub mod history;
+pub mod logging;
+pub mod output;
+pub mod plan;
+pub mod rollout;
 pub mod server;
 pub mod service;
+mod util;
+pub mod webhooks;
 
 // Re-exports for convenience
 pub use cli::Cli;
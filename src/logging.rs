@@ -0,0 +1,57 @@
+//! Process-wide logging setup.
+//!
+//! The log level can be changed while the process runs, so that reloading
+//! the configuration also applies `logging.level`. A level given on the
+//! command line (`-v`, `-q`) takes precedence over the configuration.
+//! Logs go to stdout, or to stderr when stdout carries a structured result
+//! (`--output json` and the like).
+
+use crate::config::LogLevel;
+use crate::error::{Result, ShikiError};
+use std::sync::OnceLock;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, Registry};
+
+/// Handle to the level of the installed subscriber, and whether the level
+/// was fixed on the command line.
+static LEVEL: OnceLock<(reload::Handle<LevelFilter, Registry>, bool)> = OnceLock::new();
+
+/// Installs the global subscriber. `fixed` is the level given on the
+/// command line, if any; without it logging starts at `info`. With
+/// `stderr`, logs are written to stderr instead of stdout.
+pub fn init(fixed: Option<LogLevel>, stderr: bool) -> Result<()> {
+    let level = fixed.unwrap_or_default();
+    let (filter, handle) = reload::Layer::new(LevelFilter::from_level(level.into()));
+    let writer = if stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_target(true),
+        )
+        .try_init()
+        .map_err(|e| ShikiError::config(format!("Failed to initialize logging: {}", e)))?;
+    let _ = LEVEL.set((handle, fixed.is_some()));
+    Ok(())
+}
+
+/// Sets the log level from the configuration. Returns whether the level was
+/// applied: it is not when the command line fixed the level or logging was
+/// not initialized with [`init`].
+pub fn set_level(level: LogLevel) -> bool {
+    let Some((handle, false)) = LEVEL.get() else {
+        return false;
+    };
+    handle
+        .modify(|filter| *filter = LevelFilter::from_level(level.into()))
+        .is_ok()
+}
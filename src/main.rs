@@ -3,12 +3,20 @@
 //! Entry point for the shiki application.
 
 use clap::Parser;
-use shiki::cli::{Cli, Commands, ConfigCommands};
+use shiki::cli::{
+    AgentTargetArgs, AuditCommands, BarrierCommands, Cli, ClusterCommands, Commands,
+    ConfigCommands, HistoryArgs, LockCommands, LockRunArgs, PlanCommands, ReconcileArgs,
+    RolloutCommands, RunIfLeaderArgs, ServicesCommands, SignalCommands, WatchArgs,
+};
 use shiki::config::Config;
 use shiki::error::exit_code;
+use shiki::output::OutputFormat;
 use std::process::ExitCode;
-use tracing::Level;
-use tracing_subscriber::fmt::format::FmtSpan;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether a command printed its result in a structured format; a failure
+/// is then not printed again as an error document.
+static EMITTED: AtomicBool = AtomicBool::new(false);
 
 fn main() -> ExitCode {
     let cli = Cli::parse();
@@ -20,39 +28,55 @@ fn main() -> ExitCode {
     }
 
     // Execute the command
+    let output = cli.output;
     match run(cli) {
         Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
             tracing::error!("{}", e);
+            if output != OutputFormat::Text && !EMITTED.load(Ordering::Relaxed) {
+                let error = shiki::error::ErrorResponse::from_error(&e);
+                if let Ok(document) = shiki::output::render(output, &error) {
+                    print!("{}", document);
+                }
+            }
             ExitCode::from(e.exit_code() as u8)
         }
     }
 }
 
 /// Initialize the tracing subscriber based on CLI options.
-fn init_logging(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
-    let (level_str, _is_quiet) = cli.log_level();
-
-    let level = match level_str {
-        "trace" => Level::TRACE,
-        "debug" => Level::DEBUG,
-        "info" => Level::INFO,
-        "warn" => Level::WARN,
-        "error" => Level::ERROR,
-        _ => Level::INFO,
+fn init_logging(cli: &Cli) -> shiki::Result<()> {
+    // `-v` and `-q` fix the level; otherwise `serve` follows `logging.level`
+    let fixed = if cli.quiet || cli.verbose > 0 {
+        Some(cli.log_level().0.parse()?)
+    } else {
+        None
     };
 
     // For now, use text format by default in CLI
     // The JSON format will be configured from config file in serve mode
-    tracing_subscriber::fmt()
-        .with_max_level(level)
-        .with_span_events(FmtSpan::CLOSE)
-        .with_target(true)
-        .init();
+    shiki::logging::init(fixed, cli.output != OutputFormat::Text)
+}
 
+/// Prints the result of a command: `text` prints the human-readable output,
+/// the other formats print `data`.
+fn emit<T: serde::Serialize>(cli: &Cli, data: &T, text: impl FnOnce()) -> shiki::Result<()> {
+    match cli.output {
+        OutputFormat::Text => text(),
+        format => {
+            print!("{}", shiki::output::render(format, data)?);
+            EMITTED.store(true, Ordering::Relaxed);
+        }
+    }
     Ok(())
 }
 
+/// Whether the human-readable output is printed; progress and other
+/// messages printed before the result are only shown with it.
+fn is_text(cli: &Cli) -> bool {
+    cli.output == OutputFormat::Text
+}
+
 /// Main application logic.
 fn run(cli: Cli) -> shiki::Result<()> {
     match &cli.command {
@@ -61,6 +85,18 @@ fn run(cli: Cli) -> shiki::Result<()> {
         Commands::Wait(args) => cmd_wait(&cli, args),
         Commands::Status(args) => cmd_status(&cli, args),
         Commands::Config(subcmd) => cmd_config(&cli, subcmd),
+        Commands::Plan(subcmd) => cmd_plan(&cli, subcmd),
+        Commands::Rollout(subcmd) => cmd_rollout(&cli, subcmd),
+        Commands::Cluster(subcmd) => cmd_cluster(&cli, subcmd),
+        Commands::Signal(subcmd) => cmd_signal(&cli, subcmd),
+        Commands::Barrier(subcmd) => cmd_barrier(&cli, subcmd),
+        Commands::RunIfLeader(args) => cmd_run_if_leader(&cli, args),
+        Commands::Lock(subcmd) => cmd_lock(&cli, subcmd),
+        Commands::History(args) => cmd_history(&cli, args),
+        Commands::Audit(subcmd) => cmd_audit(&cli, subcmd),
+        Commands::Watch(args) => cmd_watch(&cli, args),
+        Commands::Reconcile(args) => cmd_reconcile(&cli, args),
+        Commands::Services(subcmd) => cmd_services(&cli, subcmd),
     }
 }
 
@@ -76,6 +112,8 @@ fn cmd_serve(cli: &Cli, args: &shiki::cli::ServeArgs) -> shiki::Result<()> {
     if args.port != 8080 {
         config.server.port = args.port;
     }
+    shiki::logging::set_level(config.logging.level);
+    shiki::config::log_warnings(&config.warnings);
 
     tracing::info!(
         agent_name = %config.agent_name(),
@@ -94,15 +132,21 @@ fn cmd_serve(cli: &Cli, args: &shiki::cli::ServeArgs) -> shiki::Result<()> {
 }
 
 /// Handle the `notify` command.
-fn cmd_notify(_cli: &Cli, args: &shiki::cli::NotifyArgs) -> shiki::Result<()> {
+fn cmd_notify(cli: &Cli, args: &shiki::cli::NotifyArgs) -> shiki::Result<()> {
     let service_action = match args.action {
         shiki::cli::ServiceAction::Start => shiki::service::ServiceAction::Start,
         shiki::cli::ServiceAction::Stop => shiki::service::ServiceAction::Stop,
         shiki::cli::ServiceAction::Restart => shiki::service::ServiceAction::Restart,
     };
 
+    if let Some(selector) = &args.selector {
+        return cmd_notify_selector(cli, args, selector, service_action);
+    }
+    let target = args.target.as_deref().unwrap_or_default();
+
     tracing::info!(
-        target = %args.target,
+        target = %target,
+        via = ?args.via,
         action = %args.action,
         service = %args.service,
         wait = %args.should_wait(),
@@ -114,43 +158,196 @@ fn cmd_notify(_cli: &Cli, args: &shiki::cli::NotifyArgs) -> shiki::Result<()> {
     })?;
 
     runtime.block_on(async {
-        let client = shiki::ShikiClient::new(&args.target)?;
+        let mut client = match &args.via {
+            Some(via) => shiki::ShikiClient::new(via)?.via(args.route()),
+            None => shiki::ShikiClient::new(target)?,
+        };
+        if let Some(token) = &args.token {
+            client = client.with_token(token);
+        }
+        let options = shiki::server::response::NotifyOptions {
+            wait: args.should_wait(),
+            timeout_seconds: args.timeout,
+            wait_ready: args.wait_ready,
+        };
         let result = client
-            .notify(
-                &args.service,
-                service_action,
-                args.should_wait(),
-                args.timeout,
-            )
+            .notify_with_options(&args.service, service_action, options)
             .await?;
 
-        println!("Request ID: {}", result.request_id);
-        println!("Service: {}", result.service);
-        println!("Action: {}", result.action);
-        println!("Result: {}", result.result);
+        emit(cli, &result, || {
+            println!("Request ID: {}", result.request_id);
+            println!("Service: {}", result.service);
+            println!("Action: {}", result.action);
+            println!("Result: {}", result.result);
 
-        if let Some(prev) = &result.previous_status {
-            println!("Previous Status: {}", prev);
-        }
-        if let Some(curr) = &result.current_status {
-            println!("Current Status: {}", curr);
-        }
-        if let Some(dur) = result.duration_ms {
-            println!("Duration: {}ms", dur);
-        }
-        if let Some(msg) = &result.message {
-            println!("Message: {}", msg);
+            if let Some(prev) = &result.previous_status {
+                println!("Previous Status: {}", prev);
+            }
+            if let Some(curr) = &result.current_status {
+                println!("Current Status: {}", curr);
+            }
+            if let Some(dur) = result.duration_ms {
+                println!("Duration: {}ms", dur);
+            }
+            if let Some(msg) = &result.message {
+                println!("Message: {}", msg);
+            }
+        })
+    })
+}
+
+/// Handle `notify --selector`: notifies every matching agent and prints
+/// one row per agent.
+///
+/// With `--target`, that agent resolves the selector against its own peer
+/// list and performs the fan-out; otherwise the peers of the local
+/// configuration (and the local agent itself) are used.
+fn cmd_notify_selector(
+    cli: &Cli,
+    args: &shiki::cli::NotifyArgs,
+    selector: &shiki::cluster::Selector,
+    action: shiki::service::ServiceAction,
+) -> shiki::Result<()> {
+    use shiki::client::FanoutLimits;
+    use shiki::server::response::{
+        FanoutOptions, FanoutResponseData, FanoutStatus, FanoutSummary, NotifyOptions,
+    };
+
+    let options = NotifyOptions {
+        wait: args.should_wait(),
+        timeout_seconds: args.timeout,
+        wait_ready: args.wait_ready,
+    };
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    let data = match &args.target {
+        Some(target) => runtime.block_on(async {
+            let mut client = shiki::ShikiClient::new(target)?;
+            if let Some(token) = &args.token {
+                client = client.with_token(token);
+            }
+            let fanout = FanoutOptions {
+                parallelism: args.parallelism,
+                max_failures: args.max_failures,
+            };
+            client
+                .cluster_notify(selector, &args.service, action, options, fanout)
+                .await
+        })?,
+        None => {
+            let config = load_config(cli)?;
+            let targets = selector_targets(&config, selector, args.token.as_deref())?;
+
+            let limits = FanoutLimits {
+                parallelism: args
+                    .parallelism
+                    .unwrap_or(config.cluster.fanout.parallelism),
+                max_failures: args
+                    .max_failures
+                    .unwrap_or(config.cluster.fanout.max_failures),
+            };
+            let results = runtime.block_on(shiki::client::notify_many(
+                &targets,
+                &args.service,
+                action,
+                options,
+                limits,
+            ));
+            FanoutResponseData {
+                selector: selector.to_string(),
+                service: args.service.clone(),
+                action: action.to_string(),
+                summary: FanoutSummary::from_results(&results),
+                results,
+            }
         }
+    };
+    let results = &data.results;
+
+    let rows: Vec<[String; 5]> = results
+        .iter()
+        .map(|r| {
+            [
+                r.agent.clone(),
+                r.address.clone(),
+                r.status.to_string(),
+                r.duration_ms
+                    .map(|ms| format!("{}ms", ms))
+                    .unwrap_or_else(|| "-".to_string()),
+                r.error
+                    .clone()
+                    .or_else(|| r.result.as_ref().and_then(|d| d.current_status.clone()))
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+    emit(cli, &data, || {
+        print_table(&["AGENT", "ADDRESS", "RESULT", "DURATION", "DETAIL"], &rows);
 
+        let summary = &data.summary;
+        println!();
+        println!(
+            "{} agents: {} succeeded, {} failed, {} skipped",
+            summary.total, summary.succeeded, summary.failed, summary.skipped
+        );
+    })?;
+
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| r.status != FanoutStatus::Succeeded)
+        .map(|r| r.agent.as_str())
+        .collect();
+    if failed.is_empty() {
         Ok(())
-    })
+    } else {
+        Err(shiki::ShikiError::backend(format!(
+            "Notification did not succeed on: {}",
+            failed.join(", ")
+        )))
+    }
+}
+
+/// Resolves a selector against the local agent and the peers of the local
+/// configuration. Peers without a token of their own use `token`.
+fn selector_targets(
+    config: &Config,
+    selector: &shiki::cluster::Selector,
+    token: Option<&str>,
+) -> shiki::Result<Vec<shiki::client::FanoutTarget>> {
+    use shiki::client::FanoutTarget;
+
+    let local = FanoutTarget {
+        token: token.map(str::to_string),
+        ..FanoutTarget::new(config.agent_name(), local_agent_address(config))
+    };
+    let candidates = std::iter::once((local, config.agent.tags.as_slice())).chain(
+        config.cluster.peers.iter().map(|p| {
+            let target = FanoutTarget {
+                token: p.token.clone().or_else(|| token.map(str::to_string)),
+                ..FanoutTarget::new(p.name.clone(), p.address.clone())
+            };
+            (target, p.tags.as_slice())
+        }),
+    );
+    let targets = shiki::client::fanout::select_targets(selector, candidates);
+    if targets.is_empty() {
+        return Err(shiki::ShikiError::invalid_request(format!(
+            "No agents match selector '{}'",
+            selector
+        )));
+    }
+    Ok(targets)
 }
 
 /// Handle the `wait` command.
-fn cmd_wait(_cli: &Cli, args: &shiki::cli::WaitArgs) -> shiki::Result<()> {
+fn cmd_wait(cli: &Cli, args: &shiki::cli::WaitArgs) -> shiki::Result<()> {
     tracing::info!(
         target = %args.target,
         service = %args.service,
+        until = %args.until,
         timeout = %args.timeout,
         interval = %args.interval,
         "Waiting for service"
@@ -165,15 +362,22 @@ fn cmd_wait(_cli: &Cli, args: &shiki::cli::WaitArgs) -> shiki::Result<()> {
         let timeout = std::time::Duration::from_secs(args.timeout);
         let interval = std::time::Duration::from_secs(args.interval);
 
-        // Default to waiting for "running" status
-        let target_status = "running";
-
-        client
-            .wait_for_service(&args.service, target_status, timeout, interval)
-            .await?;
+        let service = match args.until {
+            shiki::cli::WaitCondition::Ready => {
+                client
+                    .wait_for_ready(&args.service, timeout, interval)
+                    .await?
+            }
+            condition => {
+                client
+                    .wait_for_service(&args.service, &condition.to_string(), timeout, interval)
+                    .await?
+            }
+        };
 
-        println!("Service '{}' is now {}", args.service, target_status);
-        Ok(())
+        emit(cli, &service, || {
+            println!("Service '{}' is now {}", args.service, args.until)
+        })
     })
 }
 
@@ -191,32 +395,37 @@ fn cmd_status(cli: &Cli, args: &shiki::cli::StatusArgs) -> shiki::Result<()> {
             let client = shiki::ShikiClient::new(target)?;
             let status = client.status().await?;
 
-            println!("Remote Agent Status");
-            println!("===================");
-            println!("Name: {}", status.agent.name);
-            println!("State: {:?}", status.agent.state);
-            println!("Mode: {}", status.agent.mode);
-            println!("Server: {}:{}", status.server.bind, status.server.port);
-            println!(
-                "TLS: {}",
-                if status.server.tls_enabled {
-                    "enabled"
-                } else {
-                    "disabled"
-                }
-            );
-            println!("Version: {}", status.version);
-            println!("Uptime: {}s", status.uptime_seconds);
-            println!("\nStatistics:");
-            println!("  Total Requests: {}", status.stats.requests_total);
-            println!("  Successful: {}", status.stats.requests_success);
-            println!("  Failed: {}", status.stats.requests_failed);
-
-            if !status.agent.tags.is_empty() {
-                println!("\nTags: {}", status.agent.tags.join(", "));
-            }
+            emit(cli, &status, || {
+                println!("Remote Agent Status");
+                println!("===================");
+                println!("Name: {}", status.agent.name);
+                println!("State: {:?}", status.agent.state);
+                println!("Mode: {}", status.agent.mode);
+                println!("Server: {}:{}", status.server.bind, status.server.port);
+                println!(
+                    "TLS: {}",
+                    if status.server.tls_enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                );
+                println!("Version: {}", status.version);
+                println!("Uptime: {}s", status.uptime_seconds);
+                println!(
+                    "Config: generation {} ({})",
+                    status.config.generation,
+                    status.config.hash.get(..12).unwrap_or(&status.config.hash)
+                );
+                println!("\nStatistics:");
+                println!("  Total Requests: {}", status.stats.requests_total);
+                println!("  Successful: {}", status.stats.requests_success);
+                println!("  Failed: {}", status.stats.requests_failed);
 
-            Ok(())
+                if !status.agent.tags.is_empty() {
+                    println!("\nTags: {}", status.agent.tags.join(", "));
+                }
+            })
         })
     } else if let Some(service) = &args.service {
         // Local service status check
@@ -230,81 +439,1287 @@ fn cmd_status(cli: &Cli, args: &shiki::cli::StatusArgs) -> shiki::Result<()> {
         runtime.block_on(async {
             let controller = shiki::ServiceController::from_config(&config)?;
             let status = controller.status(service).await?;
+            let data = shiki::server::response::ServiceDetailData {
+                name: status.name.clone(),
+                status: status.state.to_string(),
+                description: status.description.clone(),
+                ready: None,
+                readiness: None,
+            };
 
-            println!("Service: {}", status.name);
-            println!("Status: {}", status.state);
-            if let Some(desc) = &status.description {
-                println!("Description: {}", desc);
-            }
-
-            Ok(())
+            emit(cli, &data, || {
+                println!("Service: {}", status.name);
+                println!("Status: {}", status.state);
+                if let Some(desc) = &status.description {
+                    println!("Description: {}", desc);
+                }
+            })
         })
     } else {
         // Local agent status
         let config = load_config(cli)?;
-        println!("Agent Status");
-        println!("============");
-        println!("Name: {}", config.agent_name());
-        println!("Mode: {:?}", config.agent.mode);
-        println!("Backend: {:?}", config.agent.backend);
-        println!("Server: {}:{}", config.server.bind, config.server.port);
-        println!(
-            "TLS: {}",
-            if config.server.tls.enabled {
-                "enabled"
-            } else {
-                "disabled"
-            }
-        );
-        println!(
-            "Auth: {}",
-            if config.auth.enabled {
-                "enabled"
-            } else {
-                "disabled"
-            }
-        );
-
-        if !config.agent.tags.is_empty() {
-            println!("Tags: {}", config.agent.tags.join(", "));
-        }
+        let mut services: Vec<String> = config.services.keys().cloned().collect();
+        services.sort();
+        let data = shiki::output::LocalStatusData {
+            name: config.agent_name(),
+            mode: config.agent.mode,
+            backend: config.agent.backend,
+            server: shiki::server::response::ServerInfo {
+                bind: config.server.bind.clone(),
+                port: config.server.port,
+                tls_enabled: config.server.tls.enabled,
+            },
+            auth_enabled: config.auth.enabled,
+            tags: config.agent.tags.clone(),
+            services,
+        };
+        emit(cli, &data, || {
+            println!("Agent Status");
+            println!("============");
+            println!("Name: {}", config.agent_name());
+            println!("Mode: {:?}", config.agent.mode);
+            println!("Backend: {:?}", config.agent.backend);
+            println!("Server: {}:{}", config.server.bind, config.server.port);
+            println!(
+                "TLS: {}",
+                if config.server.tls.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+            println!(
+                "Auth: {}",
+                if config.auth.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
 
-        if !config.services.is_empty() {
-            println!("\nConfigured Services:");
-            for name in config.services.keys() {
-                println!("  - {}", name);
+            if !config.agent.tags.is_empty() {
+                println!("Tags: {}", config.agent.tags.join(", "));
             }
-        }
 
-        Ok(())
+            if !config.services.is_empty() {
+                println!("\nConfigured Services:");
+                for name in config.services.keys() {
+                    println!("  - {}", name);
+                }
+            }
+        })
     }
 }
 
 /// Handle the `config` subcommand.
 fn cmd_config(cli: &Cli, subcmd: &ConfigCommands) -> shiki::Result<()> {
     match subcmd {
-        ConfigCommands::Validate => {
+        ConfigCommands::Validate(args) => {
             let config_path = cli.config.as_deref();
-            match Config::load(config_path) {
+            let loaded = if args.no_strict {
+                Config::load(config_path)
+            } else {
+                Config::load_strict(config_path)
+            };
+            match loaded {
                 Ok(config) => {
-                    println!("✓ Configuration is valid");
                     tracing::debug!(?config, "Validated configuration");
-                    Ok(())
+                    let data = shiki::output::ConfigValidationData {
+                        valid: true,
+                        files: config
+                            .source
+                            .iter()
+                            .chain(&config.includes)
+                            .map(|file| file.display().to_string())
+                            .collect(),
+                        warnings: config.warnings.clone(),
+                    };
+                    emit(cli, &data, || {
+                        println!("✓ Configuration is valid");
+                        for file in &config.includes {
+                            println!("  + {}", file.display());
+                        }
+                        for warning in &config.warnings {
+                            println!("⚠ {}", warning);
+                        }
+                    })
                 }
                 Err(e) => {
-                    println!("✗ Configuration is invalid: {}", e);
+                    if is_text(cli) {
+                        println!("✗ Configuration is invalid: {}", e);
+                    }
                     Err(e)
                 }
             }
         }
         ConfigCommands::Show => {
             let config = load_config(cli)?;
-            let yaml = serde_yaml::to_string(&config).map_err(|e| {
+            // Secrets are redacted
+            let redacted = config.redacted();
+            let yaml = serde_yaml::to_string(&redacted).map_err(|e| {
                 shiki::ShikiError::config_with_source("Failed to serialize configuration", e)
             })?;
-            println!("{}", yaml);
+            emit(cli, &redacted, || {
+                // Merged from the main file, its includes and the drop-in files
+                for file in config.source.iter().chain(&config.includes) {
+                    println!("# {}", file.display());
+                }
+                println!("{}", yaml);
+            })
+        }
+        ConfigCommands::Schema => {
+            let schema = shiki::config::json_schema();
+            emit(cli, &schema, || {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&schema).unwrap_or_default()
+                )
+            })
+        }
+        ConfigCommands::Init(args) => {
+            let path = args
+                .path
+                .clone()
+                .or_else(|| cli.config.clone())
+                .unwrap_or_else(|| std::path::PathBuf::from("config.yaml"));
+            if path.exists() && !args.force {
+                return Err(shiki::ShikiError::config(format!(
+                    "{} already exists; use --force to overwrite it",
+                    path.display()
+                )));
+            }
+            let backend = args.backend.unwrap_or_else(shiki::config::detect_backend);
+            let name = Config::default().agent_name();
+            let schema = path.with_file_name("config.schema.json");
+            let content = shiki::config::starter_config(&name, backend, &schema);
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&path, content)?;
+
+            let data = shiki::output::ConfigInitData {
+                path: path.display().to_string(),
+                name,
+                backend,
+            };
+            emit(cli, &data, || {
+                println!(
+                    "✓ Wrote {} (agent {}, backend {})",
+                    path.display(),
+                    data.name,
+                    backend
+                );
+                println!(
+                    "  Editor schema: shiki config schema > {}",
+                    schema.display()
+                );
+                println!(
+                    "  Check it with: shiki config validate -c {}",
+                    path.display()
+                );
+            })
+        }
+    }
+}
+
+/// Handle the `audit` subcommand.
+fn cmd_audit(cli: &Cli, subcmd: &AuditCommands) -> shiki::Result<()> {
+    match subcmd {
+        AuditCommands::Verify(args) => {
+            let verification = shiki::audit::verify(&args.file)?;
+            emit(cli, &verification, || match &verification {
+                shiki::audit::Verification::Intact { records } => {
+                    println!("✓ Audit log is intact: {} records", records)
+                }
+                shiki::audit::Verification::Broken { line, reason } => {
+                    println!("✗ Audit log is broken at line {}: {}", line, reason)
+                }
+            })?;
+            match verification {
+                shiki::audit::Verification::Intact { .. } => Ok(()),
+                shiki::audit::Verification::Broken { .. } => Err(shiki::ShikiError::backend(
+                    format!("Audit log {} failed verification", args.file.display()),
+                )),
+            }
+        }
+    }
+}
+
+/// Handle the `plan` subcommand.
+fn cmd_plan(cli: &Cli, subcmd: &PlanCommands) -> shiki::Result<()> {
+    match subcmd {
+        PlanCommands::Validate(args) => {
+            let plan = shiki::plan::Plan::load(&args.file)?;
+            let data = shiki::output::PlanValidationData {
+                valid: true,
+                steps: plan.steps.len(),
+                stages: plan.stages(),
+            };
+            emit(cli, &data, || {
+                println!(
+                    "✓ Plan is valid: {} steps in {} stages",
+                    data.steps,
+                    data.stages.len()
+                );
+                for (i, stage) in data.stages.iter().enumerate() {
+                    println!("  {}. {}", i + 1, stage.join(", "));
+                }
+            })
+        }
+        PlanCommands::Graph(args) => {
+            // DOT is printed in every output format
+            let plan = shiki::plan::Plan::load(&args.file)?;
+            print!("{}", plan.to_dot());
             Ok(())
         }
+        PlanCommands::Apply(args) => {
+            let mut plan = shiki::plan::Plan::load(&args.file)?;
+            if let Some(policy) = args.on_failure {
+                plan.on_failure = policy;
+            }
+
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+                shiki::ShikiError::backend_with_source(
+                    "Failed to create async runtime".to_string(),
+                    e,
+                )
+            })?;
+
+            let report = runtime.block_on(async {
+                let executor = shiki::plan::PlanExecutor::new(
+                    plan,
+                    std::sync::Arc::new(shiki::plan::ClientRunner),
+                );
+                if !is_text(cli) {
+                    return executor.apply(|_| {}).await;
+                }
+                let mut printer = ProgressPrinter::new();
+                let report = executor.apply(|steps| printer.update(steps)).await;
+                printer.finish(&report.steps);
+                report
+            });
+
+            emit(cli, &report, || {
+                println!(
+                    "\nPlan {} in {:.1}s{}",
+                    if report.success {
+                        "completed"
+                    } else {
+                        "failed"
+                    },
+                    report.duration_ms as f64 / 1000.0,
+                    if report.rolled_back {
+                        " (rolled back)"
+                    } else {
+                        ""
+                    }
+                )
+            })?;
+
+            if report.success {
+                Ok(())
+            } else {
+                let failed: Vec<&str> = report
+                    .failed_steps()
+                    .iter()
+                    .map(|s| s.id.as_str())
+                    .collect();
+                Err(shiki::ShikiError::backend(format!(
+                    "Plan failed at step(s): {}",
+                    failed.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+/// Handle the `rollout` subcommand.
+fn cmd_rollout(cli: &Cli, subcmd: &RolloutCommands) -> shiki::Result<()> {
+    use shiki::rollout::{
+        render_table, AgentStatus, ClientRestarter, RolloutExecutor, RolloutLimits, RolloutRecord,
+        RolloutStatus,
+    };
+
+    match subcmd {
+        RolloutCommands::Status(args) => {
+            let path = args.state_file();
+            let record = RolloutRecord::load(&path)?;
+            emit(cli, &record, || {
+                println!(
+                    "Rollout of {} on '{}': {} (started {}, updated {})",
+                    record.service,
+                    record.selector,
+                    record.status,
+                    record.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    record.updated_at.format("%Y-%m-%d %H:%M:%S")
+                );
+                print!("{}", render_table(&record.agents));
+            })
+        }
+        RolloutCommands::Restart(args) => {
+            let path = args.state_file();
+            let config = load_config(cli).ok();
+            let mut record = if args.resume {
+                let record = RolloutRecord::load(&path)?;
+                if record.service != args.service {
+                    return Err(shiki::ShikiError::invalid_request(format!(
+                        "{} records a rollout of {}, not {}",
+                        path.display(),
+                        record.service,
+                        args.service
+                    )));
+                }
+                record
+            } else {
+                if let Ok(existing) = RolloutRecord::load(&path) {
+                    if existing.is_resumable() {
+                        return Err(shiki::ShikiError::invalid_request(format!(
+                            "An unfinished rollout of {} is recorded in {}; continue it with --resume or remove the file",
+                            existing.service,
+                            path.display()
+                        )));
+                    }
+                }
+                let config = config.as_ref().ok_or_else(|| {
+                    shiki::ShikiError::config("A configuration file is needed to select agents")
+                })?;
+                let selector = args
+                    .selector
+                    .as_ref()
+                    .ok_or_else(|| shiki::ShikiError::invalid_request("--selector is required"))?;
+                let targets = selector_targets(config, selector, None)?;
+                RolloutRecord::new(&args.service, selector.as_str(), &targets)
+            };
+
+            // Tokens are looked up by agent name, also for resumed rollouts
+            let mut tokens = std::collections::HashMap::new();
+            for agent in &record.agents {
+                let token = config
+                    .as_ref()
+                    .and_then(|c| c.cluster.peers.iter().find(|p| p.name == agent.name))
+                    .and_then(|p| p.token.clone())
+                    .or_else(|| args.token.clone());
+                if let Some(token) = token {
+                    tokens.insert(agent.name.clone(), token);
+                }
+            }
+
+            if is_text(cli) {
+                println!(
+                    "Rolling restart of {} on {} agent(s) ({} pending; batch {}, max unavailable {})",
+                    record.service,
+                    record.agents.len(),
+                    record.agents.len() - record.count(AgentStatus::Succeeded),
+                    args.batch,
+                    args.max_unavailable
+                );
+            }
+            let limits = RolloutLimits {
+                batch: args.batch,
+                max_unavailable: args.max_unavailable,
+                max_failures: args.max_failures,
+                on_failure: args.on_failure,
+                timeout_seconds: args.timeout,
+            };
+            let executor =
+                RolloutExecutor::new(limits, std::sync::Arc::new(ClientRestarter::new(tokens)));
+
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+                shiki::ShikiError::backend_with_source(
+                    "Failed to create async runtime".to_string(),
+                    e,
+                )
+            })?;
+            // Print one line per agent status change
+            let mut printed: Vec<AgentStatus> = Vec::new();
+            let text = is_text(cli);
+            runtime.block_on(executor.run(&mut record, |record| {
+                record.save(&path)?;
+                if !text {
+                    return Ok(());
+                }
+                for (i, agent) in record.agents.iter().enumerate() {
+                    let changed = printed.get(i) != Some(&agent.status);
+                    if changed && agent.status != AgentStatus::Pending {
+                        match &agent.message {
+                            Some(message) => {
+                                println!("  {}: {} ({})", agent.name, agent.status, message)
+                            }
+                            None => println!("  {}: {}", agent.name, agent.status),
+                        }
+                    }
+                }
+                printed = record.agents.iter().map(|a| a.status).collect();
+                Ok(())
+            }))?;
+
+            let failed = record.count(AgentStatus::Failed);
+            emit(cli, &record, || {
+                println!();
+                print!("{}", render_table(&record.agents));
+                if record.status == RolloutStatus::Completed && failed == 0 {
+                    println!("\nRollout completed");
+                }
+            })?;
+            match record.status {
+                RolloutStatus::Completed if failed == 0 => Ok(()),
+                RolloutStatus::Completed => Err(shiki::ShikiError::backend(format!(
+                    "Rollout completed with {} failed agent(s)",
+                    failed
+                ))),
+                RolloutStatus::Paused => Err(shiki::ShikiError::backend(format!(
+                    "Rollout paused after {} failed agent(s); continue with --resume (progress: {})",
+                    failed,
+                    path.display()
+                ))),
+                _ => Err(shiki::ShikiError::backend(format!(
+                    "Rollout {} after {} failed agent(s)",
+                    record.status, failed
+                ))),
+            }
+        }
+    }
+}
+
+/// Handle the `cluster` subcommand.
+fn cmd_cluster(cli: &Cli, subcmd: &ClusterCommands) -> shiki::Result<()> {
+    match subcmd {
+        ClusterCommands::Status(args) => {
+            let target = match &args.target {
+                Some(target) => target.clone(),
+                None => local_agent_address(&load_config(cli)?),
+            };
+
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+                shiki::ShikiError::backend_with_source(
+                    "Failed to create async runtime".to_string(),
+                    e,
+                )
+            })?;
+
+            runtime.block_on(async {
+                let client = shiki::ShikiClient::new(&target)?;
+                let cluster = client.cluster().await?;
+
+                emit(cli, &cluster, || {
+                    if !cluster.enabled {
+                        println!(
+                            "Cluster mode is not enabled on {} ({})",
+                            cluster.local.name, target
+                        );
+                    }
+
+                    let mut rows = vec![[
+                        cluster.local.name.clone(),
+                        cluster.local.address.clone(),
+                        "self".to_string(),
+                        "-".to_string(),
+                        cluster.local.version.clone(),
+                        "-".to_string(),
+                        "-".to_string(),
+                        cluster.local.tags.join(","),
+                    ]];
+                    for peer in &cluster.peers {
+                        rows.push([
+                            peer.name.clone(),
+                            peer.address.clone(),
+                            match (peer.member_state, peer.reachable) {
+                                (Some(state), _) => state.to_string(),
+                                (None, true) => "reachable".to_string(),
+                                (None, false) => "unreachable".to_string(),
+                            },
+                            peer.source.to_string(),
+                            peer.version.clone().unwrap_or_else(|| "-".to_string()),
+                            peer.latency_ms
+                                .map(|ms| format!("{}ms", ms))
+                                .unwrap_or_else(|| "-".to_string()),
+                            peer.last_seen
+                                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                                .unwrap_or_else(|| "never".to_string()),
+                            peer.tags.join(","),
+                        ]);
+                    }
+
+                    print_table(
+                        &[
+                            "NAME",
+                            "ADDRESS",
+                            "STATUS",
+                            "SOURCE",
+                            "VERSION",
+                            "LATENCY",
+                            "LAST SEEN",
+                            "TAGS",
+                        ],
+                        &rows,
+                    );
+                    println!(
+                        "\n{} peers: {} reachable, {} unreachable",
+                        cluster.summary.total,
+                        cluster.summary.reachable,
+                        cluster.summary.unreachable
+                    );
+
+                    for peer in cluster.peers.iter().filter(|p| !p.reachable) {
+                        if let Some(err) = &peer.last_error {
+                            println!("  {}: {}", peer.name, err);
+                        }
+                    }
+                })
+            })
+        }
+    }
+}
+
+/// Returns a client for `target`, or for the local agent.
+fn agent_client(
+    cli: &Cli,
+    target: Option<&str>,
+    token: Option<&str>,
+) -> shiki::Result<shiki::ShikiClient> {
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => local_agent_address(&load_config(cli)?),
+    };
+    let client = shiki::ShikiClient::new(&target)?;
+    Ok(match token {
+        Some(token) => client.with_token(token),
+        None => client,
+    })
+}
+
+/// Returns a client for the agent selected by `args`.
+fn target_client(cli: &Cli, args: &AgentTargetArgs) -> shiki::Result<shiki::ShikiClient> {
+    agent_client(cli, args.target.as_deref(), args.token.as_deref())
+}
+
+/// Formats the signalers of a signal.
+fn signers(signal: &shiki::server::response::SignalData) -> String {
+    signal
+        .signers
+        .iter()
+        .map(|s| s.agent.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Handle the `signal` command.
+fn cmd_signal(cli: &Cli, subcmd: &SignalCommands) -> shiki::Result<()> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    match subcmd {
+        SignalCommands::Set(args) => {
+            let client = target_client(cli, &args.agent)?;
+            // Signal as the local agent when a configuration is available
+            let id = args
+                .id
+                .clone()
+                .or_else(|| load_config(cli).ok().map(|c| c.agent_name()));
+            let signal =
+                runtime.block_on(client.set_signal(&args.name, id.as_deref(), args.ttl))?;
+            emit(cli, &signal, || {
+                println!(
+                    "Signal '{}' set ({} signaler(s): {})",
+                    signal.name,
+                    signal.count,
+                    signers(&signal)
+                )
+            })?;
+        }
+        SignalCommands::Clear(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let signal = runtime.block_on(client.clear_signal(&args.name, args.id.as_deref()))?;
+            emit(cli, &signal, || {
+                println!(
+                    "Signal '{}' cleared ({} signaler(s) left)",
+                    signal.name, signal.count
+                )
+            })?;
+        }
+        SignalCommands::List(args) => {
+            let client = target_client(cli, args)?;
+            let signals = runtime.block_on(client.signals())?;
+            if signals.is_empty() {
+                return emit(cli, &signals, || println!("No signals set"));
+            }
+            let rows: Vec<[String; 4]> = signals
+                .iter()
+                .map(|signal| {
+                    let next_expiry = signal
+                        .signers
+                        .iter()
+                        .map(|s| s.expires_at)
+                        .min()
+                        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "-".to_string());
+                    [
+                        signal.name.clone(),
+                        signal.count.to_string(),
+                        signers(signal),
+                        next_expiry,
+                    ]
+                })
+                .collect();
+            emit(cli, &signals, || {
+                print_table(&["NAME", "COUNT", "SIGNALERS", "NEXT EXPIRY"], &rows)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Handle the `barrier` command.
+fn cmd_barrier(cli: &Cli, subcmd: &BarrierCommands) -> shiki::Result<()> {
+    match subcmd {
+        BarrierCommands::Wait(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+                shiki::ShikiError::backend_with_source(
+                    "Failed to create async runtime".to_string(),
+                    e,
+                )
+            })?;
+
+            if is_text(cli) {
+                println!(
+                    "Waiting for {} signaler(s) of '{}' (timeout: {}s)",
+                    args.count, args.name, args.timeout
+                );
+            }
+            let barrier = runtime.block_on(client.wait_barrier(
+                &args.name,
+                args.count,
+                std::time::Duration::from_secs(args.timeout),
+            ))?;
+            emit(cli, &barrier, || {
+                println!(
+                    "Barrier '{}' reached after {}ms ({}/{}: {})",
+                    barrier.name,
+                    barrier.waited_ms,
+                    barrier.signal.count,
+                    barrier.required,
+                    signers(&barrier.signal)
+                )
+            })
+        }
+    }
+}
+
+/// Handle the `run-if-leader` command.
+fn cmd_run_if_leader(cli: &Cli, args: &RunIfLeaderArgs) -> shiki::Result<()> {
+    let client = agent_client(cli, args.target.as_deref(), args.token.as_deref())?;
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    // Agents started together need a moment to elect a leader
+    let leader = runtime.block_on(async {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(args.wait);
+        loop {
+            let leader = client.leader().await?;
+            if !leader.enabled {
+                return Err(shiki::ShikiError::config(format!(
+                    "Leader election is not enabled on {}",
+                    leader.agent
+                )));
+            }
+            if leader.leader.is_some() {
+                return Ok(leader);
+            }
+            if std::time::Instant::now() >= deadline {
+                return Err(shiki::ShikiError::Timeout {
+                    operation: "leader election".to_string(),
+                    seconds: args.wait,
+                });
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        }
+    })?;
+
+    let leader_name = leader.leader.as_deref().unwrap_or("-");
+    if !leader.is_leader {
+        return emit(cli, &leader, || {
+            println!(
+                "{} is not the leader (leader: {}, term {}); skipping",
+                leader.agent, leader_name, leader.term
+            )
+        });
+    }
+
+    // The command's own output follows on stdout
+    if is_text(cli) {
+        println!(
+            "{} is the leader (term {}); running: {}",
+            leader.agent,
+            leader.term,
+            args.command.join(" ")
+        );
+    }
+    let status = std::process::Command::new(&args.command[0])
+        .args(&args.command[1..])
+        .env("SHIKI_LEADER", leader_name)
+        .env("SHIKI_LEADER_TERM", leader.term.to_string())
+        .status()
+        .map_err(|e| {
+            shiki::ShikiError::backend_with_source(
+                format!("Failed to run '{}'", args.command[0]),
+                e,
+            )
+        })?;
+    if !status.success() {
+        // Exit with the command's own exit code
+        std::process::exit(status.code().unwrap_or(exit_code::GENERAL_ERROR));
+    }
+    Ok(())
+}
+
+/// Handle the `lock` command.
+fn cmd_lock(cli: &Cli, subcmd: &LockCommands) -> shiki::Result<()> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    match subcmd {
+        LockCommands::Acquire(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let lock = runtime.block_on(client.acquire_lock(
+                &args.name,
+                args.owner.as_deref(),
+                args.ttl,
+                Some(args.wait),
+            ))?;
+            emit(cli, &lock, || {
+                // The token alone goes to stdout, for `TOKEN=$(shiki lock acquire ...)`
+                eprintln!(
+                    "Lock '{}' acquired by {} until {}",
+                    lock.name,
+                    lock.owner,
+                    lock.expires_at.format("%Y-%m-%d %H:%M:%S")
+                );
+                println!("{}", lock.fencing_token);
+            })?;
+        }
+        LockCommands::Release(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let released = runtime.block_on(client.release_lock(&args.name, args.fencing_token))?;
+            emit(cli, &released, || {
+                if released.released {
+                    println!("Lock '{}' released", released.name);
+                } else {
+                    println!("Lock '{}' was not held", released.name);
+                }
+            })?;
+        }
+        LockCommands::Renew(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let lock =
+                runtime.block_on(client.renew_lock(&args.name, args.fencing_token, args.ttl))?;
+            emit(cli, &lock, || {
+                println!(
+                    "Lock '{}' renewed until {}",
+                    lock.name,
+                    lock.expires_at.format("%Y-%m-%d %H:%M:%S")
+                )
+            })?;
+        }
+        LockCommands::List(args) => {
+            let client = target_client(cli, args)?;
+            let locks = runtime.block_on(client.locks())?;
+            if locks.is_empty() {
+                return emit(cli, &locks, || println!("No locks held"));
+            }
+            let rows: Vec<[String; 4]> = locks
+                .iter()
+                .map(|lock| {
+                    [
+                        lock.name.clone(),
+                        lock.owner.clone(),
+                        lock.fencing_token.to_string(),
+                        lock.expires_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    ]
+                })
+                .collect();
+            emit(cli, &locks, || {
+                print_table(&["NAME", "OWNER", "FENCING TOKEN", "EXPIRES"], &rows)
+            })?;
+        }
+        LockCommands::Run(args) => {
+            let code = runtime.block_on(run_with_lock(cli, args))?;
+            if code != 0 {
+                // Exit with the command's own exit code
+                std::process::exit(code);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs a command while holding a lock and returns its exit code. The lock
+/// is renewed every third of its TTL; if it is lost, the command is killed.
+async fn run_with_lock(cli: &Cli, args: &LockRunArgs) -> shiki::Result<i32> {
+    let client = target_client(cli, &args.lock.agent)?;
+    // Tell concurrent runs on the same host apart
+    let owner = args.lock.owner.clone().or_else(|| {
+        load_config(cli)
+            .ok()
+            .map(|c| format!("{}:{}", c.agent_name(), std::process::id()))
+    });
+    let lock = client
+        .acquire_lock(
+            &args.lock.name,
+            owner.as_deref(),
+            args.lock.ttl,
+            Some(args.lock.wait),
+        )
+        .await?;
+    let ttl = (lock.expires_at - lock.acquired_at)
+        .to_std()
+        .unwrap_or_default();
+    // The command's own output follows on stdout
+    if is_text(cli) {
+        println!(
+            "Lock '{}' acquired by {} (fencing token {}); running: {}",
+            lock.name,
+            lock.owner,
+            lock.fencing_token,
+            args.command.join(" ")
+        );
+    }
+
+    let mut child = tokio::process::Command::new(&args.command[0])
+        .args(&args.command[1..])
+        .env("SHIKI_LOCK_NAME", &lock.name)
+        .env("SHIKI_LOCK_TOKEN", lock.fencing_token.to_string())
+        .spawn()
+        .map_err(|e| {
+            shiki::ShikiError::backend_with_source(
+                format!("Failed to run '{}'", args.command[0]),
+                e,
+            )
+        })?;
+
+    let renew_ttl = Some(ttl.as_secs().max(1));
+    let mut expires_at = lock.expires_at;
+    let mut renew = tokio::time::interval((ttl / 3).max(std::time::Duration::from_millis(100)));
+    renew.tick().await;
+    let outcome = loop {
+        tokio::select! {
+            status = child.wait() => {
+                break status.map_err(|e| {
+                    shiki::ShikiError::backend_with_source("Failed to wait for the command".to_string(), e)
+                });
+            }
+            _ = renew.tick() => {
+                match client.renew_lock(&lock.name, lock.fencing_token, renew_ttl).await {
+                    Ok(renewed) => expires_at = renewed.expires_at,
+                    Err(e @ shiki::ShikiError::LockConflict { .. }) => break Err(e),
+                    // The agent may be briefly unreachable; the lock still
+                    // holds until it expires
+                    Err(e) if chrono::Utc::now() < expires_at => {
+                        tracing::warn!(lock = %lock.name, error = %e, "Failed to renew lock");
+                    }
+                    Err(e) => {
+                        break Err(shiki::ShikiError::LockConflict {
+                            lock: lock.name.clone(),
+                            holder: None,
+                            reason: format!("expired without renewal: {}", e),
+                        });
+                    }
+                }
+            }
+        }
+    };
+
+    let status = match outcome {
+        Ok(status) => status,
+        Err(e) => {
+            tracing::error!(lock = %lock.name, "Lock lost; stopping the command");
+            let _ = child.kill().await;
+            return Err(e);
+        }
+    };
+    if let Err(e) = client.release_lock(&lock.name, lock.fencing_token).await {
+        tracing::warn!(lock = %lock.name, error = %e, "Failed to release lock");
+    }
+    Ok(status.code().unwrap_or(exit_code::GENERAL_ERROR))
+}
+
+/// Returns the address of the agent configured on this host.
+fn local_agent_address(config: &Config) -> String {
+    let host = match config.server.bind.as_str() {
+        "0.0.0.0" | "::" | "" => "127.0.0.1",
+        bind => bind,
+    };
+    let scheme = if config.server.tls.enabled {
+        "https"
+    } else {
+        "http"
+    };
+    format!("{}://{}:{}", scheme, host, config.server.port)
+}
+
+/// Handle the `history` command.
+fn cmd_history(cli: &Cli, args: &HistoryArgs) -> shiki::Result<()> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    let client = target_client(cli, &args.agent)?;
+    let query = shiki::history::HistoryQuery {
+        service: args.service.clone(),
+        action: args.action.map(|a| a.to_string()),
+        result: args.result.clone(),
+        since: args.since,
+        until: args.until,
+        limit: Some(args.limit),
+        offset: Some(args.offset),
+    };
+    let history = runtime.block_on(client.history(&query))?;
+    if history.entries.is_empty() {
+        return emit(cli, &history, || println!("No operations found"));
+    }
+
+    let rows: Vec<[String; 7]> = history
+        .entries
+        .iter()
+        .map(|entry| {
+            let state = match (&entry.previous_state, &entry.current_state) {
+                (Some(previous), Some(current)) => format!("{} -> {}", previous, current),
+                (None, Some(current)) => current.clone(),
+                (Some(previous), None) => previous.clone(),
+                (None, None) => "-".to_string(),
+            };
+            [
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                entry.service.clone(),
+                entry.action.clone(),
+                entry.result.clone(),
+                state,
+                format!("{}ms", entry.duration_ms),
+                entry.caller.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    emit(cli, &history, || {
+        print_table(
+            &[
+                "TIME", "SERVICE", "ACTION", "RESULT", "STATE", "DURATION", "CALLER",
+            ],
+            &rows,
+        );
+        println!(
+            "\nShowing {}-{} of {}",
+            history.offset + 1,
+            history.offset + history.entries.len(),
+            history.total
+        );
+    })
+}
+
+/// Handle the `watch` command.
+fn cmd_watch(cli: &Cli, args: &WatchArgs) -> shiki::Result<()> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    let client = target_client(cli, &args.agent)?;
+    let watch = runtime.block_on(client.watch())?;
+
+    let rows: Vec<[String; 5]> = watch
+        .services
+        .iter()
+        .map(|service| {
+            let state = service.state.clone().unwrap_or_else(|| "-".to_string());
+            let note = if service.stopped_by_api {
+                " (stopped through shiki)"
+            } else if service.gave_up {
+                " (gave up)"
+            } else {
+                ""
+            };
+            [
+                service.service.clone(),
+                service.policy.clone(),
+                format!("{}{}", state, note),
+                format!("{}/{}", service.restarts_in_window, service.max_restarts),
+                service
+                    .decisions
+                    .first()
+                    .map(|d| d.timestamp.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    emit(cli, &watch, || {
+        print_table(
+            &["SERVICE", "POLICY", "STATE", "RESTARTS", "LAST DECISION"],
+            &rows,
+        );
+
+        if args.decisions {
+            let rows: Vec<[String; 6]> = watch
+                .services
+                .iter()
+                .flat_map(|service| service.decisions.iter())
+                .map(|decision| {
+                    [
+                        decision.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        decision.service.clone(),
+                        decision.observed_state.clone(),
+                        decision.action.clone(),
+                        decision.result.clone(),
+                        decision.message.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            println!();
+            if rows.is_empty() {
+                println!("No decisions yet");
+            } else {
+                print_table(
+                    &["TIME", "SERVICE", "STATE", "ACTION", "RESULT", "MESSAGE"],
+                    &rows,
+                );
+            }
+        }
+    })
+}
+
+/// Handle the `reconcile` command.
+fn cmd_reconcile(cli: &Cli, args: &ReconcileArgs) -> shiki::Result<()> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+
+    let client = target_client(cli, &args.agent)?;
+    let drift = if args.dry_run {
+        runtime.block_on(client.drift())?
+    } else {
+        runtime.block_on(client.reconcile())?
+    };
+
+    let dash = || "-".to_string();
+    let rows: Vec<[String; 6]> = drift
+        .services
+        .iter()
+        .map(|entry| {
+            [
+                entry.service.clone(),
+                entry.desired.clone().unwrap_or_else(dash),
+                entry.actual.clone(),
+                entry.action.clone().unwrap_or_else(dash),
+                entry
+                    .result
+                    .clone()
+                    .or_else(|| entry.message.clone())
+                    .unwrap_or_else(dash),
+                entry.reason.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let actions = drift.services.iter().filter(|s| s.action.is_some()).count();
+    let failed: Vec<&str> = drift
+        .services
+        .iter()
+        .filter(|s| s.result.as_deref() == Some("failed"))
+        .map(|s| s.service.as_str())
+        .collect();
+    emit(cli, &drift, || {
+        print_table(
+            &["SERVICE", "DESIRED", "ACTUAL", "ACTION", "RESULT", "REASON"],
+            &rows,
+        );
+        println!();
+
+        if drift.in_sync {
+            println!("✓ All services are in their desired state");
+        } else if args.dry_run {
+            println!("{} service(s) would be changed", actions);
+        } else if failed.is_empty() {
+            println!("✓ Reconciled {} service(s)", actions);
+        } else {
+            println!("✗ Failed to reconcile: {}", failed.join(", "));
+        }
+    })?;
+    if drift.in_sync || args.dry_run || failed.is_empty() {
+        return Ok(());
+    }
+    Err(shiki::ShikiError::backend(format!(
+        "{} of {} action(s) failed",
+        failed.len(),
+        actions
+    )))
+}
+
+/// Handle the `services` command.
+fn cmd_services(cli: &Cli, subcmd: &ServicesCommands) -> shiki::Result<()> {
+    use shiki::service::ServiceAction;
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| {
+        shiki::ShikiError::backend_with_source("Failed to create async runtime".to_string(), e)
+    })?;
+    let dash = || "-".to_string();
+
+    let (args, action) = match subcmd {
+        ServicesCommands::List(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let list = runtime.block_on(client.list_all_services(
+                args.status.as_deref(),
+                args.offset,
+                args.limit,
+            ))?;
+            if list.services.is_empty() {
+                return emit(cli, &list, || println!("No services found"));
+            }
+            let rows: Vec<[String; 3]> = list
+                .services
+                .iter()
+                .map(|service| {
+                    [
+                        service.name.clone(),
+                        service.status.clone(),
+                        service.description.clone().unwrap_or_else(dash),
+                    ]
+                })
+                .collect();
+            return emit(cli, &list, || {
+                print_table(&["NAME", "STATUS", "DESCRIPTION"], &rows);
+                println!(
+                    "\nShowing {}-{} of {}",
+                    list.offset + 1,
+                    list.offset + list.services.len(),
+                    list.total
+                );
+            });
+        }
+        ServicesCommands::Show(args) => {
+            let client = target_client(cli, &args.agent)?;
+            let service = runtime.block_on(client.get_service(&args.name))?;
+            let ready = match service.ready {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "-",
+            };
+            let row = [
+                service.name.clone(),
+                service.status.clone(),
+                ready.to_string(),
+                service.description.clone().unwrap_or_else(dash),
+            ];
+            return emit(cli, &service, || {
+                print_table(&["NAME", "STATUS", "READY", "DESCRIPTION"], &[row]);
+                if let Some(message) = service
+                    .readiness
+                    .as_ref()
+                    .and_then(|r| r.last_message.as_ref())
+                {
+                    println!("\nReadiness: {}", message);
+                }
+            });
+        }
+        ServicesCommands::Start(args) => (args, ServiceAction::Start),
+        ServicesCommands::Stop(args) => (args, ServiceAction::Stop),
+        ServicesCommands::Restart(args) => (args, ServiceAction::Restart),
+    };
+
+    let client = agent_client(cli, Some(&args.target), args.token.as_deref())?;
+    let operation = runtime.block_on(client.service_action(&args.name, action))?;
+    let result = |success| if success { "completed" } else { "failed" }.to_string();
+    let rows: Vec<[String; 5]> = std::iter::once([
+        operation.service.clone(),
+        operation.action.clone(),
+        result(operation.success),
+        match &operation.previous_state {
+            Some(previous) => format!("{} -> {}", previous, operation.current_state),
+            None => operation.current_state.clone(),
+        },
+        operation.message.clone().unwrap_or_default(),
+    ])
+    .chain(operation.related.iter().map(|related| {
+        [
+            related.service.clone(),
+            related.action.clone(),
+            result(related.success),
+            related.state.clone(),
+            related.message.clone().unwrap_or_default(),
+        ]
+    }))
+    .collect();
+    emit(cli, &operation, || {
+        print_table(&["SERVICE", "ACTION", "RESULT", "STATE", "MESSAGE"], &rows)
+    })?;
+
+    if operation.success {
+        Ok(())
+    } else {
+        Err(shiki::ShikiError::backend(format!(
+            "Failed to {} {}: {}",
+            operation.action,
+            operation.service,
+            operation.message.as_deref().unwrap_or("operation failed")
+        )))
+    }
+}
+
+/// Prints rows as a left-aligned table.
+fn print_table<const N: usize>(header: &[&str; N], rows: &[[String; N]]) {
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| row.to_vec()).collect();
+    print!("{}", shiki::output::format_table(header, &rows));
+}
+
+/// Prints plan progress: a table redrawn in place on a terminal, one line
+/// per status change otherwise.
+struct ProgressPrinter {
+    /// Whether stdout is a terminal.
+    interactive: bool,
+    /// Number of lines drawn by the last redraw.
+    drawn_lines: usize,
+    /// Last printed status per step (non-interactive mode).
+    last_status: std::collections::HashMap<String, shiki::plan::StepStatus>,
+}
+
+impl ProgressPrinter {
+    fn new() -> Self {
+        use std::io::IsTerminal;
+        Self {
+            interactive: std::io::stdout().is_terminal(),
+            drawn_lines: 0,
+            last_status: std::collections::HashMap::new(),
+        }
+    }
+
+    fn update(&mut self, steps: &[shiki::plan::StepProgress]) {
+        if self.interactive {
+            let table = shiki::plan::render_table(steps);
+            if self.drawn_lines > 0 {
+                // Move back to the top of the previous table and clear it
+                print!("\x1b[{}A\x1b[J", self.drawn_lines);
+            }
+            print!("{}", table);
+            self.drawn_lines = table.lines().count();
+        } else {
+            for step in steps {
+                if self.last_status.get(&step.id) == Some(&step.status) {
+                    continue;
+                }
+                self.last_status.insert(step.id.clone(), step.status);
+                match &step.message {
+                    Some(msg) => println!("[{}] {}: {}", step.id, step.status, msg),
+                    None => println!("[{}] {}", step.id, step.status),
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, steps: &[shiki::plan::StepProgress]) {
+        if self.interactive {
+            self.update(steps);
+        } else {
+            println!();
+            print!("{}", shiki::plan::render_table(steps));
+        }
     }
 }
 
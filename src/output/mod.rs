@@ -0,0 +1,378 @@
+//! Output formats of the command-line interface.
+//!
+//! `--output text` (the default) prints the human-readable output of each
+//! command. With `json` or `yaml` every command prints one document on
+//! stdout instead: the typed data of its result, with the field names of
+//! the API responses, or an [`ErrorResponse`](crate::error::ErrorResponse)
+//! when it fails without a result. `table` prints the same data as a table.
+
+use crate::config::{key_name, AgentMode, Backend, LintWarning};
+use crate::error::{Result, ShikiError};
+use crate::server::response::ServerInfo;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+/// Output format of command results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable output.
+    #[default]
+    Text,
+    /// One JSON document.
+    Json,
+    /// One YAML document.
+    Yaml,
+    /// The result data as a table.
+    Table,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Yaml => write!(f, "yaml"),
+            OutputFormat::Table => write!(f, "table"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!(
+                "Invalid output format '{}'. Valid formats: text, json, yaml, table",
+                s
+            )),
+        }
+    }
+}
+
+/// Local agent settings, printed by `shiki status` without `--target`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalStatusData {
+    /// Agent name.
+    pub name: String,
+    /// Operation mode.
+    pub mode: AgentMode,
+    /// Service backend.
+    pub backend: Backend,
+    /// Server settings.
+    pub server: ServerInfo,
+    /// Whether authentication is enabled.
+    pub auth_enabled: bool,
+    /// Agent tags.
+    pub tags: Vec<String>,
+    /// Services defined for the exec backend, sorted by name.
+    pub services: Vec<String>,
+}
+
+/// Result of `shiki config validate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigValidationData {
+    /// Whether the configuration is valid.
+    pub valid: bool,
+    /// Files merged into the configuration, the main file first.
+    pub files: Vec<String>,
+    /// Suspicious settings.
+    pub warnings: Vec<LintWarning>,
+}
+
+/// Result of `shiki config init`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigInitData {
+    /// File written.
+    pub path: String,
+    /// Agent name set in the file.
+    pub name: String,
+    /// Service backend set in the file.
+    pub backend: Backend,
+}
+
+/// Result of `shiki plan validate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanValidationData {
+    /// Whether the plan is valid.
+    pub valid: bool,
+    /// Number of steps.
+    pub steps: usize,
+    /// Step ids by stage, in execution order.
+    pub stages: Vec<Vec<String>>,
+}
+
+/// Renders `value` in a structured format. [`OutputFormat::Text`] has no
+/// generic rendering and is rendered as a table.
+pub fn render<T: Serialize>(format: OutputFormat, value: &T) -> Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value)
+            .map(|json| json + "\n")
+            .map_err(ShikiError::from),
+        OutputFormat::Yaml => serde_yaml::to_string(value).map_err(ShikiError::from),
+        OutputFormat::Text | OutputFormat::Table => {
+            // YAML mappings keep the field order of the data
+            let value = serde_yaml::to_value(value)?;
+            Ok(table(&value))
+        }
+    }
+}
+
+/// Renders a value as a table. A list of objects, or an object holding
+/// exactly one list of objects, gets one row per object and one column per
+/// field; any other object gets one row per field.
+pub fn table(value: &Value) -> String {
+    if let Some(items) = rows_of(value) {
+        let mut header: Vec<String> = Vec::new();
+        for item in items.iter().filter_map(Value::as_mapping) {
+            for key in item.keys().map(key_name) {
+                if !header.contains(&key) {
+                    header.push(key);
+                }
+            }
+        }
+        let rows: Vec<Vec<String>> = items
+            .iter()
+            .map(|item| {
+                header
+                    .iter()
+                    .map(|key| cell(item.get(key.as_str()).unwrap_or(&Value::Null)))
+                    .collect()
+            })
+            .collect();
+        let header: Vec<String> = header.iter().map(|key| key.to_uppercase()).collect();
+        let header: Vec<&str> = header.iter().map(String::as_str).collect();
+        return format_table(&header, &rows);
+    }
+
+    let mut rows = Vec::new();
+    flatten("", value, &mut rows);
+    format_table(&["FIELD", "VALUE"], &rows)
+}
+
+/// Formats rows as a left-aligned table.
+pub fn format_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, w)| format!("{:<width$}", cell, width = w))
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
+
+    let mut table = format_row(header.to_vec());
+    for row in rows {
+        table.push_str(&format_row(row.iter().map(|c| c.as_str()).collect()));
+    }
+    table
+}
+
+fn rows_of(value: &Value) -> Option<&Vec<Value>> {
+    let is_rows = |value: &&Value| {
+        value
+            .as_sequence()
+            .is_some_and(|items| !items.is_empty() && items.iter().all(Value::is_mapping))
+    };
+    match value {
+        Value::Sequence(items) if items.iter().all(Value::is_mapping) => Some(items),
+        Value::Mapping(fields) => {
+            let mut lists = fields.values().filter(is_rows);
+            match (lists.next(), lists.next()) {
+                (Some(list), None) => list.as_sequence(),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn flatten(path: &str, value: &Value, rows: &mut Vec<Vec<String>>) {
+    match value {
+        Value::Mapping(fields) if !fields.is_empty() => {
+            for (key, value) in fields {
+                let key = key_name(key);
+                let path = if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten(&path, value, rows);
+            }
+        }
+        _ => rows.push(vec![path.to_string(), cell(value)]),
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Sequence(items) if items.is_empty() => "-".to_string(),
+        Value::Sequence(items)
+            if items
+                .iter()
+                .all(|item| !item.is_mapping() && !item.is_sequence()) =>
+        {
+            items.iter().map(cell).collect::<Vec<_>>().join(",")
+        }
+        Value::Tagged(tagged) => cell(&tagged.value),
+        // Nested data is shown as JSON
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorResponse;
+    use crate::server::response::{
+        AgentInfo, AgentState, ConfigInfo, NotifyResponseData, ServiceInfo, ServicesListData,
+        StatsInfo, StatusData,
+    };
+    use chrono::{TimeZone, Utc};
+    use std::path::PathBuf;
+
+    /// Compares `actual` with `snapshots/<name>`. Set `UPDATE_SNAPSHOTS=1`
+    /// to write the snapshots instead, after checking that a change of the
+    /// output is intended.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/output/snapshots")
+            .join(name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Cannot read snapshot {}: {}", path.display(), e));
+        assert_eq!(
+            actual,
+            expected,
+            "Output differs from snapshot {}",
+            path.display()
+        );
+    }
+
+    fn notify() -> NotifyResponseData {
+        NotifyResponseData {
+            request_id: uuid::Uuid::from_u128(0x6f0e_4b2a_9c3d_4e5f_8a1b_2c3d_4e5f_6a7b),
+            service: "nginx".to_string(),
+            action: "restart".to_string(),
+            result: "completed".to_string(),
+            previous_status: Some("running".to_string()),
+            current_status: Some("running".to_string()),
+            duration_ms: Some(1532),
+            message: None,
+            related: Vec::new(),
+        }
+    }
+
+    fn status() -> StatusData {
+        StatusData {
+            agent: AgentInfo {
+                name: "web-01".to_string(),
+                state: AgentState::Ready,
+                mode: "agent".to_string(),
+                tags: vec!["web".to_string(), "prod".to_string()],
+            },
+            server: ServerInfo {
+                bind: "0.0.0.0".to_string(),
+                port: 8080,
+                tls_enabled: false,
+            },
+            stats: StatsInfo {
+                requests_total: 42,
+                requests_success: 40,
+                requests_failed: 2,
+                active_connections: 1,
+            },
+            version: "0.1.0".to_string(),
+            uptime_seconds: 3600,
+            webhooks: None,
+            config: ConfigInfo {
+                generation: 2,
+                hash: "9f2c".to_string(),
+                loaded_at: Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap(),
+                source: Some("/etc/shiki/config.yaml".to_string()),
+            },
+        }
+    }
+
+    fn services() -> ServicesListData {
+        ServicesListData {
+            services: vec![
+                ServiceInfo {
+                    name: "nginx".to_string(),
+                    status: "running".to_string(),
+                    description: Some("A high performance web server".to_string()),
+                },
+                ServiceInfo {
+                    name: "postgresql".to_string(),
+                    status: "stopped".to_string(),
+                    description: None,
+                },
+            ],
+            total: 2,
+            limit: 100,
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_json_snapshots() {
+        let error = ErrorResponse::from_error(&ShikiError::ServiceNotFound {
+            service: "nginx".to_string(),
+        });
+        let json = OutputFormat::Json;
+        assert_snapshot("notify.json", &render(json, &notify()).unwrap());
+        assert_snapshot("status.json", &render(json, &status()).unwrap());
+        assert_snapshot("services.json", &render(json, &services()).unwrap());
+        assert_snapshot("error.json", &render(json, &error).unwrap());
+    }
+
+    #[test]
+    fn test_table() {
+        assert_snapshot(
+            "services.txt",
+            &render(OutputFormat::Table, &services()).unwrap(),
+        );
+        assert_eq!(
+            render(OutputFormat::Table, &notify()).unwrap(),
+            "\
+FIELD            VALUE
+request_id       6f0e4b2a-9c3d-4e5f-8a1b-2c3d4e5f6a7b
+service          nginx
+action           restart
+result           completed
+previous_status  running
+current_status   running
+duration_ms      1532
+"
+        );
+        let yaml = render(OutputFormat::Yaml, &notify()).unwrap();
+        assert!(yaml.starts_with("request_id: 6f0e4b2a-"), "{}", yaml);
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!("json".parse::<OutputFormat>(), Ok(OutputFormat::Json));
+        assert_eq!("YAML".parse::<OutputFormat>(), Ok(OutputFormat::Yaml));
+        assert!("xml".parse::<OutputFormat>().is_err());
+        assert_eq!(OutputFormat::default().to_string(), "text");
+    }
+}
@@ -0,0 +1,506 @@
+//! Plan execution.
+//!
+//! Steps run as soon as all of their dependencies have succeeded, so
+//! independent steps run concurrently. Each step is retried according to its
+//! retry policy, with a per-attempt timeout. When a step fails the plan's
+//! failure policy decides whether to keep going, stop, or roll back the
+//! steps that already succeeded.
+
+use crate::client::ShikiClient;
+use crate::config::RetryConfig;
+use crate::error::{Result, ShikiError};
+use crate::output::format_table;
+use crate::plan::{FailurePolicy, Plan, PlanStep, StepWait};
+use crate::server::response::NotifyOptions;
+use crate::service::ServiceAction;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// A single service operation requested by a step.
+#[derive(Debug, Clone)]
+pub struct StepRequest {
+    /// Agent address.
+    pub agent: String,
+    /// Service name.
+    pub service: String,
+    /// Action to perform.
+    pub action: ServiceAction,
+    /// Wait condition.
+    pub wait: StepWait,
+    /// Timeout in seconds.
+    pub timeout_seconds: u64,
+}
+
+/// Performs step operations against agents.
+#[async_trait]
+pub trait StepRunner: Send + Sync {
+    /// Performs the operation and returns the resulting service status.
+    async fn run(&self, request: &StepRequest) -> Result<String>;
+}
+
+/// Step runner that talks to agents over HTTP.
+#[derive(Debug, Default)]
+pub struct ClientRunner;
+
+#[async_trait]
+impl StepRunner for ClientRunner {
+    async fn run(&self, request: &StepRequest) -> Result<String> {
+        // Leave the agent time to report its own timeout first
+        let client = ShikiClient::with_timeout(
+            &request.agent,
+            Duration::from_secs(request.timeout_seconds + 5),
+        )?;
+        let options = NotifyOptions {
+            wait: request.wait != StepWait::None,
+            timeout_seconds: request.timeout_seconds,
+            wait_ready: request.wait == StepWait::Ready,
+        };
+
+        let result = client
+            .notify_with_options(&request.service, request.action, options)
+            .await?;
+
+        if result.result == "failed" {
+            return Err(ShikiError::backend(
+                result
+                    .message
+                    .filter(|m| !m.is_empty())
+                    .unwrap_or_else(|| format!("{} {} failed", request.action, request.service)),
+            ));
+        }
+
+        Ok(result.current_status.unwrap_or(result.result))
+    }
+}
+
+/// Status of a plan step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    /// Waiting for dependencies.
+    Pending,
+    /// Running (possibly retrying).
+    Running,
+    /// Completed successfully.
+    Succeeded,
+    /// Failed after all attempts.
+    Failed,
+    /// Not run because a dependency failed or the plan stopped.
+    Skipped,
+    /// Compensating action in progress.
+    RollingBack,
+    /// Compensating action completed.
+    RolledBack,
+    /// Compensating action failed.
+    RollbackFailed,
+}
+
+impl std::fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepStatus::Pending => write!(f, "pending"),
+            StepStatus::Running => write!(f, "running"),
+            StepStatus::Succeeded => write!(f, "succeeded"),
+            StepStatus::Failed => write!(f, "failed"),
+            StepStatus::Skipped => write!(f, "skipped"),
+            StepStatus::RollingBack => write!(f, "rolling_back"),
+            StepStatus::RolledBack => write!(f, "rolled_back"),
+            StepStatus::RollbackFailed => write!(f, "rollback_failed"),
+        }
+    }
+}
+
+/// Progress of a single step.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepProgress {
+    /// Step id.
+    pub id: String,
+    /// Resolved agent address.
+    pub agent: String,
+    /// Service name.
+    pub service: String,
+    /// Action.
+    pub action: ServiceAction,
+    /// Current status.
+    pub status: StepStatus,
+    /// Number of attempts made so far.
+    pub attempts: u32,
+    /// Duration of the step in milliseconds, once finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Result or error message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// When the step started.
+    #[serde(skip)]
+    started_at: Option<Instant>,
+}
+
+impl StepProgress {
+    fn new(step: &PlanStep, agent: String) -> Self {
+        Self {
+            id: step.id.clone(),
+            agent,
+            service: step.service.clone(),
+            action: step.action,
+            status: StepStatus::Pending,
+            attempts: 0,
+            duration_ms: None,
+            message: None,
+            started_at: None,
+        }
+    }
+
+    /// Returns the elapsed time: final duration if finished, running time otherwise.
+    pub fn elapsed_ms(&self) -> Option<u64> {
+        self.duration_ms
+            .or_else(|| self.started_at.map(|t| t.elapsed().as_millis() as u64))
+    }
+}
+
+/// Result of applying a plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanReport {
+    /// Whether every step succeeded.
+    pub success: bool,
+    /// Whether a rollback was performed.
+    pub rolled_back: bool,
+    /// Total duration in milliseconds.
+    pub duration_ms: u64,
+    /// Final state of every step.
+    pub steps: Vec<StepProgress>,
+}
+
+impl PlanReport {
+    /// Returns the steps that failed.
+    pub fn failed_steps(&self) -> Vec<&StepProgress> {
+        self.steps
+            .iter()
+            .filter(|s| s.status == StepStatus::Failed)
+            .collect()
+    }
+}
+
+/// Executes a plan.
+pub struct PlanExecutor {
+    /// Plan to execute.
+    plan: Arc<Plan>,
+    /// Runner performing the operations.
+    runner: Arc<dyn StepRunner>,
+}
+
+impl PlanExecutor {
+    /// Creates an executor for a validated plan.
+    pub fn new(plan: Plan, runner: Arc<dyn StepRunner>) -> Self {
+        Self {
+            plan: Arc::new(plan),
+            runner,
+        }
+    }
+
+    /// Applies the plan. `on_progress` is called whenever a step changes.
+    pub async fn apply<F>(&self, mut on_progress: F) -> PlanReport
+    where
+        F: FnMut(&[StepProgress]),
+    {
+        let started = Instant::now();
+        let plan = &self.plan;
+        let mut progress: Vec<StepProgress> = plan
+            .steps
+            .iter()
+            .map(|s| StepProgress::new(s, plan.agent_address(s)))
+            .collect();
+        let index: HashMap<&str, usize> = plan
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.id.as_str(), i))
+            .collect();
+
+        info!(
+            plan = plan.name.as_deref().unwrap_or("plan"),
+            steps = plan.steps.len(),
+            on_failure = %plan.on_failure,
+            "Applying plan"
+        );
+        on_progress(&progress);
+
+        let (attempt_tx, mut attempt_rx) = mpsc::unbounded_channel::<(usize, u32)>();
+        let mut tasks = JoinSet::new();
+        let mut task_steps = HashMap::new();
+        let mut succeeded_order = Vec::new();
+        let mut halted = false;
+
+        loop {
+            let mut changed = skip_blocked(plan, &index, &mut progress);
+
+            if !halted {
+                for (i, step) in plan.steps.iter().enumerate() {
+                    let ready = progress[i].status == StepStatus::Pending
+                        && step
+                            .depends_on
+                            .iter()
+                            .all(|d| progress[index[d.as_str()]].status == StepStatus::Succeeded);
+                    if !ready {
+                        continue;
+                    }
+
+                    progress[i].status = StepStatus::Running;
+                    progress[i].started_at = Some(Instant::now());
+                    changed = true;
+
+                    let request = StepRequest {
+                        agent: progress[i].agent.clone(),
+                        service: step.service.clone(),
+                        action: step.action,
+                        wait: step.wait,
+                        timeout_seconds: plan.timeout_for(step),
+                    };
+                    let handle = tasks.spawn(run_with_retry(
+                        Arc::clone(&self.runner),
+                        request,
+                        plan.retry_for(step),
+                        i,
+                        attempt_tx.clone(),
+                    ));
+                    task_steps.insert(handle.id(), i);
+                }
+            }
+
+            if changed {
+                on_progress(&progress);
+            }
+
+            let (i, result) = tokio::select! {
+                biased;
+                Some((i, attempt)) = attempt_rx.recv() => {
+                    progress[i].attempts = attempt;
+                    on_progress(&progress);
+                    continue;
+                }
+                joined = tasks.join_next_with_id() => match joined {
+                    None => break,
+                    Some(Ok((id, result))) => (task_steps[&id], result),
+                    Some(Err(e)) => (
+                        task_steps[&e.id()],
+                        Err(ShikiError::backend(format!("Step task failed: {}", e))),
+                    ),
+                },
+            };
+
+            let step = &mut progress[i];
+            step.duration_ms = step.elapsed_ms();
+            match result {
+                Ok(status) => {
+                    info!(step = %step.id, status = %status, "Step succeeded");
+                    step.status = StepStatus::Succeeded;
+                    step.message = Some(status);
+                    succeeded_order.push(i);
+                }
+                Err(e) => {
+                    warn!(step = %step.id, error = %e, "Step failed");
+                    step.status = StepStatus::Failed;
+                    step.message = Some(e.to_string());
+                    if plan.on_failure != FailurePolicy::Continue {
+                        halted = true;
+                    }
+                }
+            }
+            on_progress(&progress);
+        }
+
+        // Anything still pending was never started
+        let mut changed = false;
+        for step in progress.iter_mut() {
+            if step.status == StepStatus::Pending {
+                step.status = StepStatus::Skipped;
+                step.message = Some("Plan stopped after a failure".to_string());
+                changed = true;
+            }
+        }
+        if changed {
+            on_progress(&progress);
+        }
+
+        let failed = progress.iter().any(|s| s.status == StepStatus::Failed);
+        let rolled_back = failed && plan.on_failure == FailurePolicy::Rollback;
+        if rolled_back {
+            self.rollback(&succeeded_order, &mut progress, &mut on_progress)
+                .await;
+        }
+
+        PlanReport {
+            success: !failed,
+            rolled_back,
+            duration_ms: started.elapsed().as_millis() as u64,
+            steps: progress,
+        }
+    }
+
+    /// Undoes succeeded steps in reverse completion order.
+    async fn rollback<F>(
+        &self,
+        succeeded: &[usize],
+        progress: &mut [StepProgress],
+        on_progress: &mut F,
+    ) where
+        F: FnMut(&[StepProgress]),
+    {
+        info!(steps = succeeded.len(), "Rolling back plan");
+
+        for &i in succeeded.iter().rev() {
+            let step = &self.plan.steps[i];
+            let Some(action) = inverse_action(step.action) else {
+                continue;
+            };
+
+            progress[i].status = StepStatus::RollingBack;
+            on_progress(progress);
+
+            let request = StepRequest {
+                agent: progress[i].agent.clone(),
+                service: step.service.clone(),
+                action,
+                wait: StepWait::Completed,
+                timeout_seconds: self.plan.timeout_for(step),
+            };
+            let timeout = Duration::from_secs(request.timeout_seconds);
+            let result = match tokio::time::timeout(timeout, self.runner.run(&request)).await {
+                Ok(result) => result,
+                Err(_) => Err(timeout_error(&request)),
+            };
+
+            match result {
+                Ok(status) => {
+                    progress[i].status = StepStatus::RolledBack;
+                    progress[i].message = Some(format!("{} -> {}", action, status));
+                }
+                Err(e) => {
+                    warn!(step = %step.id, error = %e, "Rollback failed");
+                    progress[i].status = StepStatus::RollbackFailed;
+                    progress[i].message = Some(e.to_string());
+                }
+            }
+            on_progress(progress);
+        }
+    }
+}
+
+/// Marks pending steps whose dependencies can no longer succeed as skipped.
+fn skip_blocked(plan: &Plan, index: &HashMap<&str, usize>, progress: &mut [StepProgress]) -> bool {
+    let mut changed = false;
+    loop {
+        let mut pass_changed = false;
+        for (i, step) in plan.steps.iter().enumerate() {
+            if progress[i].status != StepStatus::Pending {
+                continue;
+            }
+            let blocker = step.depends_on.iter().find(|d| {
+                matches!(
+                    progress[index[d.as_str()]].status,
+                    StepStatus::Failed | StepStatus::Skipped
+                )
+            });
+            if let Some(dep) = blocker {
+                progress[i].status = StepStatus::Skipped;
+                progress[i].message = Some(format!("Dependency '{}' did not succeed", dep));
+                pass_changed = true;
+            }
+        }
+        if !pass_changed {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// Runs a step with its retry policy and per-attempt timeout.
+async fn run_with_retry(
+    runner: Arc<dyn StepRunner>,
+    request: StepRequest,
+    retry: RetryConfig,
+    index: usize,
+    attempts_tx: mpsc::UnboundedSender<(usize, u32)>,
+) -> Result<String> {
+    let max_attempts = retry.max_attempts.max(1);
+    let timeout = Duration::from_secs(request.timeout_seconds);
+    let mut delay_ms = retry.initial_interval_ms;
+
+    let mut attempt = 1;
+    loop {
+        let _ = attempts_tx.send((index, attempt));
+
+        let result = match tokio::time::timeout(timeout, runner.run(&request)).await {
+            Ok(result) => result,
+            Err(_) => Err(timeout_error(&request)),
+        };
+
+        match result {
+            Ok(status) => return Ok(status),
+            Err(e) if attempt < max_attempts => {
+                warn!(
+                    service = %request.service,
+                    agent = %request.agent,
+                    attempt = attempt,
+                    error = %e,
+                    "Step attempt failed, retrying"
+                );
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                delay_ms = ((delay_ms as f64 * retry.multiplier) as u64).min(retry.max_interval_ms);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn timeout_error(request: &StepRequest) -> ShikiError {
+    ShikiError::Timeout {
+        operation: format!(
+            "{} {} on {}",
+            request.action, request.service, request.agent
+        ),
+        seconds: request.timeout_seconds,
+    }
+}
+
+/// Returns the action that undoes `action`, if any.
+fn inverse_action(action: ServiceAction) -> Option<ServiceAction> {
+    match action {
+        ServiceAction::Start => Some(ServiceAction::Stop),
+        ServiceAction::Stop => Some(ServiceAction::Start),
+        ServiceAction::Restart => None,
+    }
+}
+
+/// Renders step progress as a text table.
+pub fn render_table(steps: &[StepProgress]) -> String {
+    let header = [
+        "STEP", "AGENT", "SERVICE", "ACTION", "STATUS", "ATTEMPTS", "TIME", "MESSAGE",
+    ];
+    let rows: Vec<Vec<String>> = steps
+        .iter()
+        .map(|s| {
+            vec![
+                s.id.clone(),
+                s.agent.clone(),
+                s.service.clone(),
+                s.action.to_string(),
+                s.status.to_string(),
+                s.attempts.to_string(),
+                s.elapsed_ms()
+                    .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                s.message.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    format_table(&header, &rows)
+}
@@ -0,0 +1,226 @@
+//! Tests for the plan executor.
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{Result, ShikiError};
+    use crate::plan::{render_table, Plan, PlanExecutor, StepRequest, StepRunner, StepStatus};
+    use crate::service::ServiceAction;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Fake runner recording every operation. Services listed in `failures`
+    /// fail that many times before succeeding.
+    #[derive(Default)]
+    struct FakeRunner {
+        calls: Mutex<Vec<String>>,
+        failures: Mutex<HashMap<String, u32>>,
+        delay_ms: u64,
+    }
+
+    impl FakeRunner {
+        fn failing(service: &str, times: u32) -> Self {
+            let runner = FakeRunner::default();
+            runner
+                .failures
+                .lock()
+                .unwrap()
+                .insert(service.to_string(), times);
+            runner
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl StepRunner for FakeRunner {
+        async fn run(&self, request: &StepRequest) -> Result<String> {
+            let call = format!("{} {}@{}", request.action, request.service, request.agent);
+            self.calls.lock().unwrap().push(call);
+            tokio::time::sleep(Duration::from_millis(self.delay_ms)).await;
+
+            let mut failures = self.failures.lock().unwrap();
+            if let Some(remaining) = failures.get_mut(&request.service) {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    return Err(ShikiError::backend(format!("{} failed", request.service)));
+                }
+            }
+
+            Ok(match request.action {
+                ServiceAction::Stop => "stopped".to_string(),
+                _ => "running".to_string(),
+            })
+        }
+    }
+
+    fn plan(on_failure: &str) -> Plan {
+        Plan::parse(&format!(
+            r#"
+on_failure: {}
+defaults:
+  retry:
+    max_attempts: 1
+    initial_interval_ms: 10
+steps:
+  - {{ id: db, agent: a, service: db, action: start }}
+  - {{ id: cache-b, agent: b, service: cache-b, action: start, depends_on: [db] }}
+  - {{ id: cache-c, agent: c, service: cache-c, action: start, depends_on: [db] }}
+  - {{ id: web, agent: d, service: web, action: start, depends_on: [cache-b, cache-c] }}
+"#,
+            on_failure
+        ))
+        .unwrap()
+    }
+
+    fn status_of(report: &crate::plan::PlanReport, id: &str) -> StepStatus {
+        report.steps.iter().find(|s| s.id == id).unwrap().status
+    }
+
+    #[tokio::test]
+    async fn test_apply_runs_in_dependency_order() {
+        let runner = Arc::new(FakeRunner {
+            delay_ms: 50,
+            ..Default::default()
+        });
+        let executor = PlanExecutor::new(plan("stop"), runner.clone());
+
+        let mut updates = 0;
+        let report = executor.apply(|_| updates += 1).await;
+
+        assert!(report.success);
+        assert!(!report.rolled_back);
+        assert!(updates > 0);
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[0], "start db@a");
+        assert!(calls[1..3].contains(&"start cache-b@b".to_string()));
+        assert!(calls[1..3].contains(&"start cache-c@c".to_string()));
+        assert_eq!(calls[3], "start web@d");
+        assert!(report
+            .steps
+            .iter()
+            .all(|s| s.status == StepStatus::Succeeded && s.attempts == 1));
+    }
+
+    #[tokio::test]
+    async fn test_independent_steps_run_concurrently() {
+        let runner = Arc::new(FakeRunner {
+            delay_ms: 300,
+            ..Default::default()
+        });
+        let plan = Plan::parse(
+            r#"
+steps:
+  - { id: a, agent: x, service: a, action: start }
+  - { id: b, agent: x, service: b, action: start }
+  - { id: c, agent: x, service: c, action: start }
+"#,
+        )
+        .unwrap();
+        let executor = PlanExecutor::new(plan, runner);
+
+        let started = std::time::Instant::now();
+        let report = executor.apply(|_| {}).await;
+
+        assert!(report.success);
+        assert!(started.elapsed() < Duration::from_millis(800));
+    }
+
+    #[tokio::test]
+    async fn test_failure_stop_policy() {
+        let runner = Arc::new(FakeRunner::failing("cache-b", 1));
+        let executor = PlanExecutor::new(plan("stop"), runner.clone());
+
+        let report = executor.apply(|_| {}).await;
+
+        assert!(!report.success);
+        assert_eq!(status_of(&report, "db"), StepStatus::Succeeded);
+        assert_eq!(status_of(&report, "cache-b"), StepStatus::Failed);
+        assert_eq!(status_of(&report, "web"), StepStatus::Skipped);
+        assert_eq!(report.failed_steps().len(), 1);
+        assert!(!runner.calls().contains(&"start web@d".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_failure_rollback_policy() {
+        let runner = Arc::new(FakeRunner::failing("web", 1));
+        let executor = PlanExecutor::new(plan("rollback"), runner.clone());
+
+        let report = executor.apply(|_| {}).await;
+
+        assert!(!report.success);
+        assert!(report.rolled_back);
+        assert_eq!(status_of(&report, "db"), StepStatus::RolledBack);
+        assert_eq!(status_of(&report, "cache-b"), StepStatus::RolledBack);
+        assert_eq!(status_of(&report, "web"), StepStatus::Failed);
+
+        // db is rolled back last
+        let calls = runner.calls();
+        assert_eq!(calls.last().unwrap(), "stop db@a");
+        assert_eq!(calls.iter().filter(|c| c.starts_with("stop")).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_succeed() {
+        let runner = Arc::new(FakeRunner::failing("cache-c", 2));
+        let mut plan = plan("stop");
+        plan.steps[2].retry = Some(crate::config::RetryConfig {
+            max_attempts: 3,
+            initial_interval_ms: 10,
+            max_interval_ms: 20,
+            multiplier: 2.0,
+        });
+        let executor = PlanExecutor::new(plan, runner);
+
+        let report = executor.apply(|_| {}).await;
+
+        assert!(report.success);
+        let cache = report.steps.iter().find(|s| s.id == "cache-c").unwrap();
+        assert_eq!(cache.attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_step_timeout() {
+        let runner = Arc::new(FakeRunner {
+            delay_ms: 2_000,
+            ..Default::default()
+        });
+        let plan = Plan::parse(
+            r#"
+steps:
+  - { id: slow, agent: x, service: slow, action: start, timeout_seconds: 1 }
+"#,
+        )
+        .unwrap();
+        let executor = PlanExecutor::new(plan, runner);
+
+        let report = executor.apply(|_| {}).await;
+
+        assert!(!report.success);
+        assert!(report.steps[0]
+            .message
+            .as_deref()
+            .unwrap()
+            .contains("Timeout"));
+    }
+
+    #[tokio::test]
+    async fn test_render_table() {
+        let runner = Arc::new(FakeRunner::default());
+        let executor = PlanExecutor::new(plan("stop"), runner);
+        let report = executor.apply(|_| {}).await;
+
+        let table = render_table(&report.steps);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("STEP"));
+        assert!(lines[1].starts_with("db "));
+        assert!(lines[1].contains("succeeded"));
+    }
+}
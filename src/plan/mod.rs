@@ -0,0 +1,504 @@
+//! Declarative cross-host startup plans.
+//!
+//! A plan is a YAML DAG of steps. Each step performs a service action on a
+//! remote agent and may depend on other steps:
+//!
+//! ```yaml
+//! name: web-stack
+//! on_failure: rollback
+//! agents:
+//!   a: "http://host-a:8080"
+//! steps:
+//!   - id: db
+//!     agent: a
+//!     service: postgresql
+//!     action: start
+//!     wait: ready
+//!   - id: web
+//!     agent: "host-d:8080"
+//!     service: nginx
+//!     action: start
+//!     depends_on: [db]
+//! ```
+//!
+//! Steps whose dependencies have all succeeded run concurrently.
+
+pub mod executor;
+
+#[cfg(test)]
+mod executor_tests;
+
+pub use executor::{
+    render_table, ClientRunner, PlanExecutor, PlanReport, StepProgress, StepRequest, StepRunner,
+    StepStatus,
+};
+
+use crate::config::RetryConfig;
+use crate::error::{Result, ShikiError};
+use crate::service::ServiceAction;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+/// A startup plan.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Plan {
+    /// Plan name (for display).
+    pub name: Option<String>,
+
+    /// What to do when a step fails.
+    pub on_failure: FailurePolicy,
+
+    /// Defaults applied to steps that don't set their own values.
+    pub defaults: StepDefaults,
+
+    /// Agent aliases (alias -> address).
+    pub agents: HashMap<String, String>,
+
+    /// Plan steps.
+    pub steps: Vec<PlanStep>,
+}
+
+/// Behaviour when a step fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Don't start new steps; let running steps finish.
+    #[default]
+    Stop,
+    /// Keep going; only steps depending on the failed step are skipped.
+    Continue,
+    /// Stop, then undo the steps that succeeded, in reverse order.
+    Rollback,
+}
+
+impl std::fmt::Display for FailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailurePolicy::Stop => write!(f, "stop"),
+            FailurePolicy::Continue => write!(f, "continue"),
+            FailurePolicy::Rollback => write!(f, "rollback"),
+        }
+    }
+}
+
+impl std::str::FromStr for FailurePolicy {
+    type Err = ShikiError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "stop" => Ok(FailurePolicy::Stop),
+            "continue" => Ok(FailurePolicy::Continue),
+            "rollback" => Ok(FailurePolicy::Rollback),
+            _ => Err(ShikiError::config(format!(
+                "Invalid failure policy: {}. Valid values: stop, continue, rollback",
+                s
+            ))),
+        }
+    }
+}
+
+/// Step defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StepDefaults {
+    /// Step timeout in seconds (per attempt).
+    pub timeout_seconds: u64,
+
+    /// Retry policy.
+    pub retry: RetryConfig,
+}
+
+impl Default for StepDefaults {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 60,
+            retry: RetryConfig {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// What a step waits for before it counts as done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepWait {
+    /// Done once the agent accepts the request.
+    None,
+    /// Done once the operation has completed.
+    #[default]
+    Completed,
+    /// Done once the service's readiness probe passes (start/restart).
+    Ready,
+}
+
+impl std::fmt::Display for StepWait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepWait::None => write!(f, "none"),
+            StepWait::Completed => write!(f, "completed"),
+            StepWait::Ready => write!(f, "ready"),
+        }
+    }
+}
+
+/// A single plan step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// Unique step identifier.
+    pub id: String,
+
+    /// Target agent: an alias from `agents` or an address.
+    pub agent: String,
+
+    /// Target service name.
+    pub service: String,
+
+    /// Action to perform.
+    pub action: ServiceAction,
+
+    /// Wait condition.
+    #[serde(default)]
+    pub wait: StepWait,
+
+    /// Steps that must succeed before this step runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+
+    /// Step timeout in seconds (overrides `defaults.timeout_seconds`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+
+    /// Retry policy (overrides `defaults.retry`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+}
+
+impl Plan {
+    /// Loads and validates a plan file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ShikiError::config_with_source(
+                format!("Failed to read plan file: {}", path.display()),
+                e,
+            )
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parses and validates a plan from YAML.
+    pub fn parse(content: &str) -> Result<Self> {
+        let plan: Plan = serde_yaml::from_str(content)?;
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    /// Validates the plan.
+    pub fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(ShikiError::config("steps: plan has no steps"));
+        }
+
+        let mut ids = HashSet::new();
+        for step in &self.steps {
+            if step.id.is_empty() {
+                return Err(ShikiError::config("steps: step id must not be empty"));
+            }
+            if !ids.insert(step.id.as_str()) {
+                return Err(ShikiError::config(format!(
+                    "steps.{}: duplicate step id",
+                    step.id
+                )));
+            }
+            if step.agent.is_empty() {
+                return Err(ShikiError::config(format!(
+                    "steps.{}.agent: must not be empty",
+                    step.id
+                )));
+            }
+            if step.service.is_empty() {
+                return Err(ShikiError::config(format!(
+                    "steps.{}.service: must not be empty",
+                    step.id
+                )));
+            }
+            if step.wait == StepWait::Ready && step.action == ServiceAction::Stop {
+                return Err(ShikiError::config(format!(
+                    "steps.{}.wait: 'ready' cannot be used with action 'stop'",
+                    step.id
+                )));
+            }
+            if self.timeout_for(step) == 0 {
+                return Err(ShikiError::config(format!(
+                    "steps.{}.timeout_seconds: must be greater than 0",
+                    step.id
+                )));
+            }
+            if self.retry_for(step).multiplier < 1.0 {
+                return Err(ShikiError::config(format!(
+                    "steps.{}.retry.multiplier: must be at least 1.0",
+                    step.id
+                )));
+            }
+        }
+
+        for step in &self.steps {
+            for dep in &step.depends_on {
+                if !ids.contains(dep.as_str()) {
+                    return Err(ShikiError::config(format!(
+                        "steps.{}.depends_on: unknown step '{}'",
+                        step.id, dep
+                    )));
+                }
+                if dep == &step.id {
+                    return Err(ShikiError::config(format!(
+                        "steps.{}.depends_on: step cannot depend on itself",
+                        step.id
+                    )));
+                }
+            }
+        }
+
+        let stages = self.stages();
+        let staged: usize = stages.iter().map(|s| s.len()).sum();
+        if staged != self.steps.len() {
+            let mut blocked: Vec<&str> = self
+                .steps
+                .iter()
+                .map(|s| s.id.as_str())
+                .filter(|id| !stages.iter().flatten().any(|s| s == id))
+                .collect();
+            blocked.sort_unstable();
+            return Err(ShikiError::config(format!(
+                "steps: dependency cycle between {}",
+                blocked.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the step with the given id.
+    pub fn step(&self, id: &str) -> Option<&PlanStep> {
+        self.steps.iter().find(|s| s.id == id)
+    }
+
+    /// Resolves a step's agent alias to an address.
+    pub fn agent_address(&self, step: &PlanStep) -> String {
+        self.agents
+            .get(&step.agent)
+            .cloned()
+            .unwrap_or_else(|| step.agent.clone())
+    }
+
+    /// Returns the effective timeout of a step in seconds.
+    pub fn timeout_for(&self, step: &PlanStep) -> u64 {
+        step.timeout_seconds
+            .unwrap_or(self.defaults.timeout_seconds)
+    }
+
+    /// Returns the effective retry policy of a step.
+    pub fn retry_for(&self, step: &PlanStep) -> RetryConfig {
+        step.retry
+            .clone()
+            .unwrap_or_else(|| self.defaults.retry.clone())
+    }
+
+    /// Groups steps into stages: every step only depends on steps in earlier
+    /// stages, so each stage can run concurrently. Steps that are part of a
+    /// cycle are left out.
+    pub fn stages(&self) -> Vec<Vec<String>> {
+        let mut remaining: BTreeMap<&str, BTreeSet<&str>> = self
+            .steps
+            .iter()
+            .map(|s| {
+                (
+                    s.id.as_str(),
+                    s.depends_on.iter().map(|d| d.as_str()).collect(),
+                )
+            })
+            .collect();
+
+        let mut stages = Vec::new();
+        loop {
+            let ready: Vec<&str> = remaining
+                .iter()
+                .filter(|(_, deps)| deps.is_empty())
+                .map(|(id, _)| *id)
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for id in &ready {
+                remaining.remove(id);
+            }
+            for deps in remaining.values_mut() {
+                for id in &ready {
+                    deps.remove(id);
+                }
+            }
+            stages.push(ready.into_iter().map(String::from).collect());
+        }
+        stages
+    }
+
+    /// Renders the plan as a Graphviz DOT graph.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!(
+            "digraph \"{}\" {{\n",
+            self.name.as_deref().unwrap_or("plan")
+        ));
+        out.push_str("  rankdir=LR;\n");
+        for step in &self.steps {
+            out.push_str(&format!(
+                "  \"{}\" [label=\"{}\\n{} {}@{}\"];\n",
+                step.id,
+                step.id,
+                step.action,
+                step.service,
+                self.agent_address(step)
+            ));
+        }
+        for step in &self.steps {
+            for dep in &step.depends_on {
+                out.push_str(&format!("  \"{}\" -> \"{}\";\n", dep, step.id));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"
+name: web-stack
+on_failure: rollback
+agents:
+  a: "http://host-a:8080"
+steps:
+  - id: db
+    agent: a
+    service: postgresql
+    action: start
+    wait: ready
+    timeout_seconds: 120
+  - id: cache-b
+    agent: "host-b:8080"
+    service: redis
+    action: start
+    depends_on: [db]
+  - id: cache-c
+    agent: "host-c:8080"
+    service: redis
+    action: start
+    depends_on: [db]
+    retry:
+      max_attempts: 3
+  - id: web
+    agent: "host-d:8080"
+    service: nginx
+    action: start
+    depends_on: [cache-b, cache-c]
+"#;
+
+    #[test]
+    fn test_parse_plan() {
+        let plan = Plan::parse(PLAN).unwrap();
+
+        assert_eq!(plan.name.as_deref(), Some("web-stack"));
+        assert_eq!(plan.on_failure, FailurePolicy::Rollback);
+        assert_eq!(plan.steps.len(), 4);
+
+        let db = plan.step("db").unwrap();
+        assert_eq!(db.wait, StepWait::Ready);
+        assert_eq!(plan.agent_address(db), "http://host-a:8080");
+        assert_eq!(plan.timeout_for(db), 120);
+        assert_eq!(plan.retry_for(db).max_attempts, 1);
+
+        let cache = plan.step("cache-c").unwrap();
+        assert_eq!(cache.wait, StepWait::Completed);
+        assert_eq!(plan.agent_address(cache), "host-c:8080");
+        assert_eq!(plan.timeout_for(cache), 60);
+        assert_eq!(plan.retry_for(cache).max_attempts, 3);
+    }
+
+    #[test]
+    fn test_stages() {
+        let plan = Plan::parse(PLAN).unwrap();
+
+        assert_eq!(
+            plan.stages(),
+            vec![
+                vec!["db".to_string()],
+                vec!["cache-b".to_string(), "cache-c".to_string()],
+                vec!["web".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let plan = Plan::parse(PLAN).unwrap();
+        let dot = plan.to_dot();
+
+        assert!(dot.starts_with("digraph \"web-stack\" {"));
+        assert!(dot.contains("\"db\" [label=\"db\\nstart postgresql@http://host-a:8080\"];"));
+        assert!(dot.contains("\"db\" -> \"cache-b\";"));
+        assert!(dot.contains("\"cache-c\" -> \"web\";"));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let unknown = r#"
+steps:
+  - { id: a, agent: "h:1", service: s, action: start, depends_on: [b] }
+"#;
+        let err = Plan::parse(unknown).unwrap_err();
+        assert!(err.to_string().contains("unknown step 'b'"));
+
+        let duplicate = r#"
+steps:
+  - { id: a, agent: "h:1", service: s, action: start }
+  - { id: a, agent: "h:1", service: t, action: start }
+"#;
+        let err = Plan::parse(duplicate).unwrap_err();
+        assert!(err.to_string().contains("duplicate step id"));
+
+        let cycle = r#"
+steps:
+  - { id: a, agent: "h:1", service: s, action: start, depends_on: [b] }
+  - { id: b, agent: "h:1", service: t, action: start, depends_on: [a] }
+  - { id: c, agent: "h:1", service: u, action: start }
+"#;
+        let err = Plan::parse(cycle).unwrap_err();
+        assert!(err.to_string().contains("cycle between a, b"));
+
+        let ready_stop = r#"
+steps:
+  - { id: a, agent: "h:1", service: s, action: stop, wait: ready }
+"#;
+        assert!(Plan::parse(ready_stop).is_err());
+
+        assert!(Plan::parse("steps: []").is_err());
+    }
+
+    #[test]
+    fn test_failure_policy_from_str() {
+        assert_eq!(
+            "rollback".parse::<FailurePolicy>().unwrap(),
+            FailurePolicy::Rollback
+        );
+        assert_eq!(
+            "CONTINUE".parse::<FailurePolicy>().unwrap(),
+            FailurePolicy::Continue
+        );
+        assert!("abort".parse::<FailurePolicy>().is_err());
+    }
+}
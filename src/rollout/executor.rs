@@ -0,0 +1,300 @@
+//! Rollout execution.
+//!
+//! Agents are restarted in record order, a batch at a time. A batch is at
+//! most `batch` agents, and smaller when failed agents use up part of the
+//! `max_unavailable` budget. Once more than `max_failures` agents have
+//! failed, the rollout pauses or aborts according to its failure policy.
+
+use crate::client::ShikiClient;
+use crate::error::{Result, ShikiError};
+use crate::rollout::{AgentStatus, RolloutFailurePolicy, RolloutRecord, RolloutStatus};
+use crate::server::response::NotifyOptions;
+use crate::service::ServiceAction;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// A restart of a service on one agent.
+#[derive(Debug, Clone)]
+pub struct RestartRequest {
+    /// Agent name.
+    pub agent: String,
+    /// Agent address.
+    pub address: String,
+    /// Service name.
+    pub service: String,
+    /// Timeout in seconds for the restart and the service to come back.
+    pub timeout_seconds: u64,
+}
+
+/// Restarts services on agents.
+#[async_trait]
+pub trait Restarter: Send + Sync {
+    /// Restarts the service, waits until it is back and returns its status.
+    async fn restart(&self, request: &RestartRequest) -> Result<String>;
+}
+
+/// Restarter that talks to agents over HTTP.
+#[derive(Debug, Clone)]
+pub struct ClientRestarter {
+    /// Bearer tokens by agent name.
+    tokens: HashMap<String, String>,
+    /// Interval between service status checks.
+    poll_interval: Duration,
+}
+
+impl ClientRestarter {
+    /// Creates a restarter authenticating with `tokens` (agent name -> token).
+    pub fn new(tokens: HashMap<String, String>) -> Self {
+        Self {
+            tokens,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+#[async_trait]
+impl Restarter for ClientRestarter {
+    async fn restart(&self, request: &RestartRequest) -> Result<String> {
+        let timeout = Duration::from_secs(request.timeout_seconds);
+        // Leave the agent time to report its own timeout first
+        let mut client =
+            ShikiClient::with_timeout(&request.address, timeout + Duration::from_secs(5))?;
+        if let Some(token) = self.tokens.get(&request.agent) {
+            client = client.with_token(token);
+        }
+        let deadline = Instant::now() + timeout;
+
+        let options = NotifyOptions {
+            wait: true,
+            timeout_seconds: request.timeout_seconds,
+            wait_ready: false,
+        };
+        let result = client
+            .notify_with_options(&request.service, ServiceAction::Restart, options)
+            .await?;
+        if result.result == "failed" {
+            return Err(ShikiError::backend(
+                result
+                    .message
+                    .filter(|m| !m.is_empty())
+                    .unwrap_or_else(|| format!("restart {} failed", request.service)),
+            ));
+        }
+
+        // The service is back once it runs and, if it has a readiness
+        // probe, passes it
+        loop {
+            let last = match client.get_service(&request.service).await {
+                Ok(service) if service.status == "running" && service.ready != Some(false) => {
+                    return Ok(match service.ready {
+                        Some(true) => "running (ready)".to_string(),
+                        _ => service.status,
+                    });
+                }
+                Ok(service) if service.ready == Some(false) => {
+                    format!("{} (not ready)", service.status)
+                }
+                Ok(service) => service.status,
+                Err(e) => e.to_string(),
+            };
+            if Instant::now() >= deadline {
+                return Err(ShikiError::Timeout {
+                    operation: format!(
+                        "wait for {} on {} to come back (last: {})",
+                        request.service, request.agent, last
+                    ),
+                    seconds: request.timeout_seconds,
+                });
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+/// Limits of a rollout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloutLimits {
+    /// Maximum number of agents restarted at the same time.
+    pub batch: usize,
+    /// Maximum number of agents down at the same time, failed agents included.
+    pub max_unavailable: usize,
+    /// Number of failed agents tolerated.
+    pub max_failures: usize,
+    /// What to do once more agents failed.
+    pub on_failure: RolloutFailurePolicy,
+    /// Timeout in seconds per agent.
+    pub timeout_seconds: u64,
+}
+
+/// Executes rollouts.
+pub struct RolloutExecutor {
+    limits: RolloutLimits,
+    restarter: Arc<dyn Restarter>,
+}
+
+impl RolloutExecutor {
+    /// Creates an executor.
+    pub fn new(limits: RolloutLimits, restarter: Arc<dyn Restarter>) -> Self {
+        Self { limits, restarter }
+    }
+
+    /// Runs the rollout of `record` until every agent was handled or too
+    /// many failed, and leaves the outcome in `record.status`.
+    ///
+    /// Agents that were being restarted when a previous run was interrupted
+    /// are restarted again, and the failed agents of a paused rollout are
+    /// retried. `on_progress` is called on every change, typically to save
+    /// the record; an error from it stops the rollout.
+    pub async fn run<F>(&self, record: &mut RolloutRecord, mut on_progress: F) -> Result<()>
+    where
+        F: FnMut(&mut RolloutRecord) -> Result<()>,
+    {
+        let limits = self.limits;
+        if limits.batch == 0 || limits.max_unavailable == 0 {
+            return Err(ShikiError::invalid_request(
+                "batch and max_unavailable must be > 0",
+            ));
+        }
+        if !record.is_resumable() {
+            return Err(ShikiError::invalid_request(format!(
+                "Rollout of {} is {} and cannot be continued",
+                record.service, record.status
+            )));
+        }
+
+        for agent in &mut record.agents {
+            if matches!(agent.status, AgentStatus::Restarting | AgentStatus::Failed) {
+                agent.status = AgentStatus::Pending;
+            }
+        }
+        record.status = RolloutStatus::Running;
+        on_progress(record)?;
+        info!(
+            service = %record.service,
+            agents = record.agents.len(),
+            pending = record.count(AgentStatus::Pending),
+            batch = limits.batch,
+            max_unavailable = limits.max_unavailable,
+            "Starting rollout"
+        );
+
+        loop {
+            let failed = record.count(AgentStatus::Failed);
+            let room = limits.max_unavailable.saturating_sub(failed);
+            let batch: Vec<usize> = record
+                .agents
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| a.status == AgentStatus::Pending)
+                .map(|(i, _)| i)
+                .take(limits.batch.min(room))
+                .collect();
+
+            if failed > limits.max_failures {
+                self.stop(record);
+                on_progress(record)?;
+                return Ok(());
+            }
+            if record.count(AgentStatus::Pending) == 0 {
+                record.status = RolloutStatus::Completed;
+                on_progress(record)?;
+                info!(service = %record.service, failed = failed, "Rollout completed");
+                return Ok(());
+            }
+            // Failed agents count as unavailable, so they may leave no room
+            if batch.is_empty() {
+                self.stop(record);
+                on_progress(record)?;
+                return Ok(());
+            }
+
+            for &i in &batch {
+                let agent = &mut record.agents[i];
+                agent.status = AgentStatus::Restarting;
+                agent.attempts += 1;
+                agent.duration_ms = None;
+                agent.message = None;
+            }
+            on_progress(record)?;
+
+            let mut tasks = JoinSet::new();
+            for &i in &batch {
+                let request = RestartRequest {
+                    agent: record.agents[i].name.clone(),
+                    address: record.agents[i].address.clone(),
+                    service: record.service.clone(),
+                    timeout_seconds: limits.timeout_seconds,
+                };
+                let restarter = Arc::clone(&self.restarter);
+                tasks.spawn(async move {
+                    let started = Instant::now();
+                    let outcome = restarter.restart(&request).await;
+                    (i, outcome, started.elapsed().as_millis() as u64)
+                });
+            }
+
+            while let Some(joined) = tasks.join_next().await {
+                let Ok((i, outcome, duration_ms)) = joined else {
+                    continue;
+                };
+                let agent = &mut record.agents[i];
+                agent.duration_ms = Some(duration_ms);
+                match outcome {
+                    Ok(status) => {
+                        info!(agent = %agent.name, status = %status, "Agent restarted");
+                        agent.status = AgentStatus::Succeeded;
+                        agent.message = Some(status);
+                    }
+                    Err(e) => {
+                        warn!(agent = %agent.name, error = %e, "Agent restart failed");
+                        agent.status = AgentStatus::Failed;
+                        // Errors may carry a command's multi-line output
+                        let message = e.to_string();
+                        let lines: Vec<&str> = message
+                            .lines()
+                            .map(str::trim)
+                            .filter(|l| !l.is_empty())
+                            .collect();
+                        agent.message = Some(lines.join(" "));
+                    }
+                }
+                on_progress(record)?;
+            }
+            // A task that panicked leaves its agent restarting
+            for &i in &batch {
+                if record.agents[i].status == AgentStatus::Restarting {
+                    record.agents[i].status = AgentStatus::Failed;
+                    record.agents[i].message = Some("Restart task failed".to_string());
+                }
+            }
+        }
+    }
+
+    /// Pauses or aborts a rollout after too many failures.
+    fn stop(&self, record: &mut RolloutRecord) {
+        let failed = record.count(AgentStatus::Failed);
+        match self.limits.on_failure {
+            RolloutFailurePolicy::Pause => {
+                record.status = RolloutStatus::Paused;
+            }
+            RolloutFailurePolicy::Abort => {
+                record.status = RolloutStatus::Aborted;
+                for agent in &mut record.agents {
+                    if agent.status == AgentStatus::Pending {
+                        agent.status = AgentStatus::Skipped;
+                    }
+                }
+            }
+        }
+        warn!(
+            service = %record.service,
+            failed = failed,
+            status = %record.status,
+            "Rollout stopped after failures"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Tests for the rollout executor.
+
+#[cfg(test)]
+mod tests {
+    use crate::client::FanoutTarget;
+    use crate::error::{Result, ShikiError};
+    use crate::rollout::{
+        AgentStatus, ClientRestarter, RestartRequest, Restarter, RolloutExecutor,
+        RolloutFailurePolicy, RolloutLimits, RolloutRecord, RolloutStatus,
+    };
+    use async_trait::async_trait;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Fake restarter recording every restart. Agents listed in `failing`
+    /// fail that many times before succeeding.
+    #[derive(Default)]
+    struct FakeRestarter {
+        calls: Mutex<Vec<String>>,
+        failing: Mutex<HashMap<String, u32>>,
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl FakeRestarter {
+        fn failing(agents: &[(&str, u32)]) -> Self {
+            let restarter = FakeRestarter::default();
+            for (agent, times) in agents {
+                restarter
+                    .failing
+                    .lock()
+                    .unwrap()
+                    .insert(agent.to_string(), *times);
+            }
+            restarter
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Restarter for FakeRestarter {
+        async fn restart(&self, request: &RestartRequest) -> Result<String> {
+            self.calls.lock().unwrap().push(request.agent.clone());
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let mut failing = self.failing.lock().unwrap();
+            if let Some(remaining) = failing.get_mut(&request.agent) {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    return Err(ShikiError::backend(format!("{} failed", request.agent)));
+                }
+            }
+            Ok("running".to_string())
+        }
+    }
+
+    fn record(n: usize) -> RolloutRecord {
+        let targets: Vec<FanoutTarget> = (1..=n)
+            .map(|i| FanoutTarget::new(format!("api-{}", i), format!("10.0.0.{}:8080", i)))
+            .collect();
+        RolloutRecord::new("api", "role=api", &targets)
+    }
+
+    fn limits(batch: usize, max_unavailable: usize) -> RolloutLimits {
+        RolloutLimits {
+            batch,
+            max_unavailable,
+            max_failures: 0,
+            on_failure: RolloutFailurePolicy::Pause,
+            timeout_seconds: 10,
+        }
+    }
+
+    fn statuses(record: &RolloutRecord) -> Vec<AgentStatus> {
+        record.agents.iter().map(|a| a.status).collect()
+    }
+
+    #[tokio::test]
+    async fn test_rollout_in_batches() {
+        let restarter = Arc::new(FakeRestarter::default());
+        let executor = RolloutExecutor::new(limits(2, 2), restarter.clone());
+        let mut record = record(5);
+
+        let mut updates = 0;
+        executor
+            .run(&mut record, |_| {
+                updates += 1;
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(record.status, RolloutStatus::Completed);
+        assert_eq!(record.count(AgentStatus::Succeeded), 5);
+        assert!(updates > 5);
+        assert_eq!(restarter.peak.load(Ordering::SeqCst), 2);
+
+        // Batches follow the record order
+        let calls = restarter.calls();
+        let batch = |range: std::ops::Range<usize>| -> HashSet<String> {
+            calls[range].iter().cloned().collect()
+        };
+        assert_eq!(
+            batch(0..2),
+            HashSet::from(["api-1".to_string(), "api-2".to_string()])
+        );
+        assert_eq!(
+            batch(2..4),
+            HashSet::from(["api-3".to_string(), "api-4".to_string()])
+        );
+        assert_eq!(calls[4], "api-5");
+    }
+
+    #[tokio::test]
+    async fn test_max_unavailable_limits_batch() {
+        let restarter = Arc::new(FakeRestarter::default());
+        let executor = RolloutExecutor::new(limits(3, 1), restarter.clone());
+        let mut record = record(3);
+        executor.run(&mut record, |_| Ok(())).await.unwrap();
+
+        assert_eq!(record.status, RolloutStatus::Completed);
+        assert_eq!(restarter.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let restarter = Arc::new(FakeRestarter::failing(&[("api-2", 1)]));
+        let executor = RolloutExecutor::new(limits(1, 1), restarter.clone());
+        let mut record = record(3);
+        executor.run(&mut record, |_| Ok(())).await.unwrap();
+
+        assert_eq!(record.status, RolloutStatus::Paused);
+        assert_eq!(
+            statuses(&record),
+            vec![
+                AgentStatus::Succeeded,
+                AgentStatus::Failed,
+                AgentStatus::Pending
+            ]
+        );
+        assert_eq!(
+            record.agents[1].message.as_deref(),
+            Some("Backend error: api-2 failed")
+        );
+
+        // Resuming retries the failed agent and skips the restarted one
+        executor.run(&mut record, |_| Ok(())).await.unwrap();
+        assert_eq!(record.status, RolloutStatus::Completed);
+        assert_eq!(restarter.calls(), vec!["api-1", "api-2", "api-2", "api-3"]);
+        assert_eq!(record.agents[1].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_failures_within_tolerance() {
+        let restarter = Arc::new(FakeRestarter::failing(&[("api-1", 1)]));
+        let executor = RolloutExecutor::new(
+            RolloutLimits {
+                max_failures: 1,
+                ..limits(1, 2)
+            },
+            restarter.clone(),
+        );
+        let mut record = record(3);
+        executor.run(&mut record, |_| Ok(())).await.unwrap();
+
+        // The failed agent uses up one unit of the unavailability budget
+        assert_eq!(record.status, RolloutStatus::Completed);
+        assert_eq!(record.count(AgentStatus::Failed), 1);
+        assert_eq!(record.count(AgentStatus::Succeeded), 2);
+    }
+
+    #[tokio::test]
+    async fn test_abort_skips_remaining_agents() {
+        let restarter = Arc::new(FakeRestarter::failing(&[("api-1", 1)]));
+        let executor = RolloutExecutor::new(
+            RolloutLimits {
+                on_failure: RolloutFailurePolicy::Abort,
+                ..limits(1, 1)
+            },
+            restarter.clone(),
+        );
+        let mut record = record(3);
+        executor.run(&mut record, |_| Ok(())).await.unwrap();
+
+        assert_eq!(record.status, RolloutStatus::Aborted);
+        assert_eq!(
+            statuses(&record),
+            vec![
+                AgentStatus::Failed,
+                AgentStatus::Skipped,
+                AgentStatus::Skipped
+            ]
+        );
+        assert!(executor.run(&mut record, |_| Ok(())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_rollout_is_resumed() {
+        let restarter = Arc::new(FakeRestarter::default());
+        let executor = RolloutExecutor::new(limits(1, 1), restarter.clone());
+
+        // The first run stops when the record cannot be saved
+        let mut record = record(3);
+        let mut saves = 0;
+        let err = executor
+            .run(&mut record, |_| {
+                saves += 1;
+                if saves > 3 {
+                    Err(ShikiError::backend("disk full"))
+                } else {
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("disk full"));
+        assert_eq!(record.status, RolloutStatus::Running);
+        assert_eq!(record.agents[1].status, AgentStatus::Restarting);
+
+        executor.run(&mut record, |_| Ok(())).await.unwrap();
+        assert_eq!(record.status, RolloutStatus::Completed);
+        assert_eq!(restarter.calls(), vec!["api-1", "api-2", "api-3"]);
+        assert_eq!(record.agents[1].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_restarter() {
+        let mut config = crate::config::Config::default();
+        config.agent.backend = crate::config::Backend::Exec;
+        config.agent.name = Some("api-1".to_string());
+        let dir = tempfile::tempdir().unwrap();
+        let pid = dir.path().join("api.pid");
+        config.services.insert(
+            "api".to_string(),
+            crate::config::ServiceDefinition {
+                start: format!("touch {}", pid.display()),
+                stop: format!("rm -f {}", pid.display()),
+                status: format!("test -f {}", pid.display()),
+                ..Default::default()
+            },
+        );
+        let state = Arc::new(crate::server::state::AppState::new(&config).unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            axum::serve(listener, crate::server::create_router(state))
+                .await
+                .unwrap();
+        });
+
+        let restarter = ClientRestarter::new(HashMap::new());
+        let request = |service: &str| RestartRequest {
+            agent: "api-1".to_string(),
+            address: address.clone(),
+            service: service.to_string(),
+            timeout_seconds: 5,
+        };
+        assert_eq!(restarter.restart(&request("api")).await.unwrap(), "running");
+        assert!(restarter.restart(&request("missing")).await.is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//! Rolling restarts across a set of agents.
+//!
+//! A rollout restarts one service on every agent matching a selector, a
+//! batch at a time. Each agent has to report the service running (and
+//! ready, when it has a readiness probe) before the next batch starts, so
+//! that no more than `max_unavailable` agents are down at once.
+//!
+//! Progress is written to a record file after every change. An interrupted
+//! or paused rollout is continued from that record: agents that already
+//! succeeded are not restarted again.
+
+pub mod executor;
+
+#[cfg(test)]
+mod executor_tests;
+
+pub use executor::{ClientRestarter, RestartRequest, Restarter, RolloutExecutor, RolloutLimits};
+
+use crate::client::FanoutTarget;
+use crate::error::{Result, ShikiError};
+use crate::output::format_table;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What to do once more agents failed than tolerated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RolloutFailurePolicy {
+    /// Stop; the failed agents are retried when the rollout is resumed.
+    #[default]
+    Pause,
+    /// Stop for good; the remaining agents are skipped.
+    Abort,
+}
+
+impl std::fmt::Display for RolloutFailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RolloutFailurePolicy::Pause => write!(f, "pause"),
+            RolloutFailurePolicy::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+impl std::str::FromStr for RolloutFailurePolicy {
+    type Err = ShikiError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pause" => Ok(RolloutFailurePolicy::Pause),
+            "abort" => Ok(RolloutFailurePolicy::Abort),
+            _ => Err(ShikiError::invalid_request(format!(
+                "Invalid failure policy: {}. Valid values: pause, abort",
+                s
+            ))),
+        }
+    }
+}
+
+/// State of a rollout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RolloutStatus {
+    /// In progress, or interrupted.
+    Running,
+    /// Stopped after too many failures; can be resumed.
+    Paused,
+    /// Stopped after too many failures; cannot be resumed.
+    Aborted,
+    /// Every agent was handled.
+    Completed,
+}
+
+impl std::fmt::Display for RolloutStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RolloutStatus::Running => write!(f, "running"),
+            RolloutStatus::Paused => write!(f, "paused"),
+            RolloutStatus::Aborted => write!(f, "aborted"),
+            RolloutStatus::Completed => write!(f, "completed"),
+        }
+    }
+}
+
+/// State of a single agent in a rollout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentStatus {
+    /// Not restarted yet.
+    Pending,
+    /// Restart in progress.
+    Restarting,
+    /// Restarted, and the service came back.
+    Succeeded,
+    /// Restart failed, or the service did not come back in time.
+    Failed,
+    /// Not restarted because the rollout was aborted.
+    Skipped,
+}
+
+impl std::fmt::Display for AgentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentStatus::Pending => write!(f, "pending"),
+            AgentStatus::Restarting => write!(f, "restarting"),
+            AgentStatus::Succeeded => write!(f, "succeeded"),
+            AgentStatus::Failed => write!(f, "failed"),
+            AgentStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Progress of a single agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentProgress {
+    /// Agent name.
+    pub name: String,
+    /// Agent address.
+    pub address: String,
+    /// Current state.
+    pub status: AgentStatus,
+    /// Number of restarts attempted, across resumptions.
+    #[serde(default)]
+    pub attempts: u32,
+    /// Duration of the last attempt in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Service status after the restart, or the error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Resumable progress record of a rollout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RolloutRecord {
+    /// Service being restarted.
+    pub service: String,
+    /// Selector the agents were chosen with.
+    pub selector: String,
+    /// Current state.
+    pub status: RolloutStatus,
+    /// When the rollout was started.
+    pub started_at: DateTime<Utc>,
+    /// When the record was last written.
+    pub updated_at: DateTime<Utc>,
+    /// Agents in restart order.
+    pub agents: Vec<AgentProgress>,
+}
+
+impl RolloutRecord {
+    /// Creates the record of a new rollout over `targets`.
+    pub fn new(service: &str, selector: &str, targets: &[FanoutTarget]) -> Self {
+        let now = Utc::now();
+        Self {
+            service: service.to_string(),
+            selector: selector.to_string(),
+            status: RolloutStatus::Running,
+            started_at: now,
+            updated_at: now,
+            agents: targets
+                .iter()
+                .map(|t| AgentProgress {
+                    name: t.name.clone(),
+                    address: t.address.clone(),
+                    status: AgentStatus::Pending,
+                    attempts: 0,
+                    duration_ms: None,
+                    message: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Loads a record written by [`RolloutRecord::save`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ShikiError::config_with_source(
+                format!("Failed to read rollout record: {}", path.display()),
+                e,
+            )
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            ShikiError::config_with_source(format!("Invalid rollout record: {}", path.display()), e)
+        })
+    }
+
+    /// Writes the record, replacing the file atomically so that an
+    /// interruption never leaves a truncated record behind.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.updated_at = Utc::now();
+        let content = serde_json::to_string_pretty(self)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let write = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(&tmp, path));
+        write.map_err(|e| {
+            ShikiError::backend_with_source(
+                format!("Failed to write rollout record: {}", path.display()),
+                e,
+            )
+        })
+    }
+
+    /// Returns whether the rollout can be continued.
+    pub fn is_resumable(&self) -> bool {
+        matches!(self.status, RolloutStatus::Running | RolloutStatus::Paused)
+    }
+
+    /// Returns the number of agents in `status`.
+    pub fn count(&self, status: AgentStatus) -> usize {
+        self.agents.iter().filter(|a| a.status == status).count()
+    }
+}
+
+/// Renders rollout progress as a text table.
+pub fn render_table(agents: &[AgentProgress]) -> String {
+    let header = ["AGENT", "ADDRESS", "STATUS", "ATTEMPTS", "TIME", "MESSAGE"];
+    let rows: Vec<Vec<String>> = agents
+        .iter()
+        .map(|a| {
+            vec![
+                a.name.clone(),
+                a.address.clone(),
+                a.status.to_string(),
+                a.attempts.to_string(),
+                a.duration_ms
+                    .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                a.message.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    format_table(&header, &rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets() -> Vec<FanoutTarget> {
+        vec![
+            FanoutTarget::new("api-1", "10.0.0.1:8080"),
+            FanoutTarget::new("api-2", "10.0.0.2:8080"),
+        ]
+    }
+
+    #[test]
+    fn test_record_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollout.json");
+
+        let mut record = RolloutRecord::new("api", "role=api", &targets());
+        record.agents[0].status = AgentStatus::Succeeded;
+        record.save(&path).unwrap();
+        assert!(!dir.path().join("rollout.json.tmp").exists());
+
+        let loaded = RolloutRecord::load(&path).unwrap();
+        assert_eq!(loaded, record);
+        assert_eq!(loaded.count(AgentStatus::Pending), 1);
+        assert!(loaded.is_resumable());
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(RolloutRecord::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_failure_policy_parse() {
+        assert_eq!(
+            "pause".parse::<RolloutFailurePolicy>().unwrap(),
+            RolloutFailurePolicy::Pause
+        );
+        assert_eq!(
+            "ABORT".parse::<RolloutFailurePolicy>().unwrap(),
+            RolloutFailurePolicy::Abort
+        );
+        assert!("rollback".parse::<RolloutFailurePolicy>().is_err());
+    }
+}
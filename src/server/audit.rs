@@ -0,0 +1,175 @@
+//! Request auditing.
+//!
+//! When `audit.enabled` is set, every mutating request (any method but GET
+//! and HEAD) and every request rejected for missing or invalid credentials
+//! is written to the audit log. The middleware wraps authentication so that
+//! it also sees the requests authentication rejects.
+
+use crate::audit::AuditRecord;
+use crate::cluster::forward::REQUEST_ID_HEADER;
+use crate::config::Backend;
+use crate::error::ShikiError;
+use crate::server::auth::authenticate;
+use crate::server::response::ApiResponse;
+use crate::server::state::AppState;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+/// Largest request body accepted on audited requests.
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Middleware writing audit records.
+pub async fn record_audit(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(audit) = &state.audit else {
+        return next.run(request).await;
+    };
+
+    let mut record = AuditRecord {
+        timestamp: Utc::now(),
+        peer: request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string()),
+        identity: authenticate(&state.auth(), request.headers()).ok(),
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+        ..Default::default()
+    };
+
+    let mutating = !matches!(*request.method(), Method::GET | Method::HEAD);
+    if mutating {
+        // Give the request an ID so that the record matches the response
+        // and the operation history
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<Uuid>().ok())
+            .unwrap_or_else(Uuid::new_v4)
+            .to_string();
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            request.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        record.request_id = Some(request_id);
+
+        let (parts, body) = request.into_parts();
+        let body = match to_bytes(body, MAX_BODY_BYTES).await {
+            Ok(body) => body,
+            Err(_) => {
+                let err = ShikiError::invalid_request("Request body too large");
+                return (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    Json(ApiResponse::<()>::from_error(&err)),
+                )
+                    .into_response();
+            }
+        };
+        let (service, action) = target(&record.path, &body);
+        // The ACL applies to the systemd backend and to forwarded requests
+        let acl_applies = state.controller().backend_type() == Backend::Systemd
+            || record.path.starts_with("/api/v1/peers/");
+        record.acl = service.as_deref().filter(|_| acl_applies).map(|service| {
+            if state.acl().is_allowed(service) {
+                "allowed".to_string()
+            } else {
+                "denied".to_string()
+            }
+        });
+        record.service = service;
+        record.action = action;
+        request = Request::from_parts(parts, Body::from(body));
+    }
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if !mutating && status != StatusCode::UNAUTHORIZED {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let json: Option<serde_json::Value> = serde_json::from_slice(&body).ok();
+    record.status = status.as_u16();
+    record.error = json
+        .as_ref()
+        .and_then(|json| json["error"]["message"].as_str())
+        .map(str::to_string);
+    let failed = json
+        .as_ref()
+        .is_some_and(|json| json["success"] == false || json["data"]["result"] == "failed");
+    record.outcome = if status == StatusCode::UNAUTHORIZED {
+        "unauthenticated"
+    } else if status.is_success() && !failed {
+        "success"
+    } else {
+        "failure"
+    }
+    .to_string();
+
+    if let Err(e) = audit.append(record) {
+        error!(error = %e, "Failed to write audit record");
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Returns the service and action a request acts on, from the path of the
+/// service endpoints or the `service` and `action` fields of the body.
+fn target(path: &str, body: &[u8]) -> (Option<String>, Option<String>) {
+    let mut segments: Vec<&str> = path
+        .trim_start_matches("/api/v1/")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    // Requests forwarded to a peer act on the peer's services
+    if segments.first() == Some(&"peers") && segments.len() > 2 {
+        segments.drain(..2);
+    }
+    if let ["services", service, action] = segments.as_slice() {
+        return (Some(service.to_string()), Some(action.to_string()));
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(body).unwrap_or_default();
+    let field = |name: &str| json[name].as_str().map(str::to_string);
+    (field("service"), field("action"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target() {
+        let target = |path: &str, body: &str| target(path, body.as_bytes());
+        assert_eq!(
+            target("/api/v1/services/nginx/restart", ""),
+            (Some("nginx".to_string()), Some("restart".to_string()))
+        );
+        assert_eq!(
+            target("/api/v1/peers/db/services/postgres/stop", ""),
+            (Some("postgres".to_string()), Some("stop".to_string()))
+        );
+        assert_eq!(
+            target(
+                "/api/v1/notify",
+                r#"{"service":"redis","action":"start","options":{"wait":true}}"#
+            ),
+            (Some("redis".to_string()), Some("start".to_string()))
+        );
+        assert_eq!(target("/api/v1/locks/db-migrate", ""), (None, None));
+    }
+}
@@ -0,0 +1,182 @@
+//! Request authentication.
+//!
+//! When `auth.enabled` is set, every endpoint except `/api/v1/health`
+//! requires credentials: `Authorization: Bearer <token>` for token auth, or
+//! an API key in `X-API-Key` (or as a bearer token) for API key auth.
+//!
+//! Authenticated callers are identified as `token`, or as `api-key:` and
+//! the first 8 hex digits of the SHA-256 of their key, so that audit
+//! records can tell API keys apart without revealing them.
+//!
+//! A request carrying the token of a configured peer was relayed by that
+//! agent; only then is the hop list it sends trusted to name the caller.
+
+use crate::config::{AuthConfig, AuthMethod, PeerConfig};
+use crate::error::{Result, ShikiError};
+use crate::server::response::ApiResponse;
+use crate::server::state::AppState;
+use crate::util::to_hex;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Paths that never require authentication.
+const PUBLIC_PATHS: &[&str] = &["/api/v1/health"];
+
+/// Header carrying an API key.
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Middleware rejecting unauthenticated requests.
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if PUBLIC_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    match authenticate(&state.auth(), request.headers()) {
+        Ok(_) => next.run(request).await,
+        Err(err) => {
+            warn!(path = %request.uri().path(), error = %err, "Rejected unauthenticated request");
+            state.increment_requests();
+            state.increment_failed();
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse::<()>::from_error(&err)),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Checks the credentials of a request and returns the caller's identity.
+pub fn authenticate(auth: &AuthConfig, headers: &HeaderMap) -> Result<String> {
+    if !auth.enabled {
+        return Ok("anonymous".to_string());
+    }
+
+    let bearer = bearer(headers);
+    let identity = match auth.method {
+        AuthMethod::None => Some("anonymous".to_string()),
+        AuthMethod::Token => match (&auth.token, bearer) {
+            (Some(token), Some(given)) if constant_time_eq(token, given) => {
+                Some("token".to_string())
+            }
+            _ => None,
+        },
+        AuthMethod::ApiKey => {
+            let given = headers
+                .get(API_KEY_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .or(bearer);
+            given
+                .filter(|given| auth.api_keys.iter().any(|k| constant_time_eq(k, given)))
+                .map(|key| format!("api-key:{}", &to_hex(&Sha256::digest(key))[..8]))
+        }
+    };
+
+    identity.ok_or_else(|| ShikiError::AuthFailed {
+        reason: "Missing or invalid credentials".to_string(),
+    })
+}
+
+/// Returns whether a request carries the token of one of `peers`, that is,
+/// was relayed by another agent rather than sent by a client.
+pub fn is_peer(peers: &[PeerConfig], headers: &HeaderMap) -> bool {
+    let given = headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .or(bearer(headers));
+    given.is_some_and(|given| {
+        peers
+            .iter()
+            .filter_map(|p| p.token.as_deref())
+            .any(|token| constant_time_eq(token, given))
+    })
+}
+
+/// Returns the bearer token of a request.
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Compares two strings without short-circuiting on the first difference.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_auth_disabled() {
+        assert_eq!(
+            authenticate(&AuthConfig::default(), &HeaderMap::new()).unwrap(),
+            "anonymous"
+        );
+    }
+
+    #[test]
+    fn test_token_auth() {
+        let auth = AuthConfig {
+            enabled: true,
+            method: AuthMethod::Token,
+            token: Some("secret".to_string()),
+            api_keys: vec![],
+        };
+
+        assert_eq!(
+            authenticate(&auth, &headers(&[("authorization", "Bearer secret")])).unwrap(),
+            "token"
+        );
+        assert!(authenticate(&auth, &headers(&[("authorization", "Bearer wrong")])).is_err());
+        assert!(authenticate(&auth, &headers(&[("authorization", "secret")])).is_err());
+        assert!(authenticate(&auth, &HeaderMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_api_key_auth() {
+        let auth = AuthConfig {
+            enabled: true,
+            method: AuthMethod::ApiKey,
+            token: None,
+            api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+        };
+
+        let key_2 = authenticate(&auth, &headers(&[("x-api-key", "key-2")])).unwrap();
+        let key_1 = authenticate(&auth, &headers(&[("authorization", "Bearer key-1")])).unwrap();
+        assert!(
+            key_1.starts_with("api-key:") && key_1.len() == 16,
+            "{}",
+            key_1
+        );
+        assert_ne!(key_1, key_2);
+        assert!(authenticate(&auth, &headers(&[("x-api-key", "key-3")])).is_err());
+    }
+}
@@ -2,23 +2,37 @@
 //!
 //! This module contains all the HTTP endpoint handlers for the shiki API.
 
+use crate::client::{fanout::fan_out, fanout::select_targets, FanoutLimits, FanoutTarget};
+use crate::cluster::forward::{self, ForwardRequest, HOPS_HEADER, REQUEST_ID_HEADER, ROUTE_HEADER};
+use crate::cluster::{signals, MemberState, PeerStatus};
 use crate::error::ShikiError;
+use crate::history::HistoryQuery;
+use crate::server::auth;
+use crate::server::reconcile::Reconciler;
 use crate::server::response::{
-    AgentInfo, AgentState, ApiResponse, HealthData, HealthStatus, NotifyRequest,
-    NotifyResponseData, ServerInfo, ServiceDetailData, ServiceInfo, ServiceOperationData,
-    ServicesListData, StatsInfo, StatusData,
+    AcquireLockRequest, AgentInfo, AgentState, ApiResponse, BarrierData, ClusterData,
+    ClusterMember, ClusterNotifyRequest, ClusterSummary, FanoutResponseData, FanoutSummary,
+    HealthData, HealthStatus, HeartbeatRequest, HistoryEntry, LeaderData, NotifyRequest,
+    NotifyResponseData, RelatedOperation, ReleaseLockData, RenewLockRequest, ServerInfo,
+    ServiceDetailData, ServiceInfo, ServiceOperationData, ServicesListData, SetSignalRequest,
+    StatsInfo, StatusData, VoteRequest, WatchData,
 };
+
 use crate::server::state::AppState;
 use crate::service::ServiceAction;
+use crate::webhooks::WebhookDispatcher;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Bytes,
+    extract::{ConnectInfo, Path, Query, RawQuery, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::Utc;
 use serde::Deserialize;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 use uuid::Uuid;
 
@@ -53,7 +67,7 @@ pub async fn status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
         agent: AgentInfo {
             name: state.agent_name.clone(),
             state: AgentState::Ready,
-            mode: "standalone".to_string(),
+            mode: state.mode().to_string(),
             tags: state.agent_tags.clone(),
         },
         server: ServerInfo {
@@ -69,23 +83,177 @@ pub async fn status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
         },
         version: VERSION.to_string(),
         uptime_seconds: state.uptime_seconds(),
+        webhooks: state.webhooks.as_ref().map(WebhookDispatcher::stats),
+        config: state.config_info(),
+    };
+
+    state.increment_success();
+    (StatusCode::OK, Json(ApiResponse::success(data)))
+}
+
+/// Cluster status handler.
+///
+/// GET /api/v1/cluster
+pub async fn cluster(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    state.increment_requests();
+
+    let peers = state.cluster.peers();
+    let reachable = peers.iter().filter(|p| p.reachable).count();
+
+    let data = ClusterData {
+        enabled: state.cluster_enabled,
+        local: ClusterMember {
+            name: state.agent_name.clone(),
+            address: format!("{}:{}", state.server_bind, state.server_port),
+            version: VERSION.to_string(),
+            tags: state.agent_tags.clone(),
+        },
+        summary: ClusterSummary {
+            total: peers.len(),
+            reachable,
+            unreachable: peers.len() - reachable,
+        },
+        peers,
     };
 
     state.increment_success();
     (StatusCode::OK, Json(ApiResponse::success(data)))
 }
 
+/// Cluster leader handler.
+///
+/// GET /api/v1/cluster/leader
+pub async fn cluster_leader(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    state.increment_requests();
+
+    let data = match &state.election {
+        Some(election) => election.leader(),
+        None => LeaderData {
+            enabled: false,
+            agent: state.agent_name.clone(),
+            term: 0,
+            leader: None,
+            is_leader: false,
+            lease_expires_at: None,
+            voters: 0,
+            quorum: 0,
+        },
+    };
+
+    state.increment_success();
+    (StatusCode::OK, Json(ApiResponse::success(data)))
+}
+
+/// Election vote handler, called by candidates.
+///
+/// POST /api/v1/cluster/election/vote
+pub async fn election_vote(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<VoteRequest>,
+) -> Response {
+    state.increment_requests();
+    match &state.election {
+        Some(election) => {
+            state.increment_success();
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(election.vote(&request))),
+            )
+                .into_response()
+        }
+        None => election_disabled(&state),
+    }
+}
+
+/// Election heartbeat handler, called by the leader.
+///
+/// POST /api/v1/cluster/election/heartbeat
+pub async fn election_heartbeat(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<HeartbeatRequest>,
+) -> Response {
+    state.increment_requests();
+    match &state.election {
+        Some(election) => {
+            state.increment_success();
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(election.heartbeat(&request))),
+            )
+                .into_response()
+        }
+        None => election_disabled(&state),
+    }
+}
+
+fn election_disabled(state: &AppState) -> Response {
+    state.increment_failed();
+    let err = ShikiError::invalid_request(format!(
+        "Leader election is disabled on {}",
+        state.agent_name
+    ));
+    (
+        StatusCode::FORBIDDEN,
+        Json(ApiResponse::<()>::from_error(&err)),
+    )
+        .into_response()
+}
+
 /// Notify handler - receives notifications and performs service operations.
 ///
 /// POST /api/v1/notify
 pub async fn notify(
     State(state): State<Arc<AppState>>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(request): Json<NotifyRequest>,
 ) -> impl IntoResponse {
+    let caller = caller(&state, &headers, remote);
+    let (status_code, response) =
+        execute_notify(&state, request, request_id(&headers), caller).await;
+    (status_code, Json(response))
+}
+
+/// Returns the caller's request ID, or a new one.
+fn request_id(headers: &HeaderMap) -> Uuid {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(Uuid::new_v4)
+}
+
+/// Returns who sent a request: the agent that first forwarded it, or the
+/// address of the client. The hop list is only trusted on requests
+/// authenticated with a peer token, since any client can send one.
+fn caller(
+    state: &AppState,
+    headers: &HeaderMap,
+    remote: Option<ConnectInfo<SocketAddr>>,
+) -> Option<String> {
+    let hops = if auth::is_peer(&state.peers, headers) {
+        forward::parse_list(headers.get(HOPS_HEADER).and_then(|v| v.to_str().ok()))
+    } else {
+        Vec::new()
+    };
+    hops.into_iter()
+        .next()
+        .or_else(|| remote.map(|ConnectInfo(addr)| addr.ip().to_string()))
+}
+
+/// Performs a notify request on this agent.
+async fn execute_notify(
+    state: &AppState,
+    request: NotifyRequest,
+    request_id: Uuid,
+    caller: Option<String>,
+) -> (StatusCode, ApiResponse<NotifyResponseData>) {
     state.increment_requests();
 
-    let request_id = Uuid::new_v4();
     let start_time = Instant::now();
+    let started_at = Utc::now();
+    // The whole request runs against one controller, even across a reload
+    let controller = state.controller();
 
     info!(
         request_id = %request_id,
@@ -102,42 +270,54 @@ pub async fn notify(
         _ => {
             state.increment_failed();
             let err = ShikiError::invalid_request(format!("Invalid action: {}", request.action));
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<NotifyResponseData>::from_error(&err)),
-            );
+            return (StatusCode::BAD_REQUEST, ApiResponse::from_error(&err));
         }
     };
 
     // Check if service is supported
-    if !state.controller.supports_service(&request.service) {
+    if !controller.supports_service(&request.service) {
         state.increment_failed();
         let err = ShikiError::ServiceNotFound {
             service: request.service.clone(),
         };
-        return (
-            StatusCode::NOT_FOUND,
-            Json(ApiResponse::<NotifyResponseData>::from_error(&err)),
-        );
+        return (StatusCode::NOT_FOUND, ApiResponse::from_error(&err));
     }
 
     // Get previous status
-    let previous_status = state
-        .controller
+    let previous_status = controller
         .status(&request.service)
         .await
         .ok()
         .map(|s| s.state.to_string());
 
     // Perform the action
-    let result = state
-        .controller
-        .perform_action(&request.service, action)
-        .await;
+    let result = controller.perform_action(&request.service, action).await;
+
+    // Optionally block until the readiness probe passes
+    let wait_for_ready = request.options.wait
+        && request.options.wait_ready
+        && action != ServiceAction::Stop
+        && controller.has_readiness_probe(&request.service);
+    let result = match result {
+        Ok(op_result) if op_result.success && wait_for_ready => {
+            let remaining = Duration::from_secs(request.options.timeout_seconds)
+                .saturating_sub(start_time.elapsed());
+            controller
+                .wait_ready(&request.service, remaining)
+                .await
+                .map(|()| op_result)
+        }
+        other => other,
+    };
 
     match result {
         Ok(op_result) => {
             let duration_ms = start_time.elapsed().as_millis() as u64;
+            let current_status = if op_result.success && wait_for_ready {
+                "ready".to_string()
+            } else {
+                op_result.state.to_string()
+            };
 
             let data = NotifyResponseData {
                 request_id,
@@ -149,18 +329,36 @@ pub async fn notify(
                     "failed".to_string()
                 },
                 previous_status,
-                current_status: Some(op_result.state.to_string()),
+                current_status: Some(current_status),
                 duration_ms: Some(duration_ms),
+                related: op_result
+                    .related
+                    .iter()
+                    .map(RelatedOperation::from)
+                    .collect(),
                 message: op_result.message,
             };
+            state.record_operation(HistoryEntry {
+                id: 0,
+                request_id,
+                timestamp: started_at,
+                caller,
+                service: data.service.clone(),
+                action: action.to_string(),
+                previous_state: data.previous_status.clone(),
+                current_state: data.current_status.clone(),
+                duration_ms,
+                result: data.result.clone(),
+                message: data.message.clone(),
+            });
 
             if op_result.success {
                 state.increment_success();
-                (StatusCode::OK, Json(ApiResponse::success(data)))
+                (StatusCode::OK, ApiResponse::success(data))
             } else {
                 state.increment_failed();
                 // Still return OK but with failed result in the data
-                (StatusCode::OK, Json(ApiResponse::success(data)))
+                (StatusCode::OK, ApiResponse::success(data))
             }
         }
         Err(err) => {
@@ -170,6 +368,19 @@ pub async fn notify(
                 "Notify request failed"
             );
             state.increment_failed();
+            state.record_operation(HistoryEntry {
+                id: 0,
+                request_id,
+                timestamp: started_at,
+                caller,
+                service: request.service,
+                action: action.to_string(),
+                previous_state: previous_status,
+                current_state: None,
+                duration_ms: start_time.elapsed().as_millis() as u64,
+                result: "error".to_string(),
+                message: Some(err.to_string()),
+            });
 
             let status_code = match &err {
                 ShikiError::ServiceNotFound { .. } => StatusCode::NOT_FOUND,
@@ -178,14 +389,141 @@ pub async fn notify(
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             };
 
-            (
-                status_code,
-                Json(ApiResponse::<NotifyResponseData>::from_error(&err)),
-            )
+            (status_code, ApiResponse::from_error(&err))
         }
     }
 }
 
+/// Selector-based notify handler - notifies every matching agent, this one
+/// included, and reports the per-agent results.
+///
+/// POST /api/v1/cluster/notify
+pub async fn cluster_notify(
+    State(state): State<Arc<AppState>>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(request): Json<ClusterNotifyRequest>,
+) -> impl IntoResponse {
+    state.increment_requests();
+
+    let action: ServiceAction = match request.action.parse() {
+        Ok(action) => action,
+        Err(_) => {
+            state.increment_failed();
+            let err = ShikiError::invalid_request(format!("Invalid action: {}", request.action));
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<FanoutResponseData>::from_error(&err)),
+            );
+        }
+    };
+
+    let local = FanoutTarget {
+        local: true,
+        ..FanoutTarget::new(
+            state.agent_name.clone(),
+            format!("{}:{}", state.server_bind, state.server_port),
+        )
+    };
+    // Members that gossip has declared dead are not targeted
+    let peers: Vec<PeerStatus> = state
+        .cluster
+        .peers()
+        .into_iter()
+        .filter(|p| p.member_state != Some(MemberState::Dead))
+        .collect();
+    let candidates =
+        std::iter::once((local, state.agent_tags.as_slice())).chain(peers.iter().map(|p| {
+            let target = FanoutTarget {
+                token: state.peer_config(&p.name).and_then(|c| c.token),
+                ..FanoutTarget::new(p.name.clone(), p.address.clone())
+            };
+            (target, p.tags.as_slice())
+        }));
+    let targets = select_targets(&request.selector, candidates);
+
+    if targets.is_empty() {
+        state.increment_failed();
+        let err =
+            ShikiError::invalid_request(format!("No agents match selector '{}'", request.selector));
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<FanoutResponseData>::from_error(&err)),
+        );
+    }
+
+    let limits = FanoutLimits {
+        parallelism: request
+            .fanout
+            .parallelism
+            .unwrap_or(state.fanout.parallelism),
+        max_failures: request
+            .fanout
+            .max_failures
+            .unwrap_or(state.fanout.max_failures),
+    };
+
+    info!(
+        selector = %request.selector,
+        service = %request.service,
+        action = %action,
+        targets = targets.len(),
+        "Processing cluster notify request"
+    );
+
+    let local_state = Arc::clone(&state);
+    let caller = caller(&state, &headers, remote);
+    let service = request.service.clone();
+    let options = request.options.clone();
+    let results = fan_out(&targets, limits, move |target| {
+        let state = Arc::clone(&local_state);
+        let service = service.clone();
+        let options = options.clone();
+        let caller = caller.clone();
+        async move {
+            if target.local {
+                let request = NotifyRequest {
+                    action: action.to_string(),
+                    service,
+                    options,
+                };
+                let (_, response) = execute_notify(&state, request, Uuid::new_v4(), caller).await;
+                match response.data {
+                    Some(data) => Ok(data),
+                    None => Err(ShikiError::backend(
+                        response
+                            .error
+                            .map(|e| format!("[{}] {}", e.code, e.message))
+                            .unwrap_or_else(|| "Unknown error".to_string()),
+                    )),
+                }
+            } else {
+                target
+                    .client()?
+                    .notify_with_options(&service, action, options)
+                    .await
+            }
+        }
+    })
+    .await;
+
+    let summary = FanoutSummary::from_results(&results);
+    let data = FanoutResponseData {
+        selector: request.selector.to_string(),
+        service: request.service,
+        action: action.to_string(),
+        results,
+        summary,
+    };
+
+    if data.summary.failed == 0 && data.summary.skipped == 0 {
+        state.increment_success();
+    } else {
+        state.increment_failed();
+    }
+    (StatusCode::OK, Json(ApiResponse::success(data)))
+}
+
 /// Query parameters for listing services.
 #[derive(Debug, Deserialize)]
 pub struct ListServicesQuery {
@@ -212,14 +550,15 @@ pub async fn list_services(
 ) -> impl IntoResponse {
     state.increment_requests();
 
-    let services_result = state.controller.list_services().await;
+    let controller = state.controller();
+    let services_result = controller.list_services().await;
 
     match services_result {
         Ok(service_names) => {
             let mut services = Vec::new();
 
             for name in &service_names {
-                if let Ok(status) = state.controller.status(name).await {
+                if let Ok(status) = controller.status(name).await {
                     // Apply status filter if provided
                     if let Some(ref filter_status) = query.status {
                         if status.state.to_string() != *filter_status {
@@ -274,14 +613,18 @@ pub async fn get_service(
 ) -> impl IntoResponse {
     state.increment_requests();
 
-    let status_result = state.controller.status(&name).await;
+    let controller = state.controller();
+    let status_result = controller.status(&name).await;
 
     match status_result {
         Ok(status) => {
+            let readiness = controller.readiness(&name);
             let data = ServiceDetailData {
                 name: status.name,
                 status: status.state.to_string(),
                 description: status.description,
+                ready: readiness.as_ref().map(|r| r.ready),
+                readiness,
             };
 
             state.increment_success();
@@ -310,8 +653,18 @@ pub async fn get_service(
 pub async fn start_service(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    service_action(state, name, ServiceAction::Start).await
+    let caller = caller(&state, &headers, remote);
+    service_action(
+        state,
+        name,
+        ServiceAction::Start,
+        request_id(&headers),
+        caller,
+    )
+    .await
 }
 
 /// Stop service handler.
@@ -320,8 +673,18 @@ pub async fn start_service(
 pub async fn stop_service(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    service_action(state, name, ServiceAction::Stop).await
+    let caller = caller(&state, &headers, remote);
+    service_action(
+        state,
+        name,
+        ServiceAction::Stop,
+        request_id(&headers),
+        caller,
+    )
+    .await
 }
 
 /// Restart service handler.
@@ -330,8 +693,18 @@ pub async fn stop_service(
 pub async fn restart_service(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    service_action(state, name, ServiceAction::Restart).await
+    let caller = caller(&state, &headers, remote);
+    service_action(
+        state,
+        name,
+        ServiceAction::Restart,
+        request_id(&headers),
+        caller,
+    )
+    .await
 }
 
 /// Common service action handler.
@@ -339,9 +712,15 @@ async fn service_action(
     state: Arc<AppState>,
     service: String,
     action: ServiceAction,
+    request_id: Uuid,
+    caller: Option<String>,
 ) -> impl IntoResponse {
     state.increment_requests();
 
+    let start_time = Instant::now();
+    let started_at = Utc::now();
+    let controller = state.controller();
+
     info!(
         service = %service,
         action = %action,
@@ -349,15 +728,15 @@ async fn service_action(
     );
 
     // Get previous status
-    let previous_state = state
-        .controller
+    let previous_state = controller
         .status(&service)
         .await
         .ok()
         .map(|s| s.state.to_string());
 
     // Perform the action
-    let result = state.controller.perform_action(&service, action).await;
+    let result = controller.perform_action(&service, action).await;
+    let duration_ms = start_time.elapsed().as_millis() as u64;
 
     match result {
         Ok(op_result) => {
@@ -367,8 +746,26 @@ async fn service_action(
                 success: op_result.success,
                 previous_state,
                 current_state: op_result.state.to_string(),
+                related: op_result
+                    .related
+                    .iter()
+                    .map(RelatedOperation::from)
+                    .collect(),
                 message: op_result.message,
             };
+            state.record_operation(HistoryEntry {
+                id: 0,
+                request_id,
+                timestamp: started_at,
+                caller,
+                service: data.service.clone(),
+                action: data.action.clone(),
+                previous_state: data.previous_state.clone(),
+                current_state: Some(data.current_state.clone()),
+                duration_ms,
+                result: if data.success { "completed" } else { "failed" }.to_string(),
+                message: data.message.clone(),
+            });
 
             state.increment_success();
             (StatusCode::OK, Json(ApiResponse::success(data)))
@@ -376,6 +773,19 @@ async fn service_action(
         Err(err) => {
             error!(error = %err, "Service action failed");
             state.increment_failed();
+            state.record_operation(HistoryEntry {
+                id: 0,
+                request_id,
+                timestamp: started_at,
+                caller,
+                service,
+                action: action.to_string(),
+                previous_state,
+                current_state: None,
+                duration_ms,
+                result: "error".to_string(),
+                message: Some(err.to_string()),
+            });
 
             let status_code = match &err {
                 ShikiError::ServiceNotFound { .. } => StatusCode::NOT_FOUND,
@@ -391,3 +801,857 @@ async fn service_action(
         }
     }
 }
+
+/// Operation history handler.
+///
+/// GET /api/v1/history
+pub async fn history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    state.increment_requests();
+    let Some(history) = &state.history else {
+        state.increment_failed();
+        let err = ShikiError::invalid_request(format!(
+            "Operation history is disabled on {}",
+            state.agent_name
+        ));
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::from_error(&err)),
+        )
+            .into_response();
+    };
+
+    match history.query(query).await {
+        Ok(data) => {
+            state.increment_success();
+            (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+        }
+        Err(err) => {
+            error!(error = %err, "History query failed");
+            state.increment_failed();
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<()>::from_error(&err)),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Service watcher handler.
+///
+/// GET /api/v1/watch
+pub async fn watch(State(state): State<Arc<AppState>>) -> Response {
+    state.increment_requests();
+    let Some(watcher) = &state.watcher else {
+        state.increment_failed();
+        let err =
+            ShikiError::invalid_request(format!("No services are watched on {}", state.agent_name));
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::from_error(&err)),
+        )
+            .into_response();
+    };
+
+    state.increment_success();
+    let data = WatchData {
+        services: watcher.statuses(),
+    };
+    (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+}
+
+/// Desired-state drift handler.
+///
+/// GET /api/v1/drift
+pub async fn drift(State(state): State<Arc<AppState>>) -> Response {
+    state.increment_requests();
+    let reconciler = match reconciler(&state) {
+        Ok(reconciler) => reconciler,
+        Err(response) => return response,
+    };
+
+    let data = reconciler.drift(&state).await;
+    state.increment_success();
+    (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+}
+
+/// Reconciliation handler - runs a reconciliation pass now.
+///
+/// POST /api/v1/reconcile
+pub async fn reconcile(State(state): State<Arc<AppState>>) -> Response {
+    state.increment_requests();
+    let reconciler = match reconciler(&state) {
+        Ok(reconciler) => reconciler,
+        Err(response) => return response,
+    };
+
+    let data = reconciler.reconcile(&state).await;
+    if data
+        .services
+        .iter()
+        .any(|s| s.result.as_deref() == Some("failed"))
+    {
+        state.increment_failed();
+    } else {
+        state.increment_success();
+    }
+    (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+}
+
+/// Returns the reconciler, or a 403 response when no service is declared.
+fn reconciler(state: &AppState) -> std::result::Result<&Reconciler, Response> {
+    state.reconciler.as_ref().ok_or_else(|| {
+        state.increment_failed();
+        let err = ShikiError::invalid_request(format!(
+            "No desired state is declared on {}",
+            state.agent_name
+        ));
+        (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::from_error(&err)),
+        )
+            .into_response()
+    })
+}
+
+/// Configuration reload handler - re-reads the configuration file.
+///
+/// POST /api/v1/admin/reload
+pub async fn reload_config(State(state): State<Arc<AppState>>) -> Response {
+    state.increment_requests();
+
+    match state.reload() {
+        Ok(data) => {
+            state.increment_success();
+            (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+        }
+        Err(err) => {
+            error!(error = %err, "Configuration reload failed; keeping the running configuration");
+            state.increment_failed();
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::<()>::from_error(&err)),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Forwarding handler - relays a notify or service request to a peer.
+///
+/// ANY /api/v1/peers/:peer/*path
+pub async fn forward(
+    State(state): State<Arc<AppState>>,
+    Path((peer, path)): Path<(String, String)>,
+    RawQuery(query): RawQuery,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    state.increment_requests();
+
+    let outcome = forward_to_peer(
+        &state,
+        &peer,
+        &path,
+        query.as_deref(),
+        method,
+        &headers,
+        body,
+    )
+    .await;
+    relayed_response(&state, &peer, &path, outcome)
+}
+
+/// Converts the outcome of a request relayed to a peer into a response.
+fn relayed_response(
+    state: &AppState,
+    peer: &str,
+    path: &str,
+    outcome: std::result::Result<(u16, Vec<u8>), (StatusCode, ShikiError)>,
+) -> Response {
+    match outcome {
+        Ok((status, body)) => {
+            if (200..300).contains(&status) {
+                state.increment_success();
+            } else {
+                state.increment_failed();
+            }
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
+            (
+                status,
+                [(axum::http::header::CONTENT_TYPE, "application/json")],
+                body,
+            )
+                .into_response()
+        }
+        Err((status, err)) => {
+            error!(peer = %peer, path = %path, error = %err, "Forwarding failed");
+            state.increment_failed();
+            (status, Json(ApiResponse::<()>::from_error(&err))).into_response()
+        }
+    }
+}
+
+/// Checks a forwarding request against this agent's settings and relays it.
+async fn forward_to_peer(
+    state: &AppState,
+    peer: &str,
+    path: &str,
+    query: Option<&str>,
+    method: Method,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> std::result::Result<(u16, Vec<u8>), (StatusCode, ShikiError)> {
+    if !state.forwarding.enabled {
+        return Err((
+            StatusCode::FORBIDDEN,
+            ShikiError::invalid_request(format!(
+                "Request forwarding is disabled on {}",
+                state.agent_name
+            )),
+        ));
+    }
+
+    let service =
+        forward::forwarded_service(&method, path, &body).map_err(|e| (StatusCode::NOT_FOUND, e))?;
+    if let Some(service) = service.filter(|s| !state.acl().is_allowed(s)) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            ShikiError::ServiceDenied {
+                reason: format!("Service is not allowed by the ACL of {}", state.agent_name),
+                service,
+            },
+        ));
+    }
+
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let mut hops = forward::parse_list(header(HOPS_HEADER));
+    forward::check_hops(&state.agent_name, peer, &hops, state.forwarding.max_hops)
+        .map_err(|e| (StatusCode::LOOP_DETECTED, e))?;
+    hops.push(state.agent_name.clone());
+
+    let peer_config = state.peer_config(peer).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            ShikiError::invalid_request(format!("Unknown peer: {}", peer)),
+        )
+    })?;
+
+    // Leave room for the peer to finish a waiting notify request
+    let mut timeout = state.http_timeout;
+    if method == Method::POST && path.trim_matches('/') == "notify" {
+        if let Ok(request) = serde_json::from_slice::<NotifyRequest>(&body) {
+            timeout = timeout.max(Duration::from_secs(request.options.timeout_seconds + 5));
+        }
+    }
+
+    let route = forward::parse_list(header(ROUTE_HEADER));
+    forward::forward(ForwardRequest {
+        peer: &peer_config,
+        route: &route,
+        method,
+        path,
+        query,
+        body: body.to_vec(),
+        request_id: request_id(headers),
+        hops: &hops,
+        authorization: relayed_authorization(state, peer, headers),
+        timeout,
+    })
+    .await
+    .map_err(relay_error)
+}
+
+/// Returns the caller's `Authorization` header to pass on to `peer`. Only
+/// configured peers receive it: a discovered peer is merely an address that
+/// announced itself.
+fn relayed_authorization<'a>(
+    state: &AppState,
+    peer: &str,
+    headers: &'a HeaderMap,
+) -> Option<&'a str> {
+    if !state.peers.iter().any(|p| p.name == peer) {
+        return None;
+    }
+    headers.get("authorization").and_then(|v| v.to_str().ok())
+}
+
+/// Maps an error of a relayed request to the status returned to the caller.
+fn relay_error(e: ShikiError) -> (StatusCode, ShikiError) {
+    let status = match &e {
+        ShikiError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    (status, e)
+}
+
+/// Query parameters of a signal clear request.
+#[derive(Debug, Deserialize)]
+pub struct ClearSignalQuery {
+    /// Clear the signal only for this signaler.
+    pub agent: Option<String>,
+}
+
+/// Query parameters of a barrier wait.
+#[derive(Debug, Deserialize)]
+pub struct BarrierQuery {
+    /// Number of signalers to wait for.
+    #[serde(default = "default_barrier_count")]
+    pub count: usize,
+    /// Timeout in seconds.
+    #[serde(default = "default_barrier_timeout")]
+    pub timeout: u64,
+}
+
+fn default_barrier_count() -> usize {
+    1
+}
+
+fn default_barrier_timeout() -> u64 {
+    60
+}
+
+/// Returns the coordinator a signal or barrier request has to be relayed to.
+fn signal_coordinator(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    coordinator(state, headers, state.signals.coordinator.as_ref())
+}
+
+/// Returns the coordinator a lock request has to be relayed to.
+fn lock_coordinator(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    coordinator(state, headers, state.locks.coordinator.as_ref())
+}
+
+/// Returns `configured` unless this agent is the coordinator itself.
+fn coordinator(
+    state: &AppState,
+    headers: &HeaderMap,
+    configured: Option<&String>,
+) -> Option<String> {
+    let coordinator = configured?;
+    // Requests relayed by another agent are always handled locally. A client
+    // cannot bypass the coordinator by sending the hops header itself.
+    let relayed = headers.contains_key(HOPS_HEADER) && auth::is_peer(&state.peers, headers);
+    (*coordinator != state.agent_name && !relayed).then(|| coordinator.clone())
+}
+
+/// A request relayed to a coordinator.
+struct Relay<'a> {
+    method: Method,
+    path: &'a str,
+    query: Option<&'a str>,
+    body: Vec<u8>,
+    timeout: Duration,
+}
+
+/// Relays a request to `coordinator`. Returns `None` when there is no
+/// coordinator and the request is to be handled by this agent.
+async fn relay_to_coordinator(
+    state: &AppState,
+    headers: &HeaderMap,
+    coordinator: Option<String>,
+    relay: Relay<'_>,
+) -> Option<Response> {
+    let coordinator = coordinator?;
+    let coordinator = coordinator.as_str();
+    let outcome = match state.peer_config(coordinator) {
+        Some(peer) => {
+            let hops = vec![state.agent_name.clone()];
+            forward::forward(ForwardRequest {
+                peer: &peer,
+                route: &[],
+                method: relay.method,
+                path: relay.path,
+                query: relay.query,
+                body: relay.body,
+                request_id: request_id(headers),
+                hops: &hops,
+                authorization: relayed_authorization(state, coordinator, headers),
+                timeout: relay.timeout,
+            })
+            .await
+            .map_err(relay_error)
+        }
+        None => Err((
+            StatusCode::BAD_GATEWAY,
+            ShikiError::backend(format!("Coordinator is not a known peer: {}", coordinator)),
+        )),
+    };
+    Some(relayed_response(state, coordinator, relay.path, outcome))
+}
+
+/// Returns a 400 response for an invalid signal request.
+fn signal_bad_request(state: &AppState, err: &ShikiError) -> Response {
+    state.increment_failed();
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ApiResponse::<()>::from_error(err)),
+    )
+        .into_response()
+}
+
+/// List signals handler.
+///
+/// GET /api/v1/signals
+pub async fn list_signals(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    state.increment_requests();
+    let timeout = state.http_timeout;
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        signal_coordinator(&state, &headers),
+        Relay {
+            method: Method::GET,
+            path: "signals",
+            query: None,
+            body: Vec::new(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    state.increment_success();
+    let signals = state.signal_store.list();
+    (StatusCode::OK, Json(ApiResponse::success(signals))).into_response()
+}
+
+/// Get signal handler.
+///
+/// GET /api/v1/signals/:name
+pub async fn get_signal(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    state.increment_requests();
+    if let Err(e) = signals::validate_name(&name) {
+        return signal_bad_request(&state, &e);
+    }
+    let timeout = state.http_timeout;
+    let path = format!("signals/{}", name);
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        signal_coordinator(&state, &headers),
+        Relay {
+            method: Method::GET,
+            path: &path,
+            query: None,
+            body: Vec::new(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    state.increment_success();
+    let signal = state.signal_store.get(&name);
+    (StatusCode::OK, Json(ApiResponse::success(signal))).into_response()
+}
+
+/// Set signal handler.
+///
+/// PUT /api/v1/signals/:name
+pub async fn set_signal(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    state.increment_requests();
+    let mut request: SetSignalRequest = if body.is_empty() {
+        SetSignalRequest::default()
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                let err = ShikiError::invalid_request(format!("Invalid signal request: {}", e));
+                return signal_bad_request(&state, &err);
+            }
+        }
+    };
+    // Scripts calling their local agent signal under the agent's name
+    let agent = request
+        .agent
+        .get_or_insert_with(|| state.agent_name.clone())
+        .clone();
+
+    let timeout = state.http_timeout;
+    let path = format!("signals/{}", name);
+    let body = serde_json::to_vec(&request).unwrap_or_default();
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        signal_coordinator(&state, &headers),
+        Relay {
+            method: Method::PUT,
+            path: &path,
+            query: None,
+            body,
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    let ttl = request
+        .ttl_seconds
+        .unwrap_or(state.signals.default_ttl_seconds);
+    if ttl == 0 || ttl > state.signals.max_ttl_seconds {
+        let err = ShikiError::invalid_request(format!(
+            "ttl_seconds must be between 1 and {}",
+            state.signals.max_ttl_seconds
+        ));
+        return signal_bad_request(&state, &err);
+    }
+
+    match state
+        .signal_store
+        .set(&name, &agent, Duration::from_secs(ttl))
+    {
+        Ok(signal) => {
+            state.increment_success();
+            (StatusCode::OK, Json(ApiResponse::success(signal))).into_response()
+        }
+        Err(e) => signal_bad_request(&state, &e),
+    }
+}
+
+/// Clear signal handler.
+///
+/// DELETE /api/v1/signals/:name
+pub async fn clear_signal(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<ClearSignalQuery>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
+    state.increment_requests();
+    if let Err(e) = signals::validate_name(&name) {
+        return signal_bad_request(&state, &e);
+    }
+    let timeout = state.http_timeout;
+    let path = format!("signals/{}", name);
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        signal_coordinator(&state, &headers),
+        Relay {
+            method: Method::DELETE,
+            path: &path,
+            query: raw_query.as_deref(),
+            body: Vec::new(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    state.signal_store.clear(&name, query.agent.as_deref());
+    state.increment_success();
+    let signal = state.signal_store.get(&name);
+    (StatusCode::OK, Json(ApiResponse::success(signal))).into_response()
+}
+
+/// Barrier wait handler - blocks until enough signalers have set a signal.
+///
+/// GET /api/v1/barriers/:name/wait
+pub async fn wait_barrier(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<BarrierQuery>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
+    state.increment_requests();
+    if let Err(e) = signals::validate_name(&name) {
+        return signal_bad_request(&state, &e);
+    }
+    if query.count == 0 || query.timeout == 0 || query.timeout > state.signals.max_wait_seconds {
+        let err = ShikiError::invalid_request(format!(
+            "count must be > 0 and timeout between 1 and {}",
+            state.signals.max_wait_seconds
+        ));
+        return signal_bad_request(&state, &err);
+    }
+    // Leave room for the coordinator to answer a timed out wait
+    let timeout = Duration::from_secs(query.timeout + 5);
+    let path = format!("barriers/{}/wait", name);
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        signal_coordinator(&state, &headers),
+        Relay {
+            method: Method::GET,
+            path: &path,
+            query: raw_query.as_deref(),
+            body: Vec::new(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    let started = Instant::now();
+    match state
+        .signal_store
+        .wait(&name, query.count, Duration::from_secs(query.timeout))
+        .await
+    {
+        Ok(signal) => {
+            state.increment_success();
+            let data = BarrierData {
+                name,
+                required: query.count,
+                waited_ms: started.elapsed().as_millis() as u64,
+                signal,
+            };
+            (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+        }
+        Err(err) => {
+            state.increment_failed();
+            let status = match &err {
+                ShikiError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            (status, Json(ApiResponse::<()>::from_error(&err))).into_response()
+        }
+    }
+}
+
+/// Query parameters of a lock release.
+#[derive(Debug, Deserialize)]
+pub struct ReleaseLockQuery {
+    /// Fencing token returned when the lock was acquired.
+    pub fencing_token: Option<u64>,
+}
+
+/// Returns the response for a failed lock request: 409 when the lock is held
+/// by someone else, 400 otherwise.
+fn lock_failure(state: &AppState, err: &ShikiError) -> Response {
+    state.increment_failed();
+    let status = match err {
+        ShikiError::LockConflict { .. } => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    (status, Json(ApiResponse::<()>::from_error(err))).into_response()
+}
+
+/// Checks a requested lock TTL against the configured limits.
+fn lock_ttl(state: &AppState, ttl_seconds: Option<u64>) -> Result<Duration, ShikiError> {
+    let ttl = ttl_seconds.unwrap_or(state.locks.default_ttl_seconds);
+    if ttl == 0 || ttl > state.locks.max_ttl_seconds {
+        return Err(ShikiError::invalid_request(format!(
+            "ttl_seconds must be between 1 and {}",
+            state.locks.max_ttl_seconds
+        )));
+    }
+    Ok(Duration::from_secs(ttl))
+}
+
+/// List locks handler.
+///
+/// GET /api/v1/locks
+pub async fn list_locks(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    state.increment_requests();
+    let timeout = state.http_timeout;
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        lock_coordinator(&state, &headers),
+        Relay {
+            method: Method::GET,
+            path: "locks",
+            query: None,
+            body: Vec::new(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    state.increment_success();
+    let locks = state.lock_store.list();
+    (StatusCode::OK, Json(ApiResponse::success(locks))).into_response()
+}
+
+/// Acquire lock handler - waits up to `wait_seconds` for the lock to be free.
+///
+/// POST /api/v1/locks/:name
+pub async fn acquire_lock(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    state.increment_requests();
+    let mut request: AcquireLockRequest = if body.is_empty() {
+        AcquireLockRequest::default()
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                let err = ShikiError::invalid_request(format!("Invalid lock request: {}", e));
+                return lock_failure(&state, &err);
+            }
+        }
+    };
+    // Scripts calling their local agent lock under the agent's name
+    let owner = request
+        .owner
+        .get_or_insert_with(|| state.agent_name.clone())
+        .clone();
+    let wait = request.wait_seconds.unwrap_or(0);
+    if wait > state.locks.max_wait_seconds {
+        let err = ShikiError::invalid_request(format!(
+            "wait_seconds must be at most {}",
+            state.locks.max_wait_seconds
+        ));
+        return lock_failure(&state, &err);
+    }
+
+    // Leave room for the coordinator to answer a timed out wait
+    let timeout = state.http_timeout + Duration::from_secs(wait);
+    let path = format!("locks/{}", name);
+    let body = serde_json::to_vec(&request).unwrap_or_default();
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        lock_coordinator(&state, &headers),
+        Relay {
+            method: Method::POST,
+            path: &path,
+            query: None,
+            body,
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    let ttl = match lock_ttl(&state, request.ttl_seconds) {
+        Ok(ttl) => ttl,
+        Err(e) => return lock_failure(&state, &e),
+    };
+    match state
+        .lock_store
+        .acquire(&name, &owner, ttl, Duration::from_secs(wait))
+        .await
+    {
+        Ok(lock) => {
+            state.increment_success();
+            (StatusCode::OK, Json(ApiResponse::success(lock))).into_response()
+        }
+        Err(e) => lock_failure(&state, &e),
+    }
+}
+
+/// Renew lock handler.
+///
+/// POST /api/v1/locks/:name/renew
+pub async fn renew_lock(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    state.increment_requests();
+    let request: RenewLockRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            let err = ShikiError::invalid_request(format!("Invalid lock renewal: {}", e));
+            return lock_failure(&state, &err);
+        }
+    };
+    let timeout = state.http_timeout;
+    let path = format!("locks/{}/renew", name);
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        lock_coordinator(&state, &headers),
+        Relay {
+            method: Method::POST,
+            path: &path,
+            query: None,
+            body: body.to_vec(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    let result = lock_ttl(&state, request.ttl_seconds)
+        .and_then(|ttl| state.lock_store.renew(&name, request.fencing_token, ttl));
+    match result {
+        Ok(lock) => {
+            state.increment_success();
+            (StatusCode::OK, Json(ApiResponse::success(lock))).into_response()
+        }
+        Err(e) => lock_failure(&state, &e),
+    }
+}
+
+/// Release lock handler.
+///
+/// DELETE /api/v1/locks/:name?fencing_token=N
+pub async fn release_lock(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<ReleaseLockQuery>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
+    state.increment_requests();
+    let Some(fencing_token) = query.fencing_token else {
+        let err = ShikiError::invalid_request("fencing_token is required to release a lock");
+        return lock_failure(&state, &err);
+    };
+    let timeout = state.http_timeout;
+    let path = format!("locks/{}", name);
+    if let Some(response) = relay_to_coordinator(
+        &state,
+        &headers,
+        lock_coordinator(&state, &headers),
+        Relay {
+            method: Method::DELETE,
+            path: &path,
+            query: raw_query.as_deref(),
+            body: Vec::new(),
+            timeout,
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    match state.lock_store.release(&name, fencing_token) {
+        Ok(released) => {
+            state.increment_success();
+            let data = ReleaseLockData { name, released };
+            (StatusCode::OK, Json(ApiResponse::success(data))).into_response()
+        }
+        Err(e) => lock_failure(&state, &e),
+    }
+}
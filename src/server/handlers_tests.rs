@@ -4,8 +4,8 @@
 mod tests {
     use crate::config::{Backend, ServiceDefinition};
     use crate::server::handlers::{
-        get_service, health, list_services, notify, restart_service, start_service, status,
-        stop_service,
+        cluster, cluster_notify, get_service, health, list_services, notify, restart_service,
+        start_service, status, stop_service,
     };
     use crate::server::state::AppState;
     use axum::{
@@ -42,6 +42,8 @@ mod tests {
         Router::new()
             .route("/api/v1/health", get(health))
             .route("/api/v1/status", get(status))
+            .route("/api/v1/cluster", get(cluster))
+            .route("/api/v1/cluster/notify", post(cluster_notify))
             .route("/api/v1/notify", post(notify))
             .route("/api/v1/services", get(list_services))
             .route("/api/v1/services/:name", get(get_service))
@@ -219,4 +221,1350 @@ mod tests {
         let response = app.oneshot(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn test_notify_wait_ready() {
+        let marker = tempfile::NamedTempFile::new().unwrap();
+
+        let mut config = crate::config::Config::default();
+        config.agent.backend = Backend::Exec;
+        config.services.insert(
+            "test-service".to_string(),
+            ServiceDefinition {
+                start: "true".to_string(),
+                stop: "true".to_string(),
+                status: "true".to_string(),
+                ..Default::default()
+            },
+        );
+        config.readiness.insert(
+            "test-service".to_string(),
+            crate::config::ReadinessProbe {
+                check: crate::config::ProbeCheck::File {
+                    path: marker.path().display().to_string(),
+                },
+                interval_seconds: 1,
+                timeout_seconds: 1,
+                success_threshold: 1,
+                failure_threshold: 1,
+                initial_delay_seconds: 0,
+            },
+        );
+        let state = Arc::new(AppState::new(&config).unwrap());
+        state.controller().start_probes();
+        let app = create_test_router(state);
+
+        let body = r#"{"action": "start", "service": "test-service", "options": {"wait_ready": true, "timeout_seconds": 5}}"#;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/notify")
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["data"]["current_status"], "ready");
+    }
+
+    #[tokio::test]
+    async fn test_cluster_endpoint() {
+        let mut config = crate::config::Config::default();
+        config.agent.backend = Backend::Exec;
+        config.agent.name = Some("node-1".to_string());
+        config.agent.mode = crate::config::AgentMode::Cluster;
+        config.cluster.peers = vec![crate::config::PeerConfig {
+            name: "node-2".to_string(),
+            address: "127.0.0.1:1".to_string(),
+            tags: vec![],
+            token: None,
+        }];
+        config.services.insert(
+            "test-service".to_string(),
+            ServiceDefinition {
+                start: "true".to_string(),
+                stop: "true".to_string(),
+                status: "true".to_string(),
+                ..Default::default()
+            },
+        );
+        let app = create_test_router(Arc::new(AppState::new(&config).unwrap()));
+
+        let request = Request::builder()
+            .uri("/api/v1/cluster")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let data = &json["data"];
+        assert_eq!(data["enabled"], true);
+        assert_eq!(data["local"]["name"], "node-1");
+        assert_eq!(data["peers"][0]["name"], "node-2");
+        assert_eq!(data["peers"][0]["reachable"], false);
+        assert_eq!(data["summary"]["total"], 1);
+        assert_eq!(data["summary"]["unreachable"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_cluster_notify_endpoint() {
+        let mut config = crate::config::Config::default();
+        config.agent.backend = Backend::Exec;
+        config.agent.name = Some("node-1".to_string());
+        config.agent.mode = crate::config::AgentMode::Cluster;
+        config.agent.tags = vec!["role=db".to_string()];
+        config.cluster.peers = vec![
+            crate::config::PeerConfig {
+                name: "node-2".to_string(),
+                address: "127.0.0.1:1".to_string(),
+                tags: vec!["role=db".to_string()],
+                token: None,
+            },
+            crate::config::PeerConfig {
+                name: "node-3".to_string(),
+                address: "127.0.0.1:1".to_string(),
+                tags: vec!["role=web".to_string()],
+                token: None,
+            },
+        ];
+        config.services.insert(
+            "test-service".to_string(),
+            ServiceDefinition {
+                start: "true".to_string(),
+                stop: "true".to_string(),
+                status: "true".to_string(),
+                ..Default::default()
+            },
+        );
+        let app = create_test_router(Arc::new(AppState::new(&config).unwrap()));
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/cluster/notify")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"selector":"role=db","action":"start","service":"test-service","fanout":{"max_failures":1}}"#,
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let data = &json["data"];
+        assert_eq!(data["selector"], "role=db");
+        assert_eq!(data["results"].as_array().unwrap().len(), 2);
+        assert_eq!(data["results"][0]["agent"], "node-1");
+        assert_eq!(data["results"][0]["status"], "succeeded");
+        assert_eq!(data["results"][0]["result"]["result"], "completed");
+        assert_eq!(data["results"][1]["agent"], "node-2");
+        assert_eq!(data["results"][1]["status"], "failed");
+        assert_eq!(data["summary"]["succeeded"], 1);
+        assert_eq!(data["summary"]["failed"], 1);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/cluster/notify")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"selector":"role=cache","action":"start","service":"test-service"}"#,
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Builds an exec-backend config with a single `test-service`.
+    fn agent_config(name: &str, token: &str) -> crate::config::Config {
+        let mut config = crate::config::Config::default();
+        config.agent.backend = Backend::Exec;
+        config.agent.name = Some(name.to_string());
+        config.auth = crate::config::AuthConfig {
+            enabled: true,
+            method: crate::config::AuthMethod::Token,
+            token: Some(token.to_string()),
+            api_keys: vec![],
+        };
+        config.services.insert(
+            "test-service".to_string(),
+            ServiceDefinition {
+                start: "true".to_string(),
+                stop: "true".to_string(),
+                status: "true".to_string(),
+                ..Default::default()
+            },
+        );
+        config
+    }
+
+    /// Serves the full router on an ephemeral port and returns its address.
+    async fn spawn_agent(config: &crate::config::Config) -> String {
+        let state = Arc::new(AppState::new(config).unwrap());
+        if let Some(webhooks) = &state.webhooks {
+            webhooks.spawn();
+        }
+        if let Some(watcher) = &state.watcher {
+            watcher.spawn(&state);
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let service = crate::server::create_router(state)
+            .into_make_service_with_connect_info::<std::net::SocketAddr>();
+        tokio::spawn(async move {
+            axum::serve(listener, service).await.unwrap();
+        });
+        address
+    }
+
+    fn bastion_config(peers: Vec<crate::config::PeerConfig>) -> crate::config::Config {
+        let mut config = agent_config("bastion", "bastion-secret");
+        config.cluster.forwarding.enabled = true;
+        config.cluster.peers = peers;
+        config.acl.denied = vec!["secret-*".to_string()];
+        config
+    }
+
+    fn peer(name: &str, address: &str, token: Option<&str>) -> crate::config::PeerConfig {
+        crate::config::PeerConfig {
+            name: name.to_string(),
+            address: address.to_string(),
+            tags: vec![],
+            token: token.map(str::to_string),
+        }
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_forward_notify_through_bastion() {
+        let db = spawn_agent(&agent_config("db", "db-secret")).await;
+        let bastion = crate::server::create_router(Arc::new(
+            AppState::new(&bastion_config(vec![peer("db", &db, Some("db-secret"))])).unwrap(),
+        ));
+
+        let request_id = uuid::Uuid::new_v4();
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/peers/db/notify")
+            .header("content-type", "application/json")
+            .header("authorization", "Bearer bastion-secret")
+            .header("x-request-id", request_id.to_string())
+            .body(Body::from(r#"{"action":"start","service":"test-service"}"#))
+            .unwrap();
+        let response = bastion.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = json_body(response).await;
+        assert_eq!(json["data"]["result"], "completed");
+        assert_eq!(json["data"]["request_id"], request_id.to_string());
+
+        // Service endpoints are forwarded too
+        let request = Request::builder()
+            .uri("/api/v1/peers/db/services/test-service")
+            .header("authorization", "Bearer bastion-secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = bastion.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json_body(response).await["data"]["name"], "test-service");
+
+        // The bastion requires its own credentials
+        let request = Request::builder()
+            .uri("/api/v1/peers/db/services")
+            .header("authorization", "Bearer db-secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = bastion.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_forward_multi_hop_with_client() {
+        let db = spawn_agent(&agent_config("db", "shared")).await;
+        let mut gateway = agent_config("gateway", "shared");
+        gateway.cluster.forwarding.enabled = true;
+        gateway.cluster.peers = vec![peer("db", &db, None)];
+        let gateway = spawn_agent(&gateway).await;
+        let mut bastion = bastion_config(vec![peer("gateway", &gateway, None)]);
+        bastion.auth.token = Some("shared".to_string());
+        let bastion = spawn_agent(&bastion).await;
+
+        let client = crate::ShikiClient::new(&bastion)
+            .unwrap()
+            .with_token("shared")
+            .via(vec!["gateway".to_string(), "db".to_string()]);
+        let result = client
+            .notify(
+                "test-service",
+                crate::service::ServiceAction::Start,
+                true,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.result, "completed");
+
+        // Without a peer token the caller's credentials are passed on and
+        // checked again by the next hop
+        let client = crate::ShikiClient::new(&bastion)
+            .unwrap()
+            .with_token("wrong")
+            .via(vec!["gateway".to_string(), "db".to_string()]);
+        let err = client.list_services(None, None, None).await.unwrap_err();
+        assert!(err.to_string().contains("E007"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_forward_rejections() {
+        let bastion = |config: crate::config::Config| {
+            crate::server::create_router(Arc::new(AppState::new(&config).unwrap()))
+        };
+        let send = |app: Router, uri: &str, hops: &str, body: &'static str| {
+            let request = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .header("authorization", "Bearer bastion-secret")
+                .header("x-shiki-hops", hops)
+                .body(Body::from(body))
+                .unwrap();
+            app.oneshot(request)
+        };
+        let start = r#"{"action":"start","service":"test-service"}"#;
+        let config = bastion_config(vec![peer("db", "127.0.0.1:1", None)]);
+
+        // ACL of the forwarding agent
+        let response = send(
+            bastion(config.clone()),
+            "/api/v1/peers/db/notify",
+            "",
+            r#"{"action":"start","service":"secret-vault"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(json_body(response).await["error"]["code"], "E003");
+
+        // Loop protection
+        let response = send(
+            bastion(config.clone()),
+            "/api/v1/peers/db/notify",
+            "dmz,bastion",
+            start,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::LOOP_DETECTED);
+        let response = send(
+            bastion(config.clone()),
+            "/api/v1/peers/bastion/notify",
+            "",
+            start,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::LOOP_DETECTED);
+
+        // Unknown peer and unreachable peer
+        let response = send(
+            bastion(config.clone()),
+            "/api/v1/peers/web/notify",
+            "",
+            start,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send(
+            bastion(config.clone()),
+            "/api/v1/peers/db/notify",
+            "",
+            start,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(json_body(response).await["error"]["code"], "E006");
+
+        // Endpoints that are not forwarded
+        let response = send(
+            bastion(config.clone()),
+            "/api/v1/peers/db/cluster/notify",
+            "",
+            start,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Forwarding disabled
+        let mut disabled = config;
+        disabled.cluster.forwarding.enabled = false;
+        let response = send(bastion(disabled), "/api/v1/peers/db/notify", "", start)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_signals_and_barrier() {
+        let agent = spawn_agent(&agent_config("db-1", "secret")).await;
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret");
+
+        // Without an identity the agent's own name is used
+        let signal = client.set_signal("db-ready", None, None).await.unwrap();
+        assert_eq!(signal.count, 1);
+        assert_eq!(signal.signers[0].agent, "db-1");
+
+        let waiter = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .wait_barrier("db-ready", 2, std::time::Duration::from_secs(10))
+                    .await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!waiter.is_finished());
+
+        client
+            .set_signal("db-ready", Some("db-2"), Some(60))
+            .await
+            .unwrap();
+        let barrier = waiter.await.unwrap().unwrap();
+        assert_eq!(barrier.required, 2);
+        assert_eq!(barrier.signal.count, 2);
+
+        let signals = client.signals().await.unwrap();
+        assert_eq!(signals.len(), 1);
+
+        // A barrier that is not reached times out with E005
+        let err = client
+            .wait_barrier("db-ready", 3, std::time::Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::ShikiError::Timeout { .. }), "{}", err);
+        assert!(err.to_string().contains("2 of 3"), "{}", err);
+
+        let signal = client.clear_signal("db-ready", Some("db-1")).await.unwrap();
+        assert_eq!(signal.count, 1);
+        client.clear_signal("db-ready", None).await.unwrap();
+        assert_eq!(client.signal("db-ready").await.unwrap().count, 0);
+
+        // Invalid requests
+        assert!(client.set_signal("bad name", None, None).await.is_err());
+        let err = client
+            .set_signal("db-ready", None, Some(10_000_000))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ttl_seconds"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_signals_relayed_to_coordinator() {
+        let coordinator = spawn_agent(&agent_config("coordinator", "coord-secret")).await;
+        let mut config = agent_config("db-1", "secret");
+        config.cluster.peers = vec![peer("coordinator", &coordinator, Some("coord-secret"))];
+        config.signals.coordinator = Some("coordinator".to_string());
+        let agent = spawn_agent(&config).await;
+
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret");
+        let signal = client.set_signal("db-ready", None, None).await.unwrap();
+        assert_eq!(signal.signers[0].agent, "db-1");
+
+        // The signal is held by the coordinator
+        let direct = crate::ShikiClient::new(&coordinator)
+            .unwrap()
+            .with_token("coord-secret");
+        assert_eq!(direct.signal("db-ready").await.unwrap().count, 1);
+        direct
+            .set_signal("db-ready", Some("db-2"), None)
+            .await
+            .unwrap();
+
+        let barrier = client
+            .wait_barrier("db-ready", 2, std::time::Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(barrier.signal.count, 2);
+
+        // An unknown coordinator is reported as a bad gateway
+        let mut config = agent_config("db-3", "secret");
+        config.signals.coordinator = Some("missing".to_string());
+        let response = crate::server::create_router(Arc::new(AppState::new(&config).unwrap()))
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/signals")
+                    .header("Authorization", "Bearer secret")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_leader_endpoints_without_election() {
+        let config = agent_config("db-1", "secret");
+        let app = || crate::server::create_router(Arc::new(AppState::new(&config).unwrap()));
+
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/cluster/leader")
+                    .header("Authorization", "Bearer secret")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+        assert_eq!(body["data"]["enabled"], false);
+        assert_eq!(body["data"]["is_leader"], false);
+
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/cluster/election/vote")
+                    .header("Authorization", "Bearer secret")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(r#"{"term": 1, "candidate": "db-2"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_locks() {
+        let agent = spawn_agent(&agent_config("db-1", "secret")).await;
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret");
+
+        // Without an owner the agent's own name is used
+        let lock = client
+            .acquire_lock("db-migrate", None, Some(60), None)
+            .await
+            .unwrap();
+        assert_eq!(lock.owner, "db-1");
+        assert_eq!(client.locks().await.unwrap(), vec![lock.clone()]);
+
+        // A held lock conflicts with 409/E010, also after waiting
+        let err = client
+            .acquire_lock("db-migrate", Some("db-2"), None, Some(1))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, crate::ShikiError::LockConflict { holder: Some(h), .. } if h == "db-1"),
+            "{}",
+            err
+        );
+
+        let waiter = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .acquire_lock("db-migrate", Some("db-2"), None, Some(10))
+                    .await
+            })
+        };
+        let renewed = client
+            .renew_lock("db-migrate", lock.fencing_token, Some(120))
+            .await
+            .unwrap();
+        assert!(renewed.expires_at > lock.expires_at);
+
+        // A stale token neither renews nor releases the lock
+        assert!(client
+            .release_lock("db-migrate", lock.fencing_token + 1)
+            .await
+            .is_err());
+        let released = client
+            .release_lock("db-migrate", lock.fencing_token)
+            .await
+            .unwrap();
+        assert!(released.released);
+
+        let next = waiter.await.unwrap().unwrap();
+        assert_eq!(next.owner, "db-2");
+        assert!(next.fencing_token > lock.fencing_token);
+        let err = client
+            .renew_lock("db-migrate", lock.fencing_token, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, crate::ShikiError::LockConflict { .. }),
+            "{}",
+            err
+        );
+
+        // Invalid requests
+        let err = client
+            .acquire_lock("other", None, Some(10_000_000), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ttl_seconds"), "{}", err);
+        let response = crate::server::create_router(Arc::new(
+            AppState::new(&agent_config("db-1", "secret")).unwrap(),
+        ))
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/api/v1/locks/db-migrate")
+                .header("Authorization", "Bearer secret")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_locks_relayed_to_coordinator() {
+        let coordinator = spawn_agent(&agent_config("coordinator", "coord-secret")).await;
+        let agent = |name: &str| {
+            let mut config = agent_config(name, "secret");
+            config.cluster.peers = vec![peer("coordinator", &coordinator, Some("coord-secret"))];
+            config.locks.coordinator = Some("coordinator".to_string());
+            config
+        };
+        let client = |address: &str| {
+            crate::ShikiClient::new(address)
+                .unwrap()
+                .with_token("secret")
+        };
+        let db1 = client(&spawn_agent(&agent("db-1")).await);
+        let db2 = client(&spawn_agent(&agent("db-2")).await);
+
+        // Both agents lock on the coordinator
+        let lock = db1
+            .acquire_lock("db-migrate", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(lock.owner, "db-1");
+        let err = db2
+            .acquire_lock("db-migrate", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, crate::ShikiError::LockConflict { .. }),
+            "{}",
+            err
+        );
+        assert_eq!(db2.locks().await.unwrap(), vec![lock.clone()]);
+
+        assert!(
+            db2.release_lock("db-migrate", lock.fencing_token)
+                .await
+                .unwrap()
+                .released
+        );
+        assert!(db2
+            .acquire_lock("db-migrate", None, None, None)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hops_header_from_client_is_not_trusted() {
+        let coordinator = spawn_agent(&agent_config("coordinator", "coord-secret")).await;
+        let mut config = agent_config("db-1", "secret");
+        config.cluster.peers = vec![peer("coordinator", &coordinator, Some("coord-secret"))];
+        config.locks.coordinator = Some("coordinator".to_string());
+        let address = spawn_agent(&config).await;
+
+        // A client claiming to be a relay is still sent to the coordinator
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/api/v1/locks/db-migrate", address))
+            .bearer_auth("secret")
+            .header(crate::cluster::forward::HOPS_HEADER, "db-2")
+            .json(&serde_json::json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "{}", response.status());
+
+        let coordinator = crate::ShikiClient::new(&coordinator)
+            .unwrap()
+            .with_token("coord-secret");
+        let locks = coordinator.locks().await.unwrap();
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].name, "db-migrate");
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        use crate::history::HistoryQuery;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = agent_config("db", "secret");
+        // bastion relays with a key of its own, which tells its hops apart
+        config.auth.method = crate::config::AuthMethod::ApiKey;
+        config.auth.api_keys = vec!["secret".to_string(), "peer-secret".to_string()];
+        config.cluster.peers = vec![peer("bastion", "127.0.0.1:9", Some("peer-secret"))];
+        config.history.enabled = true;
+        config.history.path = dir.path().join("history.db");
+        let pid = dir.path().join("web.pid");
+        config.services.insert(
+            "web".to_string(),
+            ServiceDefinition {
+                start: format!("touch {}", pid.display()),
+                stop: format!("rm -f {}", pid.display()),
+                status: format!("test -f {}", pid.display()),
+                ..Default::default()
+            },
+        );
+        config.services.insert(
+            "broken".to_string(),
+            ServiceDefinition {
+                start: "false".to_string(),
+                stop: "true".to_string(),
+                status: "false".to_string(),
+                ..Default::default()
+            },
+        );
+        let db = spawn_agent(&config).await;
+        let client = crate::ShikiClient::new(&db).unwrap().with_token("secret");
+
+        client
+            .notify("web", crate::service::ServiceAction::Start, true, 10)
+            .await
+            .unwrap();
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/api/v1/services/web/stop", db))
+            .bearer_auth("secret")
+            .header("x-shiki-hops", "mallory")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let failed = client
+            .notify("broken", crate::service::ServiceAction::Start, true, 10)
+            .await;
+        assert!(failed.is_err() || failed.unwrap().result == "failed");
+
+        // Requests forwarded by another agent are attributed to it
+        let mut bastion = bastion_config(vec![peer("db", &db, Some("peer-secret"))]);
+        bastion.acl.denied.clear();
+        let bastion = spawn_agent(&bastion).await;
+        let via = crate::ShikiClient::new(&bastion)
+            .unwrap()
+            .with_token("bastion-secret")
+            .via(vec!["db".to_string()]);
+        via.notify(
+            "test-service",
+            crate::service::ServiceAction::Restart,
+            true,
+            10,
+        )
+        .await
+        .unwrap();
+
+        let all = client.history(&HistoryQuery::default()).await.unwrap();
+        assert_eq!(all.total, 4);
+        let actions: Vec<(&str, &str)> = all
+            .entries
+            .iter()
+            .map(|e| (e.service.as_str(), e.action.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("test-service", "restart"),
+                ("broken", "start"),
+                ("web", "stop"),
+                ("web", "start"),
+            ]
+        );
+        assert_eq!(all.entries[0].caller.as_deref(), Some("bastion"));
+        // A hop list sent by a client is not trusted
+        assert_eq!(all.entries[2].caller.as_deref(), Some("127.0.0.1"));
+        assert_eq!(all.entries[3].caller.as_deref(), Some("127.0.0.1"));
+        assert_eq!(all.entries[3].result, "completed");
+        assert_ne!(all.entries[1].result, "completed");
+
+        let stops = client
+            .history(&HistoryQuery {
+                service: Some("web".to_string()),
+                action: Some("stop".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(stops.total, 1);
+        let stop = &stops.entries[0];
+        assert_eq!(stop.result, "completed");
+        assert_eq!(stop.previous_state.as_deref(), Some("running"));
+        assert_eq!(stop.current_state.as_deref(), Some("stopped"));
+
+        let page = client
+            .history(&HistoryQuery {
+                limit: Some(2),
+                offset: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.entries[0].action, "stop");
+
+        let future = client
+            .history(&HistoryQuery {
+                since: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(future.total, 0);
+
+        // The history survives a restart; the request counters are saved
+        // periodically and on shutdown, not with every operation
+        let restarted = spawn_agent(&config).await;
+        let client = crate::ShikiClient::new(&restarted)
+            .unwrap()
+            .with_token("secret");
+        let all = client.history(&HistoryQuery::default()).await.unwrap();
+        assert_eq!(all.total, 4);
+    }
+
+    #[tokio::test]
+    async fn test_history_disabled() {
+        let app = crate::server::create_router(Arc::new(
+            AppState::new(&agent_config("db", "secret")).unwrap(),
+        ));
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/history")
+                    .header("Authorization", "Bearer secret")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut config = agent_config("db", "secret");
+        config.audit.enabled = true;
+        config.audit.path = path.clone();
+        config.acl.denied = vec!["secret-*".to_string()];
+        config.cluster.forwarding.enabled = true;
+        config.cluster.peers = vec![peer("cache", "127.0.0.1:1", None)];
+        let db = spawn_agent(&config).await;
+
+        let client = crate::ShikiClient::new(&db).unwrap().with_token("secret");
+        let result = client
+            .notify(
+                "test-service",
+                crate::service::ServiceAction::Start,
+                true,
+                10,
+            )
+            .await
+            .unwrap();
+        // Reads are not audited
+        client.list_services(None, None, None).await.unwrap();
+        let http = reqwest::Client::new();
+        let response = http
+            .post(format!(
+                "http://{}/api/v1/peers/cache/services/secret-db/stop",
+                db
+            ))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        let response = http
+            .get(format!("http://{}/api/v1/services", db))
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        assert_eq!(
+            crate::audit::verify(&path).unwrap(),
+            crate::audit::Verification::Intact { records: 3 }
+        );
+        let records: Vec<crate::audit::AuditRecord> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let notify = &records[0];
+        assert_eq!(notify.method, "POST");
+        assert_eq!(notify.path, "/api/v1/notify");
+        assert_eq!(notify.peer.as_deref(), Some("127.0.0.1"));
+        assert_eq!(notify.identity.as_deref(), Some("token"));
+        assert_eq!(
+            notify.request_id.as_deref(),
+            Some(result.request_id.to_string().as_str())
+        );
+        assert_eq!(notify.service.as_deref(), Some("test-service"));
+        assert_eq!(notify.action.as_deref(), Some("start"));
+        assert!(notify.acl.is_none());
+        assert_eq!(notify.outcome, "success");
+
+        let denied = &records[1];
+        assert_eq!(denied.service.as_deref(), Some("secret-db"));
+        assert_eq!(denied.action.as_deref(), Some("stop"));
+        assert_eq!(denied.acl.as_deref(), Some("denied"));
+        assert_eq!(denied.status, 403);
+        assert_eq!(denied.outcome, "failure");
+        assert!(denied.error.is_some());
+
+        let rejected = &records[2];
+        assert_eq!(rejected.method, "GET");
+        assert!(rejected.identity.is_none());
+        assert_eq!(rejected.outcome, "unauthenticated");
+    }
+
+    #[tokio::test]
+    async fn test_webhooks() {
+        use crate::config::{WebhookEndpoint, WebhookEvent};
+        use crate::webhooks::WebhookPayload;
+        use std::sync::Mutex;
+
+        // Receiver collecting the posted events
+        let received: Arc<Mutex<Vec<WebhookPayload>>> = Arc::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |axum::extract::State(received): axum::extract::State<
+                        Arc<Mutex<Vec<WebhookPayload>>>,
+                    >,
+                     axum::Json(payload): axum::Json<WebhookPayload>| async move {
+                        received.lock().unwrap().push(payload);
+                        StatusCode::OK
+                    },
+                ),
+            )
+            .with_state(Arc::clone(&received));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hook = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = agent_config("web-1", "secret");
+        config.webhooks.endpoints = vec![WebhookEndpoint {
+            url: hook,
+            events: vec![
+                WebhookEvent::OperationFailed,
+                WebhookEvent::ServiceStateChanged,
+            ],
+            secret: None,
+        }];
+        let pid = dir.path().join("web.pid");
+        config.services.insert(
+            "web".to_string(),
+            ServiceDefinition {
+                start: format!("touch {}", pid.display()),
+                stop: format!("rm -f {}", pid.display()),
+                status: format!("test -f {}", pid.display()),
+                ..Default::default()
+            },
+        );
+        config.services.insert(
+            "broken".to_string(),
+            ServiceDefinition {
+                start: "false".to_string(),
+                stop: "true".to_string(),
+                status: "false".to_string(),
+                ..Default::default()
+            },
+        );
+        let agent = spawn_agent(&config).await;
+        let http = reqwest::Client::new();
+        for (service, action) in [("web", "start"), ("web", "start"), ("broken", "start")] {
+            http.post(format!(
+                "http://{}/api/v1/services/{}/{}",
+                agent, service, action
+            ))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        }
+
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret");
+        let mut stats = None;
+        for _ in 0..100 {
+            let status = client.status().await.unwrap();
+            let webhooks = status.webhooks.unwrap();
+            if webhooks.pending == 0 {
+                stats = Some(webhooks);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        let stats = stats.expect("webhook queue not drained");
+
+        // The second start does not change the state of web
+        let received = received.lock().unwrap();
+        let events: Vec<(WebhookEvent, &str)> = received
+            .iter()
+            .map(|p| (p.event, p.data["service"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (WebhookEvent::ServiceStateChanged, "web"),
+                (WebhookEvent::OperationFailed, "broken"),
+                (WebhookEvent::ServiceStateChanged, "broken"),
+            ]
+        );
+        assert_eq!(received[0].agent, "web-1");
+        assert_eq!(received[0].data["previous_state"], "stopped");
+        assert_eq!(received[0].data["current_state"], "running");
+        assert_eq!(received[1].data["result"], "failed");
+        assert_eq!(received[2].data["current_state"], "failed");
+        assert_eq!((stats.endpoints, stats.delivered, stats.dropped), (1, 3, 0));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_restarts_crashed_service() {
+        use crate::config::WatchConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = agent_config("web-1", "secret");
+        let pid = dir.path().join("web.pid");
+        config.services.insert(
+            "web".to_string(),
+            ServiceDefinition {
+                start: format!("touch {}", pid.display()),
+                stop: format!("rm -f {}", pid.display()),
+                status: format!("test -f {}", pid.display()),
+                ..Default::default()
+            },
+        );
+        config.watch.insert(
+            "web".to_string(),
+            WatchConfig {
+                interval_seconds: 1,
+                restart_delay_ms: 0,
+                ..Default::default()
+            },
+        );
+        let agent = spawn_agent(&config).await;
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret");
+
+        // Started through the API, then crashes
+        client
+            .notify("web", crate::service::ServiceAction::Start, false, 10)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        std::fs::remove_file(&pid).unwrap();
+        for _ in 0..40 {
+            if pid.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert!(pid.exists(), "watcher did not restart the service");
+
+        // The decision is recorded once the restart command returns, which
+        // may be after the service wrote its pid file
+        let mut watch = client.watch().await.unwrap();
+        for _ in 0..40 {
+            if !watch.services[0].decisions.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            watch = client.watch().await.unwrap();
+        }
+        let web = &watch.services[0];
+        assert_eq!(
+            (web.service.as_str(), web.policy.as_str()),
+            ("web", "restart")
+        );
+        assert_eq!(web.restarts_in_window, 1);
+        let decision = &web.decisions[0];
+        assert_eq!(decision.observed_state, "stopped");
+        assert_eq!(
+            (decision.action.as_str(), decision.result.as_str()),
+            ("restart", "completed")
+        );
+
+        // Stopped through the API: left alone
+        client
+            .notify("web", crate::service::ServiceAction::Stop, false, 10)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2200)).await;
+        assert!(!pid.exists());
+        let watch = client.watch().await.unwrap();
+        assert!(watch.services[0].stopped_by_api);
+        assert_eq!(watch.services[0].decisions.len(), 1);
+
+        // Agents without watchers refuse the request
+        let other = spawn_agent(&agent_config("db", "secret")).await;
+        let err = crate::ShikiClient::new(&other)
+            .unwrap()
+            .with_token("secret")
+            .watch()
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("No services are watched"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drift_and_reconcile() {
+        use crate::config::{DesiredConditions, DesiredService, DesiredState, Schedule};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = agent_config("web-1", "secret");
+        let marker = |name: &str| dir.path().join(format!("{}.pid", name));
+        for name in ["web", "legacy", "batch", "gated"] {
+            let pid = marker(name);
+            config.services.insert(
+                name.to_string(),
+                ServiceDefinition {
+                    start: format!("touch {}", pid.display()),
+                    stop: format!("rm -f {}", pid.display()),
+                    status: format!("test -f {}", pid.display()),
+                    ..Default::default()
+                },
+            );
+        }
+        std::fs::write(marker("legacy"), "").unwrap();
+
+        // A window that is not active now
+        let now = chrono::Local::now().time();
+        let schedule = Schedule {
+            days: Vec::new(),
+            from: now + chrono::Duration::hours(1),
+            to: now + chrono::Duration::hours(2),
+        };
+        let declare = |state, schedule, signal: Option<&str>| DesiredService {
+            state,
+            schedule,
+            conditions: DesiredConditions {
+                leader: false,
+                signal: signal.map(str::to_string),
+            },
+        };
+        let services = &mut config.desired_state.services;
+        services.insert(
+            "web".to_string(),
+            declare(DesiredState::Running, None, None),
+        );
+        services.insert(
+            "legacy".to_string(),
+            declare(DesiredState::Stopped, None, None),
+        );
+        services.insert(
+            "batch".to_string(),
+            declare(DesiredState::Running, Some(schedule), None),
+        );
+        services.insert(
+            "gated".to_string(),
+            declare(DesiredState::Running, None, Some("go")),
+        );
+        let agent = spawn_agent(&config).await;
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret");
+
+        let drift = client.drift().await.unwrap();
+        assert!(!drift.in_sync);
+        let summary =
+            |drift: &crate::server::response::DriftData| -> Vec<(String, Option<String>)> {
+                drift
+                    .services
+                    .iter()
+                    .map(|s| (s.service.clone(), s.action.clone()))
+                    .collect()
+            };
+        assert_eq!(
+            summary(&drift),
+            vec![
+                ("batch".to_string(), None),
+                ("gated".to_string(), None),
+                ("legacy".to_string(), Some("stop".to_string())),
+                ("web".to_string(), Some("start".to_string())),
+            ]
+        );
+        assert!(drift.services[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .starts_with("outside schedule"));
+        assert_eq!(
+            drift.services[1].reason.as_deref(),
+            Some("signal go is not set")
+        );
+        // Nothing was changed
+        assert!(marker("legacy").exists() && !marker("web").exists());
+
+        client.set_signal("go", None, None).await.unwrap();
+        let reconciled = client.reconcile().await.unwrap();
+        assert_eq!(
+            summary(&reconciled),
+            vec![
+                ("batch".to_string(), None),
+                ("gated".to_string(), Some("start".to_string())),
+                ("legacy".to_string(), Some("stop".to_string())),
+                ("web".to_string(), Some("start".to_string())),
+            ]
+        );
+        assert!(reconciled.services[1..]
+            .iter()
+            .all(|s| s.result.as_deref() == Some("completed")));
+        assert!(marker("web").exists() && marker("gated").exists());
+        assert!(!marker("legacy").exists() && !marker("batch").exists());
+
+        assert!(client.drift().await.unwrap().in_sync);
+
+        // Agents without a declaration refuse the request
+        let other = spawn_agent(&agent_config("db", "secret")).await;
+        let err = crate::ShikiClient::new(&other)
+            .unwrap()
+            .with_token("secret")
+            .drift()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No desired state"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let write_config = |token: &str, services: &[&str]| {
+            let mut yaml = format!(
+                "agent:\n  name: web-1\n  backend: exec\nauth:\n  enabled: true\n  method: token\n  token: {}\nservices:\n",
+                token
+            );
+            for service in services {
+                yaml.push_str(&format!(
+                    "  {}:\n    start: \"true\"\n    stop: \"true\"\n    status: \"true\"\n",
+                    service
+                ));
+            }
+            std::fs::write(&path, yaml).unwrap();
+        };
+        write_config("old-secret", &["web"]);
+        let config = crate::config::Config::load_from_path(&path).unwrap();
+        let agent = spawn_agent(&config).await;
+        let client = |token: &str| {
+            crate::ShikiClient::new(&agent)
+                .unwrap()
+                .with_token(token.to_string())
+        };
+
+        let status = client("old-secret").status().await.unwrap();
+        assert_eq!(status.config.generation, 1);
+        assert_eq!(status.config.source, Some(path.display().to_string()));
+
+        // Unchanged file
+        let unchanged = client("old-secret").reload_config().await.unwrap();
+        assert!(!unchanged.changed);
+        assert_eq!(unchanged.config, status.config);
+
+        // A new service and token take effect without a restart
+        write_config("new-secret", &["web", "db"]);
+        let reloaded = client("old-secret").reload_config().await.unwrap();
+        assert!(reloaded.changed);
+        assert_eq!(reloaded.config.generation, 2);
+        assert_ne!(reloaded.config.hash, status.config.hash);
+        let changes: Vec<&str> = reloaded.changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(changes, vec!["auth.token", "services.db"]);
+        assert!(reloaded.changes.iter().all(|c| !c.restart_required));
+
+        assert!(client("old-secret").status().await.is_err());
+        let client = client("new-secret");
+        assert_eq!(
+            client.start_service("db").await.unwrap().result,
+            "completed"
+        );
+
+        // An invalid file is rejected and the running configuration kept
+        std::fs::write(&path, "server:\n  port: 0\n").unwrap();
+        let err = client.reload_config().await.unwrap_err();
+        assert!(err.to_string().contains("server.port"), "{}", err);
+        let status = client.status().await.unwrap();
+        assert_eq!(status.config, reloaded.config);
+        assert!(client.get_service("db").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_list_all_services_and_service_action() {
+        let mut config = agent_config("node", "secret");
+        for name in ["alpha", "beta"] {
+            config
+                .services
+                .insert(name.to_string(), config.services["test-service"].clone());
+        }
+        let agent = spawn_agent(&config).await;
+        let client = crate::ShikiClient::new(&agent)
+            .unwrap()
+            .with_token("secret".to_string());
+
+        let all = client.list_all_services(None, 0, None).await.unwrap();
+        assert_eq!((all.services.len(), all.total, all.limit), (3, 3, 3));
+        let page = client.list_all_services(None, 1, Some(1)).await.unwrap();
+        assert_eq!((page.services.len(), page.total, page.offset), (1, 3, 1));
+        assert_eq!(page.services[0].name, all.services[1].name);
+        let stopped = client
+            .list_all_services(Some("stopped"), 0, None)
+            .await
+            .unwrap();
+        assert!(stopped.services.is_empty());
+
+        let operation = client
+            .service_action("test-service", crate::service::ServiceAction::Start)
+            .await
+            .unwrap();
+        assert!(operation.success, "{:?}", operation);
+        assert_eq!(operation.action, "start");
+        assert_eq!(operation.current_state, "running");
+        assert!(client
+            .service_action("missing", crate::service::ServiceAction::Start)
+            .await
+            .is_err());
+    }
 }
@@ -3,25 +3,33 @@
 //! This module provides the HTTP server for shiki, including
 //! routing, request handling, and response formatting.
 
+pub mod audit;
+pub mod auth;
 pub mod handlers;
+pub mod reconcile;
+pub mod reload;
 pub mod response;
 pub mod state;
+pub mod watcher;
 
 #[cfg(test)]
 mod handlers_tests;
 
-use crate::config::Config;
+use crate::cluster::{Announcement, Discovery, Gossip};
+use crate::config::{Config, WebhookEvent};
 use crate::error::Result;
 use axum::{
-    routing::{get, post},
+    middleware,
+    routing::{any, get, post},
     Router,
 };
 use state::AppState;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
-use tracing::info;
+use tracing::{info, warn};
 
 /// Creates the API router with all endpoints.
 pub fn create_router(state: Arc<AppState>) -> Router {
@@ -29,6 +37,18 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         // Health and status endpoints
         .route("/api/v1/health", get(handlers::health))
         .route("/api/v1/status", get(handlers::status))
+        // Cluster endpoint
+        .route("/api/v1/cluster", get(handlers::cluster))
+        .route("/api/v1/cluster/notify", post(handlers::cluster_notify))
+        .route("/api/v1/cluster/leader", get(handlers::cluster_leader))
+        .route(
+            "/api/v1/cluster/election/vote",
+            post(handlers::election_vote),
+        )
+        .route(
+            "/api/v1/cluster/election/heartbeat",
+            post(handlers::election_heartbeat),
+        )
         // Notification endpoint
         .route("/api/v1/notify", post(handlers::notify))
         // Service endpoints
@@ -43,16 +63,144 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/api/v1/services/:name/restart",
             post(handlers::restart_service),
         )
+        // Signals and barriers
+        .route("/api/v1/signals", get(handlers::list_signals))
+        .route(
+            "/api/v1/signals/:name",
+            get(handlers::get_signal)
+                .put(handlers::set_signal)
+                .delete(handlers::clear_signal),
+        )
+        .route("/api/v1/barriers/:name/wait", get(handlers::wait_barrier))
+        // Locks
+        .route("/api/v1/locks", get(handlers::list_locks))
+        .route(
+            "/api/v1/locks/:name",
+            post(handlers::acquire_lock).delete(handlers::release_lock),
+        )
+        .route("/api/v1/locks/:name/renew", post(handlers::renew_lock))
+        // Operation history
+        .route("/api/v1/history", get(handlers::history))
+        // Service watcher
+        .route("/api/v1/watch", get(handlers::watch))
+        // Desired state
+        .route("/api/v1/drift", get(handlers::drift))
+        .route("/api/v1/reconcile", post(handlers::reconcile))
+        // Administration
+        .route("/api/v1/admin/reload", post(handlers::reload_config))
+        // Forwarding to peers
+        .route("/api/v1/peers/:peer/*path", any(handlers::forward))
+        // Authentication
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            auth::require_auth,
+        ))
+        // Auditing, around authentication to also see rejected requests
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            audit::record_audit,
+        ))
         // Add tracing layer
         .layer(TraceLayer::new_for_http())
         // Add state
         .with_state(state)
 }
 
+/// Returns the discovery announcement of this agent.
+fn announcement(config: &Config) -> Announcement {
+    let bind = config.server.bind.as_str();
+    let address = config
+        .cluster
+        .discovery
+        .advertise_address
+        .clone()
+        .or_else(|| {
+            // A specific bind address is reachable; a wildcard is replaced by
+            // the source address of the announcement on the receiving side.
+            (bind != "0.0.0.0" && bind != "::").then(|| bind.to_string())
+        });
+    Announcement {
+        name: config.agent_name(),
+        address,
+        port: config.server.port,
+        tls: config.server.tls.enabled,
+        tags: config.agent.tags.clone(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        timestamp_ms: 0,
+    }
+}
+
+/// Returns the HTTP address of this agent announced through gossip. A
+/// wildcard bind address is replaced by the receiving members.
+fn gossip_http_address(config: &Config) -> String {
+    let host = config
+        .cluster
+        .gossip
+        .advertise_address
+        .as_deref()
+        .unwrap_or(&config.server.bind);
+    let address = match host.parse::<std::net::IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, config.server.port).to_string(),
+        Err(_) => format!("{}:{}", host, config.server.port),
+    };
+    if config.server.tls.enabled {
+        format!("https://{}", address)
+    } else {
+        address
+    }
+}
+
+/// Interval at which request counters are saved to the history database.
+const COUNTERS_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Starts the HTTP server.
 pub async fn serve(config: &Config) -> Result<()> {
     let state = Arc::new(AppState::new(config)?);
-    let router = create_router(state);
+    state.controller().start_probes();
+    if state.cluster_enabled {
+        state.cluster.spawn();
+        if config.cluster.discovery.enabled {
+            state.cluster.spawn_discovery(Discovery::bind(
+                &config.cluster.discovery,
+                announcement(config),
+            )?);
+        }
+        if config.cluster.gossip.enabled {
+            let gossip = Gossip::bind(
+                &config.cluster.gossip,
+                &config.agent_name(),
+                gossip_http_address(config),
+                config.agent.tags.clone(),
+            )
+            .await?;
+            state.cluster.spawn_gossip(gossip);
+        }
+        if let Some(election) = &state.election {
+            election.spawn();
+        }
+    }
+    if state.history.is_some() {
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COUNTERS_FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                state.save_counters();
+            }
+        });
+    }
+    if let Some(webhooks) = &state.webhooks {
+        webhooks.spawn();
+    }
+    if let Some(watcher) = &state.watcher {
+        watcher.spawn(&state);
+    }
+    if let Some(reconciler) = &state.reconciler {
+        reconciler.spawn(&state);
+    }
+    #[cfg(unix)]
+    tokio::spawn(reload::reload_on_sighup(Arc::downgrade(&state)));
+    let router = create_router(Arc::clone(&state));
 
     let addr = SocketAddr::new(
         config.server.bind.parse().map_err(|e| {
@@ -70,13 +218,65 @@ pub async fn serve(config: &Config) -> Result<()> {
         )
     })?;
 
-    axum::serve(listener, router).await.map_err(|e| {
-        crate::error::ShikiError::backend_with_source(format!("Server error: {}", e), e)
-    })?;
+    // Client addresses are recorded as callers in the operation history
+    let service = router.into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, service)
+        .with_graceful_shutdown(shutdown_signal(Arc::clone(&state)))
+        .await
+        .map_err(|e| {
+            crate::error::ShikiError::backend_with_source(format!("Server error: {}", e), e)
+        })?;
+
+    // Supervised processes get their stop signal instead of SIGKILL on drop
+    state.controller().stop_supervised().await;
+    // Deliver the remaining webhook events before exiting
+    if let Some(webhooks) = &state.webhooks {
+        let timeout = Duration::from_secs(config.webhooks.timeout_seconds);
+        if !webhooks.flush(timeout).await {
+            warn!("Exiting with undelivered webhook events");
+        }
+    }
+    state.save_counters();
+    if let Some(history) = &state.history {
+        history.flush().await;
+    }
 
     Ok(())
 }
 
+/// Resolves on SIGINT or SIGTERM, after announcing the shutdown.
+async fn shutdown_signal(state: Arc<AppState>) {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+        "SIGTERM"
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<&str>();
+
+    let signal = tokio::select! {
+        signal = interrupt => signal,
+        signal = terminate => signal,
+    };
+    info!(signal = signal, "Shutting down");
+    if let Some(webhooks) = &state.webhooks {
+        webhooks.emit(
+            WebhookEvent::AgentShuttingDown,
+            serde_json::json!({ "signal": signal }),
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,263 @@
+//! Desired-state reconciliation.
+//!
+//! `desired_state` declares which local services should be running or
+//! stopped, optionally only within a schedule or under conditions. A
+//! background loop periodically compares the declaration with the state
+//! reported by the backend and starts or stops the services that drifted.
+//! `GET /api/v1/drift` reports the difference without acting on it.
+
+use crate::client::ShikiClient;
+use crate::config::{DesiredService, DesiredState, DesiredStateConfig};
+use crate::error::{Result, ShikiError};
+use crate::server::response::{DriftData, DriftEntry};
+use crate::server::state::AppState;
+use crate::service::{ServiceAction, ServiceState};
+use chrono::{Local, Utc};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Caller recorded in the operation history for reconciliation actions.
+pub const RECONCILER_CALLER: &str = "reconciler";
+
+/// Keeps the local services in their declared state.
+#[derive(Debug)]
+pub struct Reconciler {
+    config: DesiredStateConfig,
+    /// Background reconciliation task.
+    task: Mutex<Option<JoinHandle<()>>>,
+    /// Passes are not run concurrently.
+    running: tokio::sync::Mutex<()>,
+}
+
+impl Reconciler {
+    /// Creates a reconciler for `config`. Nothing is reconciled until
+    /// [`Reconciler::spawn`] is called.
+    pub fn new(config: DesiredStateConfig) -> Self {
+        Self {
+            config,
+            task: Mutex::new(None),
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Starts the reconciliation loop. Calling this more than once has no
+    /// effect.
+    pub fn spawn(&self, state: &Arc<AppState>) {
+        let mut task = self.task.lock().unwrap_or_else(|e| e.into_inner());
+        if task.is_some() {
+            return;
+        }
+        info!(
+            services = self.config.services.len(),
+            interval_secs = self.config.interval_seconds,
+            "Starting desired-state reconciliation"
+        );
+        *task = Some(tokio::spawn(reconcile_loop(
+            Arc::downgrade(state),
+            Duration::from_secs(self.config.interval_seconds),
+        )));
+    }
+
+    /// Compares the declared and actual state of every declared service.
+    pub async fn drift(&self, state: &AppState) -> DriftData {
+        let evaluations = self.evaluate_all(state).await;
+        drift_data(evaluations.into_iter().map(|e| e.entry).collect())
+    }
+
+    /// Runs a reconciliation pass: starts or stops every drifted service.
+    /// Returns the drift found, with the result of each action.
+    pub async fn reconcile(&self, state: &AppState) -> DriftData {
+        let _running = self.running.lock().await;
+        let mut services = Vec::new();
+        for Evaluation {
+            mut entry,
+            actual,
+            action,
+        } in self.evaluate_all(state).await
+        {
+            let Some(action) = action else {
+                services.push(entry);
+                continue;
+            };
+            let result = state
+                .perform_internal(&entry.service, action, RECONCILER_CALLER, actual)
+                .await;
+            match &result {
+                Ok(current) => info!(
+                    service = %entry.service,
+                    action = %action,
+                    state = %current,
+                    "Reconciled service"
+                ),
+                Err(e) => warn!(
+                    service = %entry.service,
+                    action = %action,
+                    error = %e,
+                    "Reconciliation failed"
+                ),
+            }
+            entry.result = Some(
+                if result.is_ok() {
+                    "completed"
+                } else {
+                    "failed"
+                }
+                .to_string(),
+            );
+            entry.message = result.err().map(|e| e.to_string());
+            services.push(entry);
+        }
+        drift_data(services)
+    }
+
+    /// Evaluates every declared service, by name.
+    async fn evaluate_all(&self, state: &AppState) -> Vec<Evaluation> {
+        let mut names: Vec<&String> = self.config.services.keys().collect();
+        names.sort();
+        let mut evaluations = Vec::with_capacity(names.len());
+        for name in names {
+            evaluations.push(evaluate(state, name, &self.config.services[name]).await);
+        }
+        evaluations
+    }
+}
+
+impl Drop for Reconciler {
+    fn drop(&mut self) {
+        if let Ok(task) = self.task.lock() {
+            if let Some(task) = task.as_ref() {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// A declared service compared with its actual state.
+struct Evaluation {
+    entry: DriftEntry,
+    actual: ServiceState,
+    action: Option<ServiceAction>,
+}
+
+fn drift_data(services: Vec<DriftEntry>) -> DriftData {
+    DriftData {
+        in_sync: services.iter().all(|s| s.action.is_none()),
+        checked_at: Utc::now(),
+        services,
+    }
+}
+
+/// Evaluates the declaration of a service against its actual state.
+async fn evaluate(state: &AppState, name: &str, service: &DesiredService) -> Evaluation {
+    let actual = match state.controller().status(name).await {
+        Ok(status) => status.state,
+        Err(e) => {
+            warn!(service = %name, error = %e, "Failed to get service status");
+            ServiceState::Unknown
+        }
+    };
+    let mut entry = DriftEntry {
+        service: name.to_string(),
+        desired: None,
+        actual: actual.to_string(),
+        action: None,
+        reason: None,
+        result: None,
+        message: None,
+    };
+
+    let (desired, reason) = match desired_state(state, service).await {
+        Ok(desired) => desired,
+        Err(e) => {
+            entry.reason = Some(format!("cannot evaluate conditions: {}", e));
+            return Evaluation {
+                entry,
+                actual,
+                action: None,
+            };
+        }
+    };
+    let action = match (desired, actual) {
+        (DesiredState::Running, ServiceState::Stopped | ServiceState::Failed) => {
+            Some(ServiceAction::Start)
+        }
+        (DesiredState::Stopped, ServiceState::Running) => Some(ServiceAction::Stop),
+        _ => None,
+    };
+    entry.desired = Some(desired.to_string());
+    entry.reason = reason;
+    entry.action = action.map(|a| a.to_string());
+    Evaluation {
+        entry,
+        actual,
+        action,
+    }
+}
+
+/// Returns the state desired now, and why it differs from the declared
+/// state when it does.
+async fn desired_state(
+    state: &AppState,
+    service: &DesiredService,
+) -> Result<(DesiredState, Option<String>)> {
+    if let Some(schedule) = &service.schedule {
+        if !schedule.is_active(Local::now().naive_local()) {
+            return Ok((
+                service.state.opposite(),
+                Some(format!("outside schedule {}", schedule)),
+            ));
+        }
+    }
+    if service.conditions.leader && !state.election.as_ref().is_some_and(|e| e.is_leader()) {
+        return Ok((
+            service.state.opposite(),
+            Some("not the cluster leader".to_string()),
+        ));
+    }
+    if let Some(signal) = &service.conditions.signal {
+        if signal_count(state, signal).await? == 0 {
+            return Ok((
+                service.state.opposite(),
+                Some(format!("signal {} is not set", signal)),
+            ));
+        }
+    }
+    Ok((service.state, None))
+}
+
+/// Returns the number of signalers of a signal, asking the signal
+/// coordinator when it is another agent.
+async fn signal_count(state: &AppState, name: &str) -> Result<usize> {
+    let coordinator = state
+        .signals
+        .coordinator
+        .as_ref()
+        .filter(|c| **c != state.agent_name);
+    let Some(coordinator) = coordinator else {
+        return Ok(state.signal_store.get(name).count);
+    };
+    let peer = state.peer_config(coordinator).ok_or_else(|| {
+        ShikiError::config(format!("Unknown signal coordinator: {}", coordinator))
+    })?;
+    let mut client = ShikiClient::with_timeout(&peer.address, state.http_timeout)?;
+    if let Some(token) = peer.token {
+        client = client.with_token(token);
+    }
+    Ok(client.signal(name).await?.count)
+}
+
+/// Periodically reconciles the declared services.
+async fn reconcile_loop(state: Weak<AppState>, interval: Duration) {
+    loop {
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        if let Some(reconciler) = &state.reconciler {
+            reconciler.reconcile(&state).await;
+        }
+        drop(state);
+        tokio::time::sleep(interval).await;
+    }
+}
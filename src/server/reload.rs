@@ -0,0 +1,168 @@
+//! Configuration reload.
+//!
+//! On SIGHUP or `POST /api/v1/admin/reload` the agent re-reads and validates
+//! its configuration file. An invalid file is rejected and the running
+//! configuration stays in effect. A valid one replaces the service backend,
+//! readiness probes, ACL, authentication settings and log level at once;
+//! requests already in progress finish with the configuration they started
+//! with. Other settings only take effect after a restart, which the logged
+//! diff points out.
+
+use crate::config::Config;
+use crate::server::response::ConfigChange;
+use crate::server::state::AppState;
+use crate::util::to_hex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::sync::Weak;
+use tracing::{error, info, warn};
+
+/// Settings applied by a reload, by path prefix.
+const RELOADABLE: &[&str] = &[
+    "agent.backend",
+    "services",
+    "readiness",
+    "acl",
+    "auth",
+    "logging.level",
+];
+
+/// Returns the SHA-256 of the normalized configuration: two files that
+/// parse to the same settings have the same hash. Secrets are redacted
+/// first: the hash is published, and must not let anyone check a guess of
+/// a secret against it.
+pub fn config_hash(config: &Config) -> String {
+    let normalized = serde_json::to_value(config.redacted())
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    to_hex(&Sha256::digest(normalized.as_bytes()))
+}
+
+/// Returns the settings that differ between two configurations, by path.
+pub fn diff(old: &Config, new: &Config) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(old), Ok(new)) => diff_values("", &old, &new, &mut changes),
+        _ => changes.push(change("", "modified")),
+    }
+    changes
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<ConfigChange>) {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        if old != new {
+            changes.push(change(path, "modified"));
+        }
+        return;
+    };
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) => diff_values(&path, old, new, changes),
+            (None, Some(_)) => changes.push(change(&path, "added")),
+            (Some(_), None) => changes.push(change(&path, "removed")),
+            (None, None) => {}
+        }
+    }
+}
+
+fn change(path: &str, change: &str) -> ConfigChange {
+    ConfigChange {
+        path: path.to_string(),
+        change: change.to_string(),
+        restart_required: !RELOADABLE
+            .iter()
+            .any(|prefix| path == *prefix || path.starts_with(&format!("{}.", prefix))),
+    }
+}
+
+/// Logs the changes applied by a reload. Values are not logged, as they
+/// may be credentials.
+pub fn log_changes(changes: &[ConfigChange]) {
+    for change in changes {
+        if change.restart_required {
+            warn!(
+                path = %change.path,
+                change = %change.change,
+                "Configuration changed; takes effect after a restart"
+            );
+        } else {
+            info!(path = %change.path, change = %change.change, "Configuration changed");
+        }
+    }
+}
+
+/// Reloads the configuration on every SIGHUP.
+#[cfg(unix)]
+pub async fn reload_on_sighup(state: Weak<AppState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            warn!(error = %e, "Cannot listen for SIGHUP; reload through the API only");
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        info!(signal = "SIGHUP", "Reloading configuration");
+        if let Err(e) = state.reload() {
+            error!(error = %e, "Configuration reload failed; keeping the running configuration");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceDefinition;
+
+    #[test]
+    fn test_diff() {
+        let mut old = Config::default();
+        old.services.insert(
+            "web".to_string(),
+            ServiceDefinition {
+                start: "web start".to_string(),
+                ..Default::default()
+            },
+        );
+        let mut new = old.clone();
+        new.services.get_mut("web").unwrap().start = "web serve".to_string();
+        new.services
+            .insert("db".to_string(), ServiceDefinition::default());
+        new.acl.denied = vec!["secret-*".to_string()];
+        new.server.port = 9090;
+
+        assert_eq!(config_hash(&old), config_hash(&old.clone()));
+        assert_ne!(config_hash(&old), config_hash(&new));
+        let mut secret = old.clone();
+        secret.auth.token = Some("token".to_string());
+        let mut rotated = old.clone();
+        rotated.auth.token = Some("another-token".to_string());
+        assert_eq!(config_hash(&secret), config_hash(&rotated));
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| (c.path.as_str(), c.change.as_str(), c.restart_required))
+                .collect::<Vec<_>>(),
+            vec![
+                ("acl.denied", "modified", false),
+                ("server.port", "modified", true),
+                ("services.db", "added", false),
+                ("services.web.start", "modified", false),
+            ]
+        );
+        assert!(diff(&old, &old.clone()).is_empty());
+    }
+}
@@ -6,7 +6,9 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::cluster::{PeerStatus, Selector};
 use crate::error::{ErrorResponse, ShikiError};
+use crate::service::{ReadinessState, ServiceOperationResult};
 
 /// Standard API response wrapper.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,6 +88,27 @@ pub struct StatusData {
     pub version: String,
     /// Uptime in seconds.
     pub uptime_seconds: u64,
+    /// Webhook delivery statistics, when webhooks are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<WebhookStatsInfo>,
+    /// Configuration in effect.
+    #[serde(default)]
+    pub config: ConfigInfo,
+}
+
+/// Configuration in effect on an agent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigInfo {
+    /// Incremented by every reload that changes the configuration; 1 at
+    /// startup.
+    pub generation: u64,
+    /// SHA-256 of the normalized configuration.
+    pub hash: String,
+    /// When the configuration was loaded.
+    pub loaded_at: DateTime<Utc>,
+    /// File the configuration was loaded from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
 }
 
 /// Agent information.
@@ -139,6 +162,173 @@ pub struct StatsInfo {
     pub active_connections: u64,
 }
 
+/// Webhook delivery statistics.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookStatsInfo {
+    /// Configured endpoints.
+    pub endpoints: usize,
+    /// Events queued for delivery.
+    pub queued: u64,
+    /// Successful deliveries.
+    pub delivered: u64,
+    /// Deliveries that failed after every attempt.
+    pub failed: u64,
+    /// Events dropped because the queue was full.
+    pub dropped: u64,
+    /// Retried delivery attempts.
+    pub retries: u64,
+    /// Events waiting for or in delivery.
+    pub pending: u64,
+}
+
+/// Cluster status response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterData {
+    /// Whether cluster mode is enabled on the responding agent.
+    pub enabled: bool,
+    /// The responding agent.
+    pub local: ClusterMember,
+    /// Peers as seen by the responding agent.
+    pub peers: Vec<PeerStatus>,
+    /// Summary counts.
+    pub summary: ClusterSummary,
+}
+
+/// The responding agent in a cluster status response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterMember {
+    /// Agent name.
+    pub name: String,
+    /// Server address (bind:port).
+    pub address: String,
+    /// Application version.
+    pub version: String,
+    /// Agent tags.
+    pub tags: Vec<String>,
+}
+
+/// Cluster summary counts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClusterSummary {
+    /// Number of peers.
+    pub total: usize,
+    /// Number of reachable peers.
+    pub reachable: usize,
+    /// Number of unreachable peers.
+    pub unreachable: usize,
+}
+
+/// Selector-based notify request body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterNotifyRequest {
+    /// Agent selector (e.g. `role=db,env=prod` or `tag:db`).
+    pub selector: Selector,
+    /// Action to perform.
+    pub action: String,
+    /// Target service name.
+    pub service: String,
+    /// Options passed on to every agent.
+    #[serde(default)]
+    pub options: NotifyOptions,
+    /// Fan-out limits (defaults from `cluster.fanout`).
+    #[serde(default)]
+    pub fanout: FanoutOptions,
+}
+
+/// Fan-out limits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FanoutOptions {
+    /// Maximum number of agents notified at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallelism: Option<usize>,
+    /// Number of failed agents tolerated before the rest are skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_failures: Option<usize>,
+}
+
+/// Selector-based notify response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanoutResponseData {
+    /// Selector used.
+    pub selector: String,
+    /// Target service.
+    pub service: String,
+    /// Action performed.
+    pub action: String,
+    /// Per-agent results, in target order.
+    pub results: Vec<AgentResult>,
+    /// Summary counts.
+    pub summary: FanoutSummary,
+}
+
+/// Result of a fan-out operation on a single agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentResult {
+    /// Agent name.
+    pub agent: String,
+    /// Agent address.
+    pub address: String,
+    /// Outcome.
+    pub status: FanoutStatus,
+    /// Duration in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Response of the agent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<NotifyResponseData>,
+    /// Error message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of a fan-out operation on a single agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FanoutStatus {
+    /// The operation succeeded.
+    Succeeded,
+    /// The request or the operation failed.
+    Failed,
+    /// Not attempted because the failure tolerance was exceeded.
+    Skipped,
+}
+
+impl std::fmt::Display for FanoutStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FanoutStatus::Succeeded => write!(f, "succeeded"),
+            FanoutStatus::Failed => write!(f, "failed"),
+            FanoutStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Fan-out summary counts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FanoutSummary {
+    /// Number of matched agents.
+    pub total: usize,
+    /// Agents where the operation succeeded.
+    pub succeeded: usize,
+    /// Agents where the operation failed.
+    pub failed: usize,
+    /// Agents that were skipped.
+    pub skipped: usize,
+}
+
+impl FanoutSummary {
+    /// Counts the results.
+    pub fn from_results(results: &[AgentResult]) -> Self {
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        Self {
+            total: results.len(),
+            succeeded: count(FanoutStatus::Succeeded),
+            failed: count(FanoutStatus::Failed),
+            skipped: count(FanoutStatus::Skipped),
+        }
+    }
+}
+
 /// Notify request body.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotifyRequest {
@@ -160,6 +350,9 @@ pub struct NotifyOptions {
     /// Timeout in seconds.
     #[serde(default = "default_timeout")]
     pub timeout_seconds: u64,
+    /// Whether to also wait for the readiness probe after a start/restart.
+    #[serde(default)]
+    pub wait_ready: bool,
 }
 
 fn default_wait() -> bool {
@@ -175,6 +368,7 @@ impl Default for NotifyOptions {
         Self {
             wait: default_wait(),
             timeout_seconds: default_timeout(),
+            wait_ready: false,
         }
     }
 }
@@ -202,6 +396,37 @@ pub struct NotifyResponseData {
     /// Message (for accepted/error responses).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Other services touched by the operation (dependencies/dependents).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<RelatedOperation>,
+}
+
+/// Operation performed on another service as part of a request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedOperation {
+    /// Service name.
+    pub service: String,
+    /// Action performed.
+    pub action: String,
+    /// Whether the operation was successful.
+    pub success: bool,
+    /// Resulting state.
+    pub state: String,
+    /// Operation message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<&ServiceOperationResult> for RelatedOperation {
+    fn from(result: &ServiceOperationResult) -> Self {
+        Self {
+            service: result.service.clone(),
+            action: result.action.to_string(),
+            success: result.success,
+            state: result.state.to_string(),
+            message: result.message.clone(),
+        }
+    }
 }
 
 /// Service list response data.
@@ -239,6 +464,12 @@ pub struct ServiceDetailData {
     /// Service description.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Whether the readiness probe passes (absent when no probe is configured).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready: Option<bool>,
+    /// Readiness probe details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessState>,
 }
 
 /// Service operation response data.
@@ -258,6 +489,317 @@ pub struct ServiceOperationData {
     /// Operation message.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Other services touched by the operation (dependencies/dependents).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<RelatedOperation>,
+}
+
+/// Set signal request body.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SetSignalRequest {
+    /// Identity of the signaler (defaults to the name of the receiving agent).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Time to live in seconds (defaults to `signals.default_ttl_seconds`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+}
+
+/// State of a named signal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignalData {
+    /// Signal name.
+    pub name: String,
+    /// Number of signalers that have set the signal.
+    pub count: usize,
+    /// Signalers, by identity.
+    pub signers: Vec<SignerData>,
+}
+
+/// A signaler of a signal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignerData {
+    /// Identity of the signaler.
+    pub agent: String,
+    /// When the signal was set.
+    pub set_at: DateTime<Utc>,
+    /// When the signal expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Barrier wait response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BarrierData {
+    /// Signal name.
+    pub name: String,
+    /// Number of signalers waited for.
+    pub required: usize,
+    /// Time spent waiting in milliseconds.
+    pub waited_ms: u64,
+    /// Signal state when the barrier was reached.
+    pub signal: SignalData,
+}
+
+/// Acquire lock request body.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AcquireLockRequest {
+    /// Owner of the lock (defaults to the name of the receiving agent).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Time to live in seconds (defaults to `locks.default_ttl_seconds`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+    /// Seconds to wait while the lock is held by another owner (default: 0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_seconds: Option<u64>,
+}
+
+/// Renew lock request body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenewLockRequest {
+    /// Fencing token returned when the lock was acquired.
+    pub fencing_token: u64,
+    /// New time to live in seconds (defaults to `locks.default_ttl_seconds`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+}
+
+/// A held lock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockData {
+    /// Lock name.
+    pub name: String,
+    /// Owner of the lock.
+    pub owner: String,
+    /// Fencing token, increasing with every acquisition.
+    pub fencing_token: u64,
+    /// When the lock was acquired.
+    pub acquired_at: DateTime<Utc>,
+    /// When the lock expires unless renewed.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Release lock response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseLockData {
+    /// Lock name.
+    pub name: String,
+    /// Whether the lock was held and has been released.
+    pub released: bool,
+}
+
+/// A recorded service operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Sequence number of the entry.
+    pub id: i64,
+    /// Request ID of the operation.
+    pub request_id: Uuid,
+    /// When the operation was started.
+    pub timestamp: DateTime<Utc>,
+    /// Agent the request was forwarded by, or the client address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    /// Service name.
+    pub service: String,
+    /// Action performed.
+    pub action: String,
+    /// State before the operation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_state: Option<String>,
+    /// State after the operation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_state: Option<String>,
+    /// Duration in milliseconds.
+    pub duration_ms: u64,
+    /// Result: `completed`, `failed` or `error`.
+    pub result: String,
+    /// Operation message or error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Operation history response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryData {
+    /// Matching entries, newest first.
+    pub entries: Vec<HistoryEntry>,
+    /// Total number of matching entries.
+    pub total: usize,
+    /// Limit used in query.
+    pub limit: usize,
+    /// Offset used in query.
+    pub offset: usize,
+}
+
+/// Configuration reload response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReloadData {
+    /// Whether the configuration changed.
+    pub changed: bool,
+    /// Configuration in effect after the reload.
+    pub config: ConfigInfo,
+    /// Changed settings, by path.
+    pub changes: Vec<ConfigChange>,
+}
+
+/// A setting changed by a reload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    /// Dotted path of the setting (`services.nginx.start`).
+    pub path: String,
+    /// `added`, `removed` or `modified`.
+    pub change: String,
+    /// Whether the change only takes effect after a restart of the agent.
+    pub restart_required: bool,
+}
+
+/// Desired-state drift response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftData {
+    /// Whether every declared service is in its desired state.
+    pub in_sync: bool,
+    /// When the services were checked.
+    pub checked_at: DateTime<Utc>,
+    /// Declared services, by name.
+    pub services: Vec<DriftEntry>,
+}
+
+/// Declared and actual state of a service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriftEntry {
+    /// Service name.
+    pub service: String,
+    /// State desired now (absent when the conditions cannot be evaluated).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired: Option<String>,
+    /// State reported by the backend.
+    pub actual: String,
+    /// Action that converges the service (`start` or `stop`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Why the desired state is the opposite of the declared one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Result of the action, when reconciled (`completed` or `failed`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Error of a failed action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Service watcher response data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchData {
+    /// Watched services, by name.
+    pub services: Vec<WatchStatus>,
+}
+
+/// Watch state of a service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchStatus {
+    /// Service name.
+    pub service: String,
+    /// Policy applied when the service is down (`restart`, `notify`, `remote`).
+    pub policy: String,
+    /// Interval between status checks in seconds.
+    pub interval_seconds: u64,
+    /// State seen by the last check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Time of the last check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Restarts within the restart window.
+    pub restarts_in_window: usize,
+    /// Maximum restarts within the window.
+    pub max_restarts: u32,
+    /// Whether the watcher gave up restarting the service.
+    pub gave_up: bool,
+    /// Whether the service was stopped through shiki and is left alone.
+    pub stopped_by_api: bool,
+    /// Recent decisions, newest first.
+    pub decisions: Vec<WatchDecision>,
+}
+
+/// A policy applied by the watcher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchDecision {
+    /// When the policy was applied.
+    pub timestamp: DateTime<Utc>,
+    /// Service name.
+    pub service: String,
+    /// State that triggered the decision.
+    pub observed_state: String,
+    /// `restart`, `notify`, `remote` or `give_up`.
+    pub action: String,
+    /// `completed` or `failed`.
+    pub result: String,
+    /// Outcome details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Leader election state as seen by an agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderData {
+    /// Whether leader election is enabled on the agent.
+    pub enabled: bool,
+    /// Name of the agent answering.
+    pub agent: String,
+    /// Current election term.
+    pub term: u64,
+    /// Current leader, if one holds a valid lease.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader: Option<String>,
+    /// Whether the answering agent is the leader.
+    pub is_leader: bool,
+    /// When the leader's lease expires unless renewed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    /// Number of voting agents, including the answering agent.
+    pub voters: usize,
+    /// Number of votes needed to become leader.
+    pub quorum: usize,
+}
+
+/// Vote request sent by a candidate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRequest {
+    /// Term the candidate is running in.
+    pub term: u64,
+    /// Name of the candidate.
+    pub candidate: String,
+}
+
+/// Answer to a vote request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteData {
+    /// Term of the voter.
+    pub term: u64,
+    /// Whether the vote was granted.
+    pub granted: bool,
+}
+
+/// Lease renewal sent by the leader.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatRequest {
+    /// Term of the leader.
+    pub term: u64,
+    /// Name of the leader.
+    pub leader: String,
+    /// Lease duration in milliseconds.
+    pub lease_ms: u64,
+}
+
+/// Answer to a lease renewal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatData {
+    /// Term of the follower.
+    pub term: u64,
+    /// Whether the lease was accepted.
+    pub accepted: bool,
 }
 
 #[cfg(test)]
@@ -304,6 +846,7 @@ mod tests {
         let options = NotifyOptions::default();
         assert!(options.wait);
         assert_eq!(options.timeout_seconds, 60);
+        assert!(!options.wait_ready);
     }
 
     #[test]
@@ -349,6 +892,24 @@ mod tests {
         assert_eq!(json["description"], "Web server");
     }
 
+    #[test]
+    fn test_service_detail_ready_omitted_without_probe() {
+        let detail = ServiceDetailData {
+            name: "nginx".to_string(),
+            status: "running".to_string(),
+            description: None,
+            ready: None,
+            readiness: None,
+        };
+
+        let json = serde_json::to_value(&detail).unwrap();
+        assert!(json.get("ready").is_none());
+
+        let detail: ServiceDetailData =
+            serde_json::from_str(r#"{"name": "pg", "status": "running", "ready": true}"#).unwrap();
+        assert_eq!(detail.ready, Some(true));
+    }
+
     #[test]
     fn test_agent_state_serialization() {
         assert_eq!(
@@ -2,16 +2,34 @@
 //!
 //! This module manages the shared state across HTTP request handlers.
 
-use crate::config::Config;
-use crate::error::Result;
-use crate::service::ServiceController;
+use crate::audit::AuditLog;
+use crate::cluster::{Election, LockStore, PeerMonitor, SignalStore};
+use crate::config::{
+    AclConfig, AuthConfig, AuthMethod, Config, FanoutConfig, ForwardingConfig, LocksConfig,
+    PeerConfig, SignalsConfig, WebhookEvent,
+};
+use crate::error::{Result, ShikiError};
+use crate::history::HistoryStore;
+use crate::server::reconcile::Reconciler;
+use crate::server::reload;
+use crate::server::response::{ConfigInfo, HistoryEntry, ReloadData};
+use crate::server::watcher::Watcher;
+use crate::service::{ServiceAction, ServiceController, ServiceState};
+use crate::webhooks::WebhookDispatcher;
+use chrono::{DateTime, Utc};
+use serde_json::json;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::info;
+use uuid::Uuid;
 
 /// Shared application state.
 pub struct AppState {
-    /// Service controller for managing services.
-    pub controller: ServiceController,
+    /// Configuration replaced by a reload.
+    loaded: RwLock<Arc<LoadedConfig>>,
+    /// Reloads are not run concurrently.
+    reloading: Mutex<()>,
     /// Application start time.
     pub start_time: Instant,
     /// Agent name.
@@ -24,6 +42,38 @@ pub struct AppState {
     pub server_port: u16,
     /// TLS enabled flag.
     pub tls_enabled: bool,
+    /// Whether cluster mode is enabled.
+    pub cluster_enabled: bool,
+    /// Peer registry and health monitor.
+    pub cluster: PeerMonitor,
+    /// Default fan-out limits.
+    pub fanout: FanoutConfig,
+    /// Request forwarding settings.
+    pub forwarding: ForwardingConfig,
+    /// Configured peers, used to address forwarded and fanned-out requests.
+    pub peers: Vec<PeerConfig>,
+    /// Timeout of requests to other agents.
+    pub http_timeout: Duration,
+    /// Signal configuration.
+    pub signals: SignalsConfig,
+    /// Signals set on this agent.
+    pub signal_store: SignalStore,
+    /// Lock configuration.
+    pub locks: LocksConfig,
+    /// Locks held on this agent.
+    pub lock_store: LockStore,
+    /// Leader election, when enabled.
+    pub election: Option<Arc<Election>>,
+    /// Audit log, when enabled.
+    pub audit: Option<AuditLog>,
+    /// Operation history, when enabled.
+    pub history: Option<HistoryStore>,
+    /// Webhook dispatcher, when endpoints are configured.
+    pub webhooks: Option<WebhookDispatcher>,
+    /// Service watcher, when services are watched.
+    pub watcher: Option<Watcher>,
+    /// Desired-state reconciler, when services are declared.
+    pub reconciler: Option<Reconciler>,
     /// Statistics counters.
     pub stats: Stats,
 }
@@ -32,16 +82,187 @@ impl AppState {
     /// Creates a new application state from configuration.
     pub fn new(config: &Config) -> Result<Self> {
         let controller = ServiceController::from_config(config)?;
+        let election = if config.cluster_enabled() && config.cluster.election.enabled {
+            // Peers without a token of their own share this agent's token
+            let token = match config.auth.method {
+                AuthMethod::Token if config.auth.enabled => config.auth.token.as_deref(),
+                _ => None,
+            };
+            Some(Arc::new(Election::new(
+                &config.cluster.election,
+                &config.agent_name(),
+                &config.cluster.peers,
+                token,
+            )?))
+        } else {
+            None
+        };
+
+        // Counters continue from their saved values
+        let (history, stats) = if config.history.enabled {
+            let history = HistoryStore::open(&config.history)?;
+            let stats = Stats::from(history.saved_counters());
+            (Some(history), stats)
+        } else {
+            (None, Stats::default())
+        };
+
+        let audit = if config.audit.enabled {
+            Some(AuditLog::open(&config.audit)?)
+        } else {
+            None
+        };
+
+        let webhooks = if config.webhooks.endpoints.is_empty() {
+            None
+        } else {
+            Some(WebhookDispatcher::new(
+                &config.webhooks,
+                &config.agent_name(),
+            )?)
+        };
+
+        let watcher = if config.watch.is_empty() {
+            None
+        } else {
+            Some(Watcher::new(config.watch.clone()))
+        };
+
+        let reconciler = if config.desired_state.services.is_empty() {
+            None
+        } else {
+            Some(Reconciler::new(config.desired_state.clone()))
+        };
 
         Ok(Self {
-            controller,
+            loaded: RwLock::new(Arc::new(LoadedConfig::new(config.clone(), controller, 1))),
+            reloading: Mutex::new(()),
             start_time: Instant::now(),
             agent_name: config.agent_name(),
             agent_tags: config.agent.tags.clone(),
             server_bind: config.server.bind.clone(),
             server_port: config.server.port,
             tls_enabled: config.server.tls.enabled,
-            stats: Stats::default(),
+            cluster_enabled: config.cluster_enabled(),
+            cluster: PeerMonitor::new(
+                &config.cluster.peers,
+                Duration::from_secs(config.cluster.health_interval_seconds),
+                Duration::from_secs(config.timeout.health_seconds),
+            ),
+            fanout: config.cluster.fanout.clone(),
+            forwarding: config.cluster.forwarding.clone(),
+            peers: config.cluster.peers.clone(),
+            http_timeout: Duration::from_secs(config.timeout.http_seconds),
+            signals: config.signals.clone(),
+            signal_store: SignalStore::new(),
+            locks: config.locks.clone(),
+            lock_store: LockStore::new(),
+            election,
+            audit,
+            history,
+            webhooks,
+            watcher,
+            reconciler,
+            stats,
+        })
+    }
+
+    /// Returns the service controller in effect. Requests keep the
+    /// controller they started with across a reload.
+    pub fn controller(&self) -> Arc<ServiceController> {
+        Arc::clone(&self.loaded().controller)
+    }
+
+    /// Returns the authentication settings in effect.
+    pub fn auth(&self) -> Arc<AuthConfig> {
+        Arc::clone(&self.loaded().auth)
+    }
+
+    /// Returns the access control list in effect.
+    pub fn acl(&self) -> Arc<AclConfig> {
+        Arc::clone(&self.loaded().acl)
+    }
+
+    /// Returns the generation and hash of the configuration in effect.
+    pub fn config_info(&self) -> ConfigInfo {
+        self.loaded().info()
+    }
+
+    fn loaded(&self) -> Arc<LoadedConfig> {
+        Arc::clone(&self.loaded.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Re-reads and validates the configuration file, then swaps in the
+    /// service backend, readiness probes, ACL, authentication settings and
+    /// log level it declares. On error the running configuration is kept.
+    pub fn reload(&self) -> Result<ReloadData> {
+        let _reloading = self.reloading.lock().unwrap_or_else(|e| e.into_inner());
+        let current = self.loaded();
+        let source = current.config.source.as_ref().ok_or_else(|| {
+            ShikiError::config("The agent was started without a configuration file")
+        })?;
+        let mut config = Config::load_from_path(source)?;
+        crate::config::log_warnings(&config.warnings);
+        // The listener is not rebound, and bind and port may come from the
+        // command line
+        config.server.bind = current.config.server.bind.clone();
+        config.server.port = current.config.server.port;
+
+        let changes = reload::diff(&current.config, &config);
+        if changes.is_empty() {
+            info!(
+                generation = current.generation,
+                "Configuration is unchanged"
+            );
+            return Ok(ReloadData {
+                changed: false,
+                config: current.info(),
+                changes,
+            });
+        }
+
+        let controller = current.controller.reload(&config)?;
+        controller.start_probes();
+        if config.logging.level != current.config.logging.level {
+            crate::logging::set_level(config.logging.level);
+        }
+        let loaded = LoadedConfig::new(config, controller, current.generation + 1);
+        info!(
+            generation = loaded.generation,
+            hash = %loaded.hash,
+            changes = changes.len(),
+            "Configuration reloaded"
+        );
+        reload::log_changes(&changes);
+        let info = loaded.info();
+        *self.loaded.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(loaded);
+        Ok(ReloadData {
+            changed: true,
+            config: info,
+            changes,
+        })
+    }
+
+    /// Returns the operation mode name.
+    pub fn mode(&self) -> &'static str {
+        if self.cluster_enabled {
+            "cluster"
+        } else {
+            "standalone"
+        }
+    }
+
+    /// Returns the configuration of a peer. Discovered peers are returned
+    /// without a token, and requests relayed to them carry no credentials.
+    pub fn peer_config(&self, name: &str) -> Option<PeerConfig> {
+        if let Some(peer) = self.peers.iter().find(|p| p.name == name) {
+            return Some(peer.clone());
+        }
+        self.cluster.peer(name).map(|p| PeerConfig {
+            name: p.name,
+            address: p.address,
+            tags: p.tags,
+            token: None,
         })
     }
 
@@ -64,6 +285,141 @@ impl AppState {
     pub fn increment_failed(&self) {
         self.stats.requests_failed.fetch_add(1, Ordering::Relaxed);
     }
+
+    /// Records a completed operation: queues it for the history, if enabled,
+    /// emits the `operation.failed` and `service.state_changed` webhook
+    /// events it triggers and tells the watcher. Failures are logged and do
+    /// not affect the operation.
+    pub fn record_operation(&self, entry: HistoryEntry) {
+        if let Some(watcher) = &self.watcher {
+            watcher.observe_operation(&entry);
+        }
+        if let Some(webhooks) = &self.webhooks {
+            if entry.result != "completed" {
+                webhooks.emit(
+                    WebhookEvent::OperationFailed,
+                    json!({
+                        "request_id": entry.request_id,
+                        "service": entry.service,
+                        "action": entry.action,
+                        "result": entry.result,
+                        "previous_state": entry.previous_state,
+                        "current_state": entry.current_state,
+                        "duration_ms": entry.duration_ms,
+                        "caller": entry.caller,
+                        "message": entry.message,
+                    }),
+                );
+            }
+            if entry.current_state.is_some() && entry.current_state != entry.previous_state {
+                webhooks.emit(
+                    WebhookEvent::ServiceStateChanged,
+                    json!({
+                        "request_id": entry.request_id,
+                        "service": entry.service,
+                        "action": entry.action,
+                        "previous_state": entry.previous_state,
+                        "current_state": entry.current_state,
+                    }),
+                );
+            }
+        }
+
+        if let Some(history) = &self.history {
+            history.record(entry);
+        }
+    }
+
+    /// Performs an action on behalf of the agent itself (`caller` is the
+    /// watcher or the reconciler) and records it like an API operation.
+    /// Returns the new state; an action that ran but failed is an error.
+    pub async fn perform_internal(
+        &self,
+        service: &str,
+        action: ServiceAction,
+        caller: &str,
+        previous_state: ServiceState,
+    ) -> Result<ServiceState> {
+        let start_time = Instant::now();
+        let started_at = Utc::now();
+        let result = self.controller().perform_action(service, action).await;
+
+        let mut entry = HistoryEntry {
+            id: 0,
+            request_id: Uuid::new_v4(),
+            timestamp: started_at,
+            caller: Some(caller.to_string()),
+            service: service.to_string(),
+            action: action.to_string(),
+            previous_state: Some(previous_state.to_string()),
+            current_state: None,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+            result: "error".to_string(),
+            message: None,
+        };
+        let outcome = match result {
+            Ok(op_result) => {
+                entry.current_state = Some(op_result.state.to_string());
+                entry.message = op_result.message.clone();
+                if op_result.success {
+                    entry.result = "completed".to_string();
+                    Ok(op_result.state)
+                } else {
+                    entry.result = "failed".to_string();
+                    Err(ShikiError::backend(op_result.message.unwrap_or_else(
+                        || format!("{} left service {}", action, op_result.state),
+                    )))
+                }
+            }
+            Err(e) => {
+                entry.message = Some(e.to_string());
+                Err(e)
+            }
+        };
+        self.record_operation(entry);
+        outcome
+    }
+
+    /// Saves the request counters to the history database, if enabled.
+    pub fn save_counters(&self) {
+        if let Some(history) = &self.history {
+            history.save_counters(self.stats.snapshot());
+        }
+    }
+}
+
+/// Configuration loaded at startup or by the last reload.
+struct LoadedConfig {
+    config: Config,
+    controller: Arc<ServiceController>,
+    auth: Arc<AuthConfig>,
+    acl: Arc<AclConfig>,
+    generation: u64,
+    hash: String,
+    loaded_at: DateTime<Utc>,
+}
+
+impl LoadedConfig {
+    fn new(config: Config, controller: ServiceController, generation: u64) -> Self {
+        Self {
+            controller: Arc::new(controller),
+            auth: Arc::new(config.auth.clone()),
+            acl: Arc::new(config.acl.clone()),
+            generation,
+            hash: reload::config_hash(&config),
+            loaded_at: Utc::now(),
+            config,
+        }
+    }
+
+    fn info(&self) -> ConfigInfo {
+        ConfigInfo {
+            generation: self.generation,
+            hash: self.hash.clone(),
+            loaded_at: self.loaded_at,
+            source: self.config.source.as_ref().map(|p| p.display().to_string()),
+        }
+    }
 }
 
 /// Statistics counters.
@@ -88,6 +444,16 @@ impl Stats {
     }
 }
 
+impl From<StatsSnapshot> for Stats {
+    fn from(snapshot: StatsSnapshot) -> Self {
+        Self {
+            requests_total: AtomicU64::new(snapshot.requests_total),
+            requests_success: AtomicU64::new(snapshot.requests_success),
+            requests_failed: AtomicU64::new(snapshot.requests_failed),
+        }
+    }
+}
+
 /// Snapshot of statistics counters.
 #[derive(Debug, Clone, Default)]
 pub struct StatsSnapshot {
@@ -136,6 +502,8 @@ mod tests {
         assert_eq!(state.server_bind, "0.0.0.0");
         assert_eq!(state.server_port, 8080);
         assert!(!state.tls_enabled);
+        assert_eq!(state.mode(), "standalone");
+        assert!(state.cluster.peers().is_empty());
     }
 
     #[test]
@@ -163,6 +531,25 @@ mod tests {
         assert_eq!(snapshot.requests_failed, 1);
     }
 
+    #[test]
+    fn test_stats_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = create_test_config();
+        config.history.enabled = true;
+        config.history.path = dir.path().join("history.db");
+
+        let state = AppState::new(&config).unwrap();
+        state.increment_requests();
+        state.increment_failed();
+        state.save_counters();
+        drop(state);
+
+        let state = AppState::new(&config).unwrap();
+        let snapshot = state.stats.snapshot();
+        assert_eq!(snapshot.requests_total, 1);
+        assert_eq!(snapshot.requests_failed, 1);
+    }
+
     #[test]
     fn test_stats_default() {
         let stats = Stats::default();
@@ -0,0 +1,548 @@
+//! Service watcher.
+//!
+//! For every service with a `watch` section, a background task polls the
+//! service state. When a service that was running goes down, or fails,
+//! without having been stopped through shiki, the watcher applies the
+//! service's policy: restart it (with backoff and a limit of restarts per
+//! window), emit a `service.unhealthy` webhook event, or send a request to
+//! another agent. Decisions are logged and kept for `GET /api/v1/watch`.
+
+use crate::client::ShikiClient;
+use crate::config::{WatchConfig, WatchPolicy, WebhookEvent};
+use crate::error::{Result, ShikiError};
+use crate::server::response::{HistoryEntry, WatchDecision, WatchStatus};
+use crate::server::state::AppState;
+use crate::service::{ServiceAction, ServiceState};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Caller recorded in the operation history for watcher restarts.
+pub const WATCHER_CALLER: &str = "watcher";
+
+/// Number of decisions kept per service.
+const DECISIONS_KEPT: usize = 20;
+
+/// What the watcher does after a status check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Nothing to do.
+    None,
+    /// Restart the service.
+    Restart,
+    /// Emit a `service.unhealthy` event.
+    Notify,
+    /// Send the configured request to another agent.
+    Remote,
+    /// The restart limit has been reached.
+    GiveUp,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::None => write!(f, "none"),
+            Action::Restart => write!(f, "restart"),
+            Action::Notify => write!(f, "notify"),
+            Action::Remote => write!(f, "remote"),
+            Action::GiveUp => write!(f, "give_up"),
+        }
+    }
+}
+
+/// Watch state of a single service.
+#[derive(Debug)]
+struct Tracker {
+    config: WatchConfig,
+    /// State seen by the last check.
+    state: Option<ServiceState>,
+    last_checked: Option<DateTime<Utc>>,
+    /// Whether the service has been seen running.
+    seen_running: bool,
+    /// Whether the service was stopped through shiki.
+    stopped_by_api: bool,
+    /// Whether the current outage has been handled (notify / remote).
+    outage_handled: bool,
+    /// Whether the restart limit has been reached.
+    gave_up: bool,
+    /// Times of the restarts within the window.
+    restarts: VecDeque<Instant>,
+    /// Restarts since the window was last empty, for the backoff.
+    backoff_step: u32,
+    /// Earliest time of the next restart.
+    next_restart: Option<Instant>,
+    decisions: VecDeque<WatchDecision>,
+}
+
+impl Tracker {
+    fn new(config: WatchConfig) -> Self {
+        Self {
+            config,
+            state: None,
+            last_checked: None,
+            seen_running: false,
+            stopped_by_api: false,
+            outage_handled: false,
+            gave_up: false,
+            restarts: VecDeque::new(),
+            backoff_step: 0,
+            next_restart: None,
+            decisions: VecDeque::new(),
+        }
+    }
+
+    /// Records the result of a status check and returns what to do.
+    fn observe(&mut self, state: ServiceState, now: Instant) -> Action {
+        self.state = Some(state);
+        self.last_checked = Some(Utc::now());
+
+        let window = Duration::from_secs(self.config.window_seconds);
+        while self
+            .restarts
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= window)
+        {
+            self.restarts.pop_front();
+        }
+        if self.restarts.is_empty() {
+            self.backoff_step = 0;
+        }
+
+        match state {
+            ServiceState::Running => {
+                self.seen_running = true;
+                self.stopped_by_api = false;
+                self.outage_handled = false;
+                self.gave_up = false;
+                return Action::None;
+            }
+            ServiceState::Unknown => return Action::None,
+            ServiceState::Stopped | ServiceState::Failed => {}
+        }
+
+        // Stopped on purpose, or never started
+        if self.stopped_by_api || (state == ServiceState::Stopped && !self.seen_running) {
+            return Action::None;
+        }
+
+        match self.config.policy {
+            WatchPolicy::Notify | WatchPolicy::Remote if self.outage_handled => Action::None,
+            WatchPolicy::Notify => {
+                self.outage_handled = true;
+                Action::Notify
+            }
+            WatchPolicy::Remote => {
+                self.outage_handled = true;
+                Action::Remote
+            }
+            WatchPolicy::Restart => {
+                if self.gave_up || self.next_restart.is_some_and(|t| now < t) {
+                    return Action::None;
+                }
+                if self.restarts.len() >= self.config.max_restarts as usize {
+                    self.gave_up = true;
+                    return Action::GiveUp;
+                }
+                let delay = (self.config.restart_delay_ms as f64
+                    * self
+                        .config
+                        .restart_multiplier
+                        .powi(self.backoff_step as i32)) as u64;
+                let delay = delay.min(self.config.restart_max_delay_ms);
+                self.restarts.push_back(now);
+                self.backoff_step += 1;
+                self.next_restart = Some(now + Duration::from_millis(delay));
+                Action::Restart
+            }
+        }
+    }
+
+    /// Takes note of an operation performed through shiki.
+    fn operation(&mut self, action: &str, result: &str) {
+        match action {
+            "stop" if result == "completed" => self.stopped_by_api = true,
+            "start" | "restart" => {
+                self.stopped_by_api = false;
+                self.gave_up = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn record(&mut self, decision: WatchDecision) {
+        if self.decisions.len() == DECISIONS_KEPT {
+            self.decisions.pop_front();
+        }
+        self.decisions.push_back(decision);
+    }
+
+    fn status(&self, service: &str) -> WatchStatus {
+        WatchStatus {
+            service: service.to_string(),
+            policy: self.config.policy.to_string(),
+            interval_seconds: self.config.interval_seconds,
+            state: self.state.map(|s| s.to_string()),
+            last_checked: self.last_checked,
+            restarts_in_window: self.restarts.len(),
+            max_restarts: self.config.max_restarts,
+            gave_up: self.gave_up,
+            stopped_by_api: self.stopped_by_api,
+            decisions: self.decisions.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+/// Watches services and applies their policies.
+#[derive(Debug)]
+pub struct Watcher {
+    /// Trackers keyed by service name.
+    services: HashMap<String, Mutex<Tracker>>,
+    /// Background watch tasks.
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Watcher {
+    /// Creates a watcher for the given services. Nothing is watched until
+    /// [`Watcher::spawn`] is called.
+    pub fn new(services: HashMap<String, WatchConfig>) -> Self {
+        Self {
+            services: services
+                .into_iter()
+                .map(|(name, config)| (name, Mutex::new(Tracker::new(config))))
+                .collect(),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Starts a watch task per service. Calling this more than once has no
+    /// effect.
+    pub fn spawn(&self, state: &Arc<AppState>) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        if !tasks.is_empty() {
+            return;
+        }
+        for (name, tracker) in &self.services {
+            let config = lock(tracker).config.clone();
+            info!(
+                service = %name,
+                policy = %config.policy,
+                interval_secs = config.interval_seconds,
+                "Watching service"
+            );
+            tasks.push(tokio::spawn(watch_loop(
+                Arc::downgrade(state),
+                name.clone(),
+                Duration::from_secs(config.interval_seconds),
+            )));
+        }
+    }
+
+    /// Takes note of an operation performed through shiki, so that services
+    /// stopped on purpose are left alone.
+    pub fn observe_operation(&self, entry: &HistoryEntry) {
+        if entry.caller.as_deref() == Some(WATCHER_CALLER) {
+            return;
+        }
+        if let Some(tracker) = self.services.get(&entry.service) {
+            lock(tracker).operation(&entry.action, &entry.result);
+        }
+    }
+
+    /// Returns the watch state of every watched service, by name.
+    pub fn statuses(&self) -> Vec<WatchStatus> {
+        let mut statuses: Vec<WatchStatus> = self
+            .services
+            .iter()
+            .map(|(name, tracker)| lock(tracker).status(name))
+            .collect();
+        statuses.sort_by(|a, b| a.service.cmp(&b.service));
+        statuses
+    }
+
+    fn observe(&self, service: &str, state: ServiceState) -> Option<(Action, WatchConfig)> {
+        let mut tracker = lock(self.services.get(service)?);
+        let action = tracker.observe(state, Instant::now());
+        Some((action, tracker.config.clone()))
+    }
+
+    fn record(&self, decision: WatchDecision) {
+        if let Some(tracker) = self.services.get(&decision.service) {
+            lock(tracker).record(decision);
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        if let Ok(tasks) = self.tasks.lock() {
+            for task in tasks.iter() {
+                task.abort();
+            }
+        }
+    }
+}
+
+fn lock(tracker: &Mutex<Tracker>) -> std::sync::MutexGuard<'_, Tracker> {
+    tracker.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Periodically checks a service and applies its policy.
+async fn watch_loop(state: Weak<AppState>, service: String, interval: Duration) {
+    loop {
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        check(&state, &service).await;
+        drop(state);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Checks a service once.
+async fn check(state: &Arc<AppState>, service: &str) {
+    let Some(watcher) = &state.watcher else {
+        return;
+    };
+    let observed = match state.controller().status(service).await {
+        Ok(status) => status.state,
+        Err(e) => {
+            warn!(service = %service, error = %e, "Watcher status check failed");
+            ServiceState::Unknown
+        }
+    };
+    let Some((action, config)) = watcher.observe(service, observed) else {
+        return;
+    };
+
+    let result = match action {
+        Action::None => return,
+        Action::Restart => state
+            .perform_internal(service, ServiceAction::Restart, WATCHER_CALLER, observed)
+            .await
+            .map(|current| format!("restarted, now {}", current)),
+        Action::Notify => notify(state, service, observed, "service is down"),
+        Action::Remote => remote(state, service, &config).await,
+        Action::GiveUp => notify(
+            state,
+            service,
+            observed,
+            &format!(
+                "restart limit reached ({} restarts in {}s)",
+                config.max_restarts, config.window_seconds
+            ),
+        )
+        .map(|_| format!("gave up after {} restarts", config.max_restarts)),
+    };
+
+    let decision = WatchDecision {
+        timestamp: Utc::now(),
+        service: service.to_string(),
+        observed_state: observed.to_string(),
+        action: action.to_string(),
+        result: if result.is_ok() {
+            "completed"
+        } else {
+            "failed"
+        }
+        .to_string(),
+        message: Some(match &result {
+            Ok(message) => message.clone(),
+            Err(e) => e.to_string(),
+        }),
+    };
+    match &result {
+        Ok(message) => info!(
+            service = %service,
+            state = %observed,
+            action = %action,
+            message = %message,
+            "Watcher applied policy"
+        ),
+        Err(e) => warn!(
+            service = %service,
+            state = %observed,
+            action = %action,
+            error = %e,
+            "Watcher policy failed"
+        ),
+    }
+    watcher.record(decision);
+}
+
+/// Emits a `service.unhealthy` event.
+fn notify(state: &AppState, service: &str, observed: ServiceState, reason: &str) -> Result<String> {
+    let webhooks = state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| ShikiError::config("No webhook endpoints are configured"))?;
+    webhooks.emit(
+        WebhookEvent::ServiceUnhealthy,
+        json!({
+            "service": service,
+            "state": observed.to_string(),
+            "reason": reason,
+        }),
+    );
+    Ok(format!("emitted service.unhealthy: {}", reason))
+}
+
+/// Sends the configured request to another agent.
+async fn remote(state: &AppState, service: &str, config: &WatchConfig) -> Result<String> {
+    let remote = config
+        .remote
+        .as_ref()
+        .ok_or_else(|| ShikiError::config(format!("watch.{}.remote is not set", service)))?;
+    // A peer name is resolved to its address and token
+    let (address, token) = match state.peer_config(&remote.agent) {
+        Some(peer) => (peer.address, peer.token),
+        None => (remote.agent.clone(), None),
+    };
+    let mut client = ShikiClient::with_timeout(&address, state.http_timeout)?;
+    if let Some(token) = token {
+        client = client.with_token(token);
+    }
+    let response = client
+        .notify(
+            &remote.service,
+            remote.action,
+            false,
+            state.http_timeout.as_secs(),
+        )
+        .await?;
+    if response.result == "failed" {
+        return Err(ShikiError::backend(format!(
+            "{} {} on {} failed: {}",
+            remote.action,
+            remote.service,
+            remote.agent,
+            response.message.unwrap_or_default()
+        )));
+    }
+    Ok(format!(
+        "{} {} on {}: {}",
+        remote.action, remote.service, remote.agent, response.result
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(policy: WatchPolicy) -> Tracker {
+        Tracker::new(WatchConfig {
+            policy,
+            max_restarts: 3,
+            window_seconds: 60,
+            restart_delay_ms: 1000,
+            restart_max_delay_ms: 3000,
+            restart_multiplier: 2.0,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_restart_backoff_and_limit() {
+        let mut tracker = tracker(WatchPolicy::Restart);
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+
+        // Never seen running: a stopped service is left alone, a failed one is not
+        assert_eq!(tracker.observe(ServiceState::Stopped, at(0)), Action::None);
+        assert_eq!(tracker.observe(ServiceState::Running, at(0)), Action::None);
+
+        // Restarts after 0s, then 1s and 2s later
+        assert_eq!(
+            tracker.observe(ServiceState::Failed, at(0)),
+            Action::Restart
+        );
+        assert_eq!(tracker.observe(ServiceState::Failed, at(500)), Action::None);
+        assert_eq!(
+            tracker.observe(ServiceState::Running, at(600)),
+            Action::None
+        );
+        assert_eq!(
+            tracker.observe(ServiceState::Stopped, at(1000)),
+            Action::Restart
+        );
+        assert_eq!(
+            tracker.observe(ServiceState::Stopped, at(2500)),
+            Action::None
+        );
+        assert_eq!(
+            tracker.observe(ServiceState::Stopped, at(3000)),
+            Action::Restart
+        );
+
+        // Limit of 3 restarts per window reached
+        assert_eq!(
+            tracker.observe(ServiceState::Stopped, at(10_000)),
+            Action::GiveUp
+        );
+        assert_eq!(
+            tracker.observe(ServiceState::Stopped, at(11_000)),
+            Action::None
+        );
+        assert!(tracker.status("nginx").gave_up);
+
+        // Once the window has passed, the backoff starts over
+        assert_eq!(
+            tracker.observe(ServiceState::Running, at(70_000)),
+            Action::None
+        );
+        assert_eq!(
+            tracker.observe(ServiceState::Failed, at(70_000)),
+            Action::Restart
+        );
+        assert_eq!(tracker.backoff_step, 1);
+    }
+
+    #[test]
+    fn test_stopped_through_api_is_left_alone() {
+        let mut tracker = tracker(WatchPolicy::Restart);
+        let now = Instant::now();
+        tracker.observe(ServiceState::Running, now);
+        tracker.operation("stop", "completed");
+        assert_eq!(tracker.observe(ServiceState::Stopped, now), Action::None);
+        assert!(tracker.status("nginx").stopped_by_api);
+
+        tracker.operation("start", "completed");
+        assert_eq!(tracker.observe(ServiceState::Failed, now), Action::Restart);
+    }
+
+    #[test]
+    fn test_notify_once_per_outage() {
+        let mut tracker = tracker(WatchPolicy::Notify);
+        let now = Instant::now();
+        tracker.observe(ServiceState::Running, now);
+        assert_eq!(tracker.observe(ServiceState::Stopped, now), Action::Notify);
+        assert_eq!(tracker.observe(ServiceState::Stopped, now), Action::None);
+        tracker.observe(ServiceState::Running, now);
+        assert_eq!(tracker.observe(ServiceState::Failed, now), Action::Notify);
+    }
+
+    #[test]
+    fn test_decisions_are_bounded() {
+        let mut tracker = tracker(WatchPolicy::Notify);
+        for i in 0..DECISIONS_KEPT + 5 {
+            tracker.record(WatchDecision {
+                timestamp: Utc::now(),
+                service: "nginx".to_string(),
+                observed_state: "failed".to_string(),
+                action: "notify".to_string(),
+                result: "completed".to_string(),
+                message: Some(i.to_string()),
+            });
+        }
+        let status = tracker.status("nginx");
+        assert_eq!(status.decisions.len(), DECISIONS_KEPT);
+        // Newest first
+        assert_eq!(
+            status.decisions[0].message.as_deref(),
+            Some((DECISIONS_KEPT + 4).to_string().as_str())
+        );
+    }
+}
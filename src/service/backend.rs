@@ -5,6 +5,7 @@
 
 use crate::error::Result;
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Service state as reported by the backend.
@@ -33,7 +34,7 @@ impl std::fmt::Display for ServiceState {
 }
 
 /// Service action to perform.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ServiceAction {
     /// Start the service.
@@ -80,6 +81,10 @@ pub struct ServiceOperationResult {
     pub state: ServiceState,
     /// Optional message (e.g., error details).
     pub message: Option<String>,
+    /// Operations performed on other services as part of this one
+    /// (dependencies started first, dependents stopped first), in execution order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<ServiceOperationResult>,
 }
 
 impl ServiceOperationResult {
@@ -91,9 +96,16 @@ impl ServiceOperationResult {
             success: true,
             state,
             message: None,
+            related: Vec::new(),
         }
     }
 
+    /// Attaches the results of related operations.
+    pub fn with_related(mut self, related: Vec<ServiceOperationResult>) -> Self {
+        self.related = related;
+        self
+    }
+
     /// Creates a failed operation result.
     pub fn failure(
         service: impl Into<String>,
@@ -107,6 +119,7 @@ impl ServiceOperationResult {
             success: false,
             state,
             message: Some(message.into()),
+            related: Vec::new(),
         }
     }
 }
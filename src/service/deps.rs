@@ -0,0 +1,370 @@
+//! Dependency graph between exec services.
+//!
+//! Services can declare `requires`, `wants`, `after` and `before`:
+//!
+//! - `requires`: start the listed services first; if one fails, do not start
+//!   this service. Stopping a required service also stops this service.
+//! - `wants`: start the listed services first, but tolerate their failure.
+//! - `after` / `before`: ordering only. They don't pull services in, but when
+//!   both services take part in the same operation the order is honoured.
+//!
+//! `requires` and `wants` imply `after`.
+
+use crate::config::ServiceDefinition;
+use crate::error::{Result, ShikiError};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Resolved dependency graph between services.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Hard dependencies (service -> services it requires).
+    requires: BTreeMap<String, BTreeSet<String>>,
+    /// Soft dependencies (service -> services it wants).
+    wants: BTreeMap<String, BTreeSet<String>>,
+    /// Ordering constraints (service -> services that must start before it).
+    after: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    /// Builds the graph from service definitions.
+    ///
+    /// Fails if a service references an unknown service or if the ordering
+    /// constraints contain a cycle.
+    pub fn from_services(services: &HashMap<String, ServiceDefinition>) -> Result<Self> {
+        let mut graph = DependencyGraph::default();
+
+        for (name, def) in services {
+            graph.requires.entry(name.clone()).or_default();
+            graph.wants.entry(name.clone()).or_default();
+            graph.after.entry(name.clone()).or_default();
+
+            let fields = [
+                ("requires", &def.requires),
+                ("wants", &def.wants),
+                ("after", &def.after),
+                ("before", &def.before),
+            ];
+            for (field, deps) in fields {
+                for dep in deps {
+                    if !services.contains_key(dep) {
+                        return Err(ShikiError::config(format!(
+                            "services.{}.{}: unknown service '{}'",
+                            name, field, dep
+                        )));
+                    }
+                    if dep == name {
+                        return Err(ShikiError::config(format!(
+                            "services.{}.{}: service cannot depend on itself",
+                            name, field
+                        )));
+                    }
+                }
+            }
+
+            graph
+                .requires
+                .entry(name.clone())
+                .or_default()
+                .extend(def.requires.iter().cloned());
+            graph
+                .wants
+                .entry(name.clone())
+                .or_default()
+                .extend(def.wants.iter().cloned());
+
+            let after = graph.after.entry(name.clone()).or_default();
+            after.extend(def.requires.iter().cloned());
+            after.extend(def.wants.iter().cloned());
+            after.extend(def.after.iter().cloned());
+
+            for other in &def.before {
+                graph
+                    .after
+                    .entry(other.clone())
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+
+        if let Some(cycle) = graph.find_cycle() {
+            return Err(ShikiError::config(format!(
+                "services: dependency cycle detected: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        Ok(graph)
+    }
+
+    /// Returns whether the service takes part in any dependency relation.
+    pub fn is_linked(&self, service: &str) -> bool {
+        let has_own = |map: &BTreeMap<String, BTreeSet<String>>| {
+            map.get(service).is_some_and(|deps| !deps.is_empty())
+        };
+        has_own(&self.requires) || has_own(&self.wants) || !self.dependents(service).is_empty()
+    }
+
+    /// Returns the services the given service hard-requires.
+    pub fn requires(&self, service: &str) -> BTreeSet<String> {
+        self.requires.get(service).cloned().unwrap_or_default()
+    }
+
+    /// Returns the services that directly require the given service.
+    pub fn dependents(&self, service: &str) -> BTreeSet<String> {
+        self.requires
+            .iter()
+            .filter(|(_, deps)| deps.contains(service))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns the services to start, in order, to start `service`.
+    ///
+    /// Includes `service` itself (last) and every service it transitively
+    /// requires or wants.
+    pub fn start_order(&self, service: &str) -> Vec<String> {
+        let mut pulled = BTreeSet::new();
+        let mut stack = vec![service.to_string()];
+        while let Some(name) = stack.pop() {
+            if !pulled.insert(name.clone()) {
+                continue;
+            }
+            for map in [&self.requires, &self.wants] {
+                if let Some(deps) = map.get(&name) {
+                    stack.extend(deps.iter().cloned());
+                }
+            }
+        }
+
+        self.topological_order(&pulled)
+    }
+
+    /// Returns the services to stop, in order, to stop `service`.
+    ///
+    /// Includes `service` itself (last) and every service that transitively
+    /// requires it.
+    pub fn stop_order(&self, service: &str) -> Vec<String> {
+        let mut affected = BTreeSet::new();
+        let mut stack = vec![service.to_string()];
+        while let Some(name) = stack.pop() {
+            if !affected.insert(name.clone()) {
+                continue;
+            }
+            stack.extend(self.dependents(&name));
+        }
+
+        let mut order = self.topological_order(&affected);
+        order.reverse();
+        order
+    }
+
+    /// Sorts a subset of services so that every service comes after the
+    /// services it is ordered after. Ties are broken by name.
+    fn topological_order(&self, subset: &BTreeSet<String>) -> Vec<String> {
+        let mut remaining: BTreeMap<&String, BTreeSet<&String>> = subset
+            .iter()
+            .map(|name| {
+                let deps = self
+                    .after
+                    .get(name)
+                    .map(|deps| deps.iter().filter(|d| subset.contains(*d)).collect())
+                    .unwrap_or_default();
+                (name, deps)
+            })
+            .collect();
+
+        let mut order = Vec::with_capacity(subset.len());
+        while !remaining.is_empty() {
+            let ready: Vec<&String> = remaining
+                .iter()
+                .filter(|(_, deps)| deps.is_empty())
+                .map(|(name, _)| *name)
+                .collect();
+
+            // Cycles are rejected when the graph is built
+            if ready.is_empty() {
+                order.extend(remaining.keys().map(|name| name.to_string()));
+                break;
+            }
+
+            for name in ready {
+                remaining.remove(name);
+                for deps in remaining.values_mut() {
+                    deps.remove(name);
+                }
+                order.push(name.clone());
+            }
+        }
+
+        order
+    }
+
+    /// Finds a cycle in the ordering constraints, if any.
+    fn find_cycle(&self) -> Option<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Visiting,
+            Done,
+        }
+
+        fn visit<'a>(
+            graph: &'a BTreeMap<String, BTreeSet<String>>,
+            name: &'a String,
+            marks: &mut HashMap<&'a String, Mark>,
+            path: &mut Vec<&'a String>,
+        ) -> Option<Vec<String>> {
+            match marks.get(name) {
+                Some(Mark::Done) => return None,
+                Some(Mark::Visiting) => {
+                    let start = path.iter().position(|n| *n == name).unwrap_or(0);
+                    let mut cycle: Vec<String> =
+                        path[start..].iter().map(|n| n.to_string()).collect();
+                    cycle.push(name.clone());
+                    return Some(cycle);
+                }
+                None => {}
+            }
+
+            marks.insert(name, Mark::Visiting);
+            path.push(name);
+            if let Some(deps) = graph.get(name) {
+                for dep in deps {
+                    if let Some(cycle) = visit(graph, dep, marks, path) {
+                        return Some(cycle);
+                    }
+                }
+            }
+            path.pop();
+            marks.insert(name, Mark::Done);
+            None
+        }
+
+        let mut marks = HashMap::new();
+        for name in self.after.keys() {
+            let mut path = Vec::new();
+            if let Some(cycle) = visit(&self.after, name, &mut marks, &mut path) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(
+        requires: &[&str],
+        wants: &[&str],
+        after: &[&str],
+        before: &[&str],
+    ) -> ServiceDefinition {
+        let list = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        ServiceDefinition {
+            start: "true".to_string(),
+            stop: "true".to_string(),
+            status: "true".to_string(),
+            requires: list(requires),
+            wants: list(wants),
+            after: list(after),
+            before: list(before),
+            ..Default::default()
+        }
+    }
+
+    fn pattern_c() -> HashMap<String, ServiceDefinition> {
+        let mut services = HashMap::new();
+        services.insert("redis".to_string(), service(&[], &[], &[], &[]));
+        services.insert("db".to_string(), service(&[], &[], &[], &[]));
+        services.insert("metrics".to_string(), service(&[], &[], &[], &["app"]));
+        services.insert(
+            "app".to_string(),
+            service(&["redis", "db"], &["metrics"], &[], &[]),
+        );
+        services.insert("nginx".to_string(), service(&["app"], &[], &[], &[]));
+        services
+    }
+
+    #[test]
+    fn test_start_order() {
+        let graph = DependencyGraph::from_services(&pattern_c()).unwrap();
+
+        assert_eq!(graph.start_order("redis"), vec!["redis"]);
+        assert_eq!(
+            graph.start_order("app"),
+            vec!["db", "metrics", "redis", "app"]
+        );
+        assert_eq!(
+            graph.start_order("nginx"),
+            vec!["db", "metrics", "redis", "app", "nginx"]
+        );
+    }
+
+    #[test]
+    fn test_stop_order() {
+        let graph = DependencyGraph::from_services(&pattern_c()).unwrap();
+
+        assert_eq!(graph.stop_order("redis"), vec!["nginx", "app", "redis"]);
+        assert_eq!(graph.stop_order("nginx"), vec!["nginx"]);
+        // wants does not propagate stops
+        assert_eq!(graph.stop_order("metrics"), vec!["metrics"]);
+    }
+
+    #[test]
+    fn test_after_only_orders() {
+        let mut services = HashMap::new();
+        services.insert("a".to_string(), service(&[], &[], &["b"], &[]));
+        services.insert("b".to_string(), service(&[], &[], &[], &[]));
+        let graph = DependencyGraph::from_services(&services).unwrap();
+
+        // after does not pull services in
+        assert_eq!(graph.start_order("a"), vec!["a"]);
+        assert!(!graph.is_linked("a"));
+        assert!(!graph.is_linked("b"));
+    }
+
+    #[test]
+    fn test_is_linked() {
+        let graph = DependencyGraph::from_services(&pattern_c()).unwrap();
+
+        assert!(graph.is_linked("app"));
+        assert!(graph.is_linked("redis"));
+        assert!(!graph.is_linked("metrics"));
+        assert_eq!(
+            graph.dependents("app"),
+            BTreeSet::from(["nginx".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_unknown_dependency() {
+        let mut services = HashMap::new();
+        services.insert("app".to_string(), service(&["redis"], &[], &[], &[]));
+
+        let err = DependencyGraph::from_services(&services).unwrap_err();
+        assert!(err.to_string().contains("services.app.requires"));
+        assert!(err.to_string().contains("redis"));
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let mut services = HashMap::new();
+        services.insert("a".to_string(), service(&["b"], &[], &[], &[]));
+        services.insert("b".to_string(), service(&[], &[], &["c"], &[]));
+        services.insert("c".to_string(), service(&[], &[], &[], &["b"]));
+        assert!(DependencyGraph::from_services(&services).is_ok());
+
+        services.insert("c".to_string(), service(&[], &["a"], &[], &[]));
+        let err = DependencyGraph::from_services(&services).unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn test_self_dependency() {
+        let mut services = HashMap::new();
+        services.insert("a".to_string(), service(&[], &[], &["a"], &[]));
+
+        assert!(DependencyGraph::from_services(&services).is_err());
+    }
+}
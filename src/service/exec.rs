@@ -3,12 +3,17 @@
 //! This backend executes user-defined commands for service operations.
 //! It's designed for environments where systemd is not available,
 //! such as Docker containers.
+//!
+//! Services declared with `type: supervised` are delegated to a
+//! [`Supervisor`] that owns the process instead of running commands.
 
-use crate::config::ServiceDefinition;
+use crate::config::{ServiceDefinition, ServiceKind};
 use crate::error::{Result, ShikiError};
 use crate::service::backend::{
     ServiceAction, ServiceBackend, ServiceOperationResult, ServiceState, ServiceStatus,
 };
+use crate::service::deps::DependencyGraph;
+use crate::service::supervisor::Supervisor;
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::process::Stdio;
@@ -28,12 +33,73 @@ const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 60;
 pub struct ExecBackend {
     /// Service definitions from configuration.
     services: HashMap<String, ServiceDefinition>,
+    /// Supervisors for `type: supervised` services.
+    supervisors: HashMap<String, Supervisor>,
+    /// Dependency graph between services.
+    graph: DependencyGraph,
 }
 
 impl ExecBackend {
     /// Creates a new exec backend with the given service definitions.
+    ///
+    /// Dependency references are validated by `Config::validate`; invalid
+    /// graphs passed here directly are ignored with a warning.
     pub fn new(services: HashMap<String, ServiceDefinition>) -> Self {
-        Self { services }
+        let graph = DependencyGraph::from_services(&services).unwrap_or_else(|e| {
+            warn!(error = %e, "Ignoring invalid service dependencies");
+            DependencyGraph::default()
+        });
+        let supervisors = services
+            .iter()
+            .filter(|(_, def)| def.kind == ServiceKind::Supervised)
+            .map(|(name, def)| (name.clone(), Supervisor::new(name.clone(), def.clone())))
+            .collect();
+
+        Self {
+            services,
+            supervisors,
+            graph,
+        }
+    }
+
+    /// Creates a backend for reloaded service definitions. Supervised
+    /// services that `previous` already supervises keep their process.
+    pub fn reloaded(services: HashMap<String, ServiceDefinition>, previous: &ExecBackend) -> Self {
+        let mut backend = Self::new(services);
+        for (name, supervisor) in backend.supervisors.iter_mut() {
+            if let Some(current) = previous.supervisors.get(name) {
+                *supervisor = current.with_definition(backend.services[name].clone());
+            }
+        }
+        for (name, supervisor) in &previous.supervisors {
+            if !backend.supervisors.contains_key(name) {
+                if let Some(pid) = supervisor.pid() {
+                    warn!(service = %name, pid, "Supervised service removed; its process is left running");
+                }
+            }
+        }
+        backend
+    }
+
+    /// Returns the supervisor for a supervised service.
+    pub fn supervisor(&self, name: &str) -> Option<&Supervisor> {
+        self.supervisors.get(name)
+    }
+
+    /// Stops every running supervised process with its stop signal.
+    pub async fn stop_supervised(&self) {
+        for (name, supervisor) in &self.supervisors {
+            if supervisor.pid().is_none() {
+                continue;
+            }
+            match supervisor.stop().await {
+                Ok(result) if !result.success => {
+                    warn!(service = %name, message = ?result.message, "Failed to stop supervised service");
+                }
+                Ok(_) => {}
+                Err(e) => warn!(service = %name, error = %e, "Failed to stop supervised service"),
+            }
+        }
     }
 
     /// Gets the service definition for a service.
@@ -82,13 +148,14 @@ impl ExecBackend {
         }
 
         // Set environment variables
-        for env_var in &definition.env {
+        for (index, env_var) in definition.env.iter().enumerate() {
             if let Some((key, value)) = env_var.split_once('=') {
                 cmd.env(key, value);
             } else {
+                // The entry itself is not logged, as it may hold a secret
                 warn!(
                     service = service_name,
-                    env_var = env_var,
+                    env_index = index,
                     "Invalid environment variable format, expected KEY=VALUE"
                 );
             }
@@ -165,6 +232,10 @@ impl ServiceBackend for ExecBackend {
     }
 
     async fn status(&self, service: &str) -> Result<ServiceStatus> {
+        if let Some(supervisor) = self.supervisors.get(service) {
+            return Ok(supervisor.status());
+        }
+
         let definition = self.get_service(service)?;
         let state = self.get_service_state(service, definition).await?;
 
@@ -172,6 +243,211 @@ impl ServiceBackend for ExecBackend {
     }
 
     async fn start(&self, service: &str) -> Result<ServiceOperationResult> {
+        self.get_service(service)?;
+        if !self.graph.is_linked(service) {
+            return self.start_one(service).await;
+        }
+
+        let order = self.graph.start_order(service);
+        info!(service = service, order = ?order, "Starting service with dependencies");
+        self.start_in_order(service, &order, ServiceAction::Start)
+            .await
+    }
+
+    async fn stop(&self, service: &str) -> Result<ServiceOperationResult> {
+        self.get_service(service)?;
+        if !self.graph.is_linked(service) {
+            return self.stop_one(service).await;
+        }
+
+        let order = self.graph.stop_order(service);
+        info!(service = service, order = ?order, "Stopping service with dependents");
+
+        let mut related = Vec::new();
+        for name in &order {
+            let result = self.stop_one(name).await?;
+            if name == service {
+                return Ok(result.with_related(related));
+            }
+            if !result.success {
+                warn!(
+                    service = service,
+                    dependent = %name,
+                    "Failed to stop dependent service"
+                );
+            }
+            related.push(result);
+        }
+
+        // stop_order always ends with the service itself
+        Err(ShikiError::backend(format!(
+            "Service {} missing from stop order",
+            service
+        )))
+    }
+
+    async fn restart(&self, service: &str) -> Result<ServiceOperationResult> {
+        self.get_service(service)?;
+        if !self.graph.is_linked(service) {
+            return self.restart_one(service).await;
+        }
+
+        let dependents: Vec<String> = self
+            .graph
+            .stop_order(service)
+            .into_iter()
+            .filter(|name| name != service)
+            .collect();
+
+        if dependents.is_empty() {
+            // Make sure requirements are up, then restart the service itself
+            let order = self.graph.start_order(service);
+            return self
+                .start_in_order(service, &order, ServiceAction::Restart)
+                .await;
+        }
+
+        // Restarting a required service restarts everything that requires it
+        info!(
+            service = service,
+            dependents = ?dependents,
+            "Restarting service together with dependents"
+        );
+        // Only dependents that were running are brought back afterwards
+        let mut running = Vec::new();
+        for name in &dependents {
+            if self.status(name).await?.state == ServiceState::Running {
+                running.push(name.clone());
+            }
+        }
+
+        let stop_result = self.stop(service).await?;
+        let mut related = stop_result.related.clone();
+        if !stop_result.success {
+            return Ok(ServiceOperationResult::failure(
+                service,
+                ServiceAction::Restart,
+                stop_result.state,
+                stop_result
+                    .message
+                    .unwrap_or_else(|| "Failed to stop service".to_string()),
+            )
+            .with_related(related));
+        }
+
+        let start_result = self.start(service).await?;
+        related.extend(start_result.related.iter().cloned());
+        let result = if start_result.success {
+            ServiceOperationResult::success(service, ServiceAction::Restart, start_result.state)
+        } else {
+            ServiceOperationResult::failure(
+                service,
+                ServiceAction::Restart,
+                start_result.state,
+                start_result
+                    .message
+                    .unwrap_or_else(|| "Failed to start service".to_string()),
+            )
+        };
+
+        // Bring the running dependents back, in start order
+        for name in dependents
+            .iter()
+            .rev()
+            .filter(|name| running.contains(name))
+        {
+            let dependent_result = self.start_one(name).await?;
+            if !dependent_result.success {
+                warn!(
+                    service = service,
+                    dependent = %name,
+                    "Failed to start dependent service after restart"
+                );
+            }
+            related.push(dependent_result);
+        }
+
+        Ok(result.with_related(related))
+    }
+}
+
+impl ExecBackend {
+    /// Runs `order` (dependencies first, `service` last). `service` itself is
+    /// started or restarted depending on `action`; a service whose required
+    /// dependency failed is not attempted.
+    async fn start_in_order(
+        &self,
+        service: &str,
+        order: &[String],
+        action: ServiceAction,
+    ) -> Result<ServiceOperationResult> {
+        let mut related = Vec::new();
+        let mut failed: Vec<String> = Vec::new();
+
+        for name in order {
+            let failed_requirement = self
+                .graph
+                .requires(name)
+                .into_iter()
+                .find(|dep| failed.contains(dep));
+
+            if name == service {
+                if let Some(dep) = failed_requirement {
+                    error!(
+                        service = service,
+                        dependency = %dep,
+                        "Required dependency failed, not starting service"
+                    );
+                    return Ok(ServiceOperationResult::failure(
+                        service,
+                        action,
+                        ServiceState::Stopped,
+                        format!("Required dependency '{}' failed to start", dep),
+                    )
+                    .with_related(related));
+                }
+
+                let result = match action {
+                    ServiceAction::Restart => self.restart_one(service).await?,
+                    _ => self.start_one(service).await?,
+                };
+                return Ok(result.with_related(related));
+            }
+
+            let result = match failed_requirement {
+                Some(dep) => ServiceOperationResult::failure(
+                    name.as_str(),
+                    ServiceAction::Start,
+                    ServiceState::Stopped,
+                    format!("Required dependency '{}' failed to start", dep),
+                ),
+                None => self.start_one(name).await?,
+            };
+
+            if !result.success {
+                warn!(
+                    service = service,
+                    dependency = %name,
+                    "Dependency failed to start"
+                );
+                failed.push(name.clone());
+            }
+            related.push(result);
+        }
+
+        // start_order always ends with the service itself
+        Err(ShikiError::backend(format!(
+            "Service {} missing from start order",
+            service
+        )))
+    }
+
+    /// Starts a single service without resolving dependencies.
+    async fn start_one(&self, service: &str) -> Result<ServiceOperationResult> {
+        if let Some(supervisor) = self.supervisors.get(service) {
+            return supervisor.start().await;
+        }
+
         let definition = self.get_service(service)?;
 
         info!(service = service, "Starting service");
@@ -231,7 +507,12 @@ impl ServiceBackend for ExecBackend {
         }
     }
 
-    async fn stop(&self, service: &str) -> Result<ServiceOperationResult> {
+    /// Stops a single service without resolving dependents.
+    async fn stop_one(&self, service: &str) -> Result<ServiceOperationResult> {
+        if let Some(supervisor) = self.supervisors.get(service) {
+            return supervisor.stop().await;
+        }
+
         let definition = self.get_service(service)?;
 
         info!(service = service, "Stopping service");
@@ -291,7 +572,12 @@ impl ServiceBackend for ExecBackend {
         }
     }
 
-    async fn restart(&self, service: &str) -> Result<ServiceOperationResult> {
+    /// Restarts a single service without resolving dependencies.
+    async fn restart_one(&self, service: &str) -> Result<ServiceOperationResult> {
+        if let Some(supervisor) = self.supervisors.get(service) {
+            return supervisor.restart().await;
+        }
+
         let definition = self.get_service(service)?;
 
         info!(service = service, "Restarting service");
@@ -347,7 +633,7 @@ impl ServiceBackend for ExecBackend {
             );
 
             // Stop the service (ignore if already stopped)
-            let stop_result = self.stop(service).await?;
+            let stop_result = self.stop_one(service).await?;
             if !stop_result.success && stop_result.state != ServiceState::Stopped {
                 return Ok(ServiceOperationResult::failure(
                     service,
@@ -360,7 +646,7 @@ impl ServiceBackend for ExecBackend {
             }
 
             // Start the service
-            let start_result = self.start(service).await?;
+            let start_result = self.start_one(service).await?;
             if start_result.success {
                 Ok(ServiceOperationResult::success(
                     service,
@@ -284,4 +284,109 @@ mod tests {
         assert_eq!(parts3[1], "--config");
         assert_eq!(parts3[2], "/path/to/config file.yaml");
     }
+
+    /// Builds services whose status is tracked with marker files, so that
+    /// start/stop order is observable through a shared log file.
+    fn dependency_services(dir: &std::path::Path) -> HashMap<String, ServiceDefinition> {
+        let log = dir.join("order.log");
+        let service = |name: &str, requires: &[&str], start_ok: bool| {
+            let marker = dir.join(format!("{}.running", name));
+            let start = if start_ok {
+                format!(
+                    "sh -c 'echo start-{name} >> {log}; touch {marker}'",
+                    log = log.display(),
+                    marker = marker.display()
+                )
+            } else {
+                "false".to_string()
+            };
+            ServiceDefinition {
+                start,
+                stop: format!(
+                    "sh -c 'echo stop-{name} >> {log}; rm -f {marker}'",
+                    log = log.display(),
+                    marker = marker.display()
+                ),
+                status: format!("test -f {}", marker.display()),
+                requires: requires.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            }
+        };
+
+        let mut services = HashMap::new();
+        services.insert("db".to_string(), service("db", &[], true));
+        services.insert("app".to_string(), service("app", &["db"], true));
+        services.insert("web".to_string(), service("web", &["app"], true));
+        services.insert("broken".to_string(), service("broken", &[], false));
+        services.insert(
+            "needs-broken".to_string(),
+            service("needs-broken", &["broken"], true),
+        );
+        services
+    }
+
+    fn read_log(dir: &std::path::Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join("order.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_dependency_start_and_stop_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = ExecBackend::new(dependency_services(dir.path()));
+
+        let result = backend.start("web").await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.related.len(), 2);
+        assert_eq!(
+            read_log(dir.path()),
+            vec!["start-db", "start-app", "start-web"]
+        );
+
+        std::fs::remove_file(dir.path().join("order.log")).unwrap();
+        let result = backend.stop("db").await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.service, "db");
+        let related: Vec<&str> = result.related.iter().map(|r| r.service.as_str()).collect();
+        assert_eq!(related, vec!["web", "app"]);
+        assert_eq!(
+            read_log(dir.path()),
+            vec!["stop-web", "stop-app", "stop-db"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dependency_restart_restarts_dependents() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = ExecBackend::new(dependency_services(dir.path()));
+
+        assert!(backend.start("app").await.unwrap().success);
+        std::fs::remove_file(dir.path().join("order.log")).unwrap();
+
+        let result = backend.restart("db").await.unwrap();
+        assert!(result.success);
+        assert_eq!(
+            read_log(dir.path()),
+            vec!["stop-app", "stop-db", "start-db", "start-app"]
+        );
+        assert_eq!(
+            backend.status("app").await.unwrap().state,
+            ServiceState::Running
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dependency_required_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = ExecBackend::new(dependency_services(dir.path()));
+
+        let result = backend.start("needs-broken").await.unwrap();
+        assert!(!result.success);
+        assert!(result.message.unwrap().contains("broken"));
+        assert!(!result.related[0].success);
+        assert!(read_log(dir.path()).is_empty());
+    }
 }
@@ -4,22 +4,30 @@
 //! including the backend trait and implementations for systemd and exec backends.
 
 pub mod backend;
+pub mod deps;
 pub mod exec;
+pub mod readiness;
+pub mod supervisor;
 pub mod systemd;
 
 #[cfg(test)]
 mod exec_tests;
+#[cfg(test)]
+mod supervisor_tests;
 
 use crate::config::{Backend, Config};
 use crate::error::{Result, ShikiError};
 use exec::ExecBackend;
+use readiness::ReadinessMonitor;
 use std::sync::Arc;
+use std::time::Duration;
 use systemd::SystemdBackend;
 
 // Re-exports for convenience
 pub use backend::{
     ServiceAction, ServiceBackend, ServiceOperationResult, ServiceState, ServiceStatus,
 };
+pub use readiness::ReadinessState;
 
 /// Service controller that manages service operations.
 ///
@@ -28,13 +36,29 @@ pub use backend::{
 pub struct ServiceController {
     /// The active backend.
     backend: Arc<dyn ServiceBackend>,
+    /// The exec backend, kept to hand its supervisors over on reload.
+    exec: Option<Arc<ExecBackend>>,
     /// Backend type name.
     backend_type: Backend,
+    /// Readiness probes.
+    readiness: ReadinessMonitor,
 }
 
 impl ServiceController {
     /// Creates a new service controller from configuration.
     pub fn from_config(config: &Config) -> Result<Self> {
+        Self::build(config, None)
+    }
+
+    /// Creates a controller for a reloaded configuration. Supervised
+    /// processes and readiness states carry over from this controller; the
+    /// readiness probes of the new controller still have to be started.
+    pub fn reload(&self, config: &Config) -> Result<Self> {
+        Self::build(config, Some(self))
+    }
+
+    fn build(config: &Config, previous: Option<&ServiceController>) -> Result<Self> {
+        let mut exec = None;
         let backend: Arc<dyn ServiceBackend> = match config.agent.backend {
             Backend::Systemd => Arc::new(SystemdBackend::new(config.acl.clone())),
             Backend::Exec => {
@@ -43,16 +67,59 @@ impl ServiceController {
                         "Exec backend requires at least one service definition",
                     ));
                 }
-                Arc::new(ExecBackend::new(config.services.clone()))
+                let services = config.services.clone();
+                let backend = Arc::new(match previous.and_then(|p| p.exec.as_deref()) {
+                    Some(previous) => ExecBackend::reloaded(services, previous),
+                    None => ExecBackend::new(services),
+                });
+                exec = Some(Arc::clone(&backend));
+                backend
             }
         };
+        let probes = config.readiness.clone();
+        let readiness = match previous {
+            Some(previous) => ReadinessMonitor::reloaded(probes, &previous.readiness),
+            None => ReadinessMonitor::new(probes),
+        };
 
         Ok(Self {
             backend,
+            exec,
             backend_type: config.agent.backend,
+            readiness,
         })
     }
 
+    /// Starts the background readiness probes.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn start_probes(&self) {
+        self.readiness.spawn();
+    }
+
+    /// Returns whether a readiness probe is configured for the service.
+    pub fn has_readiness_probe(&self, service: &str) -> bool {
+        self.readiness.has_probe(service)
+    }
+
+    /// Returns the readiness state of a service, if it has a probe.
+    pub fn readiness(&self, service: &str) -> Option<ReadinessState> {
+        self.readiness.state(service)
+    }
+
+    /// Waits until the readiness probe of a service reports ready.
+    pub async fn wait_ready(&self, service: &str, timeout: Duration) -> Result<()> {
+        self.readiness.wait_ready(service, timeout).await
+    }
+
+    /// Stops the processes supervised by the exec backend. Called when the
+    /// agent shuts down so that they are not killed on drop.
+    pub async fn stop_supervised(&self) {
+        if let Some(exec) = &self.exec {
+            exec.stop_supervised().await;
+        }
+    }
+
     /// Returns the name of the active backend.
     pub fn backend_name(&self) -> &'static str {
         self.backend.name()
@@ -85,11 +152,13 @@ impl ServiceController {
 
     /// Stops a service.
     pub async fn stop(&self, service: &str) -> Result<ServiceOperationResult> {
+        self.readiness.reset(service);
         self.backend.stop(service).await
     }
 
     /// Restarts a service.
     pub async fn restart(&self, service: &str) -> Result<ServiceOperationResult> {
+        self.readiness.reset(service);
         self.backend.restart(service).await
     }
 
@@ -99,6 +168,9 @@ impl ServiceController {
         service: &str,
         action: ServiceAction,
     ) -> Result<ServiceOperationResult> {
+        if action != ServiceAction::Start {
+            self.readiness.reset(service);
+        }
         self.backend.perform_action(service, action).await
     }
 }
@@ -223,6 +295,53 @@ mod tests {
         assert!(result.success);
     }
 
+    #[tokio::test]
+    async fn test_service_controller_readiness() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("ready");
+
+        let mut config = create_exec_config();
+        config.readiness.insert(
+            "test-service".to_string(),
+            crate::config::ReadinessProbe {
+                check: crate::config::ProbeCheck::File {
+                    path: marker.display().to_string(),
+                },
+                interval_seconds: 1,
+                timeout_seconds: 1,
+                success_threshold: 1,
+                failure_threshold: 1,
+                initial_delay_seconds: 0,
+            },
+        );
+        let controller = ServiceController::from_config(&config).unwrap();
+        controller.start_probes();
+
+        assert!(controller.has_readiness_probe("test-service"));
+        assert!(controller.readiness("nonexistent").is_none());
+
+        let result = controller
+            .wait_ready("test-service", Duration::from_millis(300))
+            .await;
+        assert!(result.is_err());
+        assert!(!controller.readiness("test-service").unwrap().ready);
+
+        std::fs::write(&marker, "").unwrap();
+        controller
+            .wait_ready("test-service", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(controller.readiness("test-service").unwrap().ready);
+
+        // Stopping resets readiness until the probe passes again
+        std::fs::remove_file(&marker).unwrap();
+        controller
+            .perform_action("test-service", ServiceAction::Stop)
+            .await
+            .unwrap();
+        assert!(!controller.readiness("test-service").unwrap().ready);
+    }
+
     #[tokio::test]
     async fn test_service_controller_not_found() {
         let config = create_exec_config();
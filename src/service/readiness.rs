@@ -0,0 +1,313 @@
+//! Readiness probe runner.
+//!
+//! "running" as reported by a backend only means the process exists. The
+//! readiness monitor periodically runs the configured probe for each service
+//! (TCP connect, HTTP GET, command, file) and tracks whether the service is
+//! actually able to serve, using success/failure thresholds.
+
+use crate::config::{ProbeCheck, ReadinessProbe};
+use crate::error::{Result, ShikiError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+/// Readiness state of a single service.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessState {
+    /// Whether the service is ready.
+    pub ready: bool,
+    /// Number of consecutive successful checks.
+    pub consecutive_successes: u32,
+    /// Number of consecutive failed checks.
+    pub consecutive_failures: u32,
+    /// Result message of the last check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_message: Option<String>,
+    /// Time of the last check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Incremented on reset; results of checks started earlier are discarded.
+    #[serde(skip)]
+    epoch: u64,
+}
+
+impl ReadinessState {
+    /// Records the result of a check and applies the thresholds.
+    fn record(&mut self, probe: &ReadinessProbe, outcome: &std::result::Result<(), String>) {
+        match outcome {
+            Ok(()) => {
+                self.consecutive_successes = self.consecutive_successes.saturating_add(1);
+                self.consecutive_failures = 0;
+                self.last_message = None;
+                if self.consecutive_successes >= probe.success_threshold {
+                    self.ready = true;
+                }
+            }
+            Err(message) => {
+                self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+                self.consecutive_successes = 0;
+                self.last_message = Some(message.clone());
+                if self.consecutive_failures >= probe.failure_threshold {
+                    self.ready = false;
+                }
+            }
+        }
+        self.last_checked = Some(Utc::now());
+    }
+}
+
+/// A configured probe and its current state.
+struct ProbeEntry {
+    /// Probe configuration.
+    probe: ReadinessProbe,
+    /// Current readiness state.
+    state: Arc<watch::Sender<ReadinessState>>,
+}
+
+/// Runs readiness probes for all configured services.
+pub struct ReadinessMonitor {
+    /// Probes keyed by service name.
+    probes: HashMap<String, ProbeEntry>,
+    /// Background probe tasks.
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl ReadinessMonitor {
+    /// Creates a monitor for the given probes. Probes do not run until
+    /// [`ReadinessMonitor::spawn`] is called.
+    pub fn new(probes: HashMap<String, ReadinessProbe>) -> Self {
+        let probes = probes
+            .into_iter()
+            .map(|(name, probe)| {
+                let (state, _) = watch::channel(ReadinessState::default());
+                (
+                    name,
+                    ProbeEntry {
+                        probe,
+                        state: Arc::new(state),
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            probes,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates a monitor for reloaded probes. Services whose probe did not
+    /// change keep their readiness state.
+    pub fn reloaded(probes: HashMap<String, ReadinessProbe>, previous: &ReadinessMonitor) -> Self {
+        let mut monitor = Self::new(probes);
+        for (name, entry) in monitor.probes.iter_mut() {
+            if let Some(current) = previous.probes.get(name).filter(|e| e.probe == entry.probe) {
+                entry.state = Arc::clone(&current.state);
+            }
+        }
+        monitor
+    }
+
+    /// Returns whether a probe is configured for the service.
+    pub fn has_probe(&self, service: &str) -> bool {
+        self.probes.contains_key(service)
+    }
+
+    /// Returns the readiness state of a service, if it has a probe.
+    pub fn state(&self, service: &str) -> Option<ReadinessState> {
+        self.probes
+            .get(service)
+            .map(|entry| entry.state.borrow().clone())
+    }
+
+    /// Marks the service as not ready and restarts threshold counting.
+    ///
+    /// Called after stop/restart so that waiters don't observe a stale
+    /// "ready" from before the operation.
+    pub fn reset(&self, service: &str) {
+        if let Some(entry) = self.probes.get(service) {
+            entry.state.send_modify(|s| {
+                let epoch = s.epoch + 1;
+                *s = ReadinessState {
+                    epoch,
+                    ..Default::default()
+                };
+            });
+        }
+    }
+
+    /// Starts the background probe loops. Calling this more than once has no effect.
+    pub fn spawn(&self) {
+        let mut tasks = match self.tasks.lock() {
+            Ok(tasks) => tasks,
+            Err(_) => return,
+        };
+        if !tasks.is_empty() {
+            return;
+        }
+
+        for (name, entry) in &self.probes {
+            info!(
+                service = %name,
+                check = entry.probe.check.kind(),
+                interval_secs = entry.probe.interval_seconds,
+                "Starting readiness probe"
+            );
+            tasks.push(tokio::spawn(probe_loop(
+                name.clone(),
+                entry.probe.clone(),
+                Arc::clone(&entry.state),
+            )));
+        }
+    }
+
+    /// Waits until the service is ready.
+    pub async fn wait_ready(&self, service: &str, timeout: Duration) -> Result<()> {
+        let entry = self.probes.get(service).ok_or_else(|| {
+            ShikiError::invalid_request(format!(
+                "No readiness probe configured for service: {}",
+                service
+            ))
+        })?;
+
+        let mut rx = entry.state.subscribe();
+        tokio::time::timeout(timeout, rx.wait_for(|s| s.ready))
+            .await
+            .map_err(|_| ShikiError::Timeout {
+                operation: format!("wait for {} to be ready", service),
+                seconds: timeout.as_secs(),
+            })?
+            .map_err(|_| ShikiError::backend("Readiness monitor stopped"))?;
+
+        Ok(())
+    }
+}
+
+impl Drop for ReadinessMonitor {
+    fn drop(&mut self) {
+        if let Ok(tasks) = self.tasks.lock() {
+            for task in tasks.iter() {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Periodically runs a probe and updates the readiness state.
+async fn probe_loop(
+    name: String,
+    probe: ReadinessProbe,
+    state: Arc<watch::Sender<ReadinessState>>,
+) {
+    tokio::time::sleep(Duration::from_secs(probe.initial_delay_seconds)).await;
+
+    let interval = Duration::from_secs(probe.interval_seconds);
+    loop {
+        let epoch = state.borrow().epoch;
+        let outcome = run_check(&probe).await;
+
+        let mut changed = None;
+        state.send_if_modified(|s| {
+            if s.epoch != epoch {
+                return false;
+            }
+            let was_ready = s.ready;
+            s.record(&probe, &outcome);
+            if s.ready != was_ready {
+                changed = Some(s.ready);
+            }
+            true
+        });
+
+        match changed {
+            Some(true) => info!(service = %name, "Service is ready"),
+            Some(false) => info!(
+                service = %name,
+                reason = ?outcome.as_ref().err(),
+                "Service is no longer ready"
+            ),
+            None => debug!(service = %name, outcome = ?outcome, "Readiness check completed"),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Runs a single check with the probe timeout.
+pub async fn run_check(probe: &ReadinessProbe) -> std::result::Result<(), String> {
+    let timeout = Duration::from_secs(probe.timeout_seconds);
+    match tokio::time::timeout(timeout, check(&probe.check, timeout)).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("{} check timed out", probe.check.kind())),
+    }
+}
+
+/// Performs the check itself.
+async fn check(check: &ProbeCheck, timeout: Duration) -> std::result::Result<(), String> {
+    match check {
+        ProbeCheck::Tcp { address } => TcpStream::connect(address)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("connect to {} failed: {}", address, e)),
+        ProbeCheck::Http {
+            url,
+            expected_status,
+        } => {
+            let client = reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| format!("GET {} failed: {}", url, e))?;
+            let status = response.status().as_u16();
+            if status == *expected_status {
+                Ok(())
+            } else {
+                Err(format!(
+                    "GET {} returned {} (expected {})",
+                    url, status, expected_status
+                ))
+            }
+        }
+        ProbeCheck::Command { command } => {
+            let parts = shell_words::split(command)
+                .map_err(|e| format!("Failed to parse command '{}': {}", command, e))?;
+            let (program, args) = parts
+                .split_first()
+                .ok_or_else(|| "Empty command".to_string())?;
+            let status = Command::new(program)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .status()
+                .await
+                .map_err(|e| format!("Failed to execute '{}': {}", command, e))?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(format!("'{}' exited with {:?}", command, status.code()))
+            }
+        }
+        ProbeCheck::File { path } => {
+            if Path::new(path).exists() {
+                Ok(())
+            } else {
+                Err(format!("{} does not exist", path))
+            }
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Process supervisor for exec services.
+//!
+//! Services declared with `type: supervised` are not managed through
+//! user-written stop/status commands. Instead shiki spawns the start command
+//! as a long-running child process, keeps track of its PID, captures its
+//! output, and applies the configured restart policy when it exits.
+
+use crate::config::{ServiceDefinition, SupervisorConfig};
+use crate::error::{Result, ShikiError};
+use crate::service::backend::{ServiceAction, ServiceOperationResult, ServiceState, ServiceStatus};
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+/// Time to wait after spawning before a start is considered successful.
+const STARTUP_GRACE_MS: u64 = 250;
+
+/// Time to wait for the process to be reaped after SIGKILL.
+const KILL_GRACE_MS: u64 = 1000;
+
+/// Runtime state of a supervised process.
+#[derive(Debug, Clone, Default)]
+struct ProcessState {
+    /// PID of the running child, if any.
+    pid: Option<u32>,
+    /// Exit code of the last child (None if killed by a signal or never exited).
+    last_exit_code: Option<i32>,
+    /// Whether the last child exited successfully.
+    last_exit_success: Option<bool>,
+    /// Whether a restart is pending after backoff.
+    restart_pending: bool,
+    /// Number of automatic restarts performed.
+    restarts: u32,
+    /// Set when the process is being stopped on request.
+    stop_requested: bool,
+    /// Incremented on every explicit start; stale monitors exit on mismatch.
+    generation: u64,
+}
+
+impl ProcessState {
+    /// Maps the process state to a service state. A process waiting out its
+    /// restart backoff is not running, so it reports failed until the
+    /// respawn succeeds.
+    fn service_state(&self) -> ServiceState {
+        if self.pid.is_some() {
+            ServiceState::Running
+        } else if self.restart_pending
+            || (self.last_exit_success == Some(false) && !self.stop_requested)
+        {
+            ServiceState::Failed
+        } else {
+            ServiceState::Stopped
+        }
+    }
+}
+
+/// Supervisor for a single service.
+pub struct Supervisor {
+    /// Service name.
+    name: String,
+    /// Service definition.
+    definition: ServiceDefinition,
+    /// Process state, shared with the monitor task.
+    state: Arc<watch::Sender<ProcessState>>,
+    /// Recent stdout/stderr lines.
+    output: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Supervisor {
+    /// Creates a supervisor for the given service definition.
+    pub fn new(name: impl Into<String>, definition: ServiceDefinition) -> Self {
+        let (state, _) = watch::channel(ProcessState::default());
+        Self {
+            name: name.into(),
+            definition,
+            state: Arc::new(state),
+            output: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Creates a supervisor for a new definition of the same service that
+    /// keeps tracking the current process. The definition applies from the
+    /// next start or restart.
+    pub fn with_definition(&self, definition: ServiceDefinition) -> Self {
+        Self {
+            name: self.name.clone(),
+            definition,
+            state: Arc::clone(&self.state),
+            output: Arc::clone(&self.output),
+        }
+    }
+
+    /// Returns the PID of the running process, if any.
+    pub fn pid(&self) -> Option<u32> {
+        self.state.borrow().pid
+    }
+
+    /// Returns the most recent output lines of the process.
+    pub fn recent_output(&self) -> Vec<String> {
+        self.output
+            .lock()
+            .map(|o| o.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the current status of the supervised process.
+    pub fn status(&self) -> ServiceStatus {
+        let state = self.state.borrow().clone();
+        let description = match state.pid {
+            Some(pid) => format!("pid {}, restarts {}", pid, state.restarts),
+            None if state.restart_pending => format!(
+                "restart pending after exit code {:?}, restarts {}",
+                state.last_exit_code, state.restarts
+            ),
+            None => match state.last_exit_code {
+                Some(code) => format!("exited with code {}, restarts {}", code, state.restarts),
+                None => format!("not running, restarts {}", state.restarts),
+            },
+        };
+
+        ServiceStatus::with_description(&self.name, state.service_state(), description)
+    }
+
+    /// Spawns the process and starts supervising it.
+    pub async fn start(&self) -> Result<ServiceOperationResult> {
+        if self.state.borrow().service_state() == ServiceState::Running {
+            info!(service = %self.name, "Service is already running");
+            return Ok(ServiceOperationResult::success(
+                &self.name,
+                ServiceAction::Start,
+                ServiceState::Running,
+            ));
+        }
+
+        info!(service = %self.name, "Starting supervised service");
+
+        let child = match spawn_child(&self.name, &self.definition, &self.output) {
+            Ok(child) => child,
+            Err(e) => {
+                error!(service = %self.name, error = %e, "Failed to spawn process");
+                return Ok(ServiceOperationResult::failure(
+                    &self.name,
+                    ServiceAction::Start,
+                    ServiceState::Failed,
+                    e.to_string(),
+                ));
+            }
+        };
+
+        let mut generation = 0;
+        self.state.send_modify(|s| {
+            s.generation += 1;
+            s.pid = child.id();
+            s.stop_requested = false;
+            s.restart_pending = false;
+            s.last_exit_code = None;
+            s.last_exit_success = None;
+            generation = s.generation;
+        });
+
+        tokio::spawn(monitor(
+            self.name.clone(),
+            self.definition.clone(),
+            Arc::clone(&self.state),
+            Arc::clone(&self.output),
+            child,
+            generation,
+        ));
+
+        // Give the process a moment to fail fast (bad arguments, missing files, ...)
+        let mut rx = self.state.subscribe();
+        let _ = tokio::time::timeout(
+            Duration::from_millis(STARTUP_GRACE_MS),
+            rx.wait_for(|s| s.pid.is_none()),
+        )
+        .await;
+
+        let state = self.state.borrow().clone();
+        if state.pid.is_none() && state.last_exit_success == Some(false) {
+            warn!(service = %self.name, "Supervised process exited during startup");
+            let output = self.recent_output().join("\n");
+            return Ok(ServiceOperationResult::failure(
+                &self.name,
+                ServiceAction::Start,
+                ServiceState::Failed,
+                format!(
+                    "Process exited during startup with code {:?}\n{}",
+                    state.last_exit_code, output
+                ),
+            ));
+        }
+
+        info!(service = %self.name, pid = ?state.pid, "Supervised service started");
+        Ok(ServiceOperationResult::success(
+            &self.name,
+            ServiceAction::Start,
+            state.service_state(),
+        ))
+    }
+
+    /// Stops the process, escalating to SIGKILL after the stop timeout.
+    pub async fn stop(&self) -> Result<ServiceOperationResult> {
+        let mut pid = None;
+        self.state.send_modify(|s| {
+            s.stop_requested = true;
+            s.restart_pending = false;
+            pid = s.pid;
+        });
+
+        let Some(pid) = pid else {
+            info!(service = %self.name, "Service is already stopped");
+            return Ok(ServiceOperationResult::success(
+                &self.name,
+                ServiceAction::Stop,
+                ServiceState::Stopped,
+            ));
+        };
+
+        let config = &self.definition.supervisor;
+        info!(
+            service = %self.name,
+            pid = pid,
+            signal = ?config.stop_signal,
+            "Stopping supervised service"
+        );
+
+        send_signal(pid, config.stop_signal.as_raw())?;
+
+        let mut rx = self.state.subscribe();
+        let stop_timeout = Duration::from_secs(config.stop_timeout);
+        if tokio::time::timeout(stop_timeout, rx.wait_for(|s| s.pid.is_none()))
+            .await
+            .is_err()
+        {
+            warn!(
+                service = %self.name,
+                pid = pid,
+                timeout_secs = config.stop_timeout,
+                "Process did not exit in time, sending SIGKILL"
+            );
+            send_signal(pid, libc::SIGKILL)?;
+
+            let kill_grace = Duration::from_millis(KILL_GRACE_MS);
+            if tokio::time::timeout(kill_grace, rx.wait_for(|s| s.pid.is_none()))
+                .await
+                .is_err()
+            {
+                return Ok(ServiceOperationResult::failure(
+                    &self.name,
+                    ServiceAction::Stop,
+                    ServiceState::Unknown,
+                    format!("Process {} did not exit after SIGKILL", pid),
+                ));
+            }
+        }
+
+        info!(service = %self.name, "Supervised service stopped");
+        Ok(ServiceOperationResult::success(
+            &self.name,
+            ServiceAction::Stop,
+            ServiceState::Stopped,
+        ))
+    }
+
+    /// Stops and starts the process.
+    pub async fn restart(&self) -> Result<ServiceOperationResult> {
+        info!(service = %self.name, "Restarting supervised service");
+
+        let stop_result = self.stop().await?;
+        if !stop_result.success {
+            return Ok(ServiceOperationResult::failure(
+                &self.name,
+                ServiceAction::Restart,
+                stop_result.state,
+                stop_result
+                    .message
+                    .unwrap_or_else(|| "Failed to stop service".to_string()),
+            ));
+        }
+
+        let start_result = self.start().await?;
+        if start_result.success {
+            Ok(ServiceOperationResult::success(
+                &self.name,
+                ServiceAction::Restart,
+                start_result.state,
+            ))
+        } else {
+            Ok(ServiceOperationResult::failure(
+                &self.name,
+                ServiceAction::Restart,
+                start_result.state,
+                start_result
+                    .message
+                    .unwrap_or_else(|| "Failed to start service".to_string()),
+            ))
+        }
+    }
+}
+
+/// Waits on the child and applies the restart policy until the process is
+/// stopped, superseded by a newer start, or the policy gives up.
+async fn monitor(
+    name: String,
+    definition: ServiceDefinition,
+    state: Arc<watch::Sender<ProcessState>>,
+    output: Arc<Mutex<VecDeque<String>>>,
+    mut child: Child,
+    generation: u64,
+) {
+    let config = &definition.supervisor;
+    let mut attempt: u32 = 0;
+
+    loop {
+        let started_at = Instant::now();
+        let exit = child.wait().await;
+        let (success, code) = match &exit {
+            Ok(status) => (status.success(), status.code()),
+            Err(_) => (false, None),
+        };
+
+        let mut restart = false;
+        state.send_modify(|s| {
+            if s.generation != generation {
+                return;
+            }
+            s.pid = None;
+            s.last_exit_code = code;
+            s.last_exit_success = Some(success);
+            restart = !s.stop_requested && config.restart_policy.should_restart(success);
+            s.restart_pending = restart;
+        });
+
+        info!(
+            service = %name,
+            exit_code = ?code,
+            success = success,
+            restart = restart,
+            "Supervised process exited"
+        );
+
+        if !restart {
+            return;
+        }
+
+        // A process that stayed up longer than the maximum delay starts the backoff over
+        if started_at.elapsed() >= Duration::from_millis(config.restart_max_delay_ms) {
+            attempt = 0;
+        }
+        let delay = restart_delay(config, attempt);
+        attempt = attempt.saturating_add(1);
+
+        debug!(service = %name, delay_ms = delay.as_millis() as u64, "Restarting after backoff");
+        tokio::time::sleep(delay).await;
+
+        {
+            let current = state.borrow();
+            if current.generation != generation || current.stop_requested {
+                return;
+            }
+        }
+
+        match spawn_child(&name, &definition, &output) {
+            Ok(new_child) => {
+                state.send_modify(|s| {
+                    s.pid = new_child.id();
+                    s.restarts += 1;
+                    s.restart_pending = false;
+                });
+                child = new_child;
+            }
+            Err(e) => {
+                error!(service = %name, error = %e, "Failed to restart process");
+                state.send_modify(|s| {
+                    s.restart_pending = false;
+                    s.last_exit_success = Some(false);
+                });
+                return;
+            }
+        }
+    }
+}
+
+/// Computes the restart delay for the given attempt.
+fn restart_delay(config: &SupervisorConfig, attempt: u32) -> Duration {
+    let delay = config.restart_delay_ms as f64 * config.restart_multiplier.powi(attempt as i32);
+    Duration::from_millis(delay.min(config.restart_max_delay_ms as f64) as u64)
+}
+
+/// Spawns the start command with output capture.
+fn spawn_child(
+    name: &str,
+    definition: &ServiceDefinition,
+    output: &Arc<Mutex<VecDeque<String>>>,
+) -> Result<Child> {
+    let parts = shell_words::split(&definition.start).map_err(|e| {
+        ShikiError::backend(format!(
+            "Failed to parse command '{}': {}",
+            definition.start, e
+        ))
+    })?;
+    let (program, args) = parts
+        .split_first()
+        .ok_or_else(|| ShikiError::backend("Empty command"))?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(working_dir) = &definition.working_dir {
+        cmd.current_dir(working_dir);
+    }
+
+    for env_var in &definition.env {
+        if let Some((key, value)) = env_var.split_once('=') {
+            cmd.env(key, value);
+        } else {
+            warn!(
+                service = name,
+                env_var = env_var,
+                "Invalid environment variable format, expected KEY=VALUE"
+            );
+        }
+    }
+
+    let mut child = cmd.spawn().map_err(|e| {
+        ShikiError::backend_with_source(
+            format!("Failed to execute command '{}': {}", definition.start, e),
+            e,
+        )
+    })?;
+
+    debug!(service = name, pid = ?child.id(), "Spawned supervised process");
+
+    let capacity = definition.supervisor.output_lines;
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(capture_output(
+            name.to_string(),
+            "stdout",
+            stdout,
+            Arc::clone(output),
+            capacity,
+        ));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(capture_output(
+            name.to_string(),
+            "stderr",
+            stderr,
+            Arc::clone(output),
+            capacity,
+        ));
+    }
+
+    Ok(child)
+}
+
+/// Forwards process output to the log and keeps the most recent lines.
+async fn capture_output<R: AsyncRead + Unpin>(
+    name: String,
+    stream: &'static str,
+    reader: R,
+    output: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        info!(service = %name, stream = stream, "{}", line);
+
+        if capacity == 0 {
+            continue;
+        }
+        if let Ok(mut buffer) = output.lock() {
+            if buffer.len() >= capacity {
+                buffer.pop_front();
+            }
+            buffer.push_back(line);
+        }
+    }
+}
+
+/// Sends a signal to a process.
+fn send_signal(pid: u32, signal: i32) -> Result<()> {
+    // SAFETY: kill(2) has no memory-safety preconditions.
+    let ret = unsafe { libc::kill(pid as libc::pid_t, signal) };
+    if ret != 0 {
+        let err = std::io::Error::last_os_error();
+        // The process may already have exited between the state check and the signal
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(ShikiError::backend_with_source(
+                format!("Failed to send signal {} to pid {}: {}", signal, pid, err),
+                err,
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+//! Tests for the process supervisor.
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{
+        RestartPolicy, ServiceDefinition, ServiceKind, StopSignal, SupervisorConfig,
+    };
+    use crate::service::backend::{ServiceBackend, ServiceState};
+    use crate::service::exec::ExecBackend;
+    use crate::service::supervisor::Supervisor;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn supervised(start: &str, supervisor: SupervisorConfig) -> ServiceDefinition {
+        ServiceDefinition {
+            kind: ServiceKind::Supervised,
+            start: start.to_string(),
+            supervisor,
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for_state(supervisor: &Supervisor, state: ServiceState) -> bool {
+        for _ in 0..50 {
+            if supervisor.status().state == state {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_start_and_stop() {
+        let supervisor = Supervisor::new(
+            "sleeper",
+            supervised("sleep 30", SupervisorConfig::default()),
+        );
+
+        assert_eq!(supervisor.status().state, ServiceState::Stopped);
+
+        let result = supervisor.start().await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.state, ServiceState::Running);
+        assert!(supervisor.pid().is_some());
+
+        // Starting again is a no-op
+        let pid = supervisor.pid();
+        let result = supervisor.start().await.unwrap();
+        assert!(result.success);
+        assert_eq!(supervisor.pid(), pid);
+
+        let result = supervisor.stop().await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.state, ServiceState::Stopped);
+        assert!(supervisor.pid().is_none());
+        assert_eq!(supervisor.status().state, ServiceState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_stop_escalates_to_sigkill() {
+        let config = SupervisorConfig {
+            stop_signal: StopSignal::Usr1,
+            stop_timeout: 1,
+            ..Default::default()
+        };
+        let supervisor = Supervisor::new(
+            "stubborn",
+            supervised(
+                "sh -c 'trap \"\" USR1; while true; do sleep 0.1; done'",
+                config,
+            ),
+        );
+
+        assert!(supervisor.start().await.unwrap().success);
+
+        let result = supervisor.stop().await.unwrap();
+        assert!(result.success);
+        assert!(supervisor.pid().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_start_fails_fast() {
+        let supervisor = Supervisor::new(
+            "broken",
+            supervised("sh -c 'echo boom >&2; exit 3'", SupervisorConfig::default()),
+        );
+
+        let result = supervisor.start().await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.state, ServiceState::Failed);
+        assert!(result.message.unwrap().contains("code Some(3)"));
+        assert_eq!(supervisor.status().state, ServiceState::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_start_missing_program() {
+        let supervisor = Supervisor::new(
+            "missing",
+            supervised("/nonexistent/program", SupervisorConfig::default()),
+        );
+
+        let result = supervisor.start().await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.state, ServiceState::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_captures_output() {
+        let supervisor = Supervisor::new(
+            "chatty",
+            supervised(
+                "sh -c 'echo hello; echo world >&2; sleep 30'",
+                SupervisorConfig::default(),
+            ),
+        );
+
+        assert!(supervisor.start().await.unwrap().success);
+
+        for _ in 0..50 {
+            if supervisor.recent_output().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let output = supervisor.recent_output();
+        assert!(output.contains(&"hello".to_string()));
+        assert!(output.contains(&"world".to_string()));
+
+        supervisor.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restart_policy_on_failure() {
+        let config = SupervisorConfig {
+            restart_policy: RestartPolicy::OnFailure,
+            restart_delay_ms: 50,
+            restart_max_delay_ms: 100,
+            ..Default::default()
+        };
+        // Fails once the grace period has passed, so the start itself succeeds
+        let supervisor = Supervisor::new("flaky", supervised("sh -c 'sleep 0.5; exit 1'", config));
+
+        assert!(supervisor.start().await.unwrap().success);
+
+        let mut restarted = false;
+        for _ in 0..50 {
+            if supervisor
+                .status()
+                .description
+                .unwrap()
+                .contains("restarts 1")
+            {
+                restarted = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(restarted, "process was not restarted");
+
+        supervisor.stop().await.unwrap();
+        assert_eq!(supervisor.status().state, ServiceState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_restart_backoff_reports_failed() {
+        let config = SupervisorConfig {
+            restart_policy: RestartPolicy::OnFailure,
+            restart_delay_ms: 5000,
+            restart_max_delay_ms: 5000,
+            ..Default::default()
+        };
+        let supervisor =
+            Supervisor::new("backoff", supervised("sh -c 'sleep 0.5; exit 1'", config));
+
+        assert!(supervisor.start().await.unwrap().success);
+        assert!(wait_for_state(&supervisor, ServiceState::Failed).await);
+        let status = supervisor.status();
+        assert!(status.description.unwrap().contains("restart pending"));
+
+        // An explicit start during the backoff spawns the process right away
+        assert!(supervisor.start().await.unwrap().success);
+        assert_eq!(supervisor.status().state, ServiceState::Running);
+
+        supervisor.stop().await.unwrap();
+        assert_eq!(supervisor.status().state, ServiceState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_restart_policy_never() {
+        let supervisor = Supervisor::new(
+            "oneshot",
+            supervised("sh -c 'sleep 0.5; exit 0'", SupervisorConfig::default()),
+        );
+
+        assert!(supervisor.start().await.unwrap().success);
+        assert!(wait_for_state(&supervisor, ServiceState::Stopped).await);
+        assert!(supervisor
+            .status()
+            .description
+            .unwrap()
+            .contains("restarts 0"));
+    }
+
+    #[tokio::test]
+    async fn test_exec_backend_delegates_to_supervisor() {
+        let mut services = HashMap::new();
+        services.insert(
+            "app".to_string(),
+            supervised("sleep 30", SupervisorConfig::default()),
+        );
+        let backend = ExecBackend::new(services);
+
+        assert!(backend.supervisor("app").is_some());
+
+        let status = backend.status("app").await.unwrap();
+        assert_eq!(status.state, ServiceState::Stopped);
+
+        let result = backend.start("app").await.unwrap();
+        assert!(result.success);
+        assert_eq!(
+            backend.status("app").await.unwrap().state,
+            ServiceState::Running
+        );
+
+        let old_pid = backend.supervisor("app").unwrap().pid();
+        let result = backend.restart("app").await.unwrap();
+        assert!(result.success);
+        assert_ne!(backend.supervisor("app").unwrap().pid(), old_pid);
+
+        let result = backend.stop("app").await.unwrap();
+        assert!(result.success);
+        assert_eq!(
+            backend.status("app").await.unwrap().state,
+            ServiceState::Stopped
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_supervised_sends_stop_signal() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("stopped");
+        let start = format!(
+            "sh -c 'trap \"echo TERM > {}; exit 0\" TERM; while true; do sleep 0.1; done'",
+            marker.display()
+        );
+        let mut services = HashMap::new();
+        services.insert(
+            "app".to_string(),
+            supervised(&start, SupervisorConfig::default()),
+        );
+        services.insert(
+            "idle".to_string(),
+            supervised("sleep 30", SupervisorConfig::default()),
+        );
+        let backend = ExecBackend::new(services);
+        assert!(backend.start("app").await.unwrap().success);
+
+        backend.stop_supervised().await;
+        assert!(backend.supervisor("app").unwrap().pid().is_none());
+        assert_eq!(std::fs::read_to_string(&marker).unwrap().trim(), "TERM");
+        assert_eq!(
+            backend.status("idle").await.unwrap().state,
+            ServiceState::Stopped
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_backend_keeps_process() {
+        let mut services = HashMap::new();
+        services.insert(
+            "app".to_string(),
+            supervised("sleep 30", SupervisorConfig::default()),
+        );
+        let backend = ExecBackend::new(services.clone());
+        assert!(backend.start("app").await.unwrap().success);
+        let pid = backend.supervisor("app").unwrap().pid();
+
+        // The new definition applies from the next start
+        services.insert(
+            "app".to_string(),
+            supervised("sleep 31", SupervisorConfig::default()),
+        );
+        let reloaded = ExecBackend::reloaded(services, &backend);
+        drop(backend);
+        assert_eq!(reloaded.supervisor("app").unwrap().pid(), pid);
+        assert_eq!(
+            reloaded.status("app").await.unwrap().state,
+            ServiceState::Running
+        );
+
+        assert!(reloaded.restart("app").await.unwrap().success);
+        assert_ne!(reloaded.supervisor("app").unwrap().pid(), pid);
+        assert!(reloaded.stop("app").await.unwrap().success);
+    }
+}
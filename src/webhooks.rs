@@ -0,0 +1,426 @@
+//! Outbound webhooks.
+//!
+//! Events are put on a bounded queue and delivered by a background worker,
+//! so a slow or unreachable receiver never delays the request that emitted
+//! the event. When the queue is full, new events are dropped and counted.
+//!
+//! Every event is posted as JSON to each endpoint subscribed to it, with the
+//! `X-Shiki-Event` and `X-Shiki-Delivery` headers. Endpoints with a secret
+//! also get `X-Shiki-Signature: sha256=<hex>`, the HMAC-SHA256 of the body.
+//! Failed deliveries are retried with exponential backoff.
+
+use crate::config::{RetryConfig, WebhookEndpoint, WebhookEvent, WebhooksConfig};
+use crate::error::{Result, ShikiError};
+use crate::server::response::WebhookStatsInfo;
+use crate::util::to_hex;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Header carrying the event type.
+pub const EVENT_HEADER: &str = "x-shiki-event";
+
+/// Header carrying the delivery ID, the same for every attempt.
+pub const DELIVERY_HEADER: &str = "x-shiki-delivery";
+
+/// Header carrying the payload signature.
+pub const SIGNATURE_HEADER: &str = "x-shiki-signature";
+
+/// Body of a webhook request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// Delivery ID.
+    pub id: Uuid,
+    /// Event type.
+    pub event: WebhookEvent,
+    /// When the event occurred.
+    pub timestamp: DateTime<Utc>,
+    /// Agent that emitted the event.
+    pub agent: String,
+    /// Event details.
+    pub data: serde_json::Value,
+}
+
+/// Delivery counters.
+#[derive(Debug, Default)]
+struct DeliveryStats {
+    queued: AtomicU64,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+    retries: AtomicU64,
+    pending: AtomicU64,
+}
+
+/// Queues events and delivers them to the configured endpoints.
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    agent: String,
+    endpoints: Arc<Vec<WebhookEndpoint>>,
+    retry: RetryConfig,
+    client: reqwest::Client,
+    sender: mpsc::Sender<WebhookPayload>,
+    receiver: Mutex<Option<mpsc::Receiver<WebhookPayload>>>,
+    stats: Arc<DeliveryStats>,
+}
+
+impl WebhookDispatcher {
+    /// Creates a dispatcher for `config`. Events are queued until
+    /// [`WebhookDispatcher::spawn`] starts the delivery worker.
+    pub fn new(config: &WebhooksConfig, agent: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()
+            .map_err(|e| ShikiError::config(format!("Failed to create webhook client: {}", e)))?;
+        let (sender, receiver) = mpsc::channel(config.queue_size);
+        Ok(Self {
+            agent: agent.to_string(),
+            endpoints: Arc::new(config.endpoints.clone()),
+            retry: config.retry.clone(),
+            client,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            stats: Arc::new(DeliveryStats::default()),
+        })
+    }
+
+    /// Queues an event for the endpoints subscribed to it. Never waits: the
+    /// event is dropped when the queue is full.
+    pub fn emit(&self, event: WebhookEvent, data: serde_json::Value) {
+        if !self.endpoints.iter().any(|e| e.wants(event)) {
+            return;
+        }
+        let payload = WebhookPayload {
+            id: Uuid::new_v4(),
+            event,
+            timestamp: Utc::now(),
+            agent: self.agent.clone(),
+            data,
+        };
+        self.stats.pending.fetch_add(1, Ordering::Relaxed);
+        match self.sender.try_send(payload) {
+            Ok(()) => {
+                self.stats.queued.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.stats.pending.fetch_sub(1, Ordering::Relaxed);
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                warn!(event = %event, "Webhook queue is full, dropping event");
+            }
+        }
+    }
+
+    /// Starts the delivery worker. Does nothing when already started.
+    pub fn spawn(&self) {
+        let Some(mut receiver) = self
+            .receiver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        else {
+            return;
+        };
+        let endpoints = Arc::clone(&self.endpoints);
+        let retry = self.retry.clone();
+        let client = self.client.clone();
+        let stats = Arc::clone(&self.stats);
+        tokio::spawn(async move {
+            while let Some(payload) = receiver.recv().await {
+                dispatch(&client, &endpoints, &retry, &stats, payload).await;
+                stats.pending.fetch_sub(1, Ordering::Relaxed);
+            }
+        });
+    }
+
+    /// Waits until every queued event has been handled, for at most
+    /// `timeout`. Returns whether the queue was drained.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.stats.pending.load(Ordering::Relaxed) > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+
+    /// Returns the delivery statistics.
+    pub fn stats(&self) -> WebhookStatsInfo {
+        let stats = &self.stats;
+        WebhookStatsInfo {
+            endpoints: self.endpoints.len(),
+            queued: stats.queued.load(Ordering::Relaxed),
+            delivered: stats.delivered.load(Ordering::Relaxed),
+            failed: stats.failed.load(Ordering::Relaxed),
+            dropped: stats.dropped.load(Ordering::Relaxed),
+            retries: stats.retries.load(Ordering::Relaxed),
+            pending: stats.pending.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Delivers an event to every endpoint subscribed to it, concurrently.
+async fn dispatch(
+    client: &reqwest::Client,
+    endpoints: &[WebhookEndpoint],
+    retry: &RetryConfig,
+    stats: &Arc<DeliveryStats>,
+    payload: WebhookPayload,
+) {
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(event = %payload.event, error = %e, "Failed to serialize webhook event");
+            return;
+        }
+    };
+
+    let mut deliveries = JoinSet::new();
+    for endpoint in endpoints.iter().filter(|e| e.wants(payload.event)) {
+        deliveries.spawn(deliver(
+            client.clone(),
+            endpoint.clone(),
+            retry.clone(),
+            Arc::clone(stats),
+            payload.event,
+            payload.id,
+            body.clone(),
+        ));
+    }
+    while deliveries.join_next().await.is_some() {}
+}
+
+/// Posts an event to one endpoint, retrying failed attempts.
+async fn deliver(
+    client: reqwest::Client,
+    endpoint: WebhookEndpoint,
+    retry: RetryConfig,
+    stats: Arc<DeliveryStats>,
+    event: WebhookEvent,
+    id: Uuid,
+    body: Vec<u8>,
+) {
+    let max_attempts = retry.max_attempts.max(1);
+    let signature = endpoint.secret.as_deref().map(|secret| sign(secret, &body));
+    let mut delay_ms = retry.initial_interval_ms;
+
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.to_string())
+            .header(DELIVERY_HEADER, id.to_string())
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let result = match request.send().await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(format!("receiver returned {}", response.status())),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(()) => {
+                debug!(url = %endpoint.url, event = %event, "Webhook delivered");
+                stats.delivered.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Err(e) if attempt < max_attempts => {
+                debug!(
+                    url = %endpoint.url,
+                    event = %event,
+                    attempt = attempt,
+                    error = %e,
+                    "Webhook delivery failed, retrying"
+                );
+                stats.retries.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                delay_ms = ((delay_ms as f64 * retry.multiplier) as u64).min(retry.max_interval_ms);
+                attempt += 1;
+            }
+            Err(e) => {
+                warn!(
+                    url = %endpoint.url,
+                    event = %event,
+                    attempts = attempt,
+                    error = %e,
+                    "Webhook delivery failed"
+                );
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+}
+
+/// Returns the signature header value of `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", to_hex(&mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use tokio::net::TcpListener;
+
+    /// Requests received by a test receiver.
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// Starts a receiver answering the first `failures` requests with 500.
+    async fn receiver(failures: usize) -> (String, Received) {
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    move |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        let mut received = received.lock().unwrap();
+                        received.push((headers, body));
+                        if received.len() <= failures {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::NO_CONTENT
+                        }
+                    },
+                ),
+            )
+            .with_state(Arc::clone(&received));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/hook", addr), received)
+    }
+
+    fn config(endpoints: Vec<WebhookEndpoint>) -> WebhooksConfig {
+        WebhooksConfig {
+            endpoints,
+            retry: RetryConfig {
+                max_attempts: 3,
+                initial_interval_ms: 10,
+                max_interval_ms: 20,
+                multiplier: 2.0,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_and_event_filter() {
+        let (url, received) = receiver(0).await;
+        let dispatcher = WebhookDispatcher::new(
+            &config(vec![WebhookEndpoint {
+                url,
+                events: vec![WebhookEvent::OperationFailed],
+                secret: Some("s3cret".to_string()),
+            }]),
+            "web-1",
+        )
+        .unwrap();
+        dispatcher.spawn();
+
+        dispatcher.emit(
+            WebhookEvent::ServiceStateChanged,
+            serde_json::json!({"service": "nginx"}),
+        );
+        dispatcher.emit(
+            WebhookEvent::OperationFailed,
+            serde_json::json!({"service": "nginx", "result": "failed"}),
+        );
+        assert!(dispatcher.flush(Duration::from_secs(5)).await);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        assert_eq!(headers[EVENT_HEADER], "operation.failed");
+        assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", body).as_str());
+        let payload: WebhookPayload = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload.event, WebhookEvent::OperationFailed);
+        assert_eq!(payload.agent, "web-1");
+        assert_eq!(payload.data["service"], "nginx");
+        assert_eq!(headers[DELIVERY_HEADER], payload.id.to_string().as_str());
+
+        let stats = dispatcher.stats();
+        assert_eq!((stats.queued, stats.delivered, stats.pending), (1, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried() {
+        let (url, received) = receiver(2).await;
+        let dispatcher = WebhookDispatcher::new(
+            &config(vec![
+                WebhookEndpoint {
+                    url,
+                    ..Default::default()
+                },
+                WebhookEndpoint {
+                    url: "http://127.0.0.1:1/hook".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            "web-1",
+        )
+        .unwrap();
+        dispatcher.spawn();
+        dispatcher.emit(WebhookEvent::AgentShuttingDown, serde_json::json!({}));
+        assert!(dispatcher.flush(Duration::from_secs(5)).await);
+
+        // Two failures and a success on the first endpoint, three failures
+        // on the unreachable one; the delivery ID stays the same
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        assert!(received
+            .iter()
+            .all(|(headers, _)| headers[DELIVERY_HEADER] == received[0].0[DELIVERY_HEADER]));
+        assert!(!received[0].0.contains_key(SIGNATURE_HEADER));
+        let stats = dispatcher.stats();
+        assert_eq!((stats.delivered, stats.failed, stats.retries), (1, 1, 4));
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_events() {
+        let mut config = config(vec![WebhookEndpoint {
+            url: "http://127.0.0.1:1/hook".to_string(),
+            ..Default::default()
+        }]);
+        config.queue_size = 1;
+        // Without a worker, the queue fills up
+        let dispatcher = WebhookDispatcher::new(&config, "web-1").unwrap();
+        for _ in 0..3 {
+            dispatcher.emit(WebhookEvent::AgentShuttingDown, serde_json::json!({}));
+        }
+        let stats = dispatcher.stats();
+        assert_eq!((stats.queued, stats.dropped, stats.pending), (1, 2, 1));
+        assert!(!dispatcher.flush(Duration::from_millis(100)).await);
+    }
+}